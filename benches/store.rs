@@ -0,0 +1,126 @@
+// ============================================================================
+// benches/store.rs - Timing the Store Layer
+// ============================================================================
+// Times loading, saving, lookups, statistics and bulk volumes (against a
+// `volume()` per fruit) over the catalogues from `fruitdata::bench_support`,
+// at each of its `SIZES`, and measures how much heap memory the fruits take
+// as `FruitDimensions` and as `CompactFruit`:
+//
+//     cargo bench --features testing
+//
+// fruitdata doesn't depend on a benchmarking framework, so this is a plain
+// program (`harness = false`) that runs each operation a few times and
+// prints the fastest run, which is the least disturbed by other work on the
+// machine. Compare the output before and after a change to the store layer.
+// ============================================================================
+
+use fruitdata::bench_support::{catalogue, fruits, lookup_names, write_catalogue, SIZES};
+use fruitdata::catalog::{box_volumes, volumes, Catalogue};
+use fruitdata::compact::compact_fruits;
+use fruitdata::models::FruitDimensions;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// The system allocator, keeping count of how many bytes are in use.
+struct Counting;
+
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        IN_USE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        IN_USE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// The heap memory `make`'s result holds on to.
+fn heap_bytes<T>(make: impl FnOnce() -> T) -> (T, usize) {
+    let before = IN_USE.load(Ordering::Relaxed);
+    let value = make();
+    (value, IN_USE.load(Ordering::Relaxed) - before)
+}
+
+/// How many times each operation is run.
+const RUNS: u32 = 5;
+
+/// Run `f` `RUNS` times and print the fastest.
+fn bench<T>(name: &str, size: usize, mut f: impl FnMut() -> T) {
+    let fastest = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or(Duration::ZERO);
+    println!("{:<8} {:>8} fruits  {:>12.3?}", name, size, fastest);
+}
+
+fn main() {
+    let path = std::env::temp_dir().join(format!("fruitdata-bench-{}.json", std::process::id()));
+    let path = path
+        .to_str()
+        .expect("the temporary directory has a UTF-8 path");
+
+    for size in SIZES {
+        write_catalogue(path, size).expect("could not write the benchmark catalogue");
+
+        bench("load", size, || Catalogue::load(path).unwrap());
+
+        let mut saved = catalogue(size);
+        bench("save", size, || saved.save(path).unwrap());
+
+        let loaded = catalogue(size);
+        let names = lookup_names(size, 100);
+        bench("lookup", size, || {
+            names
+                .iter()
+                .filter(|name| loaded.get(name).is_some())
+                .count()
+        });
+
+        bench("stats", size, || loaded.aggregate());
+        // The bulk kernel, against the per-fruit loop it replaces and
+        // against the kernel on columns that are already gathered
+        bench("volumes", size, || volumes(loaded.fruits()));
+        bench("volume()", size, || {
+            loaded
+                .fruits()
+                .iter()
+                .map(FruitDimensions::volume)
+                .collect::<Vec<_>>()
+        });
+        let column = |dimension: fn(&FruitDimensions) -> f32| -> Vec<f32> {
+            loaded.fruits().iter().map(dimension).collect()
+        };
+        let (lengths, widths, heights) = (
+            column(|f| f.length.value()),
+            column(|f| f.width.value()),
+            column(|f| f.height.value()),
+        );
+        bench("columns", size, || box_volumes(&lengths, &widths, &heights));
+
+        let (plain, plain_bytes) = heap_bytes(|| fruits(size));
+        let (compact, compact_bytes) = heap_bytes(|| compact_fruits(plain.clone()));
+        println!(
+            "{:<8} {:>8} fruits  {:>9} KiB as FruitDimensions, {} KiB as CompactFruit",
+            "memory",
+            size,
+            plain_bytes / 1024,
+            compact_bytes / 1024
+        );
+        drop((plain, compact));
+    }
+
+    let _ = std::fs::remove_file(path);
+}
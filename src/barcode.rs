@@ -0,0 +1,464 @@
+// ============================================================================
+// barcode.rs - Barcode Images (the `barcode` feature)
+// ============================================================================
+// `fruitdata label` leaves drawing the barcode to the printer. For anything
+// else (a price list, a web shop, a label designed in another program)
+// `fruitdata barcode Apple -o apple.svg` draws it as an image, from the
+// same data: the fruit's PLU, or its ID (see `label::barcode_data`).
+//
+// Two symbologies are supported:
+//
+// - code128: any printable ASCII, so it works for every PLU and ID. All
+//   digits (an even number of them) are packed two to a symbol in code set
+//   C; anything else uses code set B.
+// - ean13: the retail barcode, 13 digits. A PLU or ID of up to 12 digits
+//   is padded with zeros in front and gets its check digit added; 13
+//   digits must already end in the right check digit.
+//
+// The image is an SVG (with the data written under the bars) or a PNG
+// (bars only, one bit per pixel), chosen by the output file's extension.
+// Both are written by hand, so the feature adds no dependencies: the PNG's
+// image data is stored uncompressed, which for a barcode is a few KB.
+// ============================================================================
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The bar and space widths of each Code 128 symbol, by value. 103, 104 and
+/// 105 start code sets A, B and C.
+const CODE128: [&[u8; 6]; 106] = [
+    b"212222", b"222122", b"222221", b"121223", b"121322", b"131222", b"122213", b"122312",
+    b"132212", b"221213", b"221312", b"231212", b"112232", b"122132", b"122231", b"113222",
+    b"123122", b"123221", b"223211", b"221132", b"221231", b"213212", b"223112", b"312131",
+    b"311222", b"321122", b"321221", b"312212", b"322112", b"322211", b"212123", b"212321",
+    b"232121", b"111323", b"131123", b"131321", b"112313", b"132113", b"132311", b"211313",
+    b"231113", b"231311", b"112133", b"112331", b"132131", b"113123", b"113321", b"133121",
+    b"313121", b"211331", b"231131", b"213113", b"213311", b"213131", b"311123", b"311321",
+    b"331121", b"312113", b"312311", b"332111", b"314111", b"221411", b"431111", b"111224",
+    b"111422", b"121124", b"121421", b"141122", b"141221", b"112214", b"112412", b"122114",
+    b"122411", b"142112", b"142211", b"241211", b"221114", b"413111", b"241112", b"134111",
+    b"111242", b"121142", b"121241", b"114212", b"124112", b"124211", b"411212", b"421112",
+    b"421211", b"212141", b"214121", b"412121", b"111143", b"111341", b"131141", b"114113",
+    b"114311", b"411113", b"411311", b"113141", b"114131", b"311141", b"411131", b"211412",
+    b"211214", b"211232",
+];
+const CODE128_START_B: usize = 104;
+const CODE128_START_C: usize = 105;
+const CODE128_STOP: &[u8; 7] = b"2331112";
+
+/// The EAN-13 left-hand (odd parity, "L") pattern of each digit; the
+/// right-hand ("R") patterns are their inverse, and the even parity ("G")
+/// ones the R patterns reversed.
+const EAN_L: [&[u8; 7]; 10] = [
+    b"0001101", b"0011001", b"0010011", b"0111101", b"0100011", b"0110001", b"0101111", b"0111011",
+    b"0110111", b"0001011",
+];
+/// Which of the first six digits use G patterns (1) rather than L ones (0);
+/// the first digit of the barcode isn't drawn, but picks the row.
+const EAN_PARITY: [&[u8; 6]; 10] = [
+    b"000000", b"001011", b"001101", b"001110", b"010011", b"011001", b"011100", b"010101",
+    b"010110", b"011010",
+];
+
+/// The white margin each side of the bars, in modules.
+const QUIET_ZONE: usize = 10;
+
+/// The longest data a Code 128 barcode is made of, like a label's.
+const MAX_CODE128: usize = 48;
+
+/// A kind of barcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbology {
+    /// Code 128: printable ASCII.
+    Code128,
+    /// EAN-13: 12 digits and a check digit.
+    Ean13,
+}
+
+impl FromStr for Symbology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "code128" => Ok(Symbology::Code128),
+            "ean13" | "ean" => Ok(Symbology::Ean13),
+            other => Err(format!(
+                "unknown symbology '{}' (expected code128 or ean13)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Symbology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Symbology::Code128 => write!(f, "code128"),
+            Symbology::Ean13 => write!(f, "ean13"),
+        }
+    }
+}
+
+/// The kind of image a barcode is drawn as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Svg,
+    Png,
+}
+
+impl ImageFormat {
+    /// The format a file's extension names.
+    ///
+    /// # Error Cases
+    /// The extension is neither `.svg` nor `.png`.
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("svg") => Ok(ImageFormat::Svg),
+            Some("png") => Ok(ImageFormat::Png),
+            _ => Err(format!(
+                "can't tell the image format of '{}' (use a .svg or .png file)",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// An encoded barcode: its modules (the narrowest bar or space) from left
+/// to right, `true` for a bar, and the text printed under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Barcode {
+    pub symbology: Symbology,
+    pub text: String,
+    modules: Vec<bool>,
+}
+
+impl Barcode {
+    /// Encode `data` as a `symbology` barcode.
+    ///
+    /// # Error Cases
+    /// The data can't be encoded: see `code128` and `ean13`.
+    pub fn encode(data: &str, symbology: Symbology) -> Result<Self, String> {
+        match symbology {
+            Symbology::Code128 => Barcode::code128(data),
+            Symbology::Ean13 => Barcode::ean13(data),
+        }
+    }
+
+    /// A Code 128 barcode of `data`.
+    ///
+    /// # Error Cases
+    /// The data is empty, longer than 48 characters, or has characters other
+    /// than printable ASCII.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::barcode::Barcode;
+    ///
+    /// // Start C, 41, 31, the check symbol, and the stop symbol
+    /// let barcode = Barcode::code128("4131").unwrap();
+    /// assert_eq!(barcode.modules().len(), 4 * 11 + 13);
+    /// assert!(Barcode::code128("Açaí").is_err());
+    /// ```
+    pub fn code128(data: &str) -> Result<Self, String> {
+        if data.is_empty()
+            || data.len() > MAX_CODE128
+            || !data.chars().all(|c| matches!(c, ' '..='~'))
+        {
+            return Err(format!(
+                "'{}' can't be a Code 128 barcode (1 to {} printable ASCII characters)",
+                data, MAX_CODE128
+            ));
+        }
+
+        let mut values = Vec::new();
+        if data.len().is_multiple_of(2) && data.bytes().all(|b| b.is_ascii_digit()) {
+            values.push(CODE128_START_C);
+            for pair in data.as_bytes().chunks(2) {
+                values.push(usize::from(pair[0] - b'0') * 10 + usize::from(pair[1] - b'0'));
+            }
+        } else {
+            values.push(CODE128_START_B);
+            values.extend(data.bytes().map(|b| usize::from(b - b' ')));
+        }
+        // The check symbol: the start symbol's value plus each symbol's
+        // times its position, modulo 103
+        let check = values
+            .iter()
+            .enumerate()
+            .map(|(position, value)| position.max(1) * value)
+            .sum::<usize>()
+            % 103;
+        values.push(check);
+
+        let mut modules = Vec::new();
+        for value in values {
+            push_widths(&mut modules, CODE128[value]);
+        }
+        push_widths(&mut modules, CODE128_STOP);
+        Ok(Barcode {
+            symbology: Symbology::Code128,
+            text: data.to_string(),
+            modules,
+        })
+    }
+
+    /// An EAN-13 barcode of `data`: up to 12 digits, padded with zeros in
+    /// front, to which the check digit is added, or 13 digits ending in the
+    /// right check digit.
+    ///
+    /// # Error Cases
+    /// The data isn't 1 to 13 digits, or 13 digits with the wrong check digit.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::barcode::Barcode;
+    ///
+    /// let barcode = Barcode::ean13("400638133393").unwrap();
+    /// assert_eq!(barcode.text, "4006381333931");
+    /// assert_eq!(barcode.modules().len(), 95);
+    ///
+    /// assert_eq!(Barcode::ean13("4011").unwrap().text, "0000000040112");
+    /// assert!(Barcode::ean13("4006381333932").is_err()); // wrong check digit
+    /// ```
+    pub fn ean13(data: &str) -> Result<Self, String> {
+        if data.is_empty() || data.len() > 13 || !data.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!(
+                "'{}' can't be an EAN-13 barcode (1 to 13 digits)",
+                data
+            ));
+        }
+        let mut digits: Vec<u8> = data.bytes().map(|b| b - b'0').collect();
+        let given_check = (digits.len() == 13).then(|| digits.pop()).flatten();
+        while digits.len() < 12 {
+            digits.insert(0, 0);
+        }
+        let weighted: u32 = digits
+            .iter()
+            .enumerate()
+            .map(|(position, &digit)| u32::from(digit) * if position % 2 == 0 { 1 } else { 3 })
+            .sum();
+        let check = ((10 - weighted % 10) % 10) as u8;
+        if given_check.is_some_and(|given| given != check) {
+            return Err(format!(
+                "'{}' has the wrong EAN-13 check digit (expected {})",
+                data, check
+            ));
+        }
+        digits.push(check);
+
+        let mut modules = bits(b"101");
+        let parity = EAN_PARITY[usize::from(digits[0])];
+        for (position, &digit) in digits[1..7].iter().enumerate() {
+            let left = bits(EAN_L[usize::from(digit)]);
+            if parity[position] == b'1' {
+                // G: the R pattern (L inverted) reversed
+                modules.extend(left.iter().rev().map(|bar| !bar));
+            } else {
+                modules.extend(left);
+            }
+        }
+        modules.extend(bits(b"01010"));
+        for &digit in &digits[7..] {
+            modules.extend(bits(EAN_L[usize::from(digit)]).iter().map(|bar| !bar));
+        }
+        modules.extend(bits(b"101"));
+        Ok(Barcode {
+            symbology: Symbology::Ean13,
+            text: digits
+                .iter()
+                .map(|digit| char::from(b'0' + digit))
+                .collect(),
+            modules,
+        })
+    }
+
+    /// The modules, from left to right, `true` for a bar.
+    pub fn modules(&self) -> &[bool] {
+        &self.modules
+    }
+
+    /// Draw the barcode as `format`.
+    pub fn render(&self, format: ImageFormat, scale: u32, height: u32) -> Vec<u8> {
+        match format {
+            ImageFormat::Svg => self.to_svg(scale, height).into_bytes(),
+            ImageFormat::Png => self.to_png(scale, height),
+        }
+    }
+
+    /// The barcode as an SVG image, `scale` units per module and the bars
+    /// `height` units high, with the text under them.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::barcode::Barcode;
+    ///
+    /// let svg = Barcode::code128("A&B").unwrap().to_svg(2, 60);
+    /// assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    /// assert!(svg.contains(">A&amp;B</text>"));
+    /// ```
+    pub fn to_svg(&self, scale: u32, height: u32) -> String {
+        let font_size = 8 * scale;
+        let width = self.width(scale);
+        let total_height = height + font_size + 2 * scale;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\">\n<rect width=\"{w}\" height=\"{h}\" fill=\"#fff\"/>\n",
+            w = width,
+            h = total_height
+        );
+        for (start, length) in self.bars() {
+            svg.push_str(&format!(
+                "<rect x=\"{}\" width=\"{}\" height=\"{}\"/>\n",
+                (QUIET_ZONE + start) as u32 * scale,
+                length as u32 * scale,
+                height
+            ));
+        }
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"{}\" \
+             text-anchor=\"middle\">{}</text>\n</svg>\n",
+            width / 2,
+            height + font_size,
+            font_size,
+            escape_xml(&self.text)
+        ));
+        svg
+    }
+
+    /// The barcode as a black and white PNG image, `scale` pixels per
+    /// module and `height` pixels high.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::barcode::Barcode;
+    ///
+    /// let png = Barcode::ean13("4011").unwrap().to_png(2, 60);
+    /// assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    /// assert_eq!(&png[16..24], &[0, 0, 0, 230, 0, 0, 0, 60]); // (95 + 20) * 2 x 60
+    /// ```
+    pub fn to_png(&self, scale: u32, height: u32) -> Vec<u8> {
+        let width = self.width(scale);
+
+        // One row, repeated: a filter byte (0, none), then one bit per
+        // pixel, 0 for black
+        let mut row = vec![0u8; 1 + (width as usize).div_ceil(8)];
+        for x in 0..width as usize {
+            let module = (x / scale as usize).checked_sub(QUIET_ZONE);
+            let bar = module.is_some_and(|m| self.modules.get(m) == Some(&true));
+            if !bar {
+                row[1 + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        let pixels = row.repeat(height as usize);
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        // 1 bit per pixel, greyscale, deflate, no filtering, not interlaced
+        header.extend_from_slice(&[1, 0, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        push_chunk(&mut png, b"IHDR", &header);
+        push_chunk(&mut png, b"IDAT", &zlib_stored(&pixels));
+        push_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// The image's width: the modules and the quiet zones, `scale` each.
+    fn width(&self, scale: u32) -> u32 {
+        (self.modules.len() + 2 * QUIET_ZONE) as u32 * scale
+    }
+
+    /// The bars, as each one's first module and its width in modules.
+    fn bars(&self) -> Vec<(usize, usize)> {
+        let mut bars: Vec<(usize, usize)> = Vec::new();
+        for (index, &bar) in self.modules.iter().enumerate() {
+            if !bar {
+                continue;
+            }
+            match bars.last_mut() {
+                Some((start, length)) if *start + *length == index => *length += 1,
+                _ => bars.push((index, 1)),
+            }
+        }
+        bars
+    }
+}
+
+/// Append the modules of alternating bars and spaces, starting with a bar,
+/// with the given widths (as ASCII digits).
+fn push_widths(modules: &mut Vec<bool>, widths: &[u8]) {
+    for (index, width) in widths.iter().enumerate() {
+        let bar = index % 2 == 0;
+        modules.extend(std::iter::repeat_n(bar, usize::from(width - b'0')));
+    }
+}
+
+/// The modules of a pattern written as ASCII `0`s and `1`s.
+fn bits(pattern: &[u8]) -> Vec<bool> {
+    pattern.iter().map(|&bit| bit == b'1').collect()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Append a PNG chunk: its length, type, data and CRC.
+fn push_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// `data` as a zlib stream of uncompressed ("stored") deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate, 32K window, no dictionary, fastest compression
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        stream.push(u8::from(last));
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+/// CRC-32 (ISO 3309), the checksum of a PNG chunk.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Adler-32, the checksum of a zlib stream.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,63 @@
+// ============================================================================
+// bench_support.rs - Large Catalogues for Benchmarks (the `testing` feature)
+// ============================================================================
+// The store layer is only slow for catalogues far bigger than anyone types
+// in by hand. These functions build such catalogues, of any size, from
+// `FruitGenerator` with a fixed seed, so every run of a benchmark works on
+// exactly the same data and two runs can be compared.
+//
+// `benches/store.rs` uses them to time loading, saving, lookups and
+// statistics:
+//
+//     cargo bench --features testing
+// ============================================================================
+
+use crate::catalog::{save_catalogue, Catalogue};
+use crate::models::FruitDimensions;
+use crate::testing::FruitGenerator;
+use std::error::Error;
+
+/// The seed every benchmark catalogue is generated from.
+pub const SEED: u64 = 42;
+
+/// Sizes that show how an operation scales: from a greengrocer's list to a
+/// wholesaler's.
+pub const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+/// `size` fruits with distinct names, the same on every call.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::bench_support::fruits;
+///
+/// assert_eq!(fruits(1000).len(), 1000);
+/// assert_eq!(fruits(10), fruits(10));
+/// ```
+pub fn fruits(size: usize) -> Vec<FruitDimensions> {
+    FruitGenerator::new(SEED).fruits(size)
+}
+
+/// A catalogue of `fruits(size)`.
+pub fn catalogue(size: usize) -> Catalogue {
+    Catalogue::new(fruits(size))
+}
+
+/// Write a catalogue of `fruits(size)` to `path`, as `save` would.
+pub fn write_catalogue(path: &str, size: usize) -> Result<(), Box<dyn Error>> {
+    save_catalogue(&fruits(size), path)
+}
+
+/// Names to look up in a catalogue of `fruits(size)`: `count` that are in
+/// it, spread across the whole catalogue, followed by one that isn't.
+pub fn lookup_names(size: usize, count: usize) -> Vec<String> {
+    let fruits = fruits(size);
+    let step = (size / count.max(1)).max(1);
+    let mut names: Vec<String> = fruits
+        .iter()
+        .step_by(step)
+        .take(count)
+        .map(|fruit| fruit.name.clone())
+        .collect();
+    names.push("Not a fruit".to_string());
+    names
+}
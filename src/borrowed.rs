@@ -0,0 +1,126 @@
+// ============================================================================
+// borrowed.rs - Reading Fruits Without Copying Them
+// ============================================================================
+// A pipeline that only reads a catalogue (to sum volumes, or find the
+// longest fruit) doesn't need its own copy of every name. A
+// `FruitDimensionsRef` borrows its strings from the buffer it was parsed
+// from, e.g. a memory-mapped file:
+//
+//     let fruits: Vec<FruitDimensionsRef> = serde_json::from_slice(&mapped)?;
+//
+// Only names that contain escapes (`"Caf\u00e9"`) are copied, because their
+// text in the buffer isn't the name itself. Convert a fruit to
+// `FruitDimensions` when it needs changing.
+//
+// Keys fruitdata doesn't know are skipped rather than kept in `extra`, as
+// keeping them would mean copying them, and so are `measurements`; load
+// with `Catalogue::load` when they matter.
+// ============================================================================
+
+use crate::expr::Expression;
+use crate::models::FruitDimensions;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A read-only fruit whose strings borrow from the JSON it was parsed from.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::borrowed::FruitDimensionsRef;
+/// use fruitdata::models::FruitDimensions;
+/// use std::borrow::Cow;
+///
+/// let json = br#"[
+///     {"name": "Apple", "length": 4.0, "width": 2.5, "height": 1.5, "origin": "Kent"},
+///     {"name": "Caf\u00e9 Pear", "lengthCm": 5.0, "width": 3.0, "height": 3.0}
+/// ]"#;
+/// let fruits: Vec<FruitDimensionsRef> = serde_json::from_slice(json).unwrap();
+///
+/// assert!(matches!(fruits[0].name, Cow::Borrowed("Apple")));
+/// assert!(matches!(fruits[1].name, Cow::Owned(_))); // unescaped, so copied
+/// assert_eq!(fruits[1].name, "Café Pear");
+/// assert_eq!(fruits[0].volume(), 15.0);
+///
+/// let apple = FruitDimensions::try_from(&fruits[0]).unwrap();
+/// assert_eq!(apple, FruitDimensions::new("Apple", 4.0, 2.5, 1.5)); // "origin" skipped
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct FruitDimensionsRef<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+
+    #[serde(
+        alias = "len",
+        alias = "lengthCm",
+        alias = "length_cm",
+        deserialize_with = "crate::models::finite"
+    )]
+    pub length: f32,
+
+    #[serde(
+        alias = "w",
+        alias = "widthCm",
+        alias = "width_cm",
+        deserialize_with = "crate::models::finite"
+    )]
+    pub width: f32,
+
+    #[serde(
+        alias = "h",
+        alias = "heightCm",
+        alias = "height_cm",
+        deserialize_with = "crate::models::finite"
+    )]
+    pub height: f32,
+
+    #[serde(default)]
+    pub id: Option<u64>,
+
+    /// The formula's text; it is only parsed when it's used.
+    #[serde(default, borrow)]
+    pub volume_formula: Option<Cow<'a, str>>,
+}
+
+impl FruitDimensionsRef<'_> {
+    /// The fruit's volume, as `FruitDimensions::volume` computes it. A
+    /// formula that doesn't parse is ignored, like one that can't be
+    /// computed.
+    pub fn volume(&self) -> f32 {
+        let fruit = FruitDimensions::new("", self.length, self.width, self.height);
+        let box_volume = fruit.volume();
+        match self.formula() {
+            Some(Ok(formula)) => formula
+                .evaluate_with_volume(&fruit, box_volume)
+                .unwrap_or(box_volume),
+            _ => box_volume,
+        }
+    }
+
+    fn formula(&self) -> Option<Result<Expression, String>> {
+        self.volume_formula.as_deref().map(str::parse)
+    }
+}
+
+impl TryFrom<&FruitDimensionsRef<'_>> for FruitDimensions {
+    type Error = String;
+
+    /// Copy the fruit into an owned one.
+    ///
+    /// # Error Cases
+    /// The volume formula doesn't parse.
+    fn try_from(fruit: &FruitDimensionsRef<'_>) -> Result<Self, Self::Error> {
+        let mut owned =
+            FruitDimensions::new(fruit.name.as_ref(), fruit.length, fruit.width, fruit.height);
+        owned.id = fruit.id;
+        owned.volume_formula = fruit.formula().transpose()?.map(Box::new);
+        Ok(owned)
+    }
+}
+
+impl TryFrom<FruitDimensionsRef<'_>> for FruitDimensions {
+    type Error = String;
+
+    fn try_from(fruit: FruitDimensionsRef<'_>) -> Result<Self, Self::Error> {
+        FruitDimensions::try_from(&fruit)
+    }
+}
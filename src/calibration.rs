@@ -0,0 +1,137 @@
+// ============================================================================
+// calibration.rs - How a Catalogue's Fruits Were Measured
+// ============================================================================
+// Datasets measured with different calipers don't quite agree: one reads in
+// millimetres, another reads 0.2 mm too much when closed. An object-layout
+// catalogue file (see document.rs) can say how its fruits were measured in a
+// `"calibration"` header:
+//
+//     { "calibration": { "device": "Mitutoyo 500-196", "unit": "mm", "offset": 0.2 },
+//       "fruits": [ ... ] }
+//
+// The dimensions in the file are then readings in `unit`, each `offset` too
+// big. Nothing changes while the header is only recorded; applying it
+// (`Catalogue::calibrate`, or `fruitdata convert --calibrate`) corrects every
+// dimension and measurement to centimetres, and leaves the header saying so
+// (unit `cm`, offset 0), so applying it twice is harmless.
+// ============================================================================
+
+use crate::length::Centimeters;
+use crate::models::FruitDimensions;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A unit a length may be measured in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LengthUnit {
+    /// Centimetres, the unit fruitdata stores dimensions in.
+    #[default]
+    Cm,
+    Mm,
+    /// Inches.
+    In,
+}
+
+impl LengthUnit {
+    /// How many centimetres one of this unit is.
+    pub fn in_cm(self) -> f64 {
+        match self {
+            LengthUnit::Cm => 1.0,
+            LengthUnit::Mm => 0.1,
+            LengthUnit::In => 2.54,
+        }
+    }
+}
+
+impl FromStr for LengthUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "cm" => Ok(LengthUnit::Cm),
+            "mm" => Ok(LengthUnit::Mm),
+            "in" | "inch" | "inches" => Ok(LengthUnit::In),
+            other => Err(format!("unknown unit '{}' (expected cm, mm or in)", other)),
+        }
+    }
+}
+
+impl fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LengthUnit::Cm => write!(f, "cm"),
+            LengthUnit::Mm => write!(f, "mm"),
+            LengthUnit::In => write!(f, "in"),
+        }
+    }
+}
+
+/// The `"calibration"` header: what the fruits were measured with.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::calibration::{Calibration, LengthUnit};
+/// use fruitdata::models::FruitDimensions;
+///
+/// let calibration: Calibration =
+///     serde_json::from_str(r#"{"device": "Caliper B", "unit": "mm", "offset": 0.5}"#).unwrap();
+/// assert_eq!(calibration.correct(40.5), 4.0); // (40.5 - 0.5) mm
+///
+/// let mut apple = FruitDimensions::new("Apple", 40.5, 25.5, 15.5);
+/// calibration.apply(&mut apple);
+/// assert_eq!([apple.length, apple.width, apple.height], [4.0, 2.5, 1.5]);
+///
+/// let applied = calibration.applied();
+/// assert_eq!(applied.unit, LengthUnit::Cm);
+/// assert!(applied.is_identity());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Calibration {
+    /// The measuring device, e.g. a caliper's model or serial number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// The unit of the dimensions and of `offset`.
+    #[serde(default)]
+    pub unit: LengthUnit,
+    /// What the device reads for a length of zero; subtracted from every
+    /// reading.
+    #[serde(default)]
+    pub offset: f32,
+}
+
+impl Calibration {
+    /// Whether applying the calibration would change nothing.
+    pub fn is_identity(&self) -> bool {
+        self.unit == LengthUnit::Cm && self.offset == 0.0
+    }
+
+    /// The length in centimetres of a `reading` taken with the device.
+    pub fn correct(&self, reading: f32) -> Centimeters {
+        Centimeters(((f64::from(reading) - f64::from(self.offset)) * self.unit.in_cm()) as f32)
+    }
+
+    /// Correct the dimensions of `fruit` and of each of its measurements.
+    pub fn apply(&self, fruit: &mut FruitDimensions) {
+        fruit.length = self.correct(fruit.length.value());
+        fruit.width = self.correct(fruit.width.value());
+        fruit.height = self.correct(fruit.height.value());
+        for measurement in &mut fruit.measurements {
+            measurement.length = self.correct(measurement.length.value());
+            measurement.width = self.correct(measurement.width.value());
+            measurement.height = self.correct(measurement.height.value());
+        }
+    }
+
+    /// The header for fruits this calibration has been applied to: the
+    /// same device, in centimetres, without an offset.
+    pub fn applied(&self) -> Calibration {
+        Calibration {
+            device: self.device.clone(),
+            unit: LengthUnit::Cm,
+            offset: 0.0,
+        }
+    }
+}
@@ -5,18 +5,52 @@
 // fruit catalogue. It provides three main functions:
 //
 // 1. load_catalogue() - Read fruits from a JSON file into memory
+//    (load_catalogue_partial() skips bad records instead of failing)
 // 2. save_catalogue() - Write fruits from memory to a JSON file
-// 3. initialise_fruit_catalogue() - Create a default catalogue if the file
-//    doesn't exist or can't be read
+//    (save_catalogue_with() chooses the layout through `SaveOptions`)
+// 3. initialise_fruit_catalogue() - Create a default catalogue for a new
+//    file (`init`, or `--create` when the file doesn't exist)
+//
+// It also defines the `Catalogue` type, which holds the fruits in memory,
+// validates every add/remove/rename/patch against the catalogue's rules, and
+// describes each accepted change as a `CatalogEvent`.
 //
 // Key concept: Persistence means data survives when the program exits.
 // Without these functions, changes to the fruit list would disappear when
 // the CLI program terminates. By saving to JSON files, we preserve the data.
 // ============================================================================
 
-use crate::models::FruitDimensions;
+use crate::calibration::Calibration;
+use crate::csv::{self, CsvOptions};
+use crate::document::{
+    calibration_of, case_sensitivity_of, plausible_range_of, set_calibration, CatalogueDocument,
+    Envelope,
+};
+use crate::error::{CatalogError, RecordError};
+use crate::events::{diff_in, CatalogEvent};
+use crate::expr::ComputedField;
+use crate::format::{self, Format};
+use crate::hooks::HookSettings;
+use crate::length::Centimeters;
+use crate::measurements::Measurement;
+use crate::merge::three_way_merge_in;
+use crate::models::{FieldNaming, FruitDimensions, FruitPatch};
+use crate::names::{closest_names, collate, CaseSensitivity};
+use crate::policy::{ConflictPolicy, Limits, NamePolicy, OutOfRange, PlausibleRange, Uniqueness};
+use crate::query::Query;
+use crate::stats::{group_by, Aggregate};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
+use std::fmt;
 use std::fs;
+use std::io::ErrorKind;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender};
 
 /// Load the fruit catalogue from a JSON file.
 ///
@@ -24,8 +58,9 @@ use std::fs;
 /// a Vec (vector/list) of FruitDimensions structs.
 ///
 /// # How it works
-/// 1. `fs::read_to_string(path)` reads the entire file into a String
-/// 2. `serde_json::from_str(&json)` parses the JSON string into Rust structs
+/// 1. `fs::read(path)` reads the entire file into memory
+/// 2. `CatalogueDocument::from_slice` parses the JSON into Rust structs
+///    (either a bare array of fruits or an object with a `"fruits"` key)
 /// 3. If either step fails, we return the error wrapped in a Box
 ///
 /// # Arguments
@@ -43,6 +78,7 @@ use std::fs;
 ///
 /// # Example Usage
 /// ```
+/// # use fruitdata::catalog::load_catalogue;
 /// match load_catalogue("fruits.json") {
 ///     Ok(fruits) => println!("Loaded {} fruits", fruits.len()),
 ///     Err(e) => eprintln!("Failed to load: {}", e),
@@ -51,15 +87,43 @@ use std::fs;
 pub fn load_catalogue(path: &str) -> Result<Vec<FruitDimensions>, Box<dyn Error>> {
     // Step 1: Read the entire file contents into a String
     // The `?` operator means "if this fails, return the error immediately"
-    let json = fs::read_to_string(path)?;
+    let json = fs::read(path)?;
 
-    // Step 2: Parse the JSON string into a Vec of FruitDimensions
+    // Step 2: Parse the JSON into a Vec of FruitDimensions
     // serde_json automatically uses the #[derive(Deserialize)] we set up in models.rs
     // to know how to convert JSON into our struct
-    let fruits = serde_json::from_str(&json)?;
+    let document = CatalogueDocument::from_slice(&json)?;
 
     // Step 3: Return the successfully loaded fruits
-    Ok(fruits)
+    Ok(document.fruits)
+}
+
+/// Load the fruit catalogue from a JSON file, skipping bad records.
+///
+/// Works like `load_catalogue`, except that one fruit with, say, a string
+/// where a number should be doesn't stop the others from loading. Each
+/// skipped fruit is described by a `RecordError` (see
+/// `CatalogueDocument::from_slice_partial`).
+///
+/// # Error Cases
+/// - File doesn't exist or can't be read
+/// - JSON is malformed, or isn't a list of records
+///
+/// # Example Usage
+/// ```no_run
+/// # use fruitdata::catalog::load_catalogue_partial;
+/// let (fruits, errors) = load_catalogue_partial("fruits.json").unwrap();
+/// for error in errors {
+///     eprintln!("Skipped: {}", error);
+/// }
+/// println!("Loaded {} fruits", fruits.len());
+/// ```
+pub fn load_catalogue_partial(
+    path: &str,
+) -> Result<(Vec<FruitDimensions>, Vec<RecordError>), Box<dyn Error>> {
+    let json = fs::read(path)?;
+    let (document, errors) = CatalogueDocument::from_slice_partial(&json)?;
+    Ok((document.fruits, errors))
 }
 
 /// Save the fruit catalogue to a JSON file.
@@ -94,9 +158,11 @@ pub fn load_catalogue(path: &str) -> Result<Vec<FruitDimensions>, Box<dyn Error>
 /// - Writes formatted/indented JSON (easier to read manually)
 ///
 /// # Example Usage
-/// ```
+/// ```no_run
+/// # use fruitdata::catalog::save_catalogue;
+/// # use fruitdata::models::FruitDimensions;
 /// let fruits = vec![
-///     FruitDimensions { name: "Apple".into(), length: 4.0, width: 2.5, height: 1.5 },
+///     FruitDimensions::new("Apple", 4.0, 2.5, 1.5),
 /// ];
 /// if let Err(e) = save_catalogue(&fruits, "fruits.json") {
 ///     eprintln!("Failed to save: {}", e);
@@ -116,10 +182,270 @@ pub fn save_catalogue(fruits: &[FruitDimensions], path: &str) -> Result<(), Box<
     Ok(())
 }
 
+/// How catalogue files are laid out when they are written.
+///
+/// The default (pretty-printed, two-space indent, fields in struct order)
+/// is what `save_catalogue` has always produced. Compact output is roughly
+/// half the size, which matters for large catalogues. `csv` applies to
+/// CSV files, read as well as written (see csv.rs).
+///
+/// # Config file example
+/// ```json
+/// { "save": { "pretty": false, "csv": { "delimiter": ";" } } }
+/// ```
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::SaveOptions;
+/// use fruitdata::models::FruitDimensions;
+///
+/// let fruits = vec![FruitDimensions::new("Apple", 4.0, 2.8, 1.5)];
+/// let compact = SaveOptions { pretty: false, sort_keys: true, ..SaveOptions::default() };
+/// assert_eq!(
+///     compact.to_json(&fruits).unwrap(),
+///     r#"[{"height":1.5,"length":4.0,"name":"Apple","width":2.8}]"#
+/// );
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SaveOptions {
+    /// Spread the JSON over several indented lines (default: `true`).
+    pub pretty: bool,
+    /// Spaces per indentation level when `pretty` (default: 2).
+    pub indent: usize,
+    /// Write each fruit's fields in alphabetical order instead of struct
+    /// order, which makes diffs of hand-edited files more predictable.
+    pub sort_keys: bool,
+    /// The field names to write (`"plain"`, `"camelCase"` or `"snake_case"`).
+    pub field_naming: FieldNaming,
+    /// The delimiter and header row of CSV files.
+    pub csv: CsvOptions,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions {
+            pretty: true,
+            indent: 2,
+            sort_keys: false,
+            field_naming: FieldNaming::Plain,
+            csv: CsvOptions::default(),
+        }
+    }
+}
+
+impl SaveOptions {
+    /// Serialize fruits to JSON laid out according to these options.
+    pub fn to_json(&self, fruits: &[FruitDimensions]) -> Result<String, serde_json::Error> {
+        self.document_to_json(fruits, None)
+    }
+
+    /// Serialize fruits in a file's layout: a bare array when `extra` is
+    /// `None`, otherwise an object with the `extra` keys and `"fruits"`
+    /// (see `CatalogueDocument`).
+    pub fn document_to_json(
+        &self,
+        fruits: &[FruitDimensions],
+        extra: Option<&Map<String, Value>>,
+    ) -> Result<String, serde_json::Error> {
+        let named: Vec<_> = fruits.iter().map(|f| self.field_naming.named(f)).collect();
+        match extra {
+            None => self.arrange(&named),
+            Some(extra) => self.arrange(&Envelope {
+                extra,
+                fruits: &named,
+            }),
+        }
+    }
+
+    fn arrange<T: Serialize>(&self, value: &T) -> Result<String, serde_json::Error> {
+        if self.sort_keys {
+            // `serde_json::Value` objects keep their keys in a BTreeMap,
+            // so converting first puts every fruit's fields in order. The
+            // detour through text keeps each f32 as it was written:
+            // `to_value` would widen 2.8 to 2.799999952316284.
+            let sorted: Value = serde_json::from_str(&serde_json::to_string(value)?)?;
+            self.layout(&sorted)
+        } else {
+            self.layout(value)
+        }
+    }
+
+    fn layout<T: Serialize + ?Sized>(&self, value: &T) -> Result<String, serde_json::Error> {
+        if !self.pretty {
+            return serde_json::to_string(value);
+        }
+
+        let indent = " ".repeat(self.indent);
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+        let mut bytes = Vec::new();
+        value.serialize(&mut serde_json::Serializer::with_formatter(
+            &mut bytes, formatter,
+        ))?;
+        // serde_json only ever writes valid UTF-8
+        Ok(String::from_utf8(bytes).expect("serde_json produced invalid UTF-8"))
+    }
+}
+
+/// Save the fruit catalogue to a JSON file with the given layout.
+///
+/// Works like `save_catalogue`, which is the same as calling this with
+/// `SaveOptions::default()`.
+///
+/// # Example Usage
+/// ```no_run
+/// # use fruitdata::catalog::{initialise_fruit_catalogue, save_catalogue_with, SaveOptions};
+/// let compact = SaveOptions { pretty: false, ..SaveOptions::default() };
+/// save_catalogue_with(&initialise_fruit_catalogue(), "fruits.json", &compact).unwrap();
+/// ```
+pub fn save_catalogue_with(
+    fruits: &[FruitDimensions],
+    path: &str,
+    options: &SaveOptions,
+) -> Result<(), Box<dyn Error>> {
+    fs::write(path, options.to_json(fruits)?)?;
+    Ok(())
+}
+
+/// Load the fruit catalogue from a CSV file laid out as `options` say, one
+/// fruit per row (see csv.rs for the columns).
+///
+/// # Error Cases
+/// - File doesn't exist or can't be read
+/// - A row isn't a valid fruit (the error names the row)
+///
+/// # Example Usage
+/// ```no_run
+/// # use fruitdata::catalog::load_catalogue_csv;
+/// # use fruitdata::csv::CsvOptions;
+/// let options = CsvOptions::default().with_delimiter(';');
+/// let fruits = load_catalogue_csv("fruits.csv", &options).unwrap();
+/// println!("Loaded {} fruits", fruits.len());
+/// ```
+pub fn load_catalogue_csv(
+    path: &str,
+    options: &CsvOptions,
+) -> Result<Vec<FruitDimensions>, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    Ok(csv::to_fruits(&text, options).map_err(|e| format!("{}: {}", path, e))?)
+}
+
+/// Save the fruit catalogue to a CSV file laid out as `options` say, one
+/// fruit per row. Measurements aren't written (see csv.rs); everything
+/// else reads back the same with `load_catalogue_csv`.
+///
+/// # Example Usage
+/// ```no_run
+/// # use fruitdata::catalog::{initialise_fruit_catalogue, save_catalogue_csv};
+/// # use fruitdata::csv::CsvOptions;
+/// save_catalogue_csv(&initialise_fruit_catalogue(), "fruits.csv", &CsvOptions::default()).unwrap();
+/// ```
+pub fn save_catalogue_csv(
+    fruits: &[FruitDimensions],
+    path: &str,
+    options: &CsvOptions,
+) -> Result<(), Box<dyn Error>> {
+    fs::write(path, csv::from_fruits(fruits, options)?)?;
+    Ok(())
+}
+
+/// Load the fruit catalogue from a TOML file, one `[[fruits]]` table per
+/// fruit (see toml.rs).
+///
+/// # Error Cases
+/// - File doesn't exist or can't be read
+/// - The file isn't valid TOML (the error names the line)
+/// - A fruit is missing a field or has a wrongly typed one
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{load_catalogue_toml, save_catalogue_toml};
+/// use fruitdata::models::FruitDimensions;
+///
+/// let path = std::env::temp_dir().join("fruitdata-example.toml");
+/// let path = path.to_str().unwrap();
+/// save_catalogue_toml(&[FruitDimensions::new("Apple", 4.0, 2.5, 1.5)], path).unwrap();
+/// assert!(std::fs::read_to_string(path).unwrap().starts_with("[[fruits]]\nname = \"Apple\"\n"));
+/// assert_eq!(load_catalogue_toml(path).unwrap()[0].name, "Apple");
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+#[cfg(feature = "toml")]
+pub fn load_catalogue_toml(path: &str) -> Result<Vec<FruitDimensions>, Box<dyn Error>> {
+    load_document_as(path, Format::Toml)
+}
+
+/// Save the fruit catalogue to a TOML file, laid out like `save_catalogue`
+/// lays out JSON.
+#[cfg(feature = "toml")]
+pub fn save_catalogue_toml(fruits: &[FruitDimensions], path: &str) -> Result<(), Box<dyn Error>> {
+    save_document_as(fruits, path, Format::Toml)
+}
+
+/// Load the fruit catalogue from a YAML file: a list of fruits, or a
+/// mapping with a `fruits` key (see yaml.rs).
+///
+/// # Error Cases
+/// - File doesn't exist or can't be read
+/// - The file isn't valid YAML (the error names the line)
+/// - A fruit is missing a field or has a wrongly typed one
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{load_catalogue_yaml, save_catalogue_yaml};
+/// use fruitdata::models::FruitDimensions;
+///
+/// let path = std::env::temp_dir().join("fruitdata-example.yaml");
+/// let path = path.to_str().unwrap();
+/// save_catalogue_yaml(&[FruitDimensions::new("Apple", 4.0, 2.5, 1.5)], path).unwrap();
+/// assert!(std::fs::read_to_string(path).unwrap().starts_with("- name: Apple\n  length: 4.0\n"));
+/// assert_eq!(load_catalogue_yaml(path).unwrap()[0].name, "Apple");
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+#[cfg(feature = "yaml")]
+pub fn load_catalogue_yaml(path: &str) -> Result<Vec<FruitDimensions>, Box<dyn Error>> {
+    load_document_as(path, Format::Yaml)
+}
+
+/// Save the fruit catalogue to a YAML file, laid out like `save_catalogue`
+/// lays out JSON.
+#[cfg(feature = "yaml")]
+pub fn save_catalogue_yaml(fruits: &[FruitDimensions], path: &str) -> Result<(), Box<dyn Error>> {
+    save_document_as(fruits, path, Format::Yaml)
+}
+
+/// Load the fruits of the document at `path`, which is in `format`.
+#[cfg(any(feature = "toml", feature = "yaml"))]
+fn load_document_as(path: &str, format: Format) -> Result<Vec<FruitDimensions>, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    let json = format::document_json(&bytes, format).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(CatalogueDocument::from_slice(&json)?.fruits)
+}
+
+/// Save `fruits` as a document in `format` at `path`.
+#[cfg(any(feature = "toml", feature = "yaml"))]
+fn save_document_as(
+    fruits: &[FruitDimensions],
+    path: &str,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let json = SaveOptions::default().to_json(fruits)?;
+    fs::write(path, format::document_text(json, format)?)?;
+    Ok(())
+}
+
+/// The JSON of the catalogue file at `path`, which holds `bytes`: TOML and
+/// YAML files (by their extension) are translated, anything else is taken
+/// to be JSON already.
+fn file_json<'b>(path: &Path, bytes: &'b [u8]) -> Result<Cow<'b, [u8]>, String> {
+    format::document_json(bytes, Format::for_catalogue(path))
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
 /// Create and return a default catalogue of fruits.
 ///
-/// This function is called when the programme can't load an existing catalogue
-/// (e.g., the first time the user runs fruitdata, or if the file is deleted).
+/// This function is called when there is no catalogue to load yet (e.g.,
+/// the first time the user runs fruitdata with `--create`). A file that
+/// exists but can't be read is an error instead, so it is never replaced.
 /// It provides a sensible starting point with a few common fruits.
 ///
 /// # Why this exists
@@ -137,40 +463,1565 @@ pub fn save_catalogue(fruits: &[FruitDimensions], path: &str) -> Result<(), Box<
 ///
 /// # Example Usage
 /// ```
+/// # use fruitdata::catalog::initialise_fruit_catalogue;
 /// let fruits = initialise_fruit_catalogue();
 /// println!("Default catalogue has {} fruits", fruits.len()); // prints: 4
 /// ```
 pub fn initialise_fruit_catalogue() -> Vec<FruitDimensions> {
     // Use `vec![]` macro to create a vector with initial values
     // Each FruitDimensions is constructed with specific dimensions
+    // (name, length, width, height)
     vec![
         // Orange - Medium-sized, roughly spherical
-        FruitDimensions {
-            name: "Orange".into(), // .into() converts &str to String
-            length: 5.0,
-            width: 3.0,
-            height: 2.0,
-        },
+        FruitDimensions::new("Orange", 5.0, 3.0, 2.0),
         // Apple - Small, roughly spherical
-        FruitDimensions {
-            name: "Apple".into(),
-            length: 4.0,
-            width: 2.5,
-            height: 1.5,
-        },
+        FruitDimensions::new("Apple", 4.0, 2.5, 1.5),
         // Banana - Long and thin, elongated
-        FruitDimensions {
-            name: "Banana".into(),
-            length: 6.0,
-            width: 3.5,
-            height: 2.5,
-        },
+        FruitDimensions::new("Banana", 6.0, 3.5, 2.5),
         // Pear - Similar to banana, slightly different proportions
-        FruitDimensions {
-            name: "Pear".into(),
-            length: 6.0,
-            width: 3.5,
-            height: 2.5,
-        },
+        FruitDimensions::new("Pear", 6.0, 3.5, 2.5),
     ]
 }
+
+/// The fruits a new catalogue starts with (see `initialise_with_template`).
+///
+/// Parses from a built-in template's name (`basic`, `tropical`, `citrus`,
+/// `empty`), or from the path of a catalogue file to copy the fruits of.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::CatalogueTemplate;
+///
+/// assert_eq!("Citrus".parse(), Ok(CatalogueTemplate::Citrus));
+/// assert_eq!(
+///     "templates/orchard.json".parse(),
+///     Ok(CatalogueTemplate::File("templates/orchard.json".into()))
+/// );
+/// assert!("tropcial".parse::<CatalogueTemplate>().is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CatalogueTemplate {
+    /// The four fruits of `initialise_fruit_catalogue`. This is the default.
+    #[default]
+    Basic,
+    /// Mango, pineapple, papaya and other tropical fruits.
+    Tropical,
+    /// Oranges, lemons, limes and other citrus fruits.
+    Citrus,
+    /// No fruits at all.
+    Empty,
+    /// The fruits of a catalogue file, in either layout.
+    File(PathBuf),
+}
+
+impl FromStr for CatalogueTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "basic" => Ok(CatalogueTemplate::Basic),
+            "tropical" => Ok(CatalogueTemplate::Tropical),
+            "citrus" => Ok(CatalogueTemplate::Citrus),
+            "empty" => Ok(CatalogueTemplate::Empty),
+            // Anything that looks like a path is a custom template
+            _ if s.contains(['/', '\\']) || s.ends_with(".json") => {
+                Ok(CatalogueTemplate::File(PathBuf::from(s)))
+            }
+            other => Err(format!(
+                "unknown template '{}' (expected basic, tropical, citrus, empty or a .json file)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CatalogueTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogueTemplate::Basic => write!(f, "basic"),
+            CatalogueTemplate::Tropical => write!(f, "tropical"),
+            CatalogueTemplate::Citrus => write!(f, "citrus"),
+            CatalogueTemplate::Empty => write!(f, "empty"),
+            CatalogueTemplate::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// The fruits a new catalogue made from `template` starts with.
+///
+/// # Error Cases
+/// A `File` template can't be read, isn't a catalogue file, or has a fruit
+/// `Catalogue::add` would reject (a dimension that isn't positive, a name
+/// used twice).
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, initialise_with_template, CatalogueTemplate};
+///
+/// let fruits = initialise_with_template(&CatalogueTemplate::Tropical).unwrap();
+/// assert!(fruits.iter().any(|f| f.name == "Mango"));
+///
+/// let basic = initialise_with_template(&CatalogueTemplate::Basic).unwrap();
+/// assert_eq!(basic, initialise_fruit_catalogue());
+/// assert!(initialise_with_template(&CatalogueTemplate::Empty).unwrap().is_empty());
+///
+/// let path = std::env::temp_dir().join("fruitdata-template-example.json");
+/// std::fs::write(&path, r#"[{"name": "A", "length": 1, "width": 1, "height": 1},
+///     {"name": "a", "length": 2, "width": 2, "height": 2}]"#).unwrap();
+/// let error = initialise_with_template(&CatalogueTemplate::File(path.clone())).unwrap_err();
+/// assert!(error.to_string().ends_with("Fruit 'a' already exists."));
+///
+/// std::fs::write(&path, r#"[{"name": "A", "length": -1, "width": 1, "height": 1}]"#).unwrap();
+/// assert!(initialise_with_template(&CatalogueTemplate::File(path.clone())).is_err());
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub fn initialise_with_template(
+    template: &CatalogueTemplate,
+) -> Result<Vec<FruitDimensions>, Box<dyn Error>> {
+    Ok(match template {
+        CatalogueTemplate::Basic => initialise_fruit_catalogue(),
+        CatalogueTemplate::Tropical => vec![
+            FruitDimensions::new("Mango", 13.0, 9.0, 8.0),
+            FruitDimensions::new("Pineapple", 25.0, 12.0, 12.0),
+            FruitDimensions::new("Papaya", 20.0, 10.0, 10.0),
+            FruitDimensions::new("Passion Fruit", 7.0, 6.0, 6.0),
+            FruitDimensions::new("Guava", 8.0, 7.0, 7.0),
+            FruitDimensions::new("Lychee", 3.5, 3.0, 3.0),
+        ],
+        CatalogueTemplate::Citrus => vec![
+            FruitDimensions::new("Orange", 8.0, 8.0, 7.5),
+            FruitDimensions::new("Lemon", 9.0, 6.0, 6.0),
+            FruitDimensions::new("Lime", 5.5, 5.0, 5.0),
+            FruitDimensions::new("Grapefruit", 12.0, 12.0, 11.0),
+            FruitDimensions::new("Mandarin", 6.0, 6.0, 4.5),
+            FruitDimensions::new("Kumquat", 3.5, 2.5, 2.5),
+        ],
+        CatalogueTemplate::Empty => Vec::new(),
+        CatalogueTemplate::File(path) => {
+            let bytes = fs::read(path)
+                .map_err(|e| format!("could not read template '{}': {}", path.display(), e))?;
+            // The same checks as `add`: dimensions, names, duplicates
+            let mut catalogue = Catalogue::new(Vec::new());
+            for fruit in CatalogueDocument::from_slice(&file_json(path, &bytes)?)?.fruits {
+                catalogue
+                    .add(fruit)
+                    .map_err(|e| format!("invalid template '{}': {}", path.display(), e))?;
+            }
+            catalogue.into_fruits()
+        }
+    })
+}
+
+/// Compute a fingerprint of a file's contents (64-bit FNV-1a).
+///
+/// Any change to the bytes almost certainly changes the hash, which is all
+/// we need to notice that a file was modified. It is *not* a cryptographic
+/// hash and must not be used to detect deliberate tampering.
+///
+/// # Example Usage
+/// ```
+/// # use fruitdata::catalog::content_hash;
+/// assert_eq!(content_hash(b"[]"), content_hash(b"[]"));
+/// assert_ne!(content_hash(b"[]"), content_hash(b"[ ]"));
+/// ```
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// The volume of every fruit, in order.
+///
+/// This is the bulk form of `FruitDimensions::volume`, for code that needs
+/// the volumes of a whole catalogue at once. The fruits are taken `LANES`
+/// at a time: their dimensions are gathered into three small columns on
+/// the stack and multiplied together as in `box_volumes`, and the block's
+/// fruits with a `volume_formula` are recomputed while they are still in
+/// the cache. (Gathering whole-catalogue columns first costs more than the
+/// multiplying saves.) `cargo bench --features testing` times it against
+/// a `volume()` per fruit and against `box_volumes` on ready columns.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, volumes};
+///
+/// let mut fruits = initialise_fruit_catalogue();
+/// assert_eq!(volumes(&fruits), vec![30.0, 15.0, 52.5, 52.5]);
+///
+/// fruits[1].volume_formula = Some(Box::new("0.5 * volume".parse().unwrap()));
+/// assert_eq!(volumes(&fruits), vec![30.0, 7.5, 52.5, 52.5]);
+/// ```
+pub fn volumes(fruits: &[FruitDimensions]) -> Vec<f32> {
+    let mut volumes = Vec::with_capacity(fruits.len());
+    let mut blocks = fruits.chunks_exact(LANES);
+    for block in &mut blocks {
+        let column = |dimension: fn(&FruitDimensions) -> Centimeters| -> [f32; LANES] {
+            std::array::from_fn(|lane| dimension(&block[lane]).value())
+        };
+        let (l, w, h) = (
+            column(|f| f.length),
+            column(|f| f.width),
+            column(|f| f.height),
+        );
+        let mut products = multiply(&l, &w, &h);
+        // The block is still in the cache: fix up its formulas now
+        for (fruit, product) in block.iter().zip(&mut products) {
+            if fruit.volume_formula.is_some() {
+                *product = fruit.volume();
+            }
+        }
+        volumes.extend(products);
+    }
+    volumes.extend(blocks.remainder().iter().map(FruitDimensions::volume));
+    volumes
+}
+
+/// How many products `box_volumes` computes per step.
+const LANES: usize = 8;
+
+/// `length * width * height` for each position of three columns of
+/// dimensions, for callers that already hold them as columns. Formulas
+/// don't apply here: this is every fruit's box volume.
+///
+/// The columns are multiplied `LANES` at a time with fixed-size arrays,
+/// which the compiler turns into SIMD instructions on stable Rust; the
+/// remainder is done one by one.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::box_volumes;
+///
+/// let volumes = box_volumes(&[4.0, 6.0], &[2.5, 3.5], &[1.5, 2.5]);
+/// assert_eq!(volumes, vec![15.0, 52.5]);
+/// ```
+///
+/// # Panics
+/// The columns have different lengths.
+pub fn box_volumes(lengths: &[f32], widths: &[f32], heights: &[f32]) -> Vec<f32> {
+    assert!(
+        lengths.len() == widths.len() && widths.len() == heights.len(),
+        "dimension columns of different lengths"
+    );
+    let mut volumes = Vec::with_capacity(lengths.len());
+    let mut l = lengths.chunks_exact(LANES);
+    let mut w = widths.chunks_exact(LANES);
+    let mut h = heights.chunks_exact(LANES);
+    for ((l, w), h) in (&mut l).zip(&mut w).zip(&mut h) {
+        let lanes = |column: &[f32]| -> [f32; LANES] {
+            column.try_into().expect("chunks have LANES items")
+        };
+        volumes.extend(multiply(&lanes(l), &lanes(w), &lanes(h)));
+    }
+
+    let rest = l.remainder().iter().zip(w.remainder()).zip(h.remainder());
+    volumes.extend(rest.map(|((l, w), h)| l * w * h));
+    volumes
+}
+
+/// One step of the kernel: `LANES` products at once.
+fn multiply(l: &[f32; LANES], w: &[f32; LANES], h: &[f32; LANES]) -> [f32; LANES] {
+    std::array::from_fn(|lane| l[lane] * w[lane] * h[lane])
+}
+
+// ============================================================================
+// THE CATALOGUE TYPE
+// ============================================================================
+// The free functions above move plain `Vec<FruitDimensions>` values to and
+// from disk. `Catalogue` wraps such a Vec together with the rules that every
+// change must obey, so that "no duplicates" and the configured `NamePolicy`
+// are enforced by the library rather than by each caller.
+
+/// How a caller identifies the fruit an operation should act on.
+///
+/// Most code just passes a name (`&str` converts into `FruitKey::Name`).
+/// In ID mode, where names may repeat, an ID picks one exact entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FruitKey<'a> {
+    /// Match by name, case-insensitively.
+    Name(&'a str),
+    /// Match the entry with this ID.
+    Id(u64),
+}
+
+impl<'a> From<&'a str> for FruitKey<'a> {
+    fn from(name: &'a str) -> Self {
+        FruitKey::Name(name)
+    }
+}
+
+impl<'a> From<&'a String> for FruitKey<'a> {
+    fn from(name: &'a String) -> Self {
+        FruitKey::Name(name)
+    }
+}
+
+impl From<u64> for FruitKey<'_> {
+    fn from(id: u64) -> Self {
+        FruitKey::Id(id)
+    }
+}
+
+/// The receiving ends handed out by `Catalogue::subscribe`.
+///
+/// They belong to the catalogue they were made for, so cloning a catalogue
+/// (e.g. to edit a draft of it) doesn't clone them.
+#[derive(Debug, Default)]
+struct Subscribers(Vec<Sender<CatalogEvent>>);
+
+impl Subscribers {
+    fn notify(&mut self, event: &CatalogEvent) {
+        // Forget subscribers that have dropped their receiver
+        self.0.retain(|s| s.send(event.clone()).is_ok());
+    }
+}
+
+impl Clone for Subscribers {
+    fn clone(&self) -> Self {
+        Subscribers::default()
+    }
+}
+
+/// What a `Catalogue` remembers about the file it was loaded from, so that
+/// `save` can tell whether somebody else changed the file in the meantime.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    /// `content_hash` of the file's bytes when we last read or wrote it.
+    hash: u64,
+    /// The fruits that were in the file at that point (the merge "base").
+    fruits: Vec<FruitDimensions>,
+}
+
+/// An in-memory fruit catalogue that validates every change.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::error::CatalogError;
+/// use fruitdata::models::FruitDimensions;
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+///
+/// let kiwi = FruitDimensions::new(" Kiwi ", 3.0, 2.0, 2.0);
+/// catalogue.add(kiwi).unwrap();
+/// assert!(catalogue.get("KIWI").is_some()); // stored trimmed, matched case-insensitively
+///
+/// let again = FruitDimensions::new("kiwi", 1.0, 1.0, 1.0);
+/// assert_eq!(catalogue.add(again), Err(CatalogError::DuplicateName("kiwi".into())));
+///
+/// catalogue.rename("Kiwi", "Golden Kiwi").unwrap();
+/// assert!(catalogue.get("Kiwi").is_none());
+/// ```
+///
+/// # ID mode
+/// ```
+/// use fruitdata::catalog::{Catalogue, FruitKey};
+/// use fruitdata::error::CatalogError;
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::policy::Uniqueness;
+///
+/// let mut catalogue = Catalogue::new(Vec::new()).with_uniqueness(Uniqueness::Id);
+/// catalogue.add(FruitDimensions::new("Apple", 4.0, 2.5, 1.5)).unwrap(); // gets id 1
+/// catalogue.add(FruitDimensions::new("Apple", 4.2, 2.6, 1.4)).unwrap(); // gets id 2
+///
+/// assert_eq!(catalogue.find_all("apple").len(), 2);
+/// assert_eq!(
+///     catalogue.remove("Apple"),
+///     Err(CatalogError::AmbiguousName { name: "Apple".into(), ids: vec![1, 2] })
+/// );
+/// assert_eq!(catalogue.remove(FruitKey::Id(2)).unwrap().length, 4.2);
+/// assert_eq!(catalogue.find("Apple").unwrap().id, Some(1));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Catalogue {
+    fruits: Vec<FruitDimensions>,
+    /// Where each name is in `fruits`, folded as `case_sensitivity` says
+    /// (several positions in ID mode), so lookups don't scan every fruit.
+    names: HashMap<String, Vec<usize>>,
+    name_policy: NamePolicy,
+    limits: Limits,
+    plausible_range: PlausibleRange,
+    hooks: HookSettings,
+    uniqueness: Uniqueness,
+    case_sensitivity: CaseSensitivity,
+    conflict_policy: ConflictPolicy,
+    save_options: SaveOptions,
+    file_extra: Option<Map<String, Value>>,
+    loaded: Option<Snapshot>,
+    changes: Vec<CatalogEvent>,
+    subscribers: Subscribers,
+}
+
+impl Catalogue {
+    /// Wrap an existing list of fruits (e.g. one returned by `load_catalogue`).
+    ///
+    /// The fruits are taken as-is; validation only applies to later changes.
+    pub fn new(fruits: Vec<FruitDimensions>) -> Self {
+        let mut catalogue = Catalogue {
+            fruits,
+            names: HashMap::new(),
+            name_policy: NamePolicy::default(),
+            limits: Limits::default(),
+            plausible_range: PlausibleRange::default(),
+            hooks: HookSettings::default(),
+            uniqueness: Uniqueness::default(),
+            case_sensitivity: CaseSensitivity::default(),
+            conflict_policy: ConflictPolicy::default(),
+            save_options: SaveOptions::default(),
+            file_extra: None,
+            loaded: None,
+            changes: Vec::new(),
+            subscribers: Subscribers::default(),
+        };
+        catalogue.reindex();
+        catalogue
+    }
+
+    /// Load a catalogue from a JSON file, remembering what the file looked
+    /// like so that `save` can detect concurrent modifications. A `.toml`
+    /// or `.yaml` file is read as TOML or YAML with the `toml` and `yaml`
+    /// features, and `save` writes it back the same way.
+    ///
+    /// Fields fruitdata doesn't recognise, on each fruit or (in the object
+    /// layout, see `CatalogueDocument`) on the file itself, are kept and
+    /// written back unchanged by `save`.
+    ///
+    /// # Error Cases
+    /// The same as `load_catalogue`: missing/unreadable file, invalid JSON.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let bytes = fs::read(path)?;
+        Ok(Catalogue::from_loaded(
+            &bytes,
+            CatalogueDocument::from_slice(&file_json(Path::new(path), &bytes)?)?,
+        ))
+    }
+
+    /// Load a catalogue like `load`, but reject files with unknown keys or
+    /// wrongly typed values (see `CatalogueDocument::from_slice_strict`).
+    ///
+    /// # Error Cases
+    /// The same as `load`, plus `CatalogError::SchemaViolations`.
+    pub fn load_strict(path: &str) -> Result<Self, Box<dyn Error>> {
+        let bytes = fs::read(path)?;
+        let document = CatalogueDocument::from_slice_strict(&file_json(Path::new(path), &bytes)?)?;
+        Ok(Catalogue::from_loaded(&bytes, document))
+    }
+
+    /// Build a catalogue from a parsed file, remembering the file's contents.
+    fn from_loaded(bytes: &[u8], document: CatalogueDocument) -> Self {
+        let fruits = document.fruits;
+
+        let mut catalogue = Catalogue::new(fruits.clone());
+        // A broken header is for strict loading to report; until then the
+        // names match case-insensitively, as they always have
+        catalogue.case_sensitivity =
+            case_sensitivity_of(document.extra.as_ref()).unwrap_or_default();
+        catalogue.reindex();
+        catalogue.file_extra = document.extra;
+        catalogue.loaded = Some(Snapshot {
+            hash: content_hash(bytes),
+            fruits,
+        });
+        catalogue
+    }
+
+    /// Use the given naming rules for all future `add` and `rename` calls.
+    pub fn with_name_policy(mut self, policy: NamePolicy) -> Self {
+        self.name_policy = policy;
+        self
+    }
+
+    /// Enforce the given size limits on all future `add` and `save` calls.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Check the dimensions given to all future `add`, `apply_patch` and
+    /// `add_measurement` calls against `range`, unless the file has its own
+    /// `"plausible_range"` header.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    /// use fruitdata::error::CatalogError;
+    /// use fruitdata::models::FruitDimensions;
+    /// use fruitdata::policy::{OutOfRange, PlausibleRange};
+    ///
+    /// let range = PlausibleRange { min: Some(0.1), max: Some(100.0), on_violation: OutOfRange::Fail };
+    /// let mut catalogue = Catalogue::new(initialise_fruit_catalogue()).with_plausible_range(range);
+    ///
+    /// // Measured in mm by mistake
+    /// let result = catalogue.add(FruitDimensions::new("Kiwi", 60.0, 45.0, 450.0));
+    /// assert!(matches!(result, Err(CatalogError::ImplausibleDimensions { .. })));
+    /// ```
+    pub fn with_plausible_range(mut self, range: PlausibleRange) -> Self {
+        self.plausible_range = range;
+        self
+    }
+
+    /// Run the `pre_add` hooks (see hooks.rs) on every fruit given to a
+    /// future `add`, and on the new dimensions given to `apply_patch` and
+    /// `add_measurement`.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    /// use fruitdata::error::CatalogError;
+    /// use fruitdata::hooks::HookSettings;
+    /// use fruitdata::models::FruitPatch;
+    ///
+    /// let hooks: HookSettings = serde_json::from_str(r#"{
+    ///     "pre_add": [{ "require": ".length <= 30", "message": "too long to stock" }]
+    /// }"#).unwrap();
+    /// let mut catalogue = Catalogue::new(initialise_fruit_catalogue()).with_hooks(hooks);
+    ///
+    /// let patch = FruitPatch { length: Some(99.0), ..FruitPatch::default() };
+    /// let result = catalogue.apply_patch("Apple", &patch);
+    /// assert!(matches!(result, Err(CatalogError::HookRejected { .. })));
+    /// assert_eq!(catalogue.get("Apple").unwrap().length, 4.0);
+    /// ```
+    pub fn with_hooks(mut self, hooks: HookSettings) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// The range dimensions are checked against: the file's
+    /// `"plausible_range"` header, or else the one given to
+    /// `with_plausible_range`.
+    pub fn plausible_range(&self) -> PlausibleRange {
+        // A broken header is for strict loading to report
+        plausible_range_of(self.file_extra.as_ref())
+            .ok()
+            .flatten()
+            .unwrap_or(self.plausible_range)
+    }
+
+    /// Choose whether names or IDs must be unique.
+    ///
+    /// Switching to `Uniqueness::Id` gives every fruit that doesn't have an
+    /// ID yet a fresh one, so that each entry can be addressed exactly.
+    pub fn with_uniqueness(mut self, uniqueness: Uniqueness) -> Self {
+        self.uniqueness = uniqueness;
+        if uniqueness == Uniqueness::Id {
+            for index in 0..self.fruits.len() {
+                if self.fruits[index].id.is_none() {
+                    self.fruits[index].id = Some(self.next_id());
+                }
+            }
+
+            // The snapshot must use the same IDs, or a later merge would
+            // mistake every newly numbered fruit for one we added
+            if let Some(loaded) = &mut self.loaded {
+                for (base, fruit) in loaded.fruits.iter_mut().zip(&self.fruits) {
+                    if base.id.is_none() {
+                        base.id = fruit.id;
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Match names exactly, or ignoring case, for this run, whatever the
+    /// file's `"case_sensitive"` header says (see `set_case_sensitivity`).
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    /// use fruitdata::models::FruitDimensions;
+    /// use fruitdata::names::CaseSensitivity;
+    ///
+    /// let mut catalogue = Catalogue::new(initialise_fruit_catalogue())
+    ///     .with_case_sensitivity(CaseSensitivity::Sensitive);
+    /// catalogue.add(FruitDimensions::new("APPLE", 1.0, 1.0, 1.0)).unwrap(); // a SKU
+    ///
+    /// assert_eq!(catalogue.find_all("Apple").len(), 1);
+    /// assert!(catalogue.get("apple").is_none());
+    /// ```
+    pub fn with_case_sensitivity(mut self, case: CaseSensitivity) -> Self {
+        self.case_sensitivity = case;
+        self.reindex();
+        self
+    }
+
+    /// How names are matched: as the file's `"case_sensitive"` header says,
+    /// unless `with_case_sensitivity` chose otherwise.
+    pub fn case_sensitivity(&self) -> CaseSensitivity {
+        self.case_sensitivity
+    }
+
+    /// Match names as `case` says from now on, and record the choice as the
+    /// file's `"case_sensitive"` header. A bare-array file is saved in the
+    /// object layout from now on.
+    pub fn set_case_sensitivity(&mut self, case: CaseSensitivity) {
+        self.case_sensitivity = case;
+        self.reindex();
+        self.file_extra.get_or_insert_with(Map::new).insert(
+            "case_sensitive".to_string(),
+            Value::Bool(case == CaseSensitivity::Sensitive),
+        );
+    }
+
+    /// Choose what `save` does when the file changed since it was loaded.
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// Lay out the JSON written by `save` according to `options`.
+    pub fn with_save_options(mut self, options: SaveOptions) -> Self {
+        self.save_options = options;
+        self
+    }
+
+    /// All fruits, in catalogue order.
+    pub fn fruits(&self) -> &[FruitDimensions] {
+        &self.fruits
+    }
+
+    /// Give back the underlying list (e.g. to pass to `save_catalogue`).
+    pub fn into_fruits(self) -> Vec<FruitDimensions> {
+        self.fruits
+    }
+
+    /// How many fruits the catalogue holds.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    ///
+    /// let catalogue = Catalogue::new(initialise_fruit_catalogue());
+    /// assert_eq!(catalogue.len(), 4);
+    /// assert!(!catalogue.is_empty());
+    /// assert!(catalogue.contains("pear"));
+    /// assert_eq!(catalogue.iter().map(|f| f.name.as_str()).last(), Some("Pear"));
+    /// ```
+    pub fn len(&self) -> usize {
+        self.fruits.len()
+    }
+
+    /// Whether the catalogue holds no fruits.
+    pub fn is_empty(&self) -> bool {
+        self.fruits.is_empty()
+    }
+
+    /// The fruits, in catalogue order.
+    pub fn iter(&self) -> std::slice::Iter<'_, FruitDimensions> {
+        self.fruits.iter()
+    }
+
+    /// Whether a fruit has the given name (matched like `get`).
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Totals and averages over every fruit (see `stats::Aggregate`).
+    pub fn aggregate(&self) -> Aggregate {
+        Aggregate::of(&self.fruits)
+    }
+
+    /// Totals and averages for each group of fruits, where `key_fn` gives
+    /// the groups a fruit is in (see `stats::group_by`).
+    pub fn group_by<K: Ord, I: IntoIterator<Item = K>>(
+        &self,
+        key_fn: impl Fn(&FruitDimensions) -> I,
+    ) -> BTreeMap<K, Aggregate> {
+        group_by(&self.fruits, key_fn)
+    }
+
+    /// The computed fields defined in the file's `"computed"` header (see
+    /// expr.rs), in name order. A bare-array file has none.
+    ///
+    /// # Error Cases
+    /// The header isn't an object of expression strings, or an expression
+    /// doesn't parse.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::Catalogue;
+    ///
+    /// let path = std::env::temp_dir().join("fruitdata-computed-example.json");
+    /// std::fs::write(&path, r#"{"computed": {"aspect_ratio": "length / width"}, "fruits": [
+    ///     {"name": "Apple", "length": 4.0, "width": 2.5, "height": 1.5}
+    /// ]}"#).unwrap();
+    ///
+    /// let catalogue = Catalogue::load(path.to_str().unwrap()).unwrap();
+    /// let computed = catalogue.computed_fields().unwrap();
+    /// assert_eq!(computed[0].name, "aspect_ratio");
+    /// assert_eq!(computed[0].evaluate(&catalogue.fruits()[0]), Ok(1.6));
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn computed_fields(&self) -> Result<Vec<ComputedField>, String> {
+        let Some(header) = self.file_extra.as_ref().and_then(|e| e.get("computed")) else {
+            return Ok(Vec::new());
+        };
+        let Value::Object(fields) = header else {
+            return Err("\"computed\" must map field names to expressions".to_string());
+        };
+        fields
+            .iter()
+            .map(|(name, expression)| {
+                let expression = expression
+                    .as_str()
+                    .ok_or_else(|| format!("computed field '{}' must be a string", name))?
+                    .parse()
+                    .map_err(|e| format!("computed field '{}': {}", name, e))?;
+                Ok(ComputedField {
+                    name: name.clone(),
+                    expression,
+                })
+            })
+            .collect()
+    }
+
+    /// The file's `"calibration"` header (see calibration.rs), if it has
+    /// one.
+    ///
+    /// # Error Cases
+    /// The header isn't a valid calibration.
+    pub fn calibration(&self) -> Result<Option<Calibration>, String> {
+        calibration_of(self.file_extra.as_ref())
+    }
+
+    /// Record how the fruits were measured, saved as the file's
+    /// `"calibration"` header. The dimensions are left as they are; a
+    /// bare-array file is saved in the object layout from now on.
+    pub fn set_calibration(&mut self, calibration: &Calibration) {
+        set_calibration(&mut self.file_extra, calibration);
+    }
+
+    /// Correct every fruit with the file's calibration, and record that it
+    /// has been applied, e.g. right after `load`. Returns `false`, changing
+    /// nothing, when there is no calibration to apply.
+    ///
+    /// # Error Cases
+    /// - The header isn't a valid calibration
+    /// - `CatalogError::InvalidDimensions` - A corrected dimension isn't
+    ///   positive (nothing is changed)
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::Catalogue;
+    ///
+    /// let path = std::env::temp_dir().join("fruitdata-calibrate-example.json");
+    /// std::fs::write(&path, r#"{"calibration": {"device": "Caliper B", "unit": "mm", "offset": 0.5},
+    ///     "fruits": [{"name": "Apple", "length": 40.5, "width": 25.5, "height": 15.5}]}"#).unwrap();
+    ///
+    /// let mut catalogue = Catalogue::load(path.to_str().unwrap()).unwrap();
+    /// assert!(catalogue.calibrate().unwrap());
+    /// assert_eq!(catalogue.get("Apple").unwrap().volume(), 15.0);
+    /// assert!(catalogue.calibration().unwrap().unwrap().is_identity());
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn calibrate(&mut self) -> Result<bool, Box<dyn Error>> {
+        let Some(calibration) = self.calibration()? else {
+            return Ok(false);
+        };
+        if calibration.is_identity() {
+            return Ok(false);
+        }
+
+        let corrected: Vec<FruitDimensions> = self
+            .fruits
+            .iter()
+            .map(|fruit| {
+                let mut fruit = fruit.clone();
+                calibration.apply(&mut fruit);
+                fruit
+            })
+            .collect();
+        for fruit in &corrected {
+            validate_dimensions(fruit)?;
+        }
+
+        let before = std::mem::replace(&mut self.fruits, corrected);
+        for (before, after) in before.into_iter().zip(self.fruits.clone()) {
+            if before != after {
+                self.record(CatalogEvent::Updated { before, after });
+            }
+        }
+        self.set_calibration(&calibration.applied());
+        Ok(true)
+    }
+
+    /// Find a fruit by name (case-insensitive, unless the catalogue is
+    /// case-sensitive; see `with_case_sensitivity`).
+    ///
+    /// If several fruits share the name (ID mode), the first one is returned;
+    /// use `find_all` or `find` to handle that case explicitly.
+    ///
+    /// The names are indexed, so this takes the same time however many
+    /// fruits there are.
+    pub fn get(&self, name: &str) -> Option<&FruitDimensions> {
+        let index = *self.positions_of(name).first()?;
+        Some(&self.fruits[index])
+    }
+
+    /// Every fruit with the given name (matched like `get`), in catalogue
+    /// order.
+    pub fn find_all(&self, name: &str) -> Vec<&FruitDimensions> {
+        self.positions_of(name.trim())
+            .iter()
+            .map(|&index| &self.fruits[index])
+            .collect()
+    }
+
+    /// Whether two names are the same fruit's, under the catalogue's
+    /// `CaseSensitivity`.
+    fn names_equal(&self, a: &str, b: &str) -> bool {
+        self.case_sensitivity.names_equal(a, b)
+    }
+
+    /// The names of existing fruits that `name` may be a typo of, closest
+    /// first: those within two edits of it (one for names of up to three
+    /// letters, so that "Fig" doesn't suggest every short name).
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    ///
+    /// let catalogue = Catalogue::new(initialise_fruit_catalogue());
+    /// assert_eq!(catalogue.suggestions("Banan"), ["Banana"]);
+    /// assert!(catalogue.suggestions("Durian").is_empty());
+    /// ```
+    pub fn suggestions(&self, name: &str) -> Vec<&str> {
+        let name = name.trim();
+        let max_distance = (name.chars().count() / 2).min(2);
+        closest_names(
+            name,
+            self.fruits.iter().map(|f| f.name.as_str()),
+            max_distance,
+        )
+    }
+
+    /// The distinct names that start with `prefix` (matched like `get`), in
+    /// collation order, for completing a name on the command line.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    ///
+    /// let catalogue = Catalogue::new(initialise_fruit_catalogue());
+    /// assert_eq!(catalogue.names_starting_with("p"), ["Pear"]);
+    /// assert_eq!(catalogue.names_starting_with("").len(), 4);
+    /// ```
+    pub fn names_starting_with(&self, prefix: &str) -> Vec<&str> {
+        let prefix = self.case_sensitivity.fold(prefix);
+        let mut names: Vec<&str> = Vec::new();
+        for fruit in &self.fruits {
+            if self.case_sensitivity.fold(&fruit.name).starts_with(&prefix)
+                && !names.iter().any(|name| self.names_equal(name, &fruit.name))
+            {
+                names.push(&fruit.name);
+            }
+        }
+        names.sort_by(|a, b| collate(a, b));
+        names
+    }
+
+    /// How many fruits pass `filter`, a jq-style condition on each fruit
+    /// (e.g. `.length > 5`, see `Query::matches`), or how many there are
+    /// without one.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    ///
+    /// let catalogue = Catalogue::new(initialise_fruit_catalogue());
+    /// assert_eq!(catalogue.count(None).unwrap(), 4);
+    /// let long = ".length >= 6".parse().unwrap();
+    /// assert_eq!(catalogue.count(Some(&long)).unwrap(), 2); // Banana, Pear
+    /// ```
+    ///
+    /// # Error Cases
+    /// The filter fails on a fruit, e.g. `.name.first` (a field of a
+    /// string).
+    pub fn count(&self, filter: Option<&Query>) -> Result<usize, Box<dyn Error>> {
+        match filter {
+            Some(filter) => Ok(self.select(filter)?.len()),
+            None => Ok(self.fruits.len()),
+        }
+    }
+
+    /// The fruits that pass `filter`, a jq-style condition on each fruit
+    /// (see `count`), in catalogue order.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    ///
+    /// let catalogue = Catalogue::new(initialise_fruit_catalogue());
+    /// let long = ".length >= 6".parse().unwrap();
+    /// let names: Vec<&str> = catalogue
+    ///     .select(&long)
+    ///     .unwrap()
+    ///     .iter()
+    ///     .map(|f| f.name.as_str())
+    ///     .collect();
+    /// assert_eq!(names, ["Banana", "Pear"]);
+    /// ```
+    ///
+    /// # Error Cases
+    /// As for `count`.
+    pub fn select(&self, filter: &Query) -> Result<Vec<&FruitDimensions>, Box<dyn Error>> {
+        let mut selected = Vec::new();
+        for fruit in &self.fruits {
+            if filter.matches(&serde_json::to_value(fruit)?)? {
+                selected.push(fruit);
+            }
+        }
+        Ok(selected)
+    }
+
+    /// Find exactly one fruit by name or ID.
+    ///
+    /// # Error Cases
+    /// - `EmptyName` - The name is empty after trimming
+    /// - `NotFound` - Nothing matches
+    /// - `AmbiguousName` - Several fruits have the name; use an ID instead
+    pub fn find<'a>(&self, key: impl Into<FruitKey<'a>>) -> Result<&FruitDimensions, CatalogError> {
+        let index = self.position(key.into())?;
+        Ok(&self.fruits[index])
+    }
+
+    /// Add a new fruit after validating it.
+    ///
+    /// The name is trimmed before it is checked and stored. In ID mode a
+    /// fruit without an ID is given the next free one.
+    ///
+    /// # Error Cases
+    /// - `EmptyName` - The name is empty after trimming
+    /// - `InvalidName` - The name breaks the configured `NamePolicy`
+    /// - `InvalidDimensions` - A dimension is zero, negative, NaN or infinite
+    /// - `ImplausibleDimensions` - A dimension is outside the plausible range,
+    ///   and its policy is to fail
+    /// - `HookRejected` - The fruit fails a `pre_add` hook (see `with_hooks`)
+    /// - `DuplicateName` - A fruit with the same name already exists (name mode)
+    /// - `DuplicateId` - A fruit with the same ID already exists
+    /// - `QuotaExceeded` - The catalogue already holds `max_entries` fruits
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::Catalogue;
+    /// use fruitdata::error::CatalogError;
+    /// use fruitdata::models::FruitDimensions;
+    ///
+    /// let mut catalogue = Catalogue::new(Vec::new());
+    /// assert_eq!(catalogue.add(FruitDimensions::new("Kiwi", 5.0, 4.0, 4.0)), Ok(()));
+    ///
+    /// for bad in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+    ///     let result = catalogue.add(FruitDimensions::new("Fig", bad, 1.0, 1.0));
+    ///     assert_eq!(result, Err(CatalogError::InvalidDimensions));
+    /// }
+    /// assert_eq!(catalogue.len(), 1);
+    /// ```
+    pub fn add(&mut self, mut fruit: FruitDimensions) -> Result<(), CatalogError> {
+        fruit.name = self.validate_name(&fruit.name)?;
+
+        validate_dimensions(&fruit)?;
+        self.check_plausible(&fruit)?;
+        self.hooks.check(&fruit)?;
+
+        if self.uniqueness == Uniqueness::Name && self.get(&fruit.name).is_some() {
+            return Err(CatalogError::DuplicateName(fruit.name));
+        }
+
+        if let Some(id) = fruit.id {
+            if self.fruits.iter().any(|f| f.id == Some(id)) {
+                return Err(CatalogError::DuplicateId(id));
+            }
+        }
+
+        self.limits.check_entries(self.fruits.len() + 1)?;
+
+        if self.uniqueness == Uniqueness::Id && fruit.id.is_none() {
+            fruit.id = Some(self.next_id());
+        }
+
+        self.record(CatalogEvent::Added {
+            fruit: fruit.clone(),
+        });
+        self.fruits.push(fruit);
+        self.index(self.fruits.len() - 1);
+        Ok(())
+    }
+
+    /// Remove a fruit by name (case-insensitive) or ID, returning it.
+    ///
+    /// # Error Cases
+    /// - `EmptyName` - The name is empty after trimming
+    /// - `NotFound` - No fruit matches
+    /// - `AmbiguousName` - Several fruits have the name; use an ID instead
+    pub fn remove<'a>(
+        &mut self,
+        key: impl Into<FruitKey<'a>>,
+    ) -> Result<FruitDimensions, CatalogError> {
+        let index = self.position(key.into())?;
+        let fruit = self.fruits.remove(index);
+        // Every later fruit has moved up one
+        self.reindex();
+        self.record(CatalogEvent::Removed {
+            fruit: fruit.clone(),
+        });
+        Ok(fruit)
+    }
+
+    /// Give an existing fruit a new name.
+    ///
+    /// The new name goes through the same checks as `add`. Renaming a fruit
+    /// to a different capitalisation of its own name ("kiwi" → "Kiwi") is
+    /// allowed.
+    ///
+    /// # Error Cases
+    /// - `NotFound` / `AmbiguousName` - The fruit to rename can't be identified
+    /// - `EmptyName` / `InvalidName` - The new name is rejected
+    /// - `DuplicateName` - Another fruit already uses the new name (name mode)
+    pub fn rename<'a>(
+        &mut self,
+        key: impl Into<FruitKey<'a>>,
+        new_name: &str,
+    ) -> Result<(), CatalogError> {
+        let patch = FruitPatch {
+            name: Some(new_name.to_string()),
+            ..FruitPatch::default()
+        };
+        self.apply_patch(key, &patch)
+    }
+
+    /// Change some fields of an existing fruit, leaving the rest as they are.
+    ///
+    /// The patched fruit is validated as a whole before anything changes, so
+    /// a patch with one bad field leaves the fruit untouched.
+    ///
+    /// # Error Cases
+    /// - `NotFound` / `AmbiguousName` - The fruit to patch can't be identified
+    /// - `EmptyName` / `InvalidName` - The new name is rejected
+    /// - `DuplicateName` - Another fruit already uses the new name (name mode)
+    /// - `InvalidDimensions` - A new dimension is zero, negative, NaN or
+    ///   infinite
+    /// - `ImplausibleDimensions` - A new dimension is outside the plausible
+    ///   range, and its policy is to fail
+    /// - `HookRejected` - With new dimensions, the fruit fails a `pre_add`
+    ///   hook
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    /// use fruitdata::models::FruitPatch;
+    ///
+    /// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+    /// let patch = FruitPatch { width: Some(2.8), ..FruitPatch::default() };
+    /// catalogue.apply_patch("apple", &patch).unwrap();
+    /// assert_eq!(catalogue.get("Apple").unwrap().width, 2.8);
+    /// ```
+    pub fn apply_patch<'a>(
+        &mut self,
+        key: impl Into<FruitKey<'a>>,
+        patch: &FruitPatch,
+    ) -> Result<(), CatalogError> {
+        let index = self.position(key.into())?;
+
+        // Build the patched version on the side and validate it
+        let mut updated = self.fruits[index].clone();
+        patch.apply_to(&mut updated);
+
+        if patch.name.is_some() {
+            updated.name = self.validate_name(&updated.name)?;
+
+            let clash = self.uniqueness == Uniqueness::Name
+                && self
+                    .positions_of(&updated.name)
+                    .iter()
+                    .any(|&other| other != index);
+            if clash {
+                return Err(CatalogError::DuplicateName(updated.name));
+            }
+        }
+
+        validate_dimensions(&updated)?;
+        // Only new dimensions, so an old implausible fruit can still be renamed
+        if patch.length.is_some() || patch.width.is_some() || patch.height.is_some() {
+            self.check_plausible(&updated)?;
+            self.hooks.check(&updated)?;
+        }
+
+        // Everything checks out; replace the stored fruit
+        self.unindex(index);
+        let before = std::mem::replace(&mut self.fruits[index], updated.clone());
+        self.index(index);
+        self.record(CatalogEvent::Updated {
+            before,
+            after: updated,
+        });
+        Ok(())
+    }
+
+    /// Record a dated measurement of an existing fruit (see
+    /// measurements.rs), keeping the list in date order.
+    ///
+    /// If it is the newest measurement, its dimensions also become the
+    /// fruit's; an older one is only added to the history.
+    ///
+    /// # Error Cases
+    /// - `CatalogError::NotFound` - No fruit matches the key
+    /// - `CatalogError::InvalidDimensions` - A dimension isn't positive
+    /// - `CatalogError::ImplausibleDimensions` / `HookRejected` - The
+    ///   measured fruit fails the plausible range or a `pre_add` hook
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    /// use fruitdata::measurements::Measurement;
+    /// use fruitdata::timestamp::Timestamp;
+    ///
+    /// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+    /// let reading = |day: u64, length: f32| Measurement::new(Timestamp(day * 86_400), length, 2.5, 1.5);
+    /// catalogue.add_measurement("Apple", reading(10, 4.4)).unwrap();
+    /// catalogue.add_measurement("Apple", reading(3, 4.1)).unwrap(); // an older one
+    ///
+    /// let apple = catalogue.get("Apple").unwrap();
+    /// assert_eq!(apple.length, 4.4); // the newest
+    /// assert_eq!(apple.measurements, vec![reading(3, 4.1), reading(10, 4.4)]);
+    /// ```
+    pub fn add_measurement<'a>(
+        &mut self,
+        key: impl Into<FruitKey<'a>>,
+        measurement: Measurement,
+    ) -> Result<(), CatalogError> {
+        let index = self.position(key.into())?;
+
+        let mut updated = self.fruits[index].clone();
+        let position = updated
+            .measurements
+            .partition_point(|m| m.taken <= measurement.taken);
+        updated.measurements.insert(position, measurement);
+        if position == updated.measurements.len() - 1 {
+            updated.length = measurement.length;
+            updated.width = measurement.width;
+            updated.height = measurement.height;
+        }
+
+        // Check the measurement itself, even if it is an older one
+        let mut measured = updated.clone();
+        measured.length = measurement.length;
+        measured.width = measurement.width;
+        measured.height = measurement.height;
+        validate_dimensions(&measured)?;
+        self.check_plausible(&measured)?;
+        self.hooks.check(&measured)?;
+
+        let before = std::mem::replace(&mut self.fruits[index], updated.clone());
+        self.record(CatalogEvent::Updated {
+            before,
+            after: updated,
+        });
+        Ok(())
+    }
+
+    /// Save the catalogue to a JSON file, enforcing `max_file_size`.
+    ///
+    /// The JSON is produced in memory first and its size checked before the
+    /// file is touched, so a catalogue that is too large leaves the existing
+    /// file unchanged.
+    ///
+    /// If the catalogue was created with `Catalogue::load`, the file is
+    /// checked first: when its contents no longer match what was loaded,
+    /// another process has written to it, and the `ConflictPolicy` decides
+    /// whether to abort, overwrite, or merge both sets of changes. A merge
+    /// updates this catalogue's fruits to the merged result.
+    ///
+    /// # Error Cases
+    /// - `CatalogError::ConcurrentModification` - The file changed (policy `Abort`)
+    /// - `CatalogError::MergeConflict` - Both sides changed the same fruit (policy `Merge`)
+    /// - `CatalogError::QuotaExceeded` - The JSON would exceed `max_file_size`
+    /// - Any error from serialization or reading/writing the file
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, save_catalogue, Catalogue};
+    /// use fruitdata::models::FruitDimensions;
+    /// use fruitdata::policy::ConflictPolicy;
+    ///
+    /// let path = std::env::temp_dir().join("fruitdata-save-doc.json");
+    /// let path = path.to_str().unwrap();
+    /// save_catalogue(&initialise_fruit_catalogue(), path).unwrap();
+    ///
+    /// let mut ours = Catalogue::load(path).unwrap();
+    /// ours.remove("Pear").unwrap();
+    ///
+    /// // Meanwhile, another process adds a fruit to the same file
+    /// let mut theirs = Catalogue::load(path).unwrap();
+    /// theirs.add(FruitDimensions::new("Kiwi", 3.0, 2.0, 2.0)).unwrap();
+    /// theirs.save(path).unwrap();
+    ///
+    /// // By default our save is refused...
+    /// assert!(ours.save(path).is_err());
+    ///
+    /// // ...but a merge keeps both changes
+    /// let mut ours = ours.with_conflict_policy(ConflictPolicy::Merge);
+    /// ours.save(path).unwrap();
+    /// let saved = Catalogue::load(path).unwrap();
+    /// assert!(saved.get("Kiwi").is_some());
+    /// assert!(saved.get("Pear").is_none());
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn save(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        // Step 1: Make sure we aren't about to overwrite someone else's edits
+        if let Some(loaded) = &self.loaded {
+            match fs::read(path) {
+                Ok(bytes) if content_hash(&bytes) != loaded.hash => match self.conflict_policy {
+                    ConflictPolicy::Abort => {
+                        return Err(CatalogError::ConcurrentModification.into())
+                    }
+                    ConflictPolicy::Force => {}
+                    ConflictPolicy::Merge => {
+                        let json = file_json(Path::new(path), &bytes)?;
+                        let theirs = CatalogueDocument::from_slice(&json)?.fruits;
+                        self.fruits = three_way_merge_in(
+                            &loaded.fruits,
+                            &self.fruits,
+                            &theirs,
+                            self.case_sensitivity,
+                        )
+                        .map_err(CatalogError::MergeConflict)?;
+                        self.reindex();
+                    }
+                },
+                // Unchanged, or deleted since we loaded it: nothing to lose
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        // Step 2: Serialize (in TOML or YAML if the file is one) and
+        // enforce the size limit
+        let json = self
+            .save_options
+            .document_to_json(&self.fruits, self.file_extra.as_ref())?;
+        let text = format::document_text(json, Format::for_catalogue(Path::new(path)))?;
+        self.limits.check_file_size(text.len() as u64)?;
+
+        // Step 3: Write, and remember what the file now contains
+        fs::write(path, &text)?;
+        self.loaded = Some(Snapshot {
+            hash: content_hash(text.as_bytes()),
+            fruits: self.fruits.clone(),
+        });
+        Ok(())
+    }
+
+    /// Replace every fruit at once, e.g. with an earlier state of the
+    /// catalogue rebuilt from the journal.
+    ///
+    /// The fruits are taken as-is, like `Catalogue::new`, since they were
+    /// valid when they were first stored. The change is recorded as the
+    /// events (see `events::diff`) that turn the old fruits into the new.
+    pub fn replace_all(&mut self, fruits: Vec<FruitDimensions>) {
+        for event in diff_in(&self.fruits, &fruits, self.case_sensitivity) {
+            self.record(event);
+        }
+        self.fruits = fruits;
+        self.reindex();
+    }
+
+    /// Hand over the events for every change made since the last call.
+    ///
+    /// Each successful `add`, `remove`, `rename` or `apply_patch` produces
+    /// one `CatalogEvent` (`replace_all` one per fruit it changes); failed
+    /// calls produce none. Callers that keep a
+    /// change log (see `Journal`) take the events after a successful `save`.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    /// use fruitdata::events::{Action, CatalogEvent};
+    ///
+    /// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+    /// catalogue.rename("Pear", "Nashi").unwrap();
+    /// let _ = catalogue.remove("Durian"); // fails: no event
+    ///
+    /// let changes = catalogue.take_changes();
+    /// assert_eq!(changes.len(), 1);
+    /// assert_eq!(changes[0].action(), Action::Rename);
+    /// assert!(catalogue.take_changes().is_empty());
+    /// ```
+    pub fn take_changes(&mut self) -> Vec<CatalogEvent> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Receive an event for every change made from now on, as it happens.
+    ///
+    /// The events are the same ones `take_changes` hands over, so a GUI or
+    /// a sync daemon can react to changes without polling. Dropping the
+    /// receiver unsubscribes. Subscriptions belong to this catalogue: a
+    /// clone of it starts without any.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    /// use fruitdata::events::CatalogEvent;
+    ///
+    /// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+    /// let changes = catalogue.subscribe();
+    ///
+    /// let pear = catalogue.remove("Pear").unwrap();
+    /// assert_eq!(changes.try_recv().unwrap(), CatalogEvent::Removed { fruit: pear });
+    /// assert!(changes.try_recv().is_err()); // nothing else happened
+    /// ```
+    pub fn subscribe(&mut self) -> Receiver<CatalogEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.0.push(sender);
+        receiver
+    }
+
+    /// Replace this catalogue with `draft`, an edited clone of it, and tell
+    /// the subscribers about the changes made to the draft.
+    ///
+    /// Editing a clone lets a caller throw the whole edit away if part of
+    /// it fails, without the subscribers hearing about any of it.
+    pub(crate) fn commit(&mut self, mut draft: Catalogue) {
+        let new = draft.changes.get(self.changes.len()..).unwrap_or_default();
+        for event in new {
+            self.subscribers.notify(event);
+        }
+        draft.subscribers = std::mem::take(&mut self.subscribers);
+        *self = draft;
+    }
+
+    /// Note a change for `take_changes`, and tell the subscribers.
+    fn record(&mut self, event: CatalogEvent) {
+        self.subscribers.notify(&event);
+        self.changes.push(event);
+    }
+
+    /// Resolve a key to the index of exactly one fruit.
+    fn position(&self, key: FruitKey<'_>) -> Result<usize, CatalogError> {
+        match key {
+            FruitKey::Id(id) => self
+                .fruits
+                .iter()
+                .position(|f| f.id == Some(id))
+                .ok_or_else(|| CatalogError::NotFound(format!("#{}", id))),
+            FruitKey::Name(name) => {
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(CatalogError::EmptyName);
+                }
+
+                let matches = self.positions_of(name);
+                match matches {
+                    [] => Err(CatalogError::NotFound(name.to_string())),
+                    [index] => Ok(*index),
+                    _ => Err(CatalogError::AmbiguousName {
+                        name: name.to_string(),
+                        ids: matches.iter().filter_map(|&i| self.fruits[i].id).collect(),
+                    }),
+                }
+            }
+        }
+    }
+
+    /// The positions of the fruits named `name` (as matched by `get`), in
+    /// catalogue order.
+    fn positions_of(&self, name: &str) -> &[usize] {
+        self.names
+            .get(&self.case_sensitivity.fold(name))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Rebuild the name index from scratch, after the fruits moved or the
+    /// way names are matched changed.
+    fn reindex(&mut self) {
+        self.names.clear();
+        for index in 0..self.fruits.len() {
+            self.index(index);
+        }
+    }
+
+    /// Add the fruit at `index` to the name index.
+    fn index(&mut self, index: usize) {
+        let key = self.case_sensitivity.fold(&self.fruits[index].name);
+        let positions = self.names.entry(key).or_default();
+        let at = positions.partition_point(|&other| other < index);
+        positions.insert(at, index);
+    }
+
+    /// Take the fruit at `index` out of the name index (before it is
+    /// renamed).
+    fn unindex(&mut self, index: usize) {
+        let key = self.case_sensitivity.fold(&self.fruits[index].name);
+        if let Some(positions) = self.names.get_mut(&key) {
+            positions.retain(|&other| other != index);
+            if positions.is_empty() {
+                self.names.remove(&key);
+            }
+        }
+    }
+
+    /// The smallest ID greater than every ID currently in use.
+    fn next_id(&self) -> u64 {
+        self.fruits.iter().filter_map(|f| f.id).max().unwrap_or(0) + 1
+    }
+
+    /// Trim a candidate name and check it against the name rules.
+    fn validate_name(&self, name: &str) -> Result<String, CatalogError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(CatalogError::EmptyName);
+        }
+
+        self.name_policy
+            .check(name)
+            .map_err(|reason| CatalogError::InvalidName {
+                name: name.to_string(),
+                reason,
+            })?;
+
+        Ok(name.to_string())
+    }
+
+    /// Reject `fruit` if it is outside the plausible range and the range's
+    /// policy is to fail; a warning is for the caller to give.
+    fn check_plausible(&self, fruit: &FruitDimensions) -> Result<(), CatalogError> {
+        let range = self.plausible_range();
+        match range.check(fruit) {
+            Err(reason) if range.on_violation == OutOfRange::Fail => {
+                Err(CatalogError::ImplausibleDimensions {
+                    name: fruit.name.clone(),
+                    reason,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Catalogue {
+    type Item = &'a FruitDimensions;
+    type IntoIter = std::slice::Iter<'a, FruitDimensions>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// ============================================================================
+// READ-ONLY ACCESS
+// ============================================================================
+// Some catalogues must never be changed by hand (for example the canonical
+// published one that only CI may update). `ReadOnlyCatalogue` wraps a
+// `Catalogue` so that every read works as usual but every mutation fails
+// with `CatalogError::ReadOnly`, checked by the compiler's type system
+// rather than by remembering to test a flag.
+
+/// A catalogue that can be read but not changed or saved.
+///
+/// Reading methods (`get`, `find`, `fruits`, ...) are available through
+/// `Deref`, which only ever hands out a shared `&Catalogue`. The mutating
+/// methods are redefined here to always return `CatalogError::ReadOnly`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::error::CatalogError;
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue()).into_read_only();
+/// assert!(catalogue.get("Apple").is_some());
+/// assert_eq!(catalogue.remove("Apple"), Err(CatalogError::ReadOnly));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReadOnlyCatalogue {
+    inner: Catalogue,
+}
+
+impl Catalogue {
+    /// Wrap this catalogue so that it can no longer be changed.
+    pub fn into_read_only(self) -> ReadOnlyCatalogue {
+        ReadOnlyCatalogue { inner: self }
+    }
+}
+
+impl ReadOnlyCatalogue {
+    /// Always fails: the catalogue is read-only.
+    pub fn add(&mut self, _fruit: FruitDimensions) -> Result<(), CatalogError> {
+        Err(CatalogError::ReadOnly)
+    }
+
+    /// Always fails: the catalogue is read-only.
+    pub fn remove<'a>(
+        &mut self,
+        _key: impl Into<FruitKey<'a>>,
+    ) -> Result<FruitDimensions, CatalogError> {
+        Err(CatalogError::ReadOnly)
+    }
+
+    /// Always fails: the catalogue is read-only.
+    pub fn rename<'a>(
+        &mut self,
+        _key: impl Into<FruitKey<'a>>,
+        _new_name: &str,
+    ) -> Result<(), CatalogError> {
+        Err(CatalogError::ReadOnly)
+    }
+
+    /// Always fails: the catalogue is read-only.
+    pub fn apply_patch<'a>(
+        &mut self,
+        _key: impl Into<FruitKey<'a>>,
+        _patch: &FruitPatch,
+    ) -> Result<(), CatalogError> {
+        Err(CatalogError::ReadOnly)
+    }
+
+    /// Always fails: the catalogue is read-only.
+    pub fn set_calibration(&mut self, _calibration: &Calibration) -> Result<(), CatalogError> {
+        Err(CatalogError::ReadOnly)
+    }
+
+    /// Always fails: the catalogue is read-only.
+    pub fn calibrate(&mut self) -> Result<bool, Box<dyn Error>> {
+        Err(CatalogError::ReadOnly.into())
+    }
+
+    /// Always fails: the catalogue is read-only.
+    pub fn add_measurement<'a>(
+        &mut self,
+        _key: impl Into<FruitKey<'a>>,
+        _measurement: Measurement,
+    ) -> Result<(), CatalogError> {
+        Err(CatalogError::ReadOnly)
+    }
+
+    /// Always fails: the catalogue is read-only.
+    pub fn save(&mut self, _path: &str) -> Result<(), Box<dyn Error>> {
+        Err(CatalogError::ReadOnly.into())
+    }
+
+    /// Give back the wrapped catalogue, making it writable again.
+    pub fn into_inner(self) -> Catalogue {
+        self.inner
+    }
+}
+
+impl Deref for ReadOnlyCatalogue {
+    type Target = Catalogue;
+
+    fn deref(&self) -> &Catalogue {
+        &self.inner
+    }
+}
+
+/// Check that all three dimensions of a fruit are positive, finite numbers.
+/// (NaN and infinity would be saved as `null`, and the file wouldn't load
+/// again.)
+pub(crate) fn validate_dimensions(fruit: &FruitDimensions) -> Result<(), CatalogError> {
+    let valid = |cm: Centimeters| cm.value().is_finite() && cm.value() > 0.0;
+    if !(valid(fruit.length) && valid(fruit.width) && valid(fruit.height)) {
+        return Err(CatalogError::InvalidDimensions);
+    }
+    Ok(())
+}
@@ -0,0 +1,3021 @@
+// ============================================================================
+// cli.rs - The Commands Behind the `fruitdata` Binary
+// ============================================================================
+// Every subcommand of the `fruitdata` tool is implemented here, in the
+// library, rather than in main.rs. That way the commands can be tested and
+// reused: another program can build a `Commands` value (or parse one with
+// clap), point it at a `CatalogStore`, and get exactly the behaviour of the
+// command line:
+//
+//     let outcome = run_command(&command, &store, &mut context)?;
+//
+// Chat bots and scripting hosts that get commands as text can hand a line
+// to `parse_command` (`"add Apple 4 2.5 1.5"` is split into words as a
+// shell would), or run it against a `Catalogue` they hold in one go with
+// `parse_and_run`. `Commands` is a clap `Subcommand`, so a host with its
+// own clap parser can also mount fruitdata's commands in it.
+//
+// `run_command` doesn't print anything. It returns a `CommandOutcome` whose
+// `Report` says what the command did (the fruit added, the fruits found,
+// ...) as data; present.rs turns reports into the text or JSON the binary
+// prints, and main.rs sets the exit status.
+//
+// What the binary's global flags control (the config, `--read-only`,
+// `--strict`, `--on-conflict`, `--actor`) is collected in a `Context`.
+// ============================================================================
+
+#[cfg(feature = "barcode")]
+use crate::barcode::{Barcode, ImageFormat, Symbology};
+use crate::calibration::Calibration;
+use crate::catalog::{
+    initialise_fruit_catalogue, initialise_with_template, save_catalogue_with, Catalogue,
+    CatalogueTemplate, FruitKey, SaveOptions,
+};
+use crate::config::Config;
+use crate::csv::{self, CsvOptions};
+#[cfg(unix)]
+use crate::daemon::{self, DaemonSettings};
+use crate::document::CatalogueDocument;
+use crate::duplicates::{find_duplicates, DuplicateCluster};
+use crate::error::CatalogError;
+use crate::events::{Action, CatalogueDiff};
+use crate::expr::ComputedField;
+use crate::format::{convert, convert_calibrated, document_json, export, Format};
+use crate::grading::{grade, GradeSummary};
+use crate::history::{state_at, Revision};
+use crate::import::{
+    import_into, ColumnMapping, FieldMap, FieldMapping, ImportMode, ImportSummary, JsonPath,
+    OnDuplicate,
+};
+#[cfg(feature = "mqtt")]
+use crate::ingest::ingest;
+use crate::ingest::Ingested;
+#[cfg(unix)]
+use crate::ipc;
+use crate::journal::{AuditFilter, ChangeRecord, CompactionReport, Journal};
+use crate::label::LabelFormat;
+use crate::lint::{lint, Deny, Warning};
+use crate::measurements::{growth, GrowthStep, Measurement};
+use crate::models::{FruitDimensions, FruitPatch};
+#[cfg(feature = "mqtt")]
+use crate::mqtt::{MqttOptions, Subscriber};
+use crate::names::CaseSensitivity;
+use crate::pattern::{Glob, Regex};
+use crate::pivot::{CellValue, Pivot};
+use crate::policy::ConflictPolicy;
+use crate::present::{FieldSelection, OutputFormat};
+use crate::progress::{NoProgress, Progress, Tracked};
+use crate::query::Query;
+use crate::repair::{recover, Recovery};
+use crate::sanitize::NameChange;
+use crate::sort::SortSpec;
+use crate::stats::{Aggregate, Group, GroupBy, SizeClass};
+use crate::store::{BorrowedStore, CatalogStore, FileStore};
+use crate::template::Template;
+use crate::timestamp::{Period, Timestamp};
+use crate::validate::{ValidationReport, Validator};
+use clap::Subcommand;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+/// An enum representing all possible subcommands (actions) the user can request.
+///
+/// In Rust, an `enum` is a type that can have multiple variants (possibilities).
+/// Each variant can have associated data. For example, `Get { name: String }`
+/// means the `Get` variant carries a String containing the fruit name.
+///
+/// # Why use an enum here?
+/// This structure ensures:
+/// - Type safety: The compiler ensures a command variant exists before we use it
+/// - Exhaustiveness: We must handle all possible commands in our match statement
+/// - Clear semantics: The code explicitly shows what actions are possible
+///
+/// # Aliases
+/// Common commands have short visible aliases (`ls`, `rm`, `new`). When a
+/// command is renamed, its old name stays as a hidden `alias`, so scripts
+/// that use it keep working without it cluttering `--help`.
+#[derive(Subcommand, Clone)]
+pub enum Commands {
+    /// Create the catalogue, starting with the fruits of a template.
+    /// Command: `fruitdata init [PATH] --template tropical`
+    ///
+    /// Other commands fail on a catalogue that doesn't exist (unless given
+    /// `--create`), so that a mistyped `--file` can't quietly start a new
+    /// one. The built-in templates are basic (the default), tropical,
+    /// citrus and empty; a path to a catalogue file copies its fruits
+    /// instead. An existing catalogue is never overwritten.
+    Init {
+        /// Where to create the catalogue (default: `--file`)
+        path: Option<PathBuf>,
+        /// basic, tropical, citrus, empty, or a catalogue file to copy
+        #[arg(long, default_value = "basic", value_name = "NAME|FILE")]
+        template: CatalogueTemplate,
+    },
+
+    /// List all available fruits in the catalogue.
+    /// Command: `fruitdata list`
+    ///
+    /// By default fruits are listed in catalogue order. Use `--sort-by` with
+    /// one or more comma-separated `field[:asc|desc]` keys to reorder them.
+    /// Example: `fruitdata list --sort-by volume:desc,name:asc`
+    ///
+    /// `--template` prints each fruit as a line of your own design instead.
+    /// Example: `fruitdata list --template '{{name}}: {{volume}} cm³'`
+    ///
+    /// `--fields` picks the fields to print, as tab-separated text or (with
+    /// `--format json`) as objects holding just those fields.
+    /// Example: `fruitdata list --format json --fields name,volume`
+    ///
+    /// `--glob` and `--regex` only list the fruits whose names match, and
+    /// `--count` prints how many there are instead of listing them.
+    /// Example: `fruitdata list --glob 'P*'`, `fruitdata list --regex '^.{3}$' --count`
+    ///
+    /// `--size-class small|medium|large` only lists fruits of that size, by
+    /// volume (the cut-offs are the config's `size_classes`).
+    /// Example: `fruitdata list --size-class large`
+    ///
+    /// `--head N` (or `--limit N`) and `--tail N` keep only the first or
+    /// last N fruits, after filtering and sorting.
+    /// Example: `fruitdata list --sort-by volume:desc --head 10`
+    #[command(visible_alias = "ls")]
+    List {
+        /// Sort keys, e.g. "volume:desc,name" (fields: name, length, width, height, volume)
+        #[arg(long, value_name = "SPEC")]
+        sort_by: Option<SortSpec>,
+        /// Print each fruit as this line, with {{field}} placeholders
+        /// (fields: name, id, length, width, height, volume)
+        #[arg(long, conflicts_with = "fields")]
+        template: Option<Template>,
+        /// Only these fields, as comma-separated dotted paths (e.g.
+        /// "name,volume,dims.length"); volume is computed
+        #[arg(long, value_name = "PATHS")]
+        fields: Option<FieldSelection>,
+        /// Only fruits whose whole name matches this wildcard pattern
+        /// (`*`, `?`, `[a-z]`; case-insensitive)
+        #[arg(long, value_name = "PATTERN")]
+        glob: Option<Glob>,
+        /// Only fruits whose name matches this regular expression
+        /// (case-insensitive)
+        #[arg(long, value_name = "PATTERN")]
+        regex: Option<Regex>,
+        /// Only fruits of this size class: small, medium or large (by
+        /// volume, see the config's `size_classes`)
+        #[arg(long, value_name = "CLASS")]
+        size_class: Option<SizeClass>,
+        /// Only the first N fruits (after filtering and sorting)
+        #[arg(
+            long,
+            visible_alias = "limit",
+            value_name = "N",
+            conflicts_with = "tail"
+        )]
+        head: Option<usize>,
+        /// Only the last N fruits (after filtering and sorting)
+        #[arg(long, value_name = "N")]
+        tail: Option<usize>,
+        /// Print how many fruits would be listed instead of listing them
+        #[arg(long, conflicts_with_all = ["template", "fields"])]
+        count: bool,
+        /// Output format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Show detailed information for a specific fruit.
+    /// Command: `fruitdata get AppleName`
+    ///
+    /// The `name` field will be populated with the fruit name provided by the user.
+    /// Example: `fruitdata get Apple` → Get { name: "Apple", id: None }
+    ///
+    /// In ID mode several fruits may share a name; all of them are shown
+    /// unless `--id` picks one.
+    ///
+    /// `--measurements` adds the mean, variance and trend of each dimension
+    /// over the fruit's dated measurements.
+    /// Example: `fruitdata get Apple --measurements`
+    #[command(alias = "show")]
+    Get {
+        /// The name of the fruit to look up
+        name: String,
+        /// Show only the entry with this ID
+        #[arg(long)]
+        id: Option<u64>,
+        /// Also summarise the fruit's measurements
+        #[arg(long)]
+        measurements: bool,
+    },
+
+    /// Check whether a fruit is in the catalogue, for shell conditionals.
+    /// Command: `if fruitdata exists Apple; then ...`
+    ///
+    /// Prints nothing. Exits with status 0 if there is a fruit with the
+    /// name (case-insensitive) and 2 if there isn't, so that a real error
+    /// (status 1, e.g. an unreadable catalogue) isn't
+    /// mistaken for "no".
+    Exists {
+        /// The name of the fruit to look for
+        name: String,
+    },
+
+    /// Add a new fruit to the catalogue.
+    /// Command: `fruitdata add "FruitName" 4.0 2.5 1.5`
+    ///
+    /// All fields must be provided in order: name, length, width, height
+    /// The name can contain spaces if quoted (e.g., "Dragon Fruit")
+    #[command(visible_alias = "new")]
+    Add {
+        /// Name of the fruit (e.g., "Apple", "Dragonfruit")
+        name: String,
+        /// Length dimension (must be a positive number)
+        length: f32,
+        /// Width dimension (must be a positive number)
+        width: f32,
+        /// Height dimension (must be a positive number)
+        height: f32,
+    },
+
+    /// Remove a fruit from the catalogue by name.
+    /// Command: `fruitdata remove AppleName`
+    ///
+    /// After removal, the catalogue is saved back to the JSON file.
+    #[command(visible_alias = "rm", alias = "delete")]
+    Remove {
+        /// The name of the fruit to remove
+        name: String,
+        /// Remove the entry with this ID (required when the name is shared)
+        #[arg(long)]
+        id: Option<u64>,
+    },
+
+    /// Give an existing fruit a new name.
+    /// Command: `fruitdata rename OldName NewName`
+    ///
+    /// The new name must satisfy the same rules as `add` (not empty, not a
+    /// duplicate, allowed by the configured name policy).
+    Rename {
+        /// The current name of the fruit
+        old_name: String,
+        /// The name to change it to
+        new_name: String,
+        /// Rename the entry with this ID (required when the name is shared)
+        #[arg(long)]
+        id: Option<u64>,
+    },
+
+    /// Change some of a fruit's fields, leaving the others as they are.
+    /// Command: `fruitdata update Apple --width 2.8`
+    ///
+    /// Changes can be given as flags, as a JSON Merge Patch document
+    /// (`--patch '{"length": 4.5}'`), or both; flags win over the document.
+    Update {
+        /// The name of the fruit to update
+        name: String,
+        /// Update the entry with this ID (required when the name is shared)
+        #[arg(long)]
+        id: Option<u64>,
+        /// A new name for the fruit
+        #[arg(long)]
+        new_name: Option<String>,
+        /// A new length (must be a positive number)
+        #[arg(long)]
+        length: Option<f32>,
+        /// A new width (must be a positive number)
+        #[arg(long)]
+        width: Option<f32>,
+        /// A new height (must be a positive number)
+        #[arg(long)]
+        height: Option<f32>,
+        /// A JSON Merge Patch document, e.g. '{"height": 2.0}'
+        #[arg(long, value_name = "JSON")]
+        patch: Option<String>,
+    },
+
+    /// Search the change journal: who changed what, and when.
+    /// Command: `fruitdata audit --since 2024-01-01 --actor alice --action remove`
+    ///
+    /// Every filter is optional; without any, the whole journal is shown,
+    /// oldest change first. Use `--format json` to export the results.
+    Audit {
+        /// Only changes made at or after this time (YYYY-MM-DD[THH:MM[:SS]], UTC)
+        #[arg(long, value_name = "TIME")]
+        since: Option<Timestamp>,
+        /// Only changes made before this time
+        #[arg(long, value_name = "TIME")]
+        until: Option<Timestamp>,
+        /// Only changes made by this actor
+        #[arg(long)]
+        actor: Option<String>,
+        /// Only changes of this kind: add, remove, update or rename
+        #[arg(long)]
+        action: Option<Action>,
+        /// Only changes to the fruit with this name
+        #[arg(long, value_name = "NAME")]
+        fruit: Option<String>,
+        /// Output format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Show every revision of one fruit recorded in the change journal.
+    /// Command: `fruitdata history Apple`
+    ///
+    /// Renames are followed, so `history Nashi` also shows the time the
+    /// fruit was still called "Pear". Each revision lists what changed.
+    ///
+    /// `fruitdata history gc` prunes old entries from the journal instead.
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    History {
+        /// The name of the fruit (current or former)
+        #[arg(required = true)]
+        name: Option<String>,
+        /// Show the entry with this ID (for names shared in ID mode)
+        #[arg(long)]
+        id: Option<u64>,
+        /// Output format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+    },
+
+    /// Add the records of an arbitrary JSON array or CSV file to the
+    /// catalogue.
+    /// Command: `fruitdata import data.json --map name=fruitName --map length=dims.l`
+    ///
+    /// Each `--map field=path` says where a fruit field is found in a record
+    /// (dotted paths reach into nested objects). Fields without a `--map` are
+    /// guessed from the record's keys. Either every record is applied, or
+    /// (if any is invalid) none are.
+    ///
+    /// A `.csv` file's first row names its columns. `--columns` maps fields
+    /// to columns in one go, each dimension optionally with its unit (cm,
+    /// mm or in), and `--columns-file` reads the same list from a file.
+    /// Example: `fruitdata import sheet.csv --columns name=FruitName,length=L:mm,width=W:mm,height=H:mm`
+    ///
+    /// `--format` overrides the extension (json, jsonl, csv, and toml or
+    /// yaml with their features). CSV files
+    /// use the delimiter and header row of the config file's `save.csv`
+    /// section, unless `--delimiter` or `--no-header` say otherwise; without
+    /// a header the columns are name, length, width, height and id. A file
+    /// in these columns (as `export` writes them) keeps its other columns
+    /// too, as fields of the fruits, unless a mapping is given.
+    /// Example: `fruitdata import export.txt --format csv --delimiter ';'`
+    ///
+    /// `--mode` says how the records are combined with the catalogue:
+    /// `append` (default) adds them, `merge` matches them to fruits by name
+    /// and `replace` removes every fruit first. `--on-duplicate` says what
+    /// happens to a record whose name is taken: `error` (the default, except
+    /// when merging), `skip` or `overwrite` (the default when merging).
+    /// Example: `fruitdata import prices.json --mode merge --on-duplicate skip`
+    Import {
+        /// The JSON, JSON Lines, CSV, TOML or YAML file to read
+        input: PathBuf,
+        /// Where a field is found, e.g. "length=dims.l" or "length=dims.l:mm"
+        /// (repeatable)
+        #[arg(long = "map", value_name = "FIELD=PATH")]
+        maps: Vec<FieldMap>,
+        /// Columns the fields are in, e.g. "name=FruitName,length=L_mm:mm"
+        #[arg(long, value_name = "FIELD=COLUMN,...")]
+        columns: Option<ColumnMapping>,
+        /// A file of `field=column[:unit]` mappings, one per line
+        #[arg(long, value_name = "FILE")]
+        columns_file: Option<PathBuf>,
+        /// Path to the array of records, if it isn't the whole file
+        #[arg(long, value_name = "PATH")]
+        records: Option<JsonPath>,
+        /// Format of the input: json, jsonl, csv, toml or yaml (default: from the
+        /// extension, else json)
+        #[arg(long)]
+        format: Option<Format>,
+        /// The character between the fields of a CSV file
+        #[arg(long, value_name = "CHAR")]
+        delimiter: Option<char>,
+        /// The CSV file has no header row
+        #[arg(long)]
+        no_header: bool,
+        /// How to combine the records with the catalogue: append, merge or
+        /// replace
+        #[arg(long, default_value = "append")]
+        mode: ImportMode,
+        /// What to do with a record whose name is taken: error, skip or
+        /// overwrite
+        #[arg(long, value_name = "POLICY")]
+        on_duplicate: Option<OnDuplicate>,
+        /// Show what would be imported without changing the catalogue
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Rewrite the catalogue file as compact JSON, without indentation.
+    /// Command: `fruitdata compact`
+    ///
+    /// Compact files are about half the size. Later saves go back to the
+    /// layout in the config file; set `"save": { "pretty": false }` there to
+    /// keep the catalogue compact.
+    Compact {
+        /// Also write each fruit's fields in alphabetical order
+        #[arg(long)]
+        sort_keys: bool,
+    },
+
+    /// Copy a catalogue file into another format.
+    /// Command: `fruitdata convert fruits.json fruits.jsonl`
+    ///
+    /// Formats are inferred from the file extensions (.json, .jsonl,
+    /// .ndjson, .csv, .toml, .yaml) unless given with `--from`/`--to`. The output file must not
+    /// exist yet.
+    ///
+    /// `--calibrate` corrects the dimensions with the input's calibration
+    /// header (device unit and offset), writing them in centimetres.
+    Convert {
+        /// The file to read
+        input: PathBuf,
+        /// The file to write
+        output: PathBuf,
+        /// Format of the input: json, jsonl, csv, toml or yaml
+        #[arg(long)]
+        from: Option<Format>,
+        /// Format of the output: json, jsonl, csv, toml or yaml
+        #[arg(long)]
+        to: Option<Format>,
+        /// Apply the input's calibration to every fruit
+        #[arg(long)]
+        calibrate: bool,
+    },
+
+    /// Write the fruits that pass a jq-style condition to a new file, e.g.
+    /// to publish a trimmed catalogue derived from the master one.
+    /// Command: `fruitdata export --where '.origin == "Spain"' -o spanish.json`
+    ///
+    /// Without `--where` every fruit is exported. The format is inferred
+    /// from the output's extension (.json, .jsonl, .ndjson, .csv, .toml,
+    /// .yaml) unless
+    /// given with `--to` (or `--format`), and the output file must not exist
+    /// yet. CSV files are laid out as for `import`, with their numbers
+    /// rounded as `--precision` says.
+    /// Example: `fruitdata export -o fruits.csv --delimiter ';' --no-header`
+    Export {
+        /// Only export the fruits for which this query is true (see
+        /// query.rs), e.g. '.length > 5'
+        #[arg(long = "where", value_name = "CONDITION")]
+        condition: Option<Query>,
+        /// The file to write
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+        /// Format of the output: json, jsonl, csv, toml or yaml
+        #[arg(long, alias = "format")]
+        to: Option<Format>,
+        /// The character between the fields of a CSV file
+        #[arg(long, value_name = "CHAR")]
+        delimiter: Option<char>,
+        /// Leave out the CSV header row
+        #[arg(long)]
+        no_header: bool,
+    },
+
+    /// Compare two catalogue files, fruit by fruit.
+    /// Command: `fruitdata diff old.json new.json [--format json]`
+    ///
+    /// Lists the fruits added, removed and changed (with each changed
+    /// field) going from BEFORE to AFTER. Exits with status 1 if there are
+    /// any differences, so CI can tell whether a change touched the data.
+    Diff {
+        /// The old version of the catalogue
+        before: PathBuf,
+        /// The new version of the catalogue
+        after: PathBuf,
+        /// Output format: text (default) or json
+        #[arg(long, alias = "output", default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Check a catalogue file and print a JSON report of every problem.
+    /// Command: `fruitdata validate [FILE] [--checksum HEX]`
+    ///
+    /// Checks the structure, the rules from the config (names, dimensions,
+    /// limits), duplicates and optionally the checksum. Exits with status 1
+    /// if anything is wrong, so it can gate catalogue changes in CI. With
+    /// the global `--strict`, unknown keys are errors too.
+    Validate {
+        /// The file to check (default: the catalogue, see `--file`)
+        file: Option<PathBuf>,
+        /// The checksum the file must have, as printed in an earlier report
+        #[arg(long, value_name = "HEX")]
+        checksum: Option<String>,
+    },
+
+    /// Warn about data that is allowed but looks wrong.
+    /// Command: `fruitdata lint --deny warnings`
+    ///
+    /// Looks for dimension outliers, inconsistent name casing and (if
+    /// enabled) fruits without tags; the config's `lint.rules` switch rules
+    /// on and off. Warnings don't fail the command unless they are denied.
+    Lint {
+        /// Fail if there are warnings: "warnings" for all, or a rule name
+        /// (repeatable)
+        #[arg(long, value_name = "WHAT")]
+        deny: Vec<Deny>,
+        /// Output format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Report fruits that might be duplicates, without changing anything.
+    /// Command: `fruitdata dupes --tolerance 0.05`
+    ///
+    /// Groups fruits whose names are the same apart from case and
+    /// whitespace, and fruits whose dimensions are all within the tolerance
+    /// of each other (a fraction of the larger, 0.02 by default), into
+    /// clusters. Deciding which to keep is left to you.
+    Dupes {
+        /// How close dimensions must be to count as the same, as a fraction
+        /// (0.02 is 2%)
+        #[arg(long, default_value_t = 0.02)]
+        tolerance: f32,
+        /// Output format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Sort every fruit into the grades defined in the config file, and
+    /// store each fruit's grade in it.
+    /// Command: `fruitdata grade`
+    ///
+    /// Grades are bands of volume (or another expression) in the config's
+    /// `grading` section; see grading.rs. Prints how many fruits each grade
+    /// got. The grade is stored in each fruit's `grade` field, so that e.g.
+    /// `export --where '.grade == "Class A"'` can use it.
+    Grade {
+        /// Output format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Run a jq-style query over the catalogue (a JSON array of fruits).
+    /// Command: `fruitdata query '.[] | select(.length > 5) | .name'`
+    ///
+    /// Each result is printed as JSON; `--raw-output` prints strings
+    /// without quotes. See query.rs for the supported subset of jq.
+    Query {
+        /// The query, e.g. '.[] | select(.length > 5) | .name'
+        filter: Query,
+        /// Print string results as plain text rather than JSON strings
+        #[arg(short, long)]
+        raw_output: bool,
+    },
+
+    /// Count the fruits, or the ones that pass a jq-style condition.
+    /// Command: `fruitdata count --where '.length > 5'`
+    ///
+    /// Prints only the number, so scripts don't have to parse `list`.
+    Count {
+        /// Only count the fruits for which this query is true (see query.rs),
+        /// e.g. '.length > 5'
+        #[arg(long = "where", value_name = "CONDITION")]
+        condition: Option<Query>,
+        /// Output format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Show totals and averages over the catalogue.
+    /// Command: `fruitdata stats`
+    ///
+    /// Prints the number of fruits, their total volume and estimated mass,
+    /// and the mean dimensions and volume. `--group-by` prints them for
+    /// each tag, origin or shape instead.
+    /// Example: `fruitdata stats --group-by tag`
+    Stats {
+        /// Aggregate each group separately: tag, origin or shape
+        #[arg(long, value_name = "KEY")]
+        group_by: Option<GroupBy>,
+        /// Output format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Cross-tabulate the catalogue: one row per value of one key, one
+    /// column per value of another.
+    /// Command: `fruitdata summary --rows origin --cols tag --value 'mean(volume)'`
+    ///
+    /// Keys are as for `stats --group-by`. Without `--cols` there is one
+    /// column, over all of a row's fruits. `--csv` prints comma-separated
+    /// values for spreadsheets instead of a table.
+    Summary {
+        /// The key of the rows: tag, origin or shape
+        #[arg(long, value_name = "KEY")]
+        rows: GroupBy,
+        /// The key of the columns: tag, origin or shape
+        #[arg(long, value_name = "KEY")]
+        cols: Option<GroupBy>,
+        /// What each cell shows: count, sum(FIELD) or mean(FIELD)
+        #[arg(long, default_value = "count")]
+        value: CellValue,
+        /// Print CSV instead of a table
+        #[arg(long)]
+        csv: bool,
+    },
+
+    /// Record dated measurements of a fruit.
+    /// Command: `fruitdata measure add Apple 4.4 2.7 1.6 --at 2024-06-15`
+    Measure {
+        #[command(subcommand)]
+        command: MeasureCommand,
+    },
+
+    /// Show how a fruit grew between its measurements.
+    /// Command: `fruitdata growth Apple`
+    ///
+    /// Lists each measurement, oldest first, with the fruit's volume at the
+    /// time and how much it changed since the one before.
+    Growth {
+        /// The name of the fruit
+        name: String,
+        /// Show the entry with this ID (required when the name is shared)
+        #[arg(long)]
+        id: Option<u64>,
+        /// Output format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Print a fruit's label for a label printer.
+    /// Command: `fruitdata label Apple --format zpl -o apple.zpl`
+    ///
+    /// The label shows the name, dimensions and PLU (the fruit's `plu`
+    /// field), and a Code 128 barcode of the PLU (or ID). `--format` picks
+    /// the printer language: zpl (Zebra, the default) or escpos (receipt
+    /// printers). Without `--output` the label is printed, e.g. to pipe to
+    /// the printer: `fruitdata label Apple | nc printer 9100`. The config's
+    /// `label` section can replace the templates (see label.rs).
+    Label {
+        /// The name of the fruit
+        name: String,
+        /// Label the entry with this ID (required when the name is shared)
+        #[arg(long)]
+        id: Option<u64>,
+        /// The printer language: zpl or escpos
+        #[arg(long, default_value = "zpl")]
+        format: LabelFormat,
+        /// Write the label to this file (or printer device) instead
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Draw a fruit's barcode as an image.
+    /// Command: `fruitdata barcode Apple -o apple.svg`
+    ///
+    /// The barcode holds the fruit's PLU (its `plu` field), or its ID, as on
+    /// its label. The image is an SVG or a PNG, going by the output file's
+    /// extension. EAN-13 needs a PLU or ID of digits, which is padded with
+    /// zeros to 12 and given its check digit.
+    #[cfg(feature = "barcode")]
+    Barcode {
+        /// The name of the fruit
+        name: String,
+        /// Draw the entry with this ID (required when the name is shared)
+        #[arg(long)]
+        id: Option<u64>,
+        /// The image to write: a .svg or .png file
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+        /// The kind of barcode: code128 or ean13
+        #[arg(long, default_value = "code128")]
+        symbology: Symbology,
+        /// The width of the narrowest bar, in pixels
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..=20))]
+        scale: u32,
+        /// The height of the bars, in pixels
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..=1000))]
+        height: u32,
+    },
+
+    /// Keep the catalogue in memory and run the commands sent to a socket.
+    /// Command: `fruitdata daemon [--socket FILE]`
+    ///
+    /// Each line sent to the Unix socket is a command, as typed after
+    /// `fruitdata` (e.g. `add Kiwi 3 2 2`), and is answered with one line of
+    /// JSON holding its output. The catalogue is saved every `--after`
+    /// changes, once a change is `--interval` seconds old, and when a
+    /// client sends `save` or `shutdown` (which also stops the daemon).
+    /// See daemon.rs.
+    #[cfg(unix)]
+    Daemon {
+        /// The socket to listen on (default: the catalogue file's name with
+        /// `.sock` added)
+        #[arg(long, value_name = "FILE")]
+        socket: Option<PathBuf>,
+        /// Save once the oldest unsaved change is this many seconds old, 0
+        /// for never (default: the config's `daemon.interval_secs`, 30)
+        #[arg(long, value_name = "SECONDS")]
+        interval: Option<u64>,
+        /// Save after this many changes, 0 for never (default: the
+        /// config's `daemon.after_changes`, 20)
+        #[arg(long, value_name = "N")]
+        after: Option<u32>,
+    },
+
+    /// Answer JSON-RPC 2.0 requests from editors and local tools on a Unix
+    /// socket.
+    /// Command: `fruitdata ipc [--socket FILE]`
+    ///
+    /// Each request is one line of JSON naming a command and its arguments,
+    /// e.g. `{"jsonrpc": "2.0", "id": 1, "method": "add", "params": ["Kiwi",
+    /// 3, 2, 2]}`, and is answered with the command's report as JSON. Every
+    /// change is saved at once. The `shutdown` method stops the server. See
+    /// ipc.rs.
+    #[cfg(unix)]
+    Ipc {
+        /// The socket to listen on (default: the catalogue file's name with
+        /// `.rpc` added)
+        #[arg(long, value_name = "FILE")]
+        socket: Option<PathBuf>,
+    },
+
+    /// Record the readings measuring stations publish over MQTT.
+    /// Command: `fruitdata ingest-mqtt --topic fruit/measurements`
+    ///
+    /// Each message is a JSON reading such as `{"name": "Apple", "length":
+    /// 4.4, "width": 2.7, "height": 1.6}` (see ingest.rs). A reading of a
+    /// known fruit is recorded as a measurement, one of a new fruit adds
+    /// it, and the catalogue is saved after each. Invalid readings are
+    /// skipped and listed at the end. The password, if the broker needs
+    /// one, is read from `FRUITDATA_MQTT_PASSWORD`.
+    #[cfg(feature = "mqtt")]
+    IngestMqtt {
+        /// The topic filter to subscribe to (may contain `+` and `#`)
+        #[arg(long)]
+        topic: String,
+        /// The broker, as host:port
+        #[arg(long, default_value = "localhost:1883")]
+        broker: String,
+        /// The client ID to connect with (default: fruitdata-<process ID>)
+        #[arg(long)]
+        client_id: Option<String>,
+        /// The user name to log in with
+        #[arg(long)]
+        username: Option<String>,
+        /// Stop after this many messages (default: until the broker
+        /// disconnects)
+        #[arg(long, value_name = "N")]
+        count: Option<u64>,
+        /// Report format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Recover what can be saved from a damaged catalogue file.
+    /// Command: `fruitdata repair [FILE] [-o FILE]`
+    ///
+    /// Every fruit that is still intact is written to a new file (by
+    /// default `<name>.repaired.json` next to the damaged one), and each
+    /// record that couldn't be read is listed. The damaged file is left as
+    /// it is.
+    Repair {
+        /// The damaged file (default: the catalogue, see `--file`)
+        input: Option<PathBuf>,
+        /// Where to write the recovered fruits
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// Report format: text (default) or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Rebuild the catalogue as it was at a point in time, from the journal.
+    /// Command: `fruitdata restore --at "2024-06-01T12:00" -o old-fruits.json`
+    ///
+    /// With `--output` the result goes to a new file and the catalogue is
+    /// left alone. Without it, the catalogue itself is replaced, after
+    /// asking for confirmation (skip the question with `--yes`).
+    Restore {
+        /// The moment to go back to (YYYY-MM-DD[THH:MM[:SS]], UTC)
+        #[arg(long, value_name = "TIME")]
+        at: Timestamp,
+        /// Write the restored catalogue to this new file instead
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// Replace the catalogue without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+/// Subcommands of `fruitdata history`.
+#[derive(Subcommand, Clone)]
+pub enum HistoryCommand {
+    /// Prune journal entries older than the retention period.
+    /// Command: `fruitdata history gc --keep 90d`
+    ///
+    /// Older entries are folded into one snapshot, so the catalogue can
+    /// still be restored to any time within the period kept.
+    Gc {
+        /// How much history to keep, e.g. 90d, 12h, 2w (default: the
+        /// config's `journal.keep`)
+        #[arg(long, value_name = "PERIOD")]
+        keep: Option<Period>,
+    },
+}
+
+/// Subcommands of `fruitdata measure`.
+#[derive(Subcommand, Clone)]
+pub enum MeasureCommand {
+    /// Record a measurement of a fruit.
+    /// Command: `fruitdata measure add Apple 4.4 2.7 1.6 [--at TIME]`
+    ///
+    /// If it is the newest measurement, its dimensions also become the
+    /// fruit's own.
+    Add {
+        /// The name of the fruit
+        name: String,
+        /// Length dimension (must be a positive number)
+        length: f32,
+        /// Width dimension (must be a positive number)
+        width: f32,
+        /// Height dimension (must be a positive number)
+        height: f32,
+        /// When the fruit was measured (YYYY-MM-DD[THH:MM[:SS]], UTC;
+        /// default: now)
+        #[arg(long, value_name = "TIME")]
+        at: Option<Timestamp>,
+        /// Measure the entry with this ID (required when the name is shared)
+        #[arg(long)]
+        id: Option<u64>,
+    },
+}
+
+impl Commands {
+    /// Returns `false` for commands that never load the stored catalogue,
+    /// but create it, work on other files, or (like `validate` and
+    /// `repair`) read a possibly broken file themselves.
+    fn loads_catalogue(&self) -> bool {
+        !matches!(
+            self,
+            Commands::Init { .. }
+                | Commands::Convert { .. }
+                | Commands::Diff { .. }
+                | Commands::Validate { .. }
+                | Commands::Repair { .. }
+        )
+    }
+
+    /// Whether this is one of the long-running servers (`daemon`, `ipc`,
+    /// `ingest-mqtt`), which can't be run from inside another server or
+    /// through `parse_and_run`.
+    pub fn is_server(&self) -> bool {
+        match self {
+            #[cfg(unix)]
+            Commands::Daemon { .. } | Commands::Ipc { .. } => true,
+            #[cfg(feature = "mqtt")]
+            Commands::IngestMqtt { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` for commands that change (and save) the catalogue.
+    ///
+    /// An exhaustive `match` (rather than a `_ => false` catch-all) makes
+    /// the compiler ask about every new command, so a mutating command can't
+    /// accidentally slip past `--read-only`.
+    pub fn is_mutation(&self) -> bool {
+        match self {
+            Commands::List { .. }
+            | Commands::Get { .. }
+            | Commands::Exists { .. }
+            | Commands::Audit { .. }
+            | Commands::Convert { .. }
+            | Commands::Export { .. }
+            | Commands::Validate { .. }
+            | Commands::Diff { .. }
+            | Commands::Lint { .. }
+            | Commands::Dupes { .. }
+            | Commands::Query { .. }
+            | Commands::Count { .. }
+            | Commands::Stats { .. }
+            | Commands::Summary { .. }
+            | Commands::Growth { .. }
+            | Commands::Label { .. }
+            | Commands::Repair { .. }
+            | Commands::History { command: None, .. } => false,
+            // Compacting doesn't touch the catalogue, but it permanently
+            // discards history, so it needs write access too
+            Commands::History {
+                command: Some(HistoryCommand::Gc { .. }),
+                ..
+            } => true,
+            // Restoring into a separate file leaves the catalogue untouched
+            Commands::Restore { output, .. } => output.is_none(),
+            Commands::Import { dry_run, .. } => !dry_run,
+            Commands::Init { .. }
+            | Commands::Add { .. }
+            | Commands::Compact { .. }
+            | Commands::Grade { .. }
+            | Commands::Measure { .. }
+            | Commands::Remove { .. }
+            | Commands::Rename { .. }
+            | Commands::Update { .. } => true,
+            #[cfg(feature = "mqtt")]
+            Commands::IngestMqtt { .. } => true,
+            #[cfg(feature = "barcode")]
+            Commands::Barcode { .. } => false,
+            // The commands it is sent are checked one by one
+            #[cfg(unix)]
+            Commands::Daemon { .. } | Commands::Ipc { .. } => false,
+        }
+    }
+
+    /// The format the command's `--format` flag asked for (text for
+    /// commands without one).
+    pub fn output_format(&self) -> OutputFormat {
+        match self {
+            Commands::List { format, .. }
+            | Commands::Count { format, .. }
+            | Commands::Audit { format, .. }
+            | Commands::History { format, .. }
+            | Commands::Lint { format, .. }
+            | Commands::Dupes { format, .. }
+            | Commands::Grade { format, .. }
+            | Commands::Diff { format, .. }
+            | Commands::Stats { format, .. }
+            | Commands::Growth { format, .. }
+            | Commands::Repair { format, .. } => *format,
+            #[cfg(feature = "mqtt")]
+            Commands::IngestMqtt { format, .. } => *format,
+            _ => OutputFormat::Text,
+        }
+    }
+
+    /// The fruit name `get`, `remove` or `update` looks for, unless `--id`
+    /// picks the entry instead.
+    fn looked_up_name(&self) -> Option<&str> {
+        match self {
+            Commands::Get { name, id: None, .. }
+            | Commands::Remove { name, id: None }
+            | Commands::Update { name, id: None, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The same command, looking for `fruit` instead.
+    fn looking_up(&self, fruit: &str) -> Commands {
+        let mut command = self.clone();
+        if let Commands::Get { name, .. }
+        | Commands::Remove { name, .. }
+        | Commands::Update { name, .. } = &mut command
+        {
+            *name = fruit.to_string();
+        }
+        command
+    }
+}
+
+/// A command line without the program name, as parsed by `parse_words`.
+#[derive(clap::Parser)]
+#[command(name = "fruitdata", no_binary_name = true)]
+struct Line {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+/// Parse a command from its words, as typed after `fruitdata` (without
+/// the global flags, which belong to the `Context`).
+///
+/// # Error Cases
+/// The words aren't a valid command; clap's error says how (see
+/// `clap::Error::kind`).
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::cli::{parse_words, Commands};
+///
+/// let command = parse_words(vec!["rm".into(), "Kiwi".into()]).unwrap();
+/// assert!(matches!(command, Commands::Remove { name, id: None } if name == "Kiwi"));
+/// assert!(parse_words(vec!["add".into(), "Kiwi".into()]).is_err());
+/// ```
+pub fn parse_words(words: Vec<String>) -> Result<Commands, clap::Error> {
+    use clap::Parser;
+    Line::try_parse_from(words).map(|line| line.command)
+}
+
+/// Parse one line of words, as typed after `fruitdata`, into a command.
+///
+/// # Error Cases
+/// The words aren't a valid command (the message is clap's, with usage), or
+/// a quote isn't closed.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::cli::{parse_command, Commands};
+///
+/// let command = parse_command("rename 'Golden Kiwi' Kiwi").unwrap();
+/// assert!(matches!(command, Commands::Rename { old_name, .. } if old_name == "Golden Kiwi"));
+/// assert!(parse_command("add Kiwi 3").is_err());
+/// ```
+pub fn parse_command(line: &str) -> Result<Commands, String> {
+    let words = split_words(line)?;
+    parse_words(words).map_err(|e| e.to_string().trim_end().to_string())
+}
+
+/// Split a line into words at whitespace, as a shell would: `'...'` and
+/// `"..."` quote spaces, and a backslash escapes the next character
+/// (outside single quotes).
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::cli::split_words;
+///
+/// assert_eq!(
+///     split_words(r#"add "Passion fruit" 5 4\ 4 '3'"#).unwrap(),
+///     ["add", "Passion fruit", "5", "4 4", "3"]
+/// );
+/// assert!(split_words("get 'Kiwi").is_err());
+/// ```
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unclosed single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unclosed double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unclosed double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.next());
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Parse `line` like `parse_command` and run it against `catalogue`, as
+/// `run_command` would against a store holding it, with the library's
+/// default rules (see `Context::new`). Made for chat bots and scripting
+/// hosts that want fruitdata's commands without starting the binary; the
+/// outcome's report can be shown with `present::render`.
+///
+/// The catalogue is changed only if the command succeeds, and then its
+/// subscribers (see `Catalogue::subscribe`) hear about every change.
+///
+/// Commands that read or write other files (`export`, `convert`, `diff`,
+/// `init <path>`, ...) do so with the host's permissions; a host taking
+/// commands from untrusted users should check the parsed command (see
+/// `parse_command`) first. The servers (`daemon`, `ipc`, `ingest-mqtt`)
+/// can't be started this way.
+///
+/// # Error Cases
+/// - `line` isn't a valid command (the message is clap's, with usage)
+/// - The same errors as `run_command`
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::cli::parse_and_run;
+/// use fruitdata::present::{render, OutputFormat};
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+/// let outcome = parse_and_run("add Kiwi 3 2 2", &mut catalogue).unwrap();
+/// assert_eq!(render(&outcome.report, OutputFormat::Text).unwrap(), "Added 'Kiwi'.");
+/// assert!(catalogue.find("Kiwi").is_ok());
+///
+/// let outcome = parse_and_run("get Kiwii", &mut catalogue).unwrap();
+/// assert_eq!(outcome.hints, ["Did you mean 'Kiwi'?"]);
+/// assert!(parse_and_run("add Kiwi", &mut catalogue).is_err());
+/// ```
+pub fn parse_and_run(
+    line: &str,
+    catalogue: &mut Catalogue,
+) -> Result<CommandOutcome, Box<dyn Error>> {
+    parse_and_run_with(line, catalogue, &mut Context::new(Config::default()))
+}
+
+/// Parse `line` and run it against `catalogue` like `parse_and_run`, with
+/// the rules and settings of `context`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::cli::{parse_and_run_with, Context};
+/// use fruitdata::config::Config;
+/// use fruitdata::present::{render, OutputFormat};
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+/// let mut context = Context::new(Config::default()).with_read_only(true);
+/// let outcome = parse_and_run_with("rm Pear", &mut catalogue, &mut context).unwrap();
+/// assert!(render(&outcome.report, OutputFormat::Text).unwrap().contains("read-only"));
+/// assert!(catalogue.find("Pear").is_ok());
+/// ```
+pub fn parse_and_run_with(
+    line: &str,
+    catalogue: &mut Catalogue,
+    context: &mut Context,
+) -> Result<CommandOutcome, Box<dyn Error>> {
+    let command = parse_command(line)?;
+    if command.is_server() {
+        return Err("servers can't be started from a command line".into());
+    }
+    run_command(&command, &BorrowedStore::new(catalogue), context)
+}
+
+/// Asks the user a yes/no question, e.g. before `restore` replaces the
+/// catalogue.
+pub type Confirm = Box<dyn FnMut(&str) -> Result<bool, Box<dyn Error>>>;
+
+/// The settings a command runs with, from the config file and the global
+/// flags.
+///
+/// `Context::new` starts from the library defaults: changes are allowed,
+/// loading is lenient, a missing catalogue is an error, concurrent edits
+/// abort the save, the actor is
+/// "unknown", every confirmation question is answered "no", progress
+/// isn't reported, unknown fruit names only get suggestions, and names
+/// match as the catalogue's header says (case-insensitively without one).
+pub struct Context {
+    config: Config,
+    actor: String,
+    read_only: bool,
+    strict: bool,
+    create: bool,
+    interactive: bool,
+    case_sensitive: bool,
+    conflict_policy: ConflictPolicy,
+    confirm: Confirm,
+    progress: Box<dyn Progress>,
+}
+
+impl Context {
+    /// Run commands with the rules from `config`.
+    pub fn new(config: Config) -> Self {
+        Context {
+            config,
+            actor: "unknown".to_string(),
+            read_only: false,
+            strict: false,
+            create: false,
+            interactive: false,
+            case_sensitive: false,
+            conflict_policy: ConflictPolicy::default(),
+            confirm: Box::new(|_| Ok(false)),
+            progress: Box::new(NoProgress),
+        }
+    }
+
+    /// Record changes in the journal as made by `actor`.
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = actor.into();
+        self
+    }
+
+    /// Refuse every command that would change the catalogue.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Validate files strictly: unknown keys and wrongly typed values are
+    /// errors too.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Start the default catalogue when the store doesn't exist yet,
+    /// instead of failing (`init` creates one explicitly).
+    pub fn with_create(mut self, create: bool) -> Self {
+        self.create = create;
+        self
+    }
+
+    /// When `get`, `remove` or `update` names no fruit, ask (with the
+    /// `confirm` callback) whether to use one of the closest names instead,
+    /// rather than only suggesting them.
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    /// Match fruit names exactly, so that "Kiwi" and "KIWI" are different
+    /// fruits, even if the catalogue's header doesn't ask for it. A command
+    /// that changes the catalogue sets the header, so later commands match
+    /// names the same way.
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// What to do when the store was changed by someone else meanwhile.
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// Ask questions with `confirm` (which returns `true` for "yes").
+    pub fn with_confirm(
+        mut self,
+        confirm: impl FnMut(&str) -> Result<bool, Box<dyn Error>> + 'static,
+    ) -> Self {
+        self.confirm = Box::new(confirm);
+        self
+    }
+
+    /// Report how far long operations (`convert`, `import`) have got.
+    pub fn with_progress(mut self, progress: impl Progress + 'static) -> Self {
+        self.progress = Box::new(progress);
+        self
+    }
+}
+
+/// What a command did.
+///
+/// Each variant carries the data behind the message the binary prints
+/// (see `present::render`), so callers can act on the result instead of
+/// parsing text. It serializes with an `"outcome"` tag, e.g.
+/// `{"outcome": "added", "fruit": {...}}`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Report {
+    /// The input was refused (unknown fruit, duplicate name, read-only
+    /// catalogue, ...); nothing changed.
+    Rejected {
+        #[serde(serialize_with = "message")]
+        error: CatalogError,
+    },
+
+    /// `list`: the fruits, in the requested order, and how to print them:
+    /// with a template (text only), or only some of their fields.
+    Listed {
+        fruits: Vec<FruitDimensions>,
+        #[serde(skip)]
+        template: Option<Template>,
+        #[serde(skip)]
+        fields: Option<FieldSelection>,
+        /// The catalogue's computed fields, for `fields` to pick from.
+        #[serde(skip)]
+        computed: Vec<ComputedField>,
+    },
+    /// `get`: every fruit matching `name` (none if it wasn't found).
+    Found {
+        name: String,
+        fruits: Vec<FruitDimensions>,
+        /// The catalogue's computed fields, shown with each fruit.
+        #[serde(skip)]
+        computed: Vec<ComputedField>,
+        /// Whether to summarise each fruit's measurements.
+        #[serde(skip)]
+        measurements: bool,
+    },
+    /// `exists`: whether a fruit with the name is in the catalogue.
+    Exists { name: String, exists: bool },
+
+    /// `add`: the fruit as stored (with its ID in ID mode), how its name
+    /// was cleaned up, if it was, and whether it looks mis-measured.
+    Added {
+        fruit: FruitDimensions,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cleaned: Option<NameChange>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+    },
+    /// `remove`: the fruit that was removed.
+    Removed { fruit: FruitDimensions },
+    /// `rename`: the fruit before and after.
+    Renamed {
+        before: FruitDimensions,
+        after: FruitDimensions,
+    },
+    /// `update`: the fruit before and after.
+    Updated {
+        before: FruitDimensions,
+        after: FruitDimensions,
+    },
+    /// `update` without any changes.
+    NothingToUpdate,
+
+    /// `audit`: the matching journal records, oldest first.
+    Audited { records: Vec<ChangeRecord> },
+    /// `history`: the fruit's revisions, oldest first.
+    History {
+        name: String,
+        revisions: Vec<Revision<FruitDimensions>>,
+    },
+
+    /// `history gc` without `--keep` or a configured retention period.
+    NoRetentionPeriod,
+    /// `history gc`: entries before `cutoff` were folded into a snapshot.
+    Pruned {
+        cutoff: Timestamp,
+        report: CompactionReport,
+    },
+
+    /// `import`: the input isn't (or doesn't contain) an array.
+    NotAnArray { input: PathBuf },
+    /// `import`: the array is empty.
+    NoRecords,
+    /// `import`: some records couldn't be mapped or added, so none were.
+    ImportRejected {
+        mapping: Vec<String>,
+        problems: Vec<String>,
+    },
+    /// `import`: what was done with each record (or, with `dry_run`, would
+    /// be).
+    Imported {
+        mapping: Vec<String>,
+        input: PathBuf,
+        mode: ImportMode,
+        summary: ImportSummary,
+        /// Names the ingest pipeline changed.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        cleaned: Vec<NameChange>,
+        /// Fruits kept although they are outside the plausible range.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+        dry_run: bool,
+    },
+
+    /// `compact`: the store's size before and after, in bytes (0 for a
+    /// store that isn't a file).
+    Compacted {
+        store: String,
+        bytes_before: u64,
+        bytes_after: u64,
+    },
+
+    /// `init`: the catalogue was created from `template`.
+    Initialised {
+        store: String,
+        template: String,
+        count: usize,
+    },
+    /// `init`: there is a catalogue already, which was left alone.
+    AlreadyInitialised { store: String },
+
+    /// A command that writes a new file found it existing already.
+    OutputExists { path: PathBuf },
+    /// `convert`
+    Converted {
+        count: usize,
+        input: PathBuf,
+        from: Format,
+        output: PathBuf,
+        to: Format,
+        /// The calibration applied, with `--calibrate`.
+        calibration: Option<Calibration>,
+    },
+    /// `export`: how many fruits were written to `output`.
+    Exported {
+        count: usize,
+        output: PathBuf,
+        to: Format,
+    },
+
+    /// `validate`: the report for the file at `path`.
+    Validated {
+        path: PathBuf,
+        report: ValidationReport,
+    },
+    /// `diff`: what changed from the `before` file to the `after` file.
+    Diffed {
+        before: PathBuf,
+        after: PathBuf,
+        diff: CatalogueDiff,
+    },
+    /// `lint`: the warnings, in catalogue order.
+    Linted { warnings: Vec<Warning> },
+    /// `label`: the label, and the file it was written to (if it wasn't to
+    /// be printed).
+    Labelled {
+        name: String,
+        format: LabelFormat,
+        label: String,
+        output: Option<PathBuf>,
+    },
+    /// `barcode`: what the barcode holds, and the image it was drawn in.
+    Barcoded {
+        name: String,
+        symbology: String,
+        text: String,
+        output: PathBuf,
+    },
+    /// `daemon`: how many commands it ran, and how often it saved, before
+    /// it was shut down.
+    DaemonStopped {
+        socket: PathBuf,
+        commands: u64,
+        saves: u64,
+    },
+    /// `ipc`: how many clients it served, and how many requests they made,
+    /// before it was shut down.
+    IpcStopped {
+        socket: PathBuf,
+        clients: u64,
+        requests: u64,
+    },
+    /// `grade`: how many fruits each grade got.
+    Graded { summary: GradeSummary },
+    /// `dupes`: the clusters of possible duplicates, and the tolerance
+    /// dimensions were compared with.
+    Duplicates {
+        tolerance: f32,
+        clusters: Vec<DuplicateCluster>,
+    },
+    /// `query`: every value the query produced, and whether strings are
+    /// printed raw (text output only).
+    Queried {
+        results: Vec<serde_json::Value>,
+        #[serde(skip)]
+        raw: bool,
+    },
+    /// `count` and `list --count`: how many fruits there are, or passed
+    /// the filters.
+    Counted { count: usize },
+    /// `stats`: totals and averages over the catalogue.
+    Stats { aggregate: Aggregate },
+    /// `stats --group-by`: totals and averages for each group, by key.
+    GroupedStats {
+        group_by: GroupBy,
+        groups: Vec<Group>,
+    },
+    /// `summary`: the pivot table, and whether to print it as CSV (text
+    /// output only).
+    Summarized {
+        pivot: Pivot,
+        #[serde(skip)]
+        csv: bool,
+    },
+    /// `measure add`: the fruit as it is now, and the measurement recorded.
+    Measured {
+        fruit: FruitDimensions,
+        measurement: Measurement,
+    },
+    /// `growth`: the fruit's measurements, oldest first.
+    Growth {
+        name: String,
+        steps: Vec<GrowthStep>,
+    },
+    /// `ingest-mqtt`: what each reading received on `topic` did, and why
+    /// the others were rejected.
+    Ingested {
+        topic: String,
+        outcomes: Vec<Ingested>,
+        rejected: Vec<String>,
+    },
+    /// `repair`: what was recovered from `input` into `output`.
+    Repaired {
+        input: PathBuf,
+        output: PathBuf,
+        recovery: Recovery,
+    },
+
+    /// `restore`: the journal doesn't go back as far as `at`.
+    NoRecordAt { at: Timestamp },
+    /// `restore --output`: the state at `at` was written to a new file.
+    RestoredToFile {
+        at: Timestamp,
+        output: PathBuf,
+        fruits: usize,
+    },
+    /// `restore`: the catalogue itself was put back to `at`.
+    Restored { store: String, at: Timestamp },
+    /// `restore`: the user said no.
+    RestoreCancelled,
+}
+
+impl From<CatalogError> for Report {
+    fn from(error: CatalogError) -> Self {
+        Report::Rejected { error }
+    }
+}
+
+fn message<S: serde::Serializer>(error: &CatalogError, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(error)
+}
+
+/// The result of `run_command`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandOutcome {
+    /// What the command did.
+    pub report: Report,
+    /// Remarks about how the command ran, such as a catalogue that couldn't
+    /// be loaded; the binary prints them on stderr.
+    pub notes: Vec<String>,
+    /// Set when the command ran but its verdict is "no", e.g. a file that
+    /// failed validation. The binary reports it and exits with status 1.
+    pub failure: Option<String>,
+    /// Advice on what to try next, such as the names closest to one that
+    /// wasn't found; the binary prints them on stderr after the report.
+    pub hints: Vec<String>,
+    /// The status the binary exits with when there is no `failure`: 0, 1
+    /// when `diff` found differences, or 2 when `exists` found no such
+    /// fruit.
+    pub status: i32,
+}
+
+/// Run one command against the catalogue in `store`.
+///
+/// Loads the catalogue (or, if the context allows it, starts the default
+/// one if it doesn't exist yet), runs the command, saves any changes back
+/// to the store and records them in its journal. A store that exists but
+/// can't be loaded is an error, so it is never saved over.
+///
+/// Rejected input (an unknown fruit, a duplicate name, ...) is reported as
+/// `Report::Rejected`, not as an error. `Err` means the command itself went
+/// wrong, e.g. the store couldn't be written.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::cli::{run_command, Commands, Context, Report};
+/// use fruitdata::config::Config;
+/// use fruitdata::error::CatalogError;
+/// use fruitdata::store::{CatalogStore, FileStore};
+///
+/// let path = std::env::temp_dir().join("fruitdata-cli-example.json");
+/// let path = path.to_str().unwrap();
+/// let store = FileStore::new(path);
+/// let mut context = Context::new(Config::default()).with_actor("alice").with_create(true);
+///
+/// let add = Commands::Add { name: " Kiwi".into(), length: 3.0, width: 2.0, height: 2.0 };
+/// let outcome = run_command(&add, &store, &mut context).unwrap();
+/// assert!(matches!(outcome.report, Report::Added { fruit, .. } if fruit.name == "Kiwi"));
+///
+/// let outcome = run_command(&add, &store, &mut context).unwrap();
+/// assert_eq!(outcome.report, CatalogError::DuplicateName("Kiwi".into()).into());
+///
+/// // A damaged file is left alone
+/// std::fs::write(path, r#"[{"name": "Kiwi", "length": null"#).unwrap();
+/// let error = run_command(&add, &store, &mut context).unwrap_err();
+/// assert!(error.to_string().contains("fruitdata repair"));
+/// assert_eq!(std::fs::read_to_string(path).unwrap(), r#"[{"name": "Kiwi", "length": null"#);
+/// # std::fs::remove_file(path).unwrap();
+/// # std::fs::remove_file(store.journal().unwrap().path()).unwrap();
+/// ```
+///
+/// CSV written by `export` is rounded as the config's `output.precision`
+/// (or the binary's `--precision`) says:
+/// ```
+/// use fruitdata::cli::{run_command, Commands, Context};
+/// use fruitdata::config::Config;
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::store::MemoryStore;
+///
+/// let store = MemoryStore::new(vec![FruitDimensions::new("Kiwi", 4.0, 2.84, 0.3)]);
+/// let mut config = Config::default();
+/// config.output.precision = Some(1);
+/// let mut context = Context::new(config);
+///
+/// let output = std::env::temp_dir().join("fruitdata-precision-example.csv");
+/// # let _ = std::fs::remove_file(&output);
+/// let export = Commands::Export {
+///     condition: None,
+///     output: output.clone(),
+///     to: None,
+///     delimiter: None,
+///     no_header: false,
+/// };
+/// run_command(&export, &store, &mut context).unwrap();
+/// assert_eq!(std::fs::read_to_string(&output).unwrap(), "name,length,width,height\nKiwi,4.0,2.8,0.3\n");
+/// # std::fs::remove_file(&output).unwrap();
+/// ```
+pub fn run_command(
+    command: &Commands,
+    store: &dyn CatalogStore,
+    context: &mut Context,
+) -> Result<CommandOutcome, Box<dyn Error>> {
+    let mut notes = Vec::new();
+    let config = &context.config;
+
+    // `init` creates the store, so there is nothing to load yet
+    if let Commands::Init { path, template } = command {
+        let report = if context.read_only {
+            CatalogError::ReadOnly.into()
+        } else {
+            initialise(store, path.as_deref(), template, config)?
+        };
+        return Ok(CommandOutcome {
+            report,
+            notes,
+            failure: None,
+            hints: Vec::new(),
+            status: 0,
+        });
+    }
+
+    // The `Catalogue` enforces the config's rules (naming, size limits)
+    // whenever a command changes or saves the data. A missing store is an
+    // error unless `--create` says to start the default catalogue. A store
+    // that exists but won't load is always an error: the next save would
+    // replace whatever it still holds.
+    let catalogue = match store.load() {
+        Ok(catalogue) => catalogue,
+        // Commands such as `diff` only need the rules
+        Err(_) if !command.loads_catalogue() => Catalogue::default(),
+        Err(_) if !store.exists() => {
+            if !context.create {
+                return Err(format!(
+                    "catalogue '{}' does not exist; create it with `fruitdata init`, \
+                     or pass --create",
+                    store.describe()
+                )
+                .into());
+            }
+            notes.push(format!(
+                "Catalogue '{}' does not exist; starting the default catalogue.",
+                store.describe()
+            ));
+            Catalogue::new(initialise_fruit_catalogue())
+        }
+        Err(e) => {
+            // Show where it went wrong, if the store can
+            let problem = store.explain(e.as_ref()).unwrap_or_else(|| e.to_string());
+            return Err(format!(
+                "could not load '{}'\n\n{}\n\nNothing was changed. `fruitdata repair` \
+                 saves the fruits that can still be read to a new file.",
+                store.describe(),
+                problem
+            )
+            .into());
+        }
+    };
+    // As the file's header says, before `--case-sensitive` overrides it
+    let recorded_case = catalogue.case_sensitivity();
+    let mut catalogue = if context.case_sensitive {
+        catalogue.with_case_sensitivity(CaseSensitivity::Sensitive)
+    } else {
+        catalogue
+    };
+    catalogue = catalogue
+        .with_name_policy(config.name_policy.clone())
+        .with_limits(config.limits.clone())
+        .with_plausible_range(config.plausible_range)
+        .with_hooks(config.hooks.clone())
+        .with_uniqueness(config.uniqueness)
+        .with_conflict_policy(context.conflict_policy)
+        .with_save_options(config.save.clone());
+
+    // The journal records who changed what. If it doesn't exist yet, its
+    // first entry is a snapshot of the catalogue before this command ran.
+    let journal = store.journal().filter(|_| config.journal.enabled);
+    let baseline = match &journal {
+        Some(journal) if !journal.exists() => catalogue.fruits().to_vec(),
+        _ => Vec::new(),
+    };
+
+    // In read-only mode, refuse commands that would change the catalogue
+    // before they touch anything (library users get the same guarantee
+    // from `Catalogue::into_read_only`)
+    if context.read_only && command.is_mutation() {
+        return Ok(CommandOutcome {
+            report: CatalogError::ReadOnly.into(),
+            notes,
+            failure: None,
+            hints: Vec::new(),
+            status: 0,
+        });
+    }
+
+    // A name that matches no fruit may be a typo. Suggest the closest
+    // names, or (with `--interactive`) offer to use one of them instead.
+    let mut hints = Vec::new();
+    let corrected = match command.looked_up_name() {
+        Some(name) if catalogue.find_all(name).is_empty() => {
+            let suggestions: Vec<String> = catalogue
+                .suggestions(name)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            let mut picked = None;
+            if context.interactive {
+                for suggestion in &suggestions {
+                    let question = format!(
+                        "Fruit '{}' not found. Did you mean '{}'?",
+                        name.trim(),
+                        suggestion
+                    );
+                    if (context.confirm)(&question)? {
+                        picked = Some(command.looking_up(suggestion));
+                        break;
+                    }
+                }
+            } else if !suggestions.is_empty() {
+                hints.push(format!("Did you mean {}?", one_of(&suggestions)));
+            }
+            picked
+        }
+        _ => None,
+    };
+    let command = corrected.as_ref().unwrap_or(command);
+
+    // A change made with `--case-sensitive` may store names that differ
+    // only in case ("Kiwi" and "KIWI"). Commands run without the flag
+    // couldn't tell those apart, so the file's header records it.
+    let records_case = context.case_sensitive
+        && command.is_mutation()
+        && recorded_case == CaseSensitivity::Insensitive;
+    if records_case {
+        catalogue.set_case_sensitivity(CaseSensitivity::Sensitive);
+    }
+
+    let report = execute(command, &mut catalogue, store, context)?;
+
+    // A command that changed the catalogue has saved it by now (a failed
+    // save returns early above), so every pending event really happened
+    let changes = catalogue.take_changes();
+    if records_case && !changes.is_empty() {
+        notes.push(format!(
+            "Catalogue '{}' now matches names exactly (its \"case_sensitive\" header is set).",
+            store.describe()
+        ));
+    }
+    let now = Timestamp::now();
+    let published: Vec<ChangeRecord> = match &context.config.publish.kafka {
+        Some(_) => changes
+            .iter()
+            .map(|event| ChangeRecord {
+                timestamp: now,
+                actor: context.actor.clone(),
+                event: event.clone(),
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    if let Some(journal) = journal {
+        journal.record(&baseline, &context.actor, now, changes)?;
+    }
+    // The changes are saved (and journalled) whether or not this works
+    if let Some(kafka) = context.config.publish.kafka.as_ref() {
+        if let Err(e) = kafka.publish(&published) {
+            notes.push(format!(
+                "Warning: could not publish {} changes to Kafka: {}",
+                published.len(),
+                e
+            ));
+        }
+    }
+
+    let failure = failure_of(command, &report);
+    let status = match report {
+        Report::Diffed { ref diff, .. } if !diff.is_empty() => 1,
+        Report::Exists { exists: false, .. } => 2,
+        _ => 0,
+    };
+    Ok(CommandOutcome {
+        report,
+        notes,
+        failure,
+        hints,
+        status,
+    })
+}
+
+/// Dispatch to the handler for `command`.
+///
+/// Validation errors from the catalogue (`CatalogError`) become
+/// `Report::Rejected` rather than being returned, because they describe bad
+/// input rather than a failure of the program itself.
+fn execute(
+    command: &Commands,
+    catalogue: &mut Catalogue,
+    store: &dyn CatalogStore,
+    context: &mut Context,
+) -> Result<Report, Box<dyn Error>> {
+    let config = &context.config;
+
+    let report = match command {
+        // ====================================================================
+        // COMMAND: init [--template <name|file>]
+        // ====================================================================
+        // `run_command` creates the catalogue before loading anything; this
+        // is the same, for a catalogue that is already at hand
+        Commands::Init { path, template } => initialise(store, path.as_deref(), template, config)?,
+
+        // ====================================================================
+        // COMMAND: list
+        // ====================================================================
+        // List all fruits in the catalogue (just their names)
+        Commands::List {
+            sort_by,
+            template,
+            fields,
+            glob,
+            regex,
+            size_class,
+            head,
+            tail,
+            count,
+            ..
+        } => {
+            // Filtering and sorting only change a copy; the catalogue itself
+            // is untouched
+            let mut fruits: Vec<FruitDimensions> = catalogue
+                .fruits()
+                .iter()
+                .filter(|f| glob.as_ref().is_none_or(|glob| glob.is_match(&f.name)))
+                .filter(|f| regex.as_ref().is_none_or(|regex| regex.is_match(&f.name)))
+                .filter(|f| {
+                    size_class.is_none_or(|class| f.size_class(&config.size_classes) == class)
+                })
+                .cloned()
+                .collect();
+            if let Some(spec) = sort_by {
+                spec.sort(&mut fruits);
+            }
+            if let Some(head) = head {
+                fruits.truncate(*head);
+            }
+            if let Some(tail) = tail {
+                fruits.drain(..fruits.len().saturating_sub(*tail));
+            }
+            if *count {
+                return Ok(Report::Counted {
+                    count: fruits.len(),
+                });
+            }
+            Report::Listed {
+                fruits,
+                template: template.clone(),
+                fields: fields.clone(),
+                computed: catalogue.computed_fields()?,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: get <name>
+        // ====================================================================
+        // Find the details of a specific fruit by name (and optionally a
+        // summary of its measurements)
+        Commands::Get {
+            name,
+            id,
+            measurements,
+        } => {
+            // With `--id`, show exactly that entry. Otherwise `find_all()`
+            // returns every fruit with the name (case-insensitive): "apple",
+            // "Apple", "APPLE" all match. Normally that's zero or one fruit;
+            // in ID mode it may be several.
+            let found = match fruit_key(catalogue, name, *id) {
+                Ok(FruitKey::Id(id)) => vec![catalogue.find(id)?],
+                Ok(FruitKey::Name(name)) => catalogue.find_all(name),
+                Err(e) => return Ok(e.into()),
+            };
+            Report::Found {
+                name: name.clone(),
+                fruits: found.into_iter().cloned().collect(),
+                computed: catalogue.computed_fields()?,
+                measurements: *measurements,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: exists <name>
+        // ====================================================================
+        // The answer is the exit status (see `CommandOutcome::status`)
+        Commands::Exists { name } => Report::Exists {
+            name: name.clone(),
+            exists: !catalogue.find_all(name).is_empty(),
+        },
+
+        // ====================================================================
+        // COMMAND: add <name> <length> <width> <height>
+        // ====================================================================
+        // Add a new fruit to the catalogue with the given dimensions
+        Commands::Add {
+            name,
+            length,
+            width,
+            height,
+        } => {
+            // Clean the name up (trim, control characters, whitespace) as
+            // the config says
+            let cleaned = config.sanitize.rename(name);
+            let name = cleaned
+                .as_ref()
+                .map_or(name.as_str(), |c| c.cleaned.as_str());
+            let fruit = FruitDimensions::new(name, *length, *width, *height);
+
+            // `Catalogue::add()` trims the name and validates it (not empty,
+            // allowed by the name policy, not a duplicate), the dimensions
+            // (all positive) and the config's pre_add hooks (see hooks.rs).
+            // Nothing is saved if any check fails.
+            match catalogue.add(fruit) {
+                Ok(()) => {
+                    store.save(catalogue)?;
+                    // New fruits go at the end
+                    let fruit = catalogue.fruits().last().cloned();
+                    let fruit = fruit.ok_or("the added fruit is missing")?;
+                    Report::Added {
+                        warnings: implausible(catalogue, [&fruit]),
+                        fruit,
+                        cleaned,
+                    }
+                }
+                Err(e) => e.into(),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: remove <name>
+        // ====================================================================
+        // Remove a fruit from the catalogue by name (case-insensitive)
+        Commands::Remove { name, id } => {
+            // `Catalogue::remove()` rejects empty names, reports names that
+            // don't match any fruit, and (in ID mode) refuses to guess when
+            // several fruits share the name
+            match fruit_key(catalogue, name, *id).and_then(|key| catalogue.remove(key)) {
+                Ok(fruit) => {
+                    store.save(catalogue)?;
+                    Report::Removed { fruit }
+                }
+                // Empty name or no fruit matched; nothing was removed
+                Err(e) => e.into(),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: rename <old_name> <new_name>
+        // ====================================================================
+        // Change the name of an existing fruit, keeping its dimensions
+        Commands::Rename {
+            old_name,
+            new_name,
+            id,
+        } => {
+            let changes = FruitPatch {
+                name: Some(new_name.clone()),
+                ..FruitPatch::default()
+            };
+            match patch_fruit(catalogue, old_name, *id, &changes) {
+                Ok((before, after)) => {
+                    store.save(catalogue)?;
+                    Report::Renamed { before, after }
+                }
+                Err(e) => e.into(),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: update <name> [--new-name N] [--length L] [--width W] [--height H]
+        // ====================================================================
+        // Change only the fields the user asked for
+        Commands::Update {
+            name,
+            id,
+            new_name,
+            length,
+            width,
+            height,
+            patch,
+        } => {
+            // Start from the JSON document (if any), then let flags override it
+            let mut changes: FruitPatch = match patch {
+                Some(json) => serde_json::from_str(json)?,
+                None => FruitPatch::default(),
+            };
+            changes.name = new_name.clone().or(changes.name);
+            changes.length = length.or(changes.length);
+            changes.width = width.or(changes.width);
+            changes.height = height.or(changes.height);
+
+            if changes.is_empty() {
+                return Ok(Report::NothingToUpdate);
+            }
+
+            match patch_fruit(catalogue, name, *id, &changes) {
+                Ok((before, after)) => {
+                    store.save(catalogue)?;
+                    Report::Updated { before, after }
+                }
+                Err(e) => e.into(),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: audit [--since T] [--until T] [--actor A] [--action X] [--fruit N]
+        // ====================================================================
+        // Find the journal entries that match every given filter
+        Commands::Audit {
+            since,
+            until,
+            actor,
+            action,
+            fruit,
+            ..
+        } => {
+            let filter = AuditFilter {
+                since: *since,
+                until: *until,
+                actor: actor.clone(),
+                action: *action,
+                name: fruit.clone(),
+            };
+            let entries = journal_of(store)?.read()?;
+            let records = filter.apply(&entries).into_iter().cloned().collect();
+            Report::Audited { records }
+        }
+
+        // ====================================================================
+        // COMMAND: history <name> [--id N]
+        // ====================================================================
+        // Find how one fruit changed over time
+        Commands::History {
+            name,
+            id,
+            command: None,
+            ..
+        } => {
+            let name = name.as_deref().ok_or("a fruit name is required")?;
+            let journal = journal_of(store)?;
+            // As with `--id` elsewhere, the name must match the entry too
+            // (under any of the names it has had)
+            let revisions = match id {
+                None => journal.history(name)?,
+                Some(id) => {
+                    let revisions = journal.history(*id)?;
+                    let named = revisions.iter().any(|r| {
+                        r.value.as_ref().is_some_and(|f| {
+                            catalogue
+                                .case_sensitivity()
+                                .names_equal(&f.name, name.trim())
+                        })
+                    });
+                    if named {
+                        revisions
+                    } else {
+                        Vec::new()
+                    }
+                }
+            };
+            Report::History {
+                name: name.trim().to_string(),
+                revisions,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: history gc [--keep <period>]
+        // ====================================================================
+        // Fold journal entries older than the retention period into a snapshot
+        Commands::History {
+            command: Some(HistoryCommand::Gc { keep }),
+            ..
+        } => {
+            let Some(keep) = keep.or(config.journal.keep) else {
+                return Ok(Report::NoRetentionPeriod);
+            };
+
+            let cutoff = Timestamp::now().minus(keep);
+            let report = journal_of(store)?.compact(cutoff)?;
+            Report::Pruned { cutoff, report }
+        }
+
+        // ====================================================================
+        // COMMAND: import <input> [--map field=path]... [--columns <list>]
+        //                [--columns-file <file>] [--records path] [--format <format>]
+        //                [--delimiter <char>] [--no-header] [--mode <mode>]
+        //                [--on-duplicate <policy>] [--dry-run]
+        // ====================================================================
+        // Map each record of a foreign JSON array (or CSV row) to a fruit and
+        // combine them with the catalogue
+        Commands::Import {
+            input,
+            maps,
+            columns,
+            columns_file,
+            records,
+            format,
+            delimiter,
+            no_header,
+            mode,
+            on_duplicate,
+            dry_run,
+        } => {
+            let file = fs::File::open(input)?;
+            context
+                .progress
+                .start("Reading", Some(file.metadata()?.len()));
+            let mut text = String::new();
+            let read = Tracked::new(file, context.progress.as_mut()).read_to_string(&mut text);
+            context.progress.finish();
+            read?;
+
+            let format = format
+                .or_else(|| Format::from_path(input))
+                .unwrap_or(Format::Json);
+            if records.is_some() && matches!(format, Format::JsonLines | Format::Csv) {
+                return Err("--records only applies to JSON, TOML and YAML input".into());
+            }
+            let layout = csv_layout(&config.save.csv, *delimiter, *no_header);
+            let data =
+                match format {
+                    Format::Json => serde_json::from_str(&text)?,
+                    Format::JsonLines => serde_json::Value::Array(
+                        text.lines()
+                            .enumerate()
+                            .filter(|(_, line)| !line.trim().is_empty())
+                            .map(|(number, line)| {
+                                serde_json::from_str(line).map_err(|e| {
+                                    format!("{}: line {}: {}", input.display(), number + 1, e)
+                                })
+                            })
+                            .collect::<Result<_, _>>()?,
+                    ),
+                    Format::Csv => serde_json::Value::Array(
+                        csv::records_with(&text, &layout)
+                            .map_err(|e| format!("{}: {}", input.display(), e))?,
+                    ),
+                    #[cfg(feature = "toml")]
+                    Format::Toml => crate::toml::parse(&text)
+                        .map_err(|e| format!("{}: {}", input.display(), e))?,
+                    #[cfg(feature = "yaml")]
+                    Format::Yaml => crate::yaml::parse(&text)
+                        .map_err(|e| format!("{}: {}", input.display(), e))?,
+                };
+
+            let array = match records {
+                Some(path) => path.get(&data),
+                None => Some(&data),
+            };
+            let Some(serde_json::Value::Array(items)) = array else {
+                return Ok(Report::NotAnArray {
+                    input: input.clone(),
+                });
+            };
+            if items.is_empty() {
+                return Ok(Report::NoRecords);
+            }
+
+            // Explicit mappings win over guesses from the first record: a
+            // mapping file, then --columns, then --map
+            let column_file = match columns_file {
+                Some(path) => fs::read_to_string(path)?
+                    .parse::<ColumnMapping>()
+                    .map_err(|e| format!("{}: {}", path.display(), e))?,
+                None => ColumnMapping::default(),
+            };
+            let field_mapping = FieldMapping::infer(&items[0])
+                .with(&column_file.0)
+                .with(columns.as_ref().map_or(&[][..], |columns| &columns.0))
+                .with(maps);
+            let mapping = field_mapping.describe();
+
+            // A CSV file in fruitdata's own columns (say, from `export`) is
+            // read whole, keeping the fields a mapping has no place for
+            let own_columns = format == Format::Csv
+                && maps.is_empty()
+                && columns.is_none()
+                && columns_file.is_none()
+                && csv::CATALOGUE_COLUMNS[..4]
+                    .iter()
+                    .all(|column| items.iter().all(|item| item.get(column).is_some()));
+            let mapped = if own_columns {
+                csv::to_fruits(&text, &layout).map_err(|problem| vec![problem])
+            } else {
+                field_mapping.apply(items)
+            };
+            let mut fruits = match mapped {
+                Ok(fruits) => fruits,
+                Err(problems) => return Ok(Report::ImportRejected { mapping, problems }),
+            };
+
+            // Messy exports: clean the names up before they're compared
+            let mut cleaned = Vec::new();
+            for fruit in &mut fruits {
+                if let Some(change) = config.sanitize.rename(&fruit.name) {
+                    fruit.name = change.cleaned.clone();
+                    cleaned.push(change);
+                }
+            }
+
+            // Site rules from the config: reshape each fruit, then check it
+            // in its final shape (see hooks.rs)
+            let mut problems = Vec::new();
+            for fruit in &mut fruits {
+                match config.hooks.transform(fruit) {
+                    Ok(transformed) => *fruit = transformed,
+                    Err(problem) => problems.push(problem),
+                }
+            }
+            if problems.is_empty() {
+                problems = fruits
+                    .iter()
+                    .filter_map(|fruit| config.hooks.check(fruit).err())
+                    .map(|e| e.to_string())
+                    .collect();
+            }
+            if !problems.is_empty() {
+                return Ok(Report::ImportRejected { mapping, problems });
+            }
+
+            // Import into a copy first, so that one rejected fruit leaves the
+            // catalogue exactly as it was
+            let mut staged = catalogue.clone();
+            let on_duplicate = on_duplicate.unwrap_or_else(|| mode.default_on_duplicate());
+            let summary = match import_into(
+                &mut staged,
+                fruits,
+                *mode,
+                on_duplicate,
+                context.progress.as_mut(),
+            ) {
+                Ok(summary) => summary,
+                Err(e) => {
+                    return Ok(Report::ImportRejected {
+                        mapping,
+                        problems: vec![e.to_string()],
+                    })
+                }
+            };
+
+            let warnings = implausible(&staged, summary.inserted.iter().chain(&summary.updated));
+            if !*dry_run {
+                *catalogue = staged;
+                store.save(catalogue)?;
+            }
+            Report::Imported {
+                mapping,
+                input: input.clone(),
+                mode: *mode,
+                summary,
+                cleaned,
+                warnings,
+                dry_run: *dry_run,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: compact [--sort-keys]
+        // ====================================================================
+        // Save the unchanged catalogue again, without pretty-printing
+        Commands::Compact { sort_keys } => {
+            let size = || {
+                store
+                    .path()
+                    .and_then(|path| fs::metadata(path).ok())
+                    .map_or(0, |m| m.len())
+            };
+            let bytes_before = size();
+
+            let options = SaveOptions {
+                pretty: false,
+                sort_keys: *sort_keys || config.save.sort_keys,
+                ..config.save.clone()
+            };
+            *catalogue = std::mem::take(catalogue).with_save_options(options);
+            store.save(catalogue)?;
+
+            Report::Compacted {
+                store: store.describe(),
+                bytes_before,
+                bytes_after: size(),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: convert <input> <output> [--from <format>] [--to <format>] [--calibrate]
+        // ====================================================================
+        // Copy a file into another format; the catalogue isn't involved
+        Commands::Convert {
+            input,
+            output,
+            from,
+            to,
+            calibrate,
+        } => {
+            let format_of = |path: &PathBuf, forced: Option<Format>, flag: &str| {
+                forced.or_else(|| Format::from_path(path)).ok_or_else(|| {
+                    format!(
+                        "can't tell the format of '{}' from its extension; use {}",
+                        path.display(),
+                        flag
+                    )
+                })
+            };
+            let from = format_of(input, *from, "--from")?;
+            let to = format_of(output, *to, "--to")?;
+
+            if output.exists() {
+                return Ok(Report::OutputExists {
+                    path: output.clone(),
+                });
+            }
+
+            // Write next to the output and rename at the end, so a failed
+            // conversion doesn't leave half a file behind
+            let mut temporary = output.clone().into_os_string();
+            temporary.push(".tmp");
+            // Progress is measured in bytes of the input read so far
+            let file = fs::File::open(input)?;
+            let total = file.metadata()?.len();
+            let writer = BufWriter::new(fs::File::create(&temporary)?);
+            context.progress.start("Converting", Some(total));
+            let reader = BufReader::new(Tracked::new(file, context.progress.as_mut()));
+            let converted = if *calibrate {
+                convert_calibrated(reader, from, writer, to, &config.save)
+                    .map(|(count, calibration)| (count, Some(calibration)))
+            } else {
+                convert(reader, from, writer, to, &config.save).map(|count| (count, None))
+            };
+            context.progress.finish();
+            let (count, calibration) = match converted {
+                Ok(converted) => converted,
+                Err(e) => {
+                    let _ = fs::remove_file(&temporary);
+                    return Err(e);
+                }
+            };
+            fs::rename(&temporary, output)?;
+
+            Report::Converted {
+                count,
+                input: input.clone(),
+                from,
+                output: output.clone(),
+                to,
+                calibration,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: export [--where <condition>] --output <file> [--to <format>]
+        //                [--delimiter <char>] [--no-header]
+        // ====================================================================
+        // Write the matching fruits to a new file; the catalogue is unchanged
+        Commands::Export {
+            condition,
+            output,
+            to,
+            delimiter,
+            no_header,
+        } => {
+            let to = to.or_else(|| Format::from_path(output)).ok_or_else(|| {
+                format!(
+                    "can't tell the format of '{}' from its extension; use --to",
+                    output.display()
+                )
+            })?;
+            if output.exists() {
+                return Ok(Report::OutputExists {
+                    path: output.clone(),
+                });
+            }
+
+            let fruits: Vec<FruitDimensions> = match condition {
+                Some(condition) => catalogue.select(condition)?.into_iter().cloned().collect(),
+                None => catalogue.fruits().to_vec(),
+            };
+            // CSV is for reading by people, so it is rounded as text
+            // output is (`--precision`)
+            let options = SaveOptions {
+                csv: csv_layout(&config.save.csv, *delimiter, *no_header)
+                    .with_precision(config.output.precision),
+                ..config.save.clone()
+            };
+            let writer = BufWriter::new(fs::File::create(output)?);
+            let count = export(&fruits, writer, to, &options)?;
+            Report::Exported {
+                count,
+                output: output.clone(),
+                to,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: diff <before> <after> [--format <text|json>]
+        // ====================================================================
+        // Compare two catalogue files; neither is the store's concern
+        Commands::Diff { before, after, .. } => {
+            let load = |path: &Path| -> Result<CatalogueDocument, Box<dyn Error>> {
+                let bytes = fs::read(path)
+                    .map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+                let bytes = document_json(&bytes, Format::for_catalogue(path))
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                if context.strict {
+                    CatalogueDocument::from_slice_strict(&bytes)
+                } else {
+                    Ok(CatalogueDocument::from_slice(&bytes)?)
+                }
+            };
+            let old = load(before)?;
+            let new = load(after)?;
+
+            // Names match as the new file says, unless `--case-sensitive`
+            let case = if context.case_sensitive {
+                CaseSensitivity::Sensitive
+            } else {
+                new.case_sensitivity().unwrap_or_default()
+            };
+            Report::Diffed {
+                before: before.clone(),
+                after: after.clone(),
+                diff: CatalogueDiff::between_in(&old.fruits, &new.fruits, case),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: validate [file] [--checksum <hex>]
+        // ====================================================================
+        // Check a file as it is on disk, without the fallbacks of loading
+        Commands::Validate { file, checksum } => {
+            let path = file_or_store(file.as_deref(), store)?;
+            let bytes = fs::read(path)?;
+
+            let report = Validator::default()
+                .with_name_policy(config.name_policy.clone())
+                .with_limits(config.limits.clone())
+                .with_uniqueness(config.uniqueness)
+                .with_case_sensitive(context.case_sensitive)
+                .with_strict(context.strict)
+                .with_expected_checksum(checksum.clone())
+                .validate(&bytes);
+            Report::Validated {
+                path: path.to_path_buf(),
+                report,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: lint [--deny <warnings|rule>] [--format <text|json>]
+        // ====================================================================
+        // Report suspicious data; only denied warnings are errors
+        Commands::Lint { .. } => Report::Linted {
+            warnings: lint(catalogue.fruits(), &config.lint),
+        },
+
+        // ====================================================================
+        // COMMAND: grade [--format <text|json>]
+        // ====================================================================
+        // Grade every fruit and store the grades
+        Commands::Grade { .. } => {
+            let summary = grade(catalogue, &config.grading)?;
+            if summary.changed > 0 {
+                store.save(catalogue)?;
+            }
+            Report::Graded { summary }
+        }
+
+        // ====================================================================
+        // COMMAND: dupes [--tolerance <fraction>] [--format <text|json>]
+        // ====================================================================
+        // Only report; resolving the duplicates is up to the user
+        Commands::Dupes { tolerance, .. } => {
+            if !(0.0..1.0).contains(tolerance) {
+                return Err(format!(
+                    "invalid tolerance {} (expected a fraction from 0 up to 1, e.g. 0.02)",
+                    tolerance
+                )
+                .into());
+            }
+            Report::Duplicates {
+                tolerance: *tolerance,
+                clusters: find_duplicates(catalogue.fruits(), *tolerance),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: query <filter> [--raw-output]
+        // ====================================================================
+        // Run the query over the fruits as they would be saved. Going through
+        // the JSON text keeps dimensions like 4.2 from turning into
+        // 4.199999809265137 on the way from f32 to a JSON number.
+        Commands::Query { filter, raw_output } => {
+            let fruits: serde_json::Value =
+                serde_json::from_str(&serde_json::to_string(catalogue.fruits())?)?;
+            Report::Queried {
+                results: filter.run(&fruits)?,
+                raw: *raw_output,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: count [--where <condition>]
+        // ====================================================================
+        Commands::Count { condition, .. } => Report::Counted {
+            count: catalogue.count(condition.as_ref())?,
+        },
+
+        // ====================================================================
+        // COMMAND: stats [--group-by <tag|origin|shape>] [--format <text|json>]
+        // ====================================================================
+        Commands::Stats {
+            group_by: Some(group_by),
+            ..
+        } => Report::GroupedStats {
+            group_by: *group_by,
+            groups: catalogue
+                .group_by(|fruit| group_by.keys(fruit))
+                .into_iter()
+                .map(|(key, aggregate)| Group { key, aggregate })
+                .collect(),
+        },
+        Commands::Stats { group_by: None, .. } => Report::Stats {
+            aggregate: catalogue.aggregate(),
+        },
+
+        // ====================================================================
+        // COMMAND: summary --rows <key> [--cols <key>] [--value <value>] [--csv]
+        // ====================================================================
+        Commands::Summary {
+            rows,
+            cols,
+            value,
+            csv,
+        } => Report::Summarized {
+            pivot: Pivot::new(catalogue.fruits(), *rows, *cols, *value),
+            csv: *csv,
+        },
+
+        // ====================================================================
+        // COMMAND: measure add <name> <length> <width> <height> [--at T] [--id N]
+        // ====================================================================
+        // Record a dated measurement (the newest also sets the dimensions)
+        Commands::Measure {
+            command:
+                MeasureCommand::Add {
+                    name,
+                    length,
+                    width,
+                    height,
+                    at,
+                    id,
+                },
+        } => {
+            let measurement =
+                Measurement::new(at.unwrap_or_else(Timestamp::now), *length, *width, *height);
+            let added = fruit_key(catalogue, name, *id)
+                .and_then(|key| catalogue.add_measurement(key, measurement));
+            match added {
+                Ok(()) => {
+                    store.save(catalogue)?;
+                    let key = match id {
+                        Some(id) => FruitKey::Id(*id),
+                        None => FruitKey::Name(name),
+                    };
+                    Report::Measured {
+                        fruit: catalogue.find(key)?.clone(),
+                        measurement,
+                    }
+                }
+                Err(e) => e.into(),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: growth <name> [--id N] [--format <text|json>]
+        // ====================================================================
+        // Compare each of a fruit's measurements with the one before
+        Commands::Growth { name, id, .. } => {
+            let fruit = match fruit_key(catalogue, name, *id).and_then(|key| catalogue.find(key)) {
+                Ok(fruit) => fruit,
+                Err(e) => return Ok(e.into()),
+            };
+            Report::Growth {
+                name: fruit.name.clone(),
+                steps: growth(&fruit.measurements),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: label <name> [--id N] [--format <zpl|escpos>] [--output <file>]
+        // ====================================================================
+        // Fill in the label template for the printer
+        Commands::Label {
+            name,
+            id,
+            format,
+            output,
+        } => {
+            let fruit = match fruit_key(catalogue, name, *id).and_then(|key| catalogue.find(key)) {
+                Ok(fruit) => fruit,
+                Err(e) => return Ok(e.into()),
+            };
+            let label = config.label.render(fruit, *format)?;
+            if let Some(output) = output {
+                fs::write(output, &label)?;
+            }
+            Report::Labelled {
+                name: fruit.name.clone(),
+                format: *format,
+                label,
+                output: output.clone(),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: barcode <name> --output <file> [--symbology <code128|ean13>]
+        // ====================================================================
+        // Encode the fruit's PLU or ID and draw it
+        #[cfg(feature = "barcode")]
+        Commands::Barcode {
+            name,
+            id,
+            output,
+            symbology,
+            scale,
+            height,
+        } => {
+            let image_format = ImageFormat::from_path(output)?;
+            let fruit = match fruit_key(catalogue, name, *id).and_then(|key| catalogue.find(key)) {
+                Ok(fruit) => fruit,
+                Err(e) => return Ok(e.into()),
+            };
+            let data = crate::label::barcode_data(fruit).ok_or_else(|| {
+                format!(
+                    "'{}' has no PLU or ID to make a barcode of (set its \"plu\" field)",
+                    fruit.name
+                )
+            })?;
+            let barcode = Barcode::encode(&data, *symbology)?;
+            fs::write(output, barcode.render(image_format, *scale, *height))?;
+            Report::Barcoded {
+                name: fruit.name.clone(),
+                symbology: symbology.to_string(),
+                text: barcode.text,
+                output: output.clone(),
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: daemon [--socket <file>] [--interval <secs>] [--after N]
+        // ====================================================================
+        // Serve commands from the socket until a client asks to stop
+        #[cfg(unix)]
+        Commands::Daemon {
+            socket,
+            interval,
+            after,
+        } => {
+            let socket = match (socket, store.path()) {
+                (Some(socket), _) => socket.clone(),
+                (None, Some(path)) => {
+                    let mut socket = path.as_os_str().to_owned();
+                    socket.push(".sock");
+                    PathBuf::from(socket)
+                }
+                (None, None) => return Err("the daemon needs a --socket to listen on".into()),
+            };
+            let settings = DaemonSettings {
+                interval_secs: interval.unwrap_or(config.daemon.interval_secs),
+                after_changes: after.unwrap_or(config.daemon.after_changes),
+            };
+            let output = config.output.clone();
+            // Nobody is at the daemon's terminal to answer questions
+            context.interactive = false;
+            let summary = daemon::serve(
+                &socket,
+                store,
+                catalogue.clone(),
+                context,
+                output,
+                &settings,
+            )?;
+            Report::DaemonStopped {
+                socket,
+                commands: summary.commands,
+                saves: summary.saves,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: ipc [--socket <file>]
+        // ====================================================================
+        // Answer JSON-RPC requests from the socket until a client asks to stop
+        #[cfg(unix)]
+        Commands::Ipc { socket } => {
+            let socket = match (socket, store.path()) {
+                (Some(socket), _) => socket.clone(),
+                (None, Some(path)) => {
+                    let mut socket = path.as_os_str().to_owned();
+                    socket.push(".rpc");
+                    PathBuf::from(socket)
+                }
+                (None, None) => return Err("the server needs a --socket to listen on".into()),
+            };
+            let output = config.output.clone();
+            context.interactive = false;
+            let summary = ipc::serve(&socket, store, context, output)?;
+            Report::IpcStopped {
+                socket,
+                clients: summary.clients,
+                requests: summary.requests,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: ingest-mqtt --topic <filter> [--broker <host:port>] [--count N]
+        // ====================================================================
+        // Subscribe to the stations' topic, and record each reading as it
+        // arrives
+        #[cfg(feature = "mqtt")]
+        Commands::IngestMqtt {
+            topic,
+            broker,
+            client_id,
+            username,
+            count,
+            ..
+        } => {
+            let client_id = client_id
+                .clone()
+                .unwrap_or_else(|| format!("fruitdata-{}", std::process::id()));
+            let mut options = MqttOptions::new(broker, client_id);
+            if let Some(username) = username {
+                let password = std::env::var("FRUITDATA_MQTT_PASSWORD").ok();
+                options = options.with_credentials(username, password);
+            }
+            let mut subscriber = Subscriber::connect(&options)?;
+            subscriber.subscribe(topic)?;
+
+            let mut outcomes = Vec::new();
+            let mut rejected = Vec::new();
+            context.progress.start("Ingesting", *count);
+            while count.is_none_or(|count| ((outcomes.len() + rejected.len()) as u64) < count) {
+                let Some(message) = subscriber.next_message()? else {
+                    break;
+                };
+                match ingest(catalogue, &message.payload, Timestamp::now()) {
+                    Ok(outcome) => {
+                        store.save(catalogue)?;
+                        outcomes.push(outcome);
+                    }
+                    Err(e) => rejected.push(format!("{}: {}", message.topic, e)),
+                }
+                // Only once the reading is saved, so that the broker sends
+                // it again if we stop first
+                subscriber.acknowledge(&message)?;
+                context.progress.advance(1);
+            }
+            context.progress.finish();
+            subscriber.disconnect()?;
+            Report::Ingested {
+                topic: topic.clone(),
+                outcomes,
+                rejected,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: repair [file] [--output <file>] [--format <text|json>]
+        // ====================================================================
+        // Salvage the intact fruits of a broken file into a new one
+        Commands::Repair { input, output, .. } => {
+            let input = file_or_store(input.as_deref(), store)?;
+            let output = match output {
+                Some(output) => output.clone(),
+                None => input.with_extension("repaired.json"),
+            };
+            let output_path = output
+                .to_str()
+                .ok_or_else(|| "invalid output path".to_string())?;
+            if output.exists() {
+                return Ok(Report::OutputExists { path: output });
+            }
+
+            let recovery = recover(&fs::read(input)?);
+            save_catalogue_with(&recovery.fruits, output_path, &config.save)?;
+            Report::Repaired {
+                input: input.to_path_buf(),
+                output,
+                recovery,
+            }
+        }
+
+        // ====================================================================
+        // COMMAND: restore --at <time> [--output <file>] [--yes]
+        // ====================================================================
+        // Replay the journal up to the given time
+        Commands::Restore { at, output, yes } => {
+            let Some(fruits) = state_at(&journal_of(store)?.read()?, *at) else {
+                return Ok(Report::NoRecordAt { at: *at });
+            };
+
+            match output {
+                // Write to a new file; never overwrite an existing one
+                Some(output) => {
+                    let output_path = output
+                        .to_str()
+                        .ok_or_else(|| "invalid output path".to_string())?;
+                    if Path::new(output_path).exists() {
+                        return Ok(Report::OutputExists {
+                            path: output.clone(),
+                        });
+                    }
+                    save_catalogue_with(&fruits, output_path, &config.save)?;
+                    Report::RestoredToFile {
+                        at: *at,
+                        output: output.clone(),
+                        fruits: fruits.len(),
+                    }
+                }
+
+                // Replace the catalogue itself. Going through `Catalogue`
+                // keeps the size limits and conflict detection, and journals
+                // the restore as ordinary changes made by this actor.
+                None => {
+                    let question = format!(
+                        "Replace '{}' with its {} fruits as of {}?",
+                        store.describe(),
+                        fruits.len(),
+                        at
+                    );
+                    if !*yes && !(context.confirm)(&question)? {
+                        return Ok(Report::RestoreCancelled);
+                    }
+                    catalogue.replace_all(fruits);
+                    store.save(catalogue)?;
+                    Report::Restored {
+                        store: store.describe(),
+                        at: *at,
+                    }
+                }
+            }
+        }
+    };
+
+    Ok(report)
+}
+
+/// Why a command that ran to the end still failed: a file that didn't
+/// validate, or lint warnings that `--deny` forbids.
+fn failure_of(command: &Commands, report: &Report) -> Option<String> {
+    match (command, report) {
+        (_, Report::Validated { path, report }) if !report.valid => Some(format!(
+            "'{}' failed validation ({} errors)",
+            path.display(),
+            report.errors.len()
+        )),
+        (Commands::Lint { deny, .. }, Report::Linted { warnings }) => {
+            let denied = warnings
+                .iter()
+                .filter(|w| deny.iter().any(|d| d.covers(w)))
+                .count();
+            (denied > 0).then(|| format!("{} denied lint warnings", denied))
+        }
+        _ => None,
+    }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+/// Create the catalogue in `store` (or in the file at `path`) from
+/// `template`, unless it exists.
+fn initialise(
+    store: &dyn CatalogStore,
+    path: Option<&Path>,
+    template: &CatalogueTemplate,
+    config: &Config,
+) -> Result<Report, Box<dyn Error>> {
+    let file;
+    let store = match path {
+        Some(path) => {
+            file = FileStore::new(path.to_str().ok_or("invalid file path")?);
+            &file as &dyn CatalogStore
+        }
+        None => store,
+    };
+    if store.exists() {
+        return Ok(Report::AlreadyInitialised {
+            store: store.describe(),
+        });
+    }
+    let mut catalogue = Catalogue::new(initialise_with_template(template)?)
+        .with_uniqueness(config.uniqueness)
+        .with_save_options(config.save.clone());
+    store.save(&mut catalogue)?;
+    Ok(Report::Initialised {
+        store: store.describe(),
+        template: template.to_string(),
+        count: catalogue.fruits().len(),
+    })
+}
+
+/// A warning for each of `fruits` outside the catalogue's plausible range
+/// (with a `fail` policy, such fruits never get this far).
+fn implausible<'a>(
+    catalogue: &Catalogue,
+    fruits: impl IntoIterator<Item = &'a FruitDimensions>,
+) -> Vec<String> {
+    let range = catalogue.plausible_range();
+    fruits
+        .into_iter()
+        .filter_map(|fruit| {
+            let reason = range.check(fruit).err()?;
+            Some(format!(
+                "Warning: '{}' looks mis-measured: {} (is it in the wrong unit?).",
+                fruit.name, reason
+            ))
+        })
+        .collect()
+}
+
+/// Quote `names` as a list for a sentence: "'A'", "'A' or 'B'", "'A', 'B'
+/// or 'C'".
+fn one_of(names: &[String]) -> String {
+    let quoted: Vec<String> = names.iter().map(|n| format!("'{}'", n)).collect();
+    match quoted.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+        _ => quoted.concat(),
+    }
+}
+
+/// Turn a fruit name plus an optional `--id` flag into a library `FruitKey`.
+///
+/// Without `--id` the name is used as-is. With `--id`, the ID selects the
+/// entry, but the name must still match it, so that a mistyped ID can't
+/// silently act on a different fruit.
+fn fruit_key<'a>(
+    catalogue: &Catalogue,
+    name: &'a str,
+    id: Option<u64>,
+) -> Result<FruitKey<'a>, CatalogError> {
+    match id {
+        None => Ok(FruitKey::Name(name)),
+        Some(id) => {
+            let fruit = catalogue.find(FruitKey::Id(id))?;
+            if catalogue
+                .case_sensitivity()
+                .names_equal(&fruit.name, name.trim())
+            {
+                Ok(FruitKey::Id(id))
+            } else {
+                Err(CatalogError::NotFound(format!("{} #{}", name.trim(), id)))
+            }
+        }
+    }
+}
+
+/// Apply `changes` to the fruit called `name` (or with the given ID),
+/// returning the fruit before and after.
+fn patch_fruit(
+    catalogue: &mut Catalogue,
+    name: &str,
+    id: Option<u64>,
+    changes: &FruitPatch,
+) -> Result<(FruitDimensions, FruitDimensions), CatalogError> {
+    let key = fruit_key(catalogue, name, id)?;
+    let before = catalogue.find(key)?.clone();
+    catalogue.apply_patch(key, changes)?;
+
+    // Fruits have an ID in ID mode and a unique name otherwise
+    let after = match before.id {
+        Some(id) => catalogue.find(id)?,
+        None => {
+            let name = changes.name.as_deref().unwrap_or(&before.name);
+            catalogue.find(name.trim())?
+        }
+    };
+    Ok((before, after.clone()))
+}
+
+/// The configured CSV layout, with `--delimiter` and `--no-header` applied.
+fn csv_layout(configured: &CsvOptions, delimiter: Option<char>, no_header: bool) -> CsvOptions {
+    configured
+        .with_delimiter(delimiter.unwrap_or(configured.delimiter))
+        .with_header(configured.header && !no_header)
+}
+
+/// The store's journal, for the commands that need one.
+fn journal_of(store: &dyn CatalogStore) -> Result<Journal, String> {
+    store
+        .journal()
+        .ok_or_else(|| format!("'{}' has no change journal", store.describe()))
+}
+
+/// The file a command was given, or else the store's own file.
+fn file_or_store<'a>(
+    file: Option<&'a Path>,
+    store: &'a dyn CatalogStore,
+) -> Result<&'a Path, String> {
+    file.or_else(|| store.path())
+        .ok_or_else(|| format!("'{}' isn't a file; name the file to use", store.describe()))
+}
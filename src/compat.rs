@@ -0,0 +1,101 @@
+// ============================================================================
+// compat.rs - Reading Files Written by Other Versions
+// ============================================================================
+// Catalogue files outlive the programs that wrote them. Early versions of
+// fruitdata (and many spreadsheet exports) wrote `len`, `w` and `h`; our
+// JavaScript services write `lengthCm`; a later version may rename a field
+// again. This module lists every name a field has had, so they all load.
+//
+// The compatibility policy:
+//
+// 1. A field's name in saved files only changes in a major release.
+// 2. Its old names stay accepted as aliases for good; a file that loaded
+//    once keeps loading.
+// 3. A new name is accepted as an alias one release *before* files are
+//    written with it, so the previous version can read newer files.
+// 4. Files are always written with the current names (or those chosen with
+//    `SaveOptions::field_naming`), never with an alias.
+//
+// serde needs each alias spelled out as an attribute, so `FruitDimensions`,
+// `FruitPatch`, `CompactFruit`, `FruitDimensionsRef` and `RawFruitRecord`
+// repeat the names below; `FIELD_ALIASES` is the list to keep them in step with, and the one
+// strict loading and `import` use. `upgrade` translates the old names in a
+// raw JSON fruit for tools that work on the JSON itself.
+// ============================================================================
+
+use serde_json::{Map, Value};
+
+/// Every field with other accepted names: the current name, then its
+/// aliases, oldest first.
+pub const FIELD_ALIASES: &[(&str, &[&str])] = &[
+    ("length", &["len", "lengthCm", "length_cm"]),
+    ("width", &["w", "widthCm", "width_cm"]),
+    ("height", &["h", "heightCm", "height_cm"]),
+];
+
+/// The current name of the field `key` names, if `key` is a current name or
+/// an alias of one in `FIELD_ALIASES`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::compat::canonical_name;
+///
+/// assert_eq!(canonical_name("len"), Some("length"));
+/// assert_eq!(canonical_name("width"), Some("width"));
+/// assert_eq!(canonical_name("origin"), None);
+/// ```
+pub fn canonical_name(key: &str) -> Option<&'static str> {
+    FIELD_ALIASES
+        .iter()
+        .find(|(name, aliases)| *name == key || aliases.contains(&key))
+        .map(|(name, _)| *name)
+}
+
+/// Every name `field` is accepted under, the current one first (just the
+/// field itself if it has no aliases).
+pub fn accepted_names(field: &'static str) -> Vec<&'static str> {
+    let aliases = FIELD_ALIASES
+        .iter()
+        .find(|(name, _)| *name == field)
+        .map_or(&[][..], |(_, aliases)| *aliases);
+    std::iter::once(field)
+        .chain(aliases.iter().copied())
+        .collect()
+}
+
+/// Rename the aliased keys of a raw JSON fruit to their current names,
+/// returning each translation as `(old, new)`.
+///
+/// A key whose current name (or another of its aliases) is present as well
+/// is left alone, as it can't be told which of the two is right (loading
+/// such a fruit fails with a "duplicate field" error).
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::compat::upgrade;
+/// use serde_json::json;
+///
+/// let mut fruit = json!({"name": "Apple", "len": 4.0, "w": 2.5, "height": 1.5});
+/// let translated = upgrade(fruit.as_object_mut().unwrap());
+///
+/// assert_eq!(translated, vec![("len".to_string(), "length"), ("w".to_string(), "width")]);
+/// assert_eq!(fruit, json!({"name": "Apple", "length": 4.0, "width": 2.5, "height": 1.5}));
+/// ```
+pub fn upgrade(fruit: &mut Map<String, Value>) -> Vec<(String, &'static str)> {
+    let mut aliased: Vec<(String, &'static str)> = Vec::new();
+    for key in fruit.keys() {
+        let Some(name) = canonical_name(key) else {
+            continue;
+        };
+        // Of several aliases of one field, only the first is translated
+        if name != key && !fruit.contains_key(name) && !aliased.iter().any(|(_, n)| *n == name) {
+            aliased.push((key.clone(), name));
+        }
+    }
+    for (old, new) in &aliased {
+        if let Some(value) = fruit.remove(old) {
+            fruit.insert(new.to_string(), value);
+        }
+    }
+    aliased
+}
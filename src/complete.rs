@@ -0,0 +1,209 @@
+// ============================================================================
+// complete.rs - Shell Completion, Including Fruit Names
+// ============================================================================
+// Static completion scripts only know the command line's shape: they can
+// offer `get` after `fruitdata`, but not `Apple` after `fruitdata get`.
+// fruitdata completes dynamically instead, with the protocol of clap's
+// dynamic completion (`clap_complete::CompleteEnv`):
+//
+//     source <(COMPLETE=bash fruitdata)
+//
+// prints a small script that registers the completion. On <TAB>, the shell
+// runs fruitdata again with `COMPLETE` set, the words of the command line
+// after `--` and the index of the word being completed in
+// `_CLAP_COMPLETE_INDEX`, and offers each line it prints. Subcommands and
+// flags come from the clap definition; after `get`, `exists`, `remove`,
+// `update`, `rename`, `growth` and `measure add`, the names come from the catalogue
+// the command line would use (`-f`, or the default file).
+//
+// Only bash is supported so far.
+// ============================================================================
+
+use crate::store::{CatalogStore, FileStore};
+use clap::Command;
+
+/// The commands whose first argument is the name of an existing fruit, as
+/// the path of subcommand names leading to them.
+const FRUIT_NAME_COMMANDS: &[&str] = &[
+    "get",
+    "exists",
+    "remove",
+    "update",
+    "rename",
+    "growth",
+    "measure add",
+];
+
+/// The environment variable that asks for completions, set to the shell's
+/// name.
+pub const SHELL_VAR: &str = "COMPLETE";
+
+/// The environment variable holding the index of the word being completed.
+pub const INDEX_VAR: &str = "_CLAP_COMPLETE_INDEX";
+
+/// The shell `COMPLETE` asks for completions for, if it is set ("" and "0"
+/// mean it isn't).
+pub fn requested_shell() -> Option<String> {
+    std::env::var(SHELL_VAR)
+        .ok()
+        .filter(|shell| !shell.is_empty() && shell != "0")
+}
+
+/// What to print for a completion request from `shell`: the registration
+/// script when there are no `words`, otherwise the candidates for the word
+/// at `index` (by default the last one), one per line.
+///
+/// # Error Cases
+/// `shell` isn't one fruitdata can complete for.
+pub fn respond(
+    shell: &str,
+    command: &Command,
+    words: &[String],
+    index: Option<usize>,
+) -> Result<String, String> {
+    if shell != "bash" {
+        return Err(format!(
+            "can't complete for the '{}' shell (only bash is supported)",
+            shell
+        ));
+    }
+    if words.is_empty() {
+        return Ok(bash_script(command.get_name()));
+    }
+    let index = index.unwrap_or(words.len() - 1);
+    let mut lines: String = complete(command, words, index)
+        .into_iter()
+        .map(|candidate| candidate + "\n")
+        .collect();
+    lines.truncate(lines.trim_end().len());
+    Ok(lines)
+}
+
+/// The bash script that registers the completion for `program`.
+pub fn bash_script(program: &str) -> String {
+    let function = format!("_{}", program.replace('-', "_"));
+    format!(
+        r#"{function}() {{
+    local IFS=$'\n'
+    COMPREPLY=($({SHELL_VAR}=bash {INDEX_VAR}="$COMP_CWORD" "${{COMP_WORDS[0]}}" -- "${{COMP_WORDS[@]}}" 2>/dev/null))
+}}
+complete -o nosort -o default -F {function} {program}
+"#
+    )
+}
+
+/// The candidates for `words[index]`, where `words` is a whole command line
+/// (the program first) for `command`.
+///
+/// # Example Usage
+/// ```
+/// use clap::{Arg, Command, Subcommand};
+/// use fruitdata::complete::complete;
+///
+/// let path = std::env::temp_dir().join("fruitdata-complete-example.json");
+/// std::fs::write(&path, r#"[{"name": "Pear", "length": 6, "width": 3.5, "height": 2.5},
+///     {"name": "Plum", "length": 3, "width": 3, "height": 3},
+///     {"name": "Apple", "length": 4, "width": 2.5, "height": 1.5}]"#).unwrap();
+///
+/// let command = fruitdata::cli::Commands::augment_subcommands(
+///     Command::new("fruitdata").arg(Arg::new("file").short('f').long("file")),
+/// );
+/// let words: Vec<String> = ["fruitdata", "-f", path.to_str().unwrap(), "get", "P"]
+///     .iter().map(|w| w.to_string()).collect();
+/// assert_eq!(complete(&command, &words, 4), ["Pear", "Plum"]);
+///
+/// let words: Vec<String> = ["fruitdata", "gr"].iter().map(|w| w.to_string()).collect();
+/// assert_eq!(complete(&command, &words, 1), ["grade", "growth"]);
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub fn complete(command: &Command, words: &[String], index: usize) -> Vec<String> {
+    let current = words.get(index).map(String::as_str).unwrap_or("");
+    let mut command = command.clone();
+    let mut path: Vec<String> = Vec::new();
+    let mut file = default_file(&command);
+    let mut positionals = 0;
+
+    // Walk the words before the one being completed, following the
+    // subcommands and skipping flags (and the values of those that take one)
+    let mut before = words.iter().take(index).skip(1);
+    while let Some(word) = before.next() {
+        if let Some(value) = word.strip_prefix("--file=") {
+            file = Some(value.to_string());
+        } else if word == "-f" || word == "--file" {
+            file = before.next().cloned();
+        } else if word.starts_with('-') {
+            if takes_value(&command, word) {
+                before.next();
+            }
+        } else if let Some(subcommand) = command.find_subcommand(word).cloned() {
+            path.push(subcommand.get_name().to_string());
+            command = subcommand;
+            positionals = 0;
+        } else {
+            positionals += 1;
+        }
+    }
+
+    if current.starts_with('-') {
+        return command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(|arg| arg.get_long())
+            .map(|long| format!("--{}", long))
+            .filter(|flag| flag.starts_with(current))
+            .collect();
+    }
+    if positionals > 0 {
+        return Vec::new();
+    }
+    if command.has_subcommands() {
+        return command
+            .get_subcommands()
+            .filter(|subcommand| !subcommand.is_hide_set())
+            .flat_map(|subcommand| {
+                std::iter::once(subcommand.get_name()).chain(subcommand.get_visible_aliases())
+            })
+            .filter(|name| name.starts_with(current))
+            .map(str::to_string)
+            .collect();
+    }
+    match file {
+        Some(file) if FRUIT_NAME_COMMANDS.contains(&path.join(" ").as_str()) => {
+            // A catalogue that can't be loaded has nothing to offer
+            match FileStore::new(&file).load() {
+                Ok(catalogue) => catalogue
+                    .names_starting_with(current)
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+                Err(_) => Vec::new(),
+            }
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The catalogue file `command` uses without `-f`.
+fn default_file(command: &Command) -> Option<String> {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some("file"))
+        .and_then(|arg| arg.get_default_values().first())
+        .and_then(|value| value.to_str())
+        .map(str::to_string)
+}
+
+/// Whether `flag` (e.g. `--actor` or `-f`) is one of `command`'s options
+/// that take a value in the next word.
+fn takes_value(command: &Command, flag: &str) -> bool {
+    if flag.contains('=') {
+        return false;
+    }
+    command.get_arguments().any(|arg| {
+        let matches = match flag.strip_prefix("--") {
+            Some(long) => arg.get_long() == Some(long),
+            None => flag.strip_prefix('-').and_then(|s| s.chars().next()) == arg.get_short(),
+        };
+        matches && arg.get_action().takes_values()
+    })
+}
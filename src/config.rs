@@ -0,0 +1,128 @@
+// ============================================================================
+// config.rs - User/Organisation Configuration File
+// ============================================================================
+// Settings that should apply every time fruitdata runs (rather than being
+// typed as flags) live in a JSON config file. By default the CLI looks for
+// `fruitdata.config.json` in the current directory; `--config <path>` points
+// it somewhere else. A missing file simply means "use the defaults".
+//
+// Example `fruitdata.config.json`:
+//
+//     {
+//       "name_policy": { "max_length": 40, "reserved_names": ["Unknown"] },
+//       "limits": { "max_entries": 10000, "max_file_size": 5000000 }
+//     }
+//
+// Every section is optional (`#[serde(default)]`), so a config file only
+// needs to mention the settings it wants to change.
+// ============================================================================
+
+use crate::catalog::SaveOptions;
+#[cfg(unix)]
+use crate::daemon::DaemonSettings;
+use crate::grading::GradingSettings;
+use crate::hooks::HookSettings;
+use crate::journal::JournalSettings;
+use crate::label::LabelSettings;
+use crate::lint::LintSettings;
+use crate::policy::{Limits, NamePolicy, PlausibleRange, Uniqueness};
+use crate::present::RenderOptions;
+use crate::publish::PublishSettings;
+use crate::retry::RetryPolicy;
+use crate::sanitize::SanitizeSettings;
+use crate::stats::SizeClasses;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+
+/// All settings that can be provided in the config file.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    /// Naming conventions enforced when fruits are added or renamed.
+    pub name_policy: NamePolicy,
+
+    /// Size limits (maximum fruits, maximum file size) for the catalogue.
+    pub limits: Limits,
+
+    /// The dimensions a fruit can plausibly have, and what happens to one
+    /// outside them (a catalogue's own header wins).
+    pub plausible_range: PlausibleRange,
+
+    /// Whether names (the default) or IDs must be unique.
+    pub uniqueness: Uniqueness,
+
+    /// Whether changes are recorded in the change journal.
+    pub journal: JournalSettings,
+
+    /// Where changes are published as they are made (nowhere by default).
+    pub publish: PublishSettings,
+
+    /// How the catalogue JSON is laid out when saved.
+    pub save: SaveOptions,
+
+    /// The volumes that divide small, medium and large fruits.
+    pub size_classes: SizeClasses,
+
+    /// The grades `fruitdata grade` sorts fruits into (none by default).
+    pub grading: GradingSettings,
+
+    /// The templates of the labels `fruitdata label` prints.
+    pub label: LabelSettings,
+
+    /// Which `fruitdata lint` rules run.
+    pub lint: LintSettings,
+
+    /// How the names of added and imported fruits are cleaned up.
+    pub sanitize: SanitizeSettings,
+
+    /// Site rules that check added fruits and reshape imported ones.
+    pub hooks: HookSettings,
+
+    /// How loads and saves that fail with a transient error are retried.
+    pub retry: RetryPolicy,
+
+    /// When `fruitdata daemon` saves the catalogue it keeps in memory.
+    #[cfg(unix)]
+    pub daemon: DaemonSettings,
+
+    /// How text output shows numbers: units and decimal places (the
+    /// `--units` and `--precision` flags override them).
+    pub output: RenderOptions,
+}
+
+impl Config {
+    /// Load the configuration from a JSON file.
+    ///
+    /// # Error Cases
+    /// - The file doesn't exist or can't be read
+    /// - The file isn't valid JSON or has settings of the wrong type
+    pub fn load(path: &str) -> Result<Config, Box<dyn Error>> {
+        let json = fs::read_to_string(path)?;
+        let config = serde_json::from_str(&json)?;
+        Ok(config)
+    }
+
+    /// Load the configuration, falling back to the defaults if the file
+    /// doesn't exist.
+    ///
+    /// Unlike the catalogue, a broken config file is *not* silently ignored:
+    /// if it exists but can't be parsed, the error is returned so that a typo
+    /// doesn't quietly disable the organisation's rules.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::config::Config;
+    ///
+    /// let config = Config::load_or_default("does-not-exist.json").unwrap();
+    /// assert_eq!(config, Config::default());
+    /// ```
+    pub fn load_or_default(path: &str) -> Result<Config, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
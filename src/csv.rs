@@ -0,0 +1,449 @@
+// ============================================================================
+// csv.rs - Reading Comma-Separated Spreadsheet Exports
+// ============================================================================
+// Most measurement data that isn't already JSON arrives as a spreadsheet
+// saved as CSV:
+//
+//     FruitName,L_mm,W_mm,H_mm
+//     Apple,40,25,15
+//     "Pear, Conference",60,40,40
+//
+// `parse` splits such text into rows of fields, following RFC 4180: fields
+// containing commas, quotes or line breaks are quoted, and a quote inside
+// a quoted field is doubled (`"6"" ruler"`). Lines may end in `\n` or
+// `\r\n`, and a byte-order mark at the start (which Excel writes) is
+// skipped.
+//
+// `records` goes one step further and turns each row after the header into
+// a JSON object keyed by column name, so that CSV files can be imported
+// with the same `FieldMapping`s as JSON ones (see import.rs).
+//
+// `to_fruits` and `from_fruits` read and write whole catalogues as CSV, one
+// fruit per row (see `catalog::load_catalogue_csv`). The columns are the
+// fruit's fields, `name,length,width,height` and then `id`, `volume_formula`
+// and any fields fruitdata doesn't know about, in that order; measurements
+// have no place in a table and are left out. European spreadsheets separate
+// fields with `;`, so the delimiter is a `CsvOptions` setting, as is whether
+// the first row names the columns.
+// ============================================================================
+
+use crate::models::FruitDimensions;
+use crate::present::RenderOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The columns of a catalogue written without a header row, in order.
+pub const CATALOGUE_COLUMNS: [&str; 5] = ["name", "length", "width", "height", "id"];
+
+/// How a CSV file is laid out.
+///
+/// # Config file example
+/// ```json
+/// { "save": { "csv": { "delimiter": ";", "header": true } } }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct CsvOptions {
+    /// The character between fields (default: `,`).
+    pub delimiter: char,
+    /// Whether the first row names the columns (default: `true`). Without
+    /// one, the columns are `CATALOGUE_COLUMNS`.
+    pub header: bool,
+    /// Decimal places for the dimensions, as `--precision` sets them for
+    /// `export`; `None` writes every digit. Not read from the config's
+    /// `save` section, so a catalogue saved as CSV is never rounded.
+    #[serde(skip)]
+    pub precision: Option<usize>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ',',
+            header: true,
+            precision: None,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Separate fields with `delimiter` instead.
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Read and write a header row, or not.
+    pub fn with_header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Write the dimensions to `precision` decimal places, as text output
+    /// shows them (see `RenderOptions`).
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::csv::{from_fruits, CsvOptions};
+    /// use fruitdata::models::FruitDimensions;
+    ///
+    /// let kiwi = FruitDimensions::new("Kiwi", 4.0, 2.84, 0.3);
+    /// let options = CsvOptions::default().with_precision(Some(1));
+    /// assert_eq!(from_fruits(&[kiwi], &options).unwrap(), "name,length,width,height\nKiwi,4.0,2.8,0.3\n");
+    /// ```
+    pub fn with_precision(mut self, precision: Option<usize>) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Check that the delimiter can separate fields.
+    fn check(&self) -> Result<(), String> {
+        if matches!(self.delimiter, '"' | '\r' | '\n') {
+            return Err(format!(
+                "{:?} can't be the delimiter (it has a meaning of its own in CSV)",
+                self.delimiter
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Split CSV text into rows of fields. Blank lines are skipped.
+///
+/// # Error Cases
+/// - A quoted field is never closed
+/// - A closing quote is followed by something other than a comma or the
+///   end of the line
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::csv::parse;
+///
+/// let rows = parse("name,notes\r\nApple,\"crisp, \"\"red\"\"\"\n").unwrap();
+/// assert_eq!(rows, vec![vec!["name", "notes"], vec!["Apple", "crisp, \"red\""]]);
+///
+/// assert!(parse("name\n\"Apple\n").is_err());
+/// ```
+pub fn parse(text: &str) -> Result<Vec<Vec<String>>, String> {
+    parse_with(text, ',')
+}
+
+/// Split CSV text like `parse`, with `delimiter` between the fields.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::csv::parse_with;
+///
+/// let rows = parse_with("name;length\n\"Pear; Conference\";6,5\n", ';').unwrap();
+/// assert_eq!(rows[1], ["Pear; Conference", "6,5"]);
+/// ```
+pub fn parse_with(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    CsvOptions::default().with_delimiter(delimiter).check()?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+
+    // Whether the current row has anything in it yet (a blank line doesn't)
+    let mut started = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                started = true;
+                let opened = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                        None => return Err(format!("line {}: unclosed quote", opened)),
+                    }
+                }
+                if !matches!(chars.peek(), None | Some('\r' | '\n'))
+                    && chars.peek() != Some(&delimiter)
+                {
+                    return Err(format!(
+                        "line {}: unexpected text after a quoted field",
+                        line
+                    ));
+                }
+            }
+            c if c == delimiter => {
+                started = true;
+                row.push(std::mem::take(&mut field));
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if started {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                started = false;
+                line += 1;
+            }
+            c => {
+                started = true;
+                field.push(c);
+            }
+        }
+    }
+    if started {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Parse CSV text whose first row names the columns, turning every other
+/// row into a JSON object from column name to text. Empty cells are left
+/// out, so they count as missing. Surrounding whitespace is trimmed from
+/// the column names.
+///
+/// # Error Cases
+/// - Anything `parse` rejects
+/// - There is no header row, or two columns have the same name
+/// - A row has more fields than the header has columns
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::csv::records;
+/// use serde_json::json;
+///
+/// let records = records("FruitName,L_cm,Id\nApple,4.0,\nPear,6.0,7\n").unwrap();
+/// assert_eq!(records[0], json!({"FruitName": "Apple", "L_cm": "4.0"}));
+/// assert_eq!(records[1]["Id"], "7");
+/// ```
+pub fn records(text: &str) -> Result<Vec<Value>, String> {
+    records_with(text, &CsvOptions::default())
+}
+
+/// Parse CSV text into records like `records`, laid out as `options` say.
+/// Without a header row, the columns are `CATALOGUE_COLUMNS`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::csv::{records_with, CsvOptions};
+/// use serde_json::json;
+///
+/// let options = CsvOptions::default().with_delimiter(';').with_header(false);
+/// let records = records_with("Apple;4;2.5;1.5\n", &options).unwrap();
+/// assert_eq!(records[0], json!({"name": "Apple", "length": "4", "width": "2.5", "height": "1.5"}));
+/// ```
+pub fn records_with(text: &str, options: &CsvOptions) -> Result<Vec<Value>, String> {
+    let mut rows = parse_with(text, options.delimiter)?.into_iter();
+    let header: Vec<String> = if options.header {
+        rows.next()
+            .ok_or("the file is empty (expected a header row)")?
+            .iter()
+            .map(|column| column.trim().to_string())
+            .collect()
+    } else {
+        CATALOGUE_COLUMNS.iter().map(|c| c.to_string()).collect()
+    };
+    for (index, column) in header.iter().enumerate() {
+        if header[..index].contains(column) {
+            return Err(format!("the header names column '{}' twice", column));
+        }
+    }
+
+    rows.enumerate()
+        .map(|(index, row)| {
+            if row.len() > header.len() {
+                return Err(format!(
+                    "row {}: {} fields, but the header has {} columns",
+                    index + 1,
+                    row.len(),
+                    header.len()
+                ));
+            }
+            let fields: Map<String, Value> = header
+                .iter()
+                .zip(row)
+                .filter(|(_, cell)| !cell.trim().is_empty())
+                .map(|(column, cell)| (column.clone(), Value::String(cell)))
+                .collect();
+            Ok(Value::Object(fields))
+        })
+        .collect()
+}
+
+/// Read a catalogue from CSV text laid out as `options` say: one fruit per
+/// row, in the columns described in the module comment. Numbers, `true`
+/// and `false`, and JSON arrays and objects in the other columns are read
+/// as such; anything else is text.
+///
+/// # Error Cases
+/// - Anything `records_with` rejects
+/// - A row has no name, or a dimension or ID that isn't a number
+/// - A fruit is otherwise invalid (e.g. a dimension that isn't finite)
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::csv::{to_fruits, CsvOptions};
+///
+/// let text = "name,length,width,height,origin,plu\nApple,4,2.5,1.5,Kent,4131\n";
+/// let fruits = to_fruits(text, &CsvOptions::default()).unwrap();
+/// assert_eq!(fruits[0].width, 2.5);
+/// assert_eq!(fruits[0].extra["origin"], "Kent");
+/// assert_eq!(fruits[0].extra["plu"], 4131);
+///
+/// assert!(to_fruits("name,length\nApple,long\n", &CsvOptions::default()).is_err());
+/// ```
+pub fn to_fruits(text: &str, options: &CsvOptions) -> Result<Vec<FruitDimensions>, String> {
+    records_with(text, options)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, record)| {
+            let row = index + 1;
+            let Value::Object(cells) = record else {
+                unreachable!("records are objects")
+            };
+            let mut fields = Map::new();
+            for (column, cell) in cells {
+                let Value::String(cell) = cell else {
+                    unreachable!("cells are strings")
+                };
+                let value = match column.as_str() {
+                    "name" | "volume_formula" => Value::String(cell),
+                    "length" | "width" | "height" | "id" => serde_json::from_str(cell.trim())
+                        .ok()
+                        .filter(Value::is_number)
+                        .ok_or_else(|| {
+                            format!("row {}: {} '{}' isn't a number", row, column, cell)
+                        })?,
+                    _ => match serde_json::from_str(cell.trim()) {
+                        Ok(value @ (Value::Number(_) | Value::Bool(_))) => value,
+                        Ok(value @ (Value::Array(_) | Value::Object(_))) => value,
+                        _ => Value::String(cell),
+                    },
+                };
+                fields.insert(column, value);
+            }
+            serde_json::from_value(Value::Object(fields)).map_err(|e| format!("row {}: {}", row, e))
+        })
+        .collect()
+}
+
+/// Write `fruits` as CSV laid out as `options` say, one per row (see the
+/// module comment). Without a header row only `CATALOGUE_COLUMNS` are
+/// written, as there would be no telling the other columns apart; `id`
+/// only if a fruit has one.
+///
+/// # Error Cases
+/// The delimiter is a quote or a line break.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::csv::{from_fruits, CsvOptions};
+/// use fruitdata::models::FruitDimensions;
+///
+/// let mut pear = FruitDimensions::new("Pear; Conference", 6.0, 3.5, 2.5);
+/// pear.extra.insert("origin".into(), "Kent".into());
+///
+/// let options = CsvOptions::default().with_delimiter(';');
+/// assert_eq!(
+///     from_fruits(&[pear], &options).unwrap(),
+///     "name;length;width;height;origin\n\"Pear; Conference\";6;3.5;2.5;Kent\n"
+/// );
+///
+/// // Dimensions are written as stored, without float noise
+/// let kiwi = FruitDimensions::new("Kiwi", 4.1, 2.8, 0.3);
+/// assert_eq!(
+///     from_fruits(&[kiwi], &CsvOptions::default()).unwrap(),
+///     "name,length,width,height\nKiwi,4.1,2.8,0.3\n"
+/// );
+/// ```
+pub fn from_fruits(fruits: &[FruitDimensions], options: &CsvOptions) -> Result<String, String> {
+    options.check()?;
+    let records: Vec<Map<String, Value>> = fruits
+        .iter()
+        .map(|fruit| match serde_json::to_value(fruit) {
+            Ok(Value::Object(fields)) => fields,
+            _ => unreachable!("fruits serialize to objects"),
+        })
+        .collect();
+
+    // The fields every fruit has, then the optional ones any fruit has
+    let mut columns: Vec<String> = CATALOGUE_COLUMNS[..4]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    let optional = |column: &str| records.iter().any(|record| record.contains_key(column));
+    for column in ["id", "volume_formula"] {
+        if optional(column) && (options.header || column == "id") {
+            columns.push(column.to_string());
+        }
+    }
+    if options.header {
+        let mut extra: Vec<&String> = fruits.iter().flat_map(|fruit| fruit.extra.keys()).collect();
+        extra.sort();
+        extra.dedup();
+        columns.extend(extra.into_iter().cloned());
+    }
+
+    let mut text = String::new();
+    if options.header {
+        text.push_str(&write_row(&columns, options.delimiter));
+    }
+    let numbers = RenderOptions {
+        precision: options.precision,
+        ..RenderOptions::default()
+    };
+    for (fruit, record) in fruits.iter().zip(&records) {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|column| match column.as_str() {
+                // From the f32 itself: the record's copy was widened to
+                // f64, and would write 2.8 as 2.799999952316284
+                "length" => numbers.number(fruit.length.value()),
+                "width" => numbers.number(fruit.width.value()),
+                "height" => numbers.number(fruit.height.value()),
+                _ => match record.get(column) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(text)) => text.clone(),
+                    Some(value) => value.to_string(),
+                },
+            })
+            .collect();
+        text.push_str(&write_row(&cells, options.delimiter));
+    }
+    Ok(text)
+}
+
+/// One row of CSV, ending in a line break. Fields containing the
+/// delimiter, a quote or a line break are quoted.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::csv::write_row;
+///
+/// let row = write_row(&["Apple".to_string(), "crisp, \"red\"".to_string()], ',');
+/// assert_eq!(row, "Apple,\"crisp, \"\"red\"\"\"\n");
+/// ```
+pub fn write_row(fields: &[String], delimiter: char) -> String {
+    let mut row = String::new();
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            row.push(delimiter);
+        }
+        if field.contains([delimiter, '"', '\r', '\n']) {
+            row.push('"');
+            row.push_str(&field.replace('"', "\"\""));
+            row.push('"');
+        } else {
+            row.push_str(field);
+        }
+    }
+    row.push('\n');
+    row
+}
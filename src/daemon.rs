@@ -0,0 +1,587 @@
+// ============================================================================
+// daemon.rs - Keeping the Catalogue in Memory Between Commands
+// ============================================================================
+// Every `fruitdata` command loads the catalogue file, changes it and writes
+// it back. For a script making thousands of small updates, that reading and
+// writing is most of the work. `fruitdata daemon` loads the catalogue once
+// and then takes commands over a Unix socket, one per line, written just
+// like the command line after `fruitdata`:
+//
+//     $ fruitdata daemon &
+//     $ echo 'add Kiwi 3 2 2' | socat - UNIX-CONNECT:fruits.json.sock
+//     {"status":0,"output":"Added 'Kiwi'.","notes":[],"hints":[],"error":null}
+//
+// Each line gets one line back: a JSON `Response` with what the command
+// would have printed, and its exit status. A line that is a JSON-RPC
+// request (see rpc.rs) is answered in JSON-RPC instead, with the command's
+// report as JSON; `save` and `shutdown` are methods there too. The commands run exactly as they
+// would from the shell (same rules, same journal entries), but against the
+// catalogue in memory (`Cached`), which is only written to the file:
+//
+// - after every `after_changes` changes (default 20),
+// - once the oldest unsaved change is `interval_secs` old (default 30),
+// - and when a client sends `save` or `shutdown` (which also stops the
+//   daemon).
+//
+// Either trigger can be turned off with 0, in the config file's `daemon`
+// section or with `--interval` and `--after`. Changes not yet saved are
+// lost if the daemon is killed, so stop it with `shutdown`.
+//
+// Several clients can be connected at a time, each sending as many lines
+// as it likes before closing its end. Their lines are run one at a time, in
+// the order they arrive, so a client that stays connected without sending
+// anything doesn't hold up the others.
+// ============================================================================
+
+use crate::catalog::Catalogue;
+use crate::cli::{parse_command, run_command, CommandOutcome, Commands, Context};
+use crate::journal::Journal;
+use crate::present::{render_with, RenderOptions};
+use crate::rpc::{self, CommandResult, RpcError};
+use crate::store::CatalogStore;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the daemon sleeps when no client has anything to say.
+const IDLE: Duration = Duration::from_millis(10);
+
+/// When the daemon saves, from the config file's `daemon` section.
+///
+/// # Config file example
+/// ```json
+/// { "daemon": { "interval_secs": 10, "after_changes": 100 } }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct DaemonSettings {
+    /// Save once the oldest unsaved change is this many seconds old (0:
+    /// never on a timer).
+    pub interval_secs: u64,
+    /// Save after this many changes (0: never because of the count).
+    pub after_changes: u32,
+}
+
+impl Default for DaemonSettings {
+    fn default() -> Self {
+        DaemonSettings {
+            interval_secs: 30,
+            after_changes: 20,
+        }
+    }
+}
+
+impl DaemonSettings {
+    /// How long a change may stay unsaved, if there is a limit.
+    pub fn interval(&self) -> Option<Duration> {
+        (self.interval_secs > 0).then(|| Duration::from_secs(self.interval_secs))
+    }
+}
+
+/// A store that keeps the catalogue in memory, and only writes it to the
+/// store it wraps every `after_changes` saves or when told to `flush`.
+///
+/// The journal is still written for every change, so nothing goes
+/// unrecorded even while the catalogue itself is waiting to be saved.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::initialise_fruit_catalogue;
+/// use fruitdata::daemon::Cached;
+/// use fruitdata::store::{CatalogStore, MemoryStore};
+///
+/// let file = MemoryStore::new(initialise_fruit_catalogue());
+/// let cached = Cached::new(&file, 2);
+///
+/// let mut catalogue = cached.load().unwrap();
+/// catalogue.remove("Pear").unwrap();
+/// cached.save(&mut catalogue).unwrap();
+/// assert_eq!(file.fruits().len(), 4); // not written yet
+/// assert_eq!(cached.load().unwrap().len(), 3);
+///
+/// cached.save(&mut catalogue).unwrap(); // the second change
+/// assert_eq!(file.fruits().len(), 3);
+/// assert_eq!(cached.unsaved(), 0);
+/// ```
+pub struct Cached<'a> {
+    inner: &'a dyn CatalogStore,
+    after_changes: u32,
+    state: RefCell<CacheState>,
+}
+
+/// The catalogue in memory, and how much of it isn't saved yet.
+#[derive(Default)]
+struct CacheState {
+    catalogue: Option<Catalogue>,
+    unsaved: u32,
+    /// When the oldest unsaved change was made.
+    since: Option<Instant>,
+}
+
+impl<'a> Cached<'a> {
+    /// Cache the catalogue in `inner`, writing it back every
+    /// `after_changes` saves (never, for 0).
+    pub fn new(inner: &'a dyn CatalogStore, after_changes: u32) -> Self {
+        Cached {
+            inner,
+            after_changes,
+            state: RefCell::new(CacheState::default()),
+        }
+    }
+
+    /// Start with `catalogue` (as loaded from `inner`) instead of loading it
+    /// again.
+    pub fn with_catalogue(self, catalogue: Catalogue) -> Self {
+        self.state.borrow_mut().catalogue = Some(catalogue);
+        self
+    }
+
+    /// How many saves haven't been written to the wrapped store yet.
+    pub fn unsaved(&self) -> u32 {
+        self.state.borrow().unsaved
+    }
+
+    /// Write the catalogue to the wrapped store if anything is unsaved,
+    /// returning how many changes were written.
+    ///
+    /// # Error Cases
+    /// Saving fails; the changes stay in memory, to be saved next time.
+    pub fn flush(&self) -> Result<u32, Box<dyn Error>> {
+        let mut state = self.state.borrow_mut();
+        let unsaved = state.unsaved;
+        if unsaved == 0 {
+            return Ok(0);
+        }
+        if let Some(catalogue) = state.catalogue.as_mut() {
+            self.inner.save(catalogue)?;
+        }
+        state.unsaved = 0;
+        state.since = None;
+        Ok(unsaved)
+    }
+
+    /// `flush`, if the oldest unsaved change is at least `age` old.
+    pub fn flush_older_than(&self, age: Duration) -> Result<u32, Box<dyn Error>> {
+        let due = self
+            .state
+            .borrow()
+            .since
+            .is_some_and(|since| since.elapsed() >= age);
+        if due {
+            self.flush()
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Drop the catalogue in memory, so that the next load reads the wrapped
+    /// store again (after something changed it directly). Unsaved changes
+    /// are lost; `flush` first to keep them.
+    pub fn forget(&self) {
+        *self.state.borrow_mut() = CacheState::default();
+    }
+}
+
+impl CatalogStore for Cached<'_> {
+    fn describe(&self) -> String {
+        self.inner.describe()
+    }
+
+    fn exists(&self) -> bool {
+        self.state.borrow().catalogue.is_some() || self.inner.exists()
+    }
+
+    fn load(&self) -> Result<Catalogue, Box<dyn Error>> {
+        if let Some(catalogue) = &self.state.borrow().catalogue {
+            return Ok(catalogue.clone());
+        }
+        let catalogue = self.inner.load()?;
+        self.state.borrow_mut().catalogue = Some(catalogue.clone());
+        Ok(catalogue)
+    }
+
+    fn save(&self, catalogue: &mut Catalogue) -> Result<(), Box<dyn Error>> {
+        {
+            let mut state = self.state.borrow_mut();
+            let mut kept = catalogue.clone();
+            // The caller reports these changes; the copy mustn't again
+            kept.take_changes();
+            state.catalogue = Some(kept);
+            state.unsaved += 1;
+            state.since.get_or_insert_with(Instant::now);
+        }
+        if self.after_changes > 0 && self.unsaved() >= self.after_changes {
+            self.flush().map_err(|e| {
+                format!(
+                    "the change was made, but saving '{}' failed: {}",
+                    self.describe(),
+                    e
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    fn explain(&self, error: &(dyn Error + 'static)) -> Option<String> {
+        self.inner.explain(error)
+    }
+
+    fn journal(&self) -> Option<Journal> {
+        self.inner.journal()
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.inner.path()
+    }
+}
+
+/// The answer to one line sent to the daemon.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Response {
+    /// The exit status the command would have had: 0 for success, 1 for an
+    /// error, 2 for a line that isn't a valid command (or as `exists`
+    /// and `diff` say).
+    pub status: i32,
+    /// What the command would have printed on stdout.
+    pub output: String,
+    /// What it would have printed on stderr before the output.
+    pub notes: Vec<String>,
+    /// What it would have printed on stderr after the output.
+    pub hints: Vec<String>,
+    /// What went wrong, if anything.
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn failed(status: i32, error: impl Into<String>) -> Self {
+        Response {
+            status,
+            error: Some(error.into()),
+            ..Response::default()
+        }
+    }
+}
+
+/// What the daemon did before it was stopped.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DaemonSummary {
+    /// How many lines were run.
+    pub commands: u64,
+    /// How many times the catalogue was written to its file.
+    pub saves: u64,
+}
+
+/// Whether the daemon should go on after a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Next {
+    Continue,
+    Stop,
+}
+
+/// One connected client, and what it has sent that isn't a whole line yet.
+struct Client {
+    stream: UnixStream,
+    pending: Vec<u8>,
+    open: bool,
+}
+
+/// The running daemon: the catalogue in memory and what it has done.
+struct Daemon<'a, 'c> {
+    cached: Cached<'a>,
+    context: &'c mut Context,
+    output: RenderOptions,
+    interval: Option<Duration>,
+    summary: DaemonSummary,
+    /// Why the last autosave failed, to tell the next client.
+    autosave_error: Option<String>,
+}
+
+impl Daemon<'_, '_> {
+    /// Run one line and answer it, with the line to send back.
+    fn handle(&mut self, line: &str) -> (Option<String>, Next) {
+        let line = line.trim();
+        if line.starts_with(['{', '[']) {
+            return self.handle_rpc(line);
+        }
+
+        self.summary.commands += 1;
+        let (mut response, next) = match line {
+            "save" => (self.save(), Next::Continue),
+            "shutdown" => {
+                let response = self.save();
+                let next = match response.error {
+                    None => Next::Stop,
+                    Some(_) => Next::Continue,
+                };
+                (response, next)
+            }
+            line => match parse_command(line) {
+                Ok(command) => (self.run(&command), Next::Continue),
+                Err(message) => (Response::failed(2, message), Next::Continue),
+            },
+        };
+        response.notes.splice(0..0, self.autosave_warning());
+        let json = serde_json::to_string(&response).expect("responses serialize to JSON");
+        (Some(json), next)
+    }
+
+    /// Answer a JSON-RPC request (or batch).
+    fn handle_rpc(&mut self, line: &str) -> (Option<String>, Next) {
+        let mut next = Next::Continue;
+        let reply = rpc::handle(line, |request| {
+            self.summary.commands += 1;
+            let mut result = match request.method.as_str() {
+                "save" | "shutdown" => {
+                    let changes = self
+                        .flush()
+                        .map_err(|e| RpcError::new(rpc::codes::COMMAND_FAILED, e.to_string()))?;
+                    if request.method == "shutdown" {
+                        next = Next::Stop;
+                    }
+                    CommandResult {
+                        report: json!({ "outcome": "saved", "changes": changes }),
+                        ..CommandResult::default()
+                    }
+                }
+                _ => {
+                    let command = request.command()?;
+                    let outcome = self
+                        .outcome(&command)
+                        .map_err(|e| RpcError::new(rpc::codes::COMMAND_FAILED, e.to_string()))?;
+                    CommandResult::from_outcome(outcome, &self.output)?
+                }
+            };
+            result.notes.splice(0..0, self.autosave_warning());
+            Ok(result)
+        });
+        (reply, next)
+    }
+
+    /// The warning about a failed autosave, once.
+    fn autosave_warning(&mut self) -> Option<String> {
+        let error = self.autosave_error.take()?;
+        Some(format!("Warning: autosave failed: {}", error))
+    }
+
+    /// Write the unsaved changes, if any, returning how many there were.
+    fn flush(&mut self) -> Result<u32, Box<dyn Error>> {
+        let changes = self.cached.flush()?;
+        if changes > 0 {
+            self.summary.saves += 1;
+        }
+        Ok(changes)
+    }
+
+    /// `flush`, answered as a line.
+    fn save(&mut self) -> Response {
+        match self.flush() {
+            Ok(0) => Response {
+                output: "Nothing to save.".to_string(),
+                ..Response::default()
+            },
+            Ok(changes) => Response {
+                output: format!("Saved {} changes.", changes),
+                ..Response::default()
+            },
+            Err(e) => Response::failed(1, e.to_string()),
+        }
+    }
+
+    /// Run a command against the catalogue in memory.
+    fn outcome(&mut self, command: &Commands) -> Result<CommandOutcome, Box<dyn Error>> {
+        let unsaved = self.cached.unsaved();
+        let outcome = match command {
+            // These read (or rewrite) the file itself, which must be
+            // up to date
+            Commands::Validate { .. } | Commands::Repair { .. } => {
+                self.cached.flush()?;
+                let outcome = run_command(command, &self.cached, self.context);
+                self.cached.forget();
+                outcome
+            }
+            _ => run_command(command, &self.cached, self.context),
+        };
+        if self.cached.unsaved() < unsaved {
+            self.summary.saves += 1;
+        }
+        outcome
+    }
+
+    fn run(&mut self, command: &Commands) -> Response {
+        if command.is_server() {
+            return Response::failed(1, "servers can't be started from the daemon");
+        }
+        let outcome = match self.outcome(command) {
+            Ok(outcome) => outcome,
+            Err(e) => return Response::failed(1, e.to_string()),
+        };
+        let output = match render_with(&outcome.report, command.output_format(), &self.output) {
+            Ok(output) => output,
+            Err(e) => return Response::failed(1, e.to_string()),
+        };
+        Response {
+            status: if outcome.failure.is_some() {
+                1
+            } else {
+                outcome.status
+            },
+            output,
+            notes: outcome.notes,
+            hints: outcome.hints,
+            error: outcome.failure,
+        }
+    }
+
+    /// Save if the oldest unsaved change has waited long enough.
+    fn autosave(&mut self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        match self.cached.flush_older_than(interval) {
+            Ok(0) => {}
+            Ok(_) => self.summary.saves += 1,
+            Err(e) => self.autosave_error = Some(e.to_string()),
+        }
+    }
+
+    /// Read whatever `client` has sent and answer each whole line.
+    fn serve(&mut self, client: &mut Client) -> Result<(bool, Next), Box<dyn Error>> {
+        let mut busy = false;
+        let mut buffer = [0; 4096];
+        loop {
+            match client.stream.read(&mut buffer) {
+                Ok(0) => {
+                    client.open = false;
+                    break;
+                }
+                Ok(read) => {
+                    busy = true;
+                    client.pending.extend_from_slice(&buffer[..read]);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    client.open = false;
+                    break;
+                }
+            }
+        }
+
+        while let Some(end) = client.pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = client.pending.drain(..=end).collect();
+            let (reply, next) = match std::str::from_utf8(&line) {
+                Ok(line) => self.handle(line),
+                Err(_) => {
+                    let response = Response::failed(2, "the line isn't valid UTF-8");
+                    let json =
+                        serde_json::to_string(&response).expect("responses serialize to JSON");
+                    (Some(json), Next::Continue)
+                }
+            };
+            if let Some(mut reply) = reply {
+                reply.push('\n');
+                // Answers are written whole, however slowly the client reads
+                client.stream.set_nonblocking(false)?;
+                let written = client.stream.write_all(reply.as_bytes());
+                client.stream.set_nonblocking(true)?;
+                // A client that went away doesn't stop the daemon
+                if written.is_err() {
+                    client.open = false;
+                }
+            }
+            if next == Next::Stop {
+                return Ok((busy, Next::Stop));
+            }
+        }
+        Ok((busy, Next::Continue))
+    }
+}
+
+/// Listen on `socket` and run the commands clients send (see the module
+/// comment) against `catalogue`, as loaded from `store`, until a client
+/// sends `shutdown`. The changes are saved to `store` as `settings` say.
+///
+/// A socket file left behind by a daemon that is no longer running is
+/// replaced; the socket file is removed when the daemon stops.
+///
+/// # Error Cases
+/// - Another daemon is listening on `socket`
+/// - The socket can't be created
+pub fn serve(
+    socket: &Path,
+    store: &dyn CatalogStore,
+    catalogue: Catalogue,
+    context: &mut Context,
+    output: RenderOptions,
+    settings: &DaemonSettings,
+) -> Result<DaemonSummary, Box<dyn Error>> {
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(format!("a daemon is already listening on '{}'", socket.display()).into());
+        }
+        fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    listener.set_nonblocking(true)?;
+
+    let mut daemon = Daemon {
+        cached: Cached::new(store, settings.after_changes).with_catalogue(catalogue),
+        context,
+        output,
+        interval: settings.interval(),
+        summary: DaemonSummary::default(),
+        autosave_error: None,
+    };
+    let mut clients: Vec<Client> = Vec::new();
+    let result = 'serving: loop {
+        let mut busy = false;
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = stream.set_nonblocking(true) {
+                        break 'serving Err(e.into());
+                    }
+                    busy = true;
+                    clients.push(Client {
+                        stream,
+                        pending: Vec::new(),
+                        open: true,
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break 'serving Err(e.into()),
+            }
+        }
+
+        for client in &mut clients {
+            match daemon.serve(client) {
+                Ok((read, Next::Continue)) => busy |= read,
+                Ok((_, Next::Stop)) => break 'serving Ok(daemon.summary),
+                Err(e) => break 'serving Err(e),
+            }
+        }
+        clients.retain(|client| client.open);
+
+        daemon.autosave();
+        if !busy {
+            thread::sleep(IDLE);
+        }
+    };
+
+    // Whatever stopped the daemon, try not to lose the changes
+    let saved = daemon.cached.flush();
+    let _ = fs::remove_file(socket);
+    let mut summary = result?;
+    if saved? > 0 {
+        summary.saves += 1;
+    }
+    Ok(summary)
+}
@@ -0,0 +1,341 @@
+// ============================================================================
+// diagnostic.rs - Pointing at the Problem in a Catalogue File
+// ============================================================================
+// serde's error messages say *what* is wrong ("expected `,` or `}` at line
+// 14 column 9"), which leaves the user counting lines in a hand-edited file.
+// A `Diagnostic` shows the offending line itself, marks the spot, and where
+// the mistake is a common one, suggests a fix:
+//
+//     error: expected `,` or `}`
+//       --> fruits.json:3:48
+//       |
+//     3 |   {"name": "Pear", "length": 6.0, "width": 3.5 "height": 2.5},
+//       |                                                ^
+//       = hint: a comma is probably missing before this point
+//
+// Strict-loading problems are reported by path (`$[2].lenght`) rather than
+// position, so `Diagnostic::at_path` finds where a path leads in the text.
+// ============================================================================
+
+use crate::error::CatalogError;
+use std::error::Error;
+use std::fmt;
+
+/// An error message tied to a position in a source file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// The file name shown in the report.
+    pub source_name: String,
+    /// Line and column of the problem, both from 1.
+    pub line: usize,
+    pub column: usize,
+    /// The text of that line.
+    pub source_line: String,
+    /// A suggestion for fixing the problem, if there is a likely one.
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    /// Describe a parse or type error from `serde_json` in `text`.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::diagnostic::Diagnostic;
+    ///
+    /// let text = "[\n  {\"name\": \"Pear\", \"length\": 6.0 \"width\": 3.5}\n]";
+    /// let error = serde_json::from_str::<serde_json::Value>(text).unwrap_err();
+    /// let diagnostic = Diagnostic::from_json_error("fruits.json", text, &error);
+    ///
+    /// assert_eq!((diagnostic.line, diagnostic.column), (2, 34));
+    /// assert_eq!(diagnostic.message, "expected `,` or `}`");
+    /// assert!(diagnostic.to_string().contains(" --> fruits.json:2:34"));
+    /// ```
+    pub fn from_json_error(source_name: &str, text: &str, error: &serde_json::Error) -> Self {
+        // The position is shown separately, so drop it from the message
+        let message = error.to_string();
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) => message.to_string(),
+            None => message,
+        };
+        let hint = hint_for(&message);
+        Diagnostic::new(
+            source_name,
+            text,
+            error.line(),
+            error.column(),
+            message,
+            hint,
+        )
+    }
+
+    /// Describe a problem found at a JSON path such as `$[2].lenght` or
+    /// `$.fruits[0]`. If the path doesn't exist in `text` (a field that is
+    /// missing), the nearest part of it that does is used.
+    ///
+    /// Returns `None` if not even the start of the path can be found.
+    pub fn at_path(source_name: &str, text: &str, path: &str, message: &str) -> Option<Self> {
+        let mut segments = parse_path(path)?;
+        let offset = loop {
+            if let Some(offset) = locate(text.as_bytes(), &segments) {
+                break offset;
+            }
+            segments.pop()?;
+        };
+
+        let before = &text[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = offset - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        let hint = hint_for(message);
+        Some(Diagnostic::new(
+            source_name,
+            text,
+            line,
+            column,
+            format!("{}: {}", path, message),
+            hint,
+        ))
+    }
+
+    /// `column` counts bytes, as serde does; the diagnostic counts
+    /// characters, so that the caret lands right under "Açaí".
+    fn new(
+        source_name: &str,
+        text: &str,
+        line: usize,
+        column: usize,
+        message: String,
+        hint: Option<String>,
+    ) -> Self {
+        let source_line = text.lines().nth(line.saturating_sub(1)).unwrap_or("");
+        let column = source_line
+            .char_indices()
+            .take_while(|(i, _)| *i + 1 < column)
+            .count()
+            + 1;
+        Diagnostic {
+            message,
+            source_name: source_name.to_string(),
+            line,
+            column,
+            source_line: source_line.to_string(),
+            hint,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+
+        // Line the caret up under the column, keeping tabs so that it
+        // lines up the same way the source line does
+        let marker: String = self
+            .source_line
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(
+            f,
+            "{} --> {}:{}:{}",
+            gutter, self.source_name, self.line, self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", number, self.source_line)?;
+        write!(f, "{} | {}^", gutter, marker)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n{} = hint: {}", gutter, hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Diagnostic {}
+
+/// Explain a failure to load `bytes` (read from `source_name`) with source
+/// snippets, if it is the kind of error that has a location: a JSON error or
+/// strict loading's schema violations. Returns `None` for anything else,
+/// such as an I/O error.
+pub fn explain(source_name: &str, bytes: &[u8], error: &(dyn Error + 'static)) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes);
+
+    if let Some(error) = error.downcast_ref::<serde_json::Error>() {
+        return Some(Diagnostic::from_json_error(source_name, &text, error).to_string());
+    }
+    if let Some(CatalogError::SchemaViolations(problems)) = error.downcast_ref::<CatalogError>() {
+        let diagnostics: Vec<String> = problems
+            .iter()
+            .map(|problem| {
+                let (path, message) = problem.split_once(": ").unwrap_or(("$", problem));
+                match Diagnostic::at_path(source_name, &text, path, message) {
+                    Some(diagnostic) => diagnostic.to_string(),
+                    None => format!("error: {}", problem),
+                }
+            })
+            .collect();
+        return Some(diagnostics.join("\n\n"));
+    }
+    None
+}
+
+/// Suggest a fix for the mistakes people make most when editing JSON.
+fn hint_for(message: &str) -> Option<String> {
+    let hint = if message.starts_with("expected `,` or `}`")
+        || message.starts_with("expected `,` or `]`")
+    {
+        "a comma is probably missing before this point"
+    } else if message.starts_with("trailing comma") {
+        "JSON doesn't allow a comma after the last item; remove it"
+    } else if message.starts_with("key must be a string") {
+        "field names need double quotes, e.g. \"name\""
+    } else if message.starts_with("EOF while parsing") {
+        "the file ends too early; if it was cut short, `fruitdata repair` can recover the intact fruits"
+    } else if message.starts_with("missing field") || message.ends_with("missing") {
+        "every fruit needs a name, length, width and height"
+    } else if message.starts_with("invalid type: string") || message.contains("found a string") {
+        "numbers are written without quotes, e.g. 4.5 rather than \"4.5\""
+    } else if message.ends_with("unknown field") {
+        "check the spelling, or load without --strict to keep extra fields"
+    } else {
+        return None;
+    };
+    Some(hint.to_string())
+}
+
+/// One step of a JSON path.
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Split `$.fruits[2].length` into its steps.
+fn parse_path(path: &str) -> Option<Vec<Segment>> {
+    let mut rest = path.strip_prefix('$')?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            segments.push(Segment::Key(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            segments.push(Segment::Index(after[..end].parse().ok()?));
+            rest = &after[end + 1..];
+        } else {
+            return None;
+        }
+    }
+    Some(segments)
+}
+
+/// The byte offset of the value a path leads to, or `None` if the text
+/// doesn't contain it.
+fn locate(text: &[u8], segments: &[Segment]) -> Option<usize> {
+    let mut pos = skip_whitespace(text, 0);
+    for segment in segments {
+        pos = match segment {
+            Segment::Key(key) => member(text, pos, key)?,
+            Segment::Index(index) => element(text, pos, *index)?,
+        };
+    }
+    Some(pos)
+}
+
+/// In the object starting at `pos`, the offset of `key`'s value.
+fn member(text: &[u8], pos: usize, key: &str) -> Option<usize> {
+    if text.get(pos) != Some(&b'{') {
+        return None;
+    }
+    let mut pos = skip_whitespace(text, pos + 1);
+    while text.get(pos) == Some(&b'"') {
+        let end = skip_value(text, pos)?;
+        let name: String = serde_json::from_slice(&text[pos..end]).ok()?;
+        pos = skip_whitespace(text, end);
+        if text.get(pos) != Some(&b':') {
+            return None;
+        }
+        pos = skip_whitespace(text, pos + 1);
+        if name == key {
+            return Some(pos);
+        }
+        pos = skip_whitespace(text, skip_value(text, pos)?);
+        if text.get(pos) == Some(&b',') {
+            pos = skip_whitespace(text, pos + 1);
+        }
+    }
+    None
+}
+
+/// In the array starting at `pos`, the offset of element `index`.
+fn element(text: &[u8], pos: usize, index: usize) -> Option<usize> {
+    if text.get(pos) != Some(&b'[') {
+        return None;
+    }
+    let mut pos = skip_whitespace(text, pos + 1);
+    for _ in 0..index {
+        pos = skip_whitespace(text, skip_value(text, pos)?);
+        if text.get(pos) != Some(&b',') {
+            return None;
+        }
+        pos = skip_whitespace(text, pos + 1);
+    }
+    (text.get(pos) != Some(&b']')).then_some(pos)
+}
+
+/// The offset just past the value starting at `pos`.
+fn skip_value(text: &[u8], pos: usize) -> Option<usize> {
+    match *text.get(pos)? {
+        b'"' => {
+            let mut i = pos + 1;
+            while i < text.len() {
+                match text[i] {
+                    b'\\' => i += 2,
+                    b'"' => return Some(i + 1),
+                    _ => i += 1,
+                }
+            }
+            None
+        }
+        b'{' | b'[' => {
+            let mut depth = 0usize;
+            let mut i = pos;
+            while i < text.len() {
+                match text[i] {
+                    b'"' => {
+                        i = skip_value(text, i)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            None
+        }
+        // A number, true, false or null
+        _ => Some(
+            text[pos..]
+                .iter()
+                .position(|b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace())
+                .map_or(text.len(), |n| pos + n),
+        ),
+    }
+}
+
+fn skip_whitespace(text: &[u8], pos: usize) -> usize {
+    text[pos.min(text.len())..]
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .map_or(text.len(), |n| pos + n)
+}
@@ -0,0 +1,442 @@
+// ============================================================================
+// document.rs - The Layout of a Catalogue File
+// ============================================================================
+// fruitdata has always written a catalogue as a bare JSON array of fruits:
+//
+//     [ {"name": "Apple", ...}, {"name": "Pear", ...} ]
+//
+// Richer tools wrap the array in an object so they can store information
+// about the file as a whole next to it:
+//
+//     { "generator": "orchard-sync 2.1", "fruits": [ ... ] }
+//
+// Both layouts can be read. Keys other than `"fruits"` are kept in
+// `CatalogueDocument::extra` and written back on save, and a file is saved
+// in the same layout it was loaded in, so fruitdata can safely edit files
+// written by other tools.
+//
+// Strict loading (`CatalogueDocument::from_slice_strict`) is the opposite:
+// for CI checks that should notice schema drift, any key fruitdata doesn't
+// know, and any value of the wrong type, is reported with its path.
+// ============================================================================
+
+use crate::calibration::Calibration;
+use crate::catalog::validate_dimensions;
+use crate::compat::{accepted_names, canonical_name};
+use crate::error::{CatalogError, FieldError, RecordError};
+use crate::expr::Expression;
+use crate::measurements::Measurement;
+use crate::models::{FruitDimensions, FRUIT_KEYS};
+use crate::names::CaseSensitivity;
+use crate::policy::PlausibleRange;
+use crate::raw::{describe, dimension, RawFruitRecord};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::error::Error;
+
+/// Everything stored in a catalogue file.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::document::CatalogueDocument;
+///
+/// let json = br#"{"generator": "orchard-sync", "fruits": [
+///     {"name": "Apple", "length": 4.0, "width": 2.5, "height": 1.5, "origin": "Kent"}
+/// ]}"#;
+/// let document = CatalogueDocument::from_slice(json).unwrap();
+///
+/// assert_eq!(document.fruits[0].extra["origin"], "Kent");
+/// assert_eq!(document.extra.as_ref().unwrap()["generator"], "orchard-sync");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatalogueDocument {
+    /// The fruits, in file order.
+    pub fruits: Vec<FruitDimensions>,
+
+    /// The file's other top-level keys, or `None` if the file is a bare
+    /// array (and should stay one when saved).
+    pub extra: Option<Map<String, Value>>,
+}
+
+/// The top-level keys of the object layout that strict loading accepts.
+const FILE_KEYS: &[&str] = &["fruits", "calibration", "case_sensitive", "plausible_range"];
+
+/// The object layout, as serde sees it. The other keys are written first,
+/// so that information about the file comes before the (long) fruit list.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Envelope<F, E> {
+    #[serde(flatten)]
+    pub(crate) extra: E,
+    pub(crate) fruits: F,
+}
+
+impl CatalogueDocument {
+    /// Parse a catalogue file in either layout.
+    ///
+    /// The layout is picked by the first character (`[` or `{`), so an error
+    /// in the file is reported against the layout it is actually in.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        let object = bytes
+            .iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .is_some_and(|&b| b == b'{');
+
+        if object {
+            let envelope: Envelope<Vec<FruitDimensions>, Map<String, Value>> =
+                serde_json::from_slice(bytes)?;
+            Ok(CatalogueDocument {
+                fruits: envelope.fruits,
+                extra: Some(envelope.extra),
+            })
+        } else {
+            Ok(CatalogueDocument {
+                fruits: serde_json::from_slice(bytes)?,
+                extra: None,
+            })
+        }
+    }
+
+    /// Parse a catalogue file in either layout, skipping fruits that can't
+    /// be read instead of failing.
+    ///
+    /// The file must still be valid JSON with a list of records; it is each
+    /// record that may be bad (a missing field, a string where a number
+    /// should be). Those are left out of the document and returned as
+    /// `RecordError`s, in file order, each listing every bad field (see
+    /// raw.rs).
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::document::CatalogueDocument;
+    ///
+    /// let json = br#"[
+    ///     {"name": "Apple", "length": 4.0, "width": 2.5, "height": 1.5},
+    ///     {"name": "Pear", "length": "6", "width": 3.5, "height": 2.5}
+    /// ]"#;
+    /// let (document, errors) = CatalogueDocument::from_slice_partial(json).unwrap();
+    ///
+    /// assert_eq!(document.fruits.len(), 1);
+    /// assert_eq!(errors[0].index, 1);
+    /// assert_eq!(errors[0].name.as_deref(), Some("Pear"));
+    /// assert_eq!(errors[0].message, "length: expected a number, found a string");
+    /// ```
+    pub fn from_slice_partial(bytes: &[u8]) -> Result<(Self, Vec<RecordError>), serde_json::Error> {
+        let object = bytes
+            .iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .is_some_and(|&b| b == b'{');
+
+        let (records, extra): (Vec<Value>, _) = if object {
+            let envelope: Envelope<Vec<Value>, Map<String, Value>> = serde_json::from_slice(bytes)?;
+            (envelope.fruits, Some(envelope.extra))
+        } else {
+            (serde_json::from_slice(bytes)?, None)
+        };
+
+        let mut fruits = Vec::new();
+        let mut errors = Vec::new();
+        for (index, record) in records.into_iter().enumerate() {
+            let raw = match RawFruitRecord::deserialize(record) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    errors.push(RecordError {
+                        index,
+                        name: None,
+                        message: e.to_string(),
+                        problems: Vec::new(),
+                    });
+                    continue;
+                }
+            };
+            let name = raw.name().map(str::to_string);
+            match FruitDimensions::try_from(raw) {
+                Ok(fruit) => fruits.push(fruit),
+                Err(problems) => errors.push(RecordError {
+                    index,
+                    name,
+                    message: join(&problems),
+                    problems,
+                }),
+            }
+        }
+
+        Ok((CatalogueDocument { fruits, extra }, errors))
+    }
+
+    /// Parse a catalogue file, rejecting anything the schema doesn't allow.
+    ///
+    /// Unlike `from_slice`, unknown keys (on a fruit or on the file) are
+    /// errors rather than data to keep, and values must have exactly the
+    /// expected type: a string name, finite numbers that fit in an `f32`, and
+    /// a non-negative integer ID. Every problem is reported, each with a
+    /// JSONPath-style location such as `$[2].lenght`.
+    ///
+    /// # Error Cases
+    /// - The file isn't valid JSON
+    /// - `CatalogError::SchemaViolations` - One or more problems were found
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::document::CatalogueDocument;
+    /// use fruitdata::error::CatalogError;
+    ///
+    /// let json = br#"[{"name": "Apple", "lenght": 4.0, "width": 2.5, "height": "1.5"}]"#;
+    /// let error = CatalogueDocument::from_slice_strict(json).unwrap_err();
+    /// let error = error.downcast::<CatalogError>().unwrap();
+    /// assert_eq!(
+    ///     *error,
+    ///     CatalogError::SchemaViolations(vec![
+    ///         "$[0].lenght: unknown field".into(),
+    ///         "$[0].length: missing".into(),
+    ///         "$[0].height: expected a number, found a string".into(),
+    ///     ])
+    /// );
+    /// ```
+    pub fn from_slice_strict(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let value: Value = serde_json::from_slice(bytes)?;
+
+        let mut problems = Vec::new();
+        match &value {
+            Value::Array(fruits) => check_fruits(fruits, "$", &mut problems),
+            Value::Object(file) => {
+                for key in file.keys().filter(|k| !FILE_KEYS.contains(&k.as_str())) {
+                    problems.push(format!("$.{}: unknown field", key));
+                }
+                if let Some(calibration) = file.get("calibration") {
+                    if let Err(e) = Calibration::deserialize(calibration) {
+                        problems.push(format!("$.calibration: {}", e));
+                    }
+                }
+                if let Some(flag) = file.get("case_sensitive").filter(|v| !v.is_boolean()) {
+                    problems.push(format!(
+                        "$.case_sensitive: expected a boolean, found {}",
+                        describe(flag)
+                    ));
+                }
+                if let Some(range) = file.get("plausible_range") {
+                    if let Err(e) = PlausibleRange::deserialize(range) {
+                        problems.push(format!("$.plausible_range: {}", e));
+                    }
+                }
+                match file.get("fruits") {
+                    Some(Value::Array(fruits)) => check_fruits(fruits, "$.fruits", &mut problems),
+                    Some(other) => problems.push(format!(
+                        "$.fruits: expected an array, found {}",
+                        describe(other)
+                    )),
+                    None => problems.push("$.fruits: missing".to_string()),
+                }
+            }
+            other => problems.push(format!(
+                "$: expected an array or an object, found {}",
+                describe(other)
+            )),
+        }
+
+        if !problems.is_empty() {
+            return Err(CatalogError::SchemaViolations(problems).into());
+        }
+        Ok(CatalogueDocument::from_slice(bytes)?)
+    }
+
+    /// The file's `"calibration"` header (see calibration.rs), if it has
+    /// one.
+    ///
+    /// # Error Cases
+    /// The header isn't a valid calibration.
+    pub fn calibration(&self) -> Result<Option<Calibration>, String> {
+        calibration_of(self.extra.as_ref())
+    }
+
+    /// Whether the file's `"case_sensitive"` header asks for names to be
+    /// matched exactly (see `CaseSensitivity`).
+    ///
+    /// # Error Cases
+    /// The header isn't `true` or `false`.
+    pub fn case_sensitivity(&self) -> Result<CaseSensitivity, String> {
+        case_sensitivity_of(self.extra.as_ref())
+    }
+
+    /// The file's `"plausible_range"` header (see `PlausibleRange`), if it
+    /// has one.
+    ///
+    /// # Error Cases
+    /// The header isn't a valid range.
+    pub fn plausible_range(&self) -> Result<Option<PlausibleRange>, String> {
+        plausible_range_of(self.extra.as_ref())
+    }
+
+    /// Apply the file's calibration to every fruit, and record that it has
+    /// been applied. Returns `false`, changing nothing, when there is no
+    /// calibration to apply.
+    ///
+    /// # Error Cases
+    /// - The header isn't a valid calibration
+    /// - A corrected dimension isn't positive (nothing is changed)
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::document::CatalogueDocument;
+    ///
+    /// let json = br#"{"calibration": {"unit": "mm", "offset": 0.5}, "fruits": [
+    ///     {"name": "Apple", "length": 40.5, "width": 25.5, "height": 15.5}
+    /// ]}"#;
+    /// let mut document = CatalogueDocument::from_slice(json).unwrap();
+    ///
+    /// assert!(document.calibrate().unwrap());
+    /// assert_eq!(document.fruits[0].length, 4.0);
+    /// assert!(!document.calibrate().unwrap()); // already applied
+    ///
+    /// let json = br#"{"calibration": {"unit": "mm", "offset": 100}, "fruits": [
+    ///     {"name": "Apple", "length": 40.5, "width": 25.5, "height": 15.5}
+    /// ]}"#;
+    /// let mut document = CatalogueDocument::from_slice(json).unwrap();
+    /// let error = document.calibrate().unwrap_err();
+    /// assert_eq!(error, "Apple: Dimensions must be positive numbers.");
+    /// assert_eq!(document.fruits[0].length, 40.5);
+    /// ```
+    pub fn calibrate(&mut self) -> Result<bool, String> {
+        let Some(calibration) = self.calibration()? else {
+            return Ok(false);
+        };
+        if calibration.is_identity() {
+            return Ok(false);
+        }
+        let mut corrected = self.fruits.clone();
+        for fruit in &mut corrected {
+            calibration.apply(fruit);
+            validate_dimensions(fruit).map_err(|e| format!("{}: {}", fruit.name, e))?;
+        }
+        self.fruits = corrected;
+        set_calibration(&mut self.extra, &calibration.applied());
+        Ok(true)
+    }
+}
+
+/// The `"calibration"` header among a file's top-level keys.
+pub(crate) fn calibration_of(
+    extra: Option<&Map<String, Value>>,
+) -> Result<Option<Calibration>, String> {
+    match extra.and_then(|extra| extra.get("calibration")) {
+        None => Ok(None),
+        Some(header) => Calibration::deserialize(header)
+            .map(Some)
+            .map_err(|e| format!("invalid calibration: {}", e)),
+    }
+}
+
+/// The `"case_sensitive"` header among a file's top-level keys; a file
+/// without one is case-insensitive.
+pub(crate) fn case_sensitivity_of(
+    extra: Option<&Map<String, Value>>,
+) -> Result<CaseSensitivity, String> {
+    match extra.and_then(|extra| extra.get("case_sensitive")) {
+        None => Ok(CaseSensitivity::Insensitive),
+        Some(Value::Bool(sensitive)) => Ok(CaseSensitivity::from_flag(*sensitive)),
+        Some(other) => Err(format!(
+            "\"case_sensitive\" must be true or false, found {}",
+            other
+        )),
+    }
+}
+
+/// The `"plausible_range"` header among a file's top-level keys.
+pub(crate) fn plausible_range_of(
+    extra: Option<&Map<String, Value>>,
+) -> Result<Option<PlausibleRange>, String> {
+    match extra.and_then(|extra| extra.get("plausible_range")) {
+        None => Ok(None),
+        Some(header) => PlausibleRange::deserialize(header)
+            .map(Some)
+            .map_err(|e| format!("invalid plausible range: {}", e)),
+    }
+}
+
+/// Store `calibration` as a file's `"calibration"` header, switching a
+/// bare-array file to the object layout.
+pub(crate) fn set_calibration(extra: &mut Option<Map<String, Value>>, calibration: &Calibration) {
+    let value = serde_json::to_value(calibration).expect("a calibration is always valid JSON");
+    extra
+        .get_or_insert_with(Map::new)
+        .insert("calibration".to_string(), value);
+}
+
+/// Check every fruit in an array, adding one message per problem.
+fn check_fruits(fruits: &[Value], path: &str, problems: &mut Vec<String>) {
+    for (index, fruit) in fruits.iter().enumerate() {
+        let path = format!("{}[{}]", path, index);
+        let Value::Object(fields) = fruit else {
+            problems.push(format!(
+                "{}: expected an object, found {}",
+                path,
+                describe(fruit)
+            ));
+            continue;
+        };
+
+        for key in fields.keys() {
+            if !FRUIT_KEYS.contains(&key.as_str()) && canonical_name(key).is_none() {
+                problems.push(format!("{}.{}: unknown field", path, key));
+            }
+        }
+
+        // Each field may appear under any of its names, but must appear
+        let lookup = |names: &[&'static str]| {
+            names
+                .iter()
+                .find_map(|name| fields.get(*name).map(|value| (*name, value)))
+        };
+        for field in ["name", "length", "width", "height"] {
+            let names = accepted_names(field);
+            match lookup(&names) {
+                None => problems.push(format!("{}.{}: missing", path, names[0])),
+                Some((name, value)) => {
+                    let problem = if name == "name" {
+                        (!value.is_string())
+                            .then(|| format!("expected a string, found {}", describe(value)))
+                    } else {
+                        dimension(value).err()
+                    };
+                    if let Some(problem) = problem {
+                        problems.push(format!("{}.{}: {}", path, name, problem));
+                    }
+                }
+            }
+        }
+
+        if let Some(id) = fields.get("id") {
+            if id.as_u64().is_none() {
+                problems.push(format!(
+                    "{}.id: expected a non-negative integer, found {}",
+                    path,
+                    describe(id)
+                ));
+            }
+        }
+
+        if let Some(formula) = fields.get("volume_formula") {
+            let problem = match formula.as_str() {
+                None => Some(format!("expected a string, found {}", describe(formula))),
+                Some(text) => text.parse::<Expression>().err(),
+            };
+            if let Some(problem) = problem {
+                problems.push(format!("{}.volume_formula: {}", path, problem));
+            }
+        }
+
+        if let Some(measurements) = fields.get("measurements") {
+            if let Err(e) = Vec::<Measurement>::deserialize(measurements) {
+                problems.push(format!("{}.measurements: {}", path, e));
+            }
+        }
+    }
+}
+
+/// The problems of a record, on one line.
+fn join(problems: &[FieldError]) -> String {
+    problems
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
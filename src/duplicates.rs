@@ -0,0 +1,143 @@
+// ============================================================================
+// duplicates.rs - Finding Fruits That Are Probably the Same
+// ============================================================================
+// The catalogue refuses a name that is already taken, but that doesn't stop
+// every duplicate:
+//
+// - "Green Apple" and "GreenApple" are different names, and so are any two
+//   names in ID mode
+// - the same fruit entered twice under different names ("Kiwi" and
+//   "Kiwifruit") usually has near-identical dimensions
+//
+// `find_duplicates` looks for both and groups the fruits into clusters.
+// Fruits are similar when each of their dimensions is within a tolerance
+// of the other's (a fraction of the larger one, so 0.02 is 2%), and
+// similarity is transitive within a cluster: if A is like B and B is like
+// C, all three are one cluster.
+//
+// Nothing is changed. Which fruit to keep is a decision for a person, who
+// can then `remove` or `rename` the others.
+// ============================================================================
+
+use crate::models::FruitDimensions;
+use crate::names::fold_case;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Why the fruits of a cluster might be duplicates.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    /// Their names are the same, ignoring case and whitespace.
+    SameName,
+    /// Their dimensions are within the tolerance of each other.
+    SimilarDimensions,
+}
+
+/// Fruits that might all be the same fruit, in catalogue order.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DuplicateCluster {
+    pub reason: DuplicateReason,
+    pub fruits: Vec<FruitDimensions>,
+}
+
+/// Find the clusters of possible duplicates among `fruits`: first those
+/// with the same name, then those with similar dimensions (see the module
+/// comment). Fruits whose names already match aren't compared by
+/// dimensions, so each pair is reported once. Clusters of each kind are in
+/// the order of their first fruit.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::duplicates::{find_duplicates, DuplicateReason};
+/// use fruitdata::models::FruitDimensions;
+///
+/// let fruits = vec![
+///     FruitDimensions::new("Green Apple", 4.0, 2.5, 1.5),
+///     FruitDimensions::new("Kiwi", 3.0, 2.0, 2.0),
+///     FruitDimensions::new("greenapple", 7.0, 6.0, 6.0),
+///     FruitDimensions::new("Kiwifruit", 3.05, 2.0, 1.98),
+///     FruitDimensions::new("Plum", 3.0, 2.5, 2.5),
+/// ];
+///
+/// let clusters = find_duplicates(&fruits, 0.02);
+/// assert_eq!(clusters.len(), 2);
+/// assert_eq!(clusters[0].reason, DuplicateReason::SameName);
+/// assert_eq!(clusters[0].fruits[1].name, "greenapple");
+/// assert_eq!(clusters[1].reason, DuplicateReason::SimilarDimensions);
+/// assert_eq!(clusters[1].fruits[1].name, "Kiwifruit"); // not Plum: too wide
+/// ```
+pub fn find_duplicates(fruits: &[FruitDimensions], tolerance: f32) -> Vec<DuplicateCluster> {
+    let keys: Vec<String> = fruits.iter().map(|fruit| name_key(&fruit.name)).collect();
+
+    let mut by_name: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (index, key) in keys.iter().enumerate() {
+        by_name.entry(key).or_default().push(index);
+    }
+    let mut named: Vec<Vec<usize>> = by_name
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .collect();
+    named.sort();
+
+    // Join similar fruits (union-find). Sorted by length, a fruit only
+    // needs comparing with the ones after it until they get too long.
+    let mut parent: Vec<usize> = (0..fruits.len()).collect();
+    let mut by_length: Vec<usize> = (0..fruits.len()).collect();
+    by_length.sort_by(|a, b| fruits[*a].length.total_cmp(&fruits[*b].length));
+    for (position, &a) in by_length.iter().enumerate() {
+        for &b in &by_length[position + 1..] {
+            if fruits[b].length * (1.0 - tolerance) > fruits[a].length {
+                break;
+            }
+            if keys[a] != keys[b] && similar(&fruits[a], &fruits[b], tolerance) {
+                let (root_a, root_b) = (root(&mut parent, a), root(&mut parent, b));
+                parent[root_a.max(root_b)] = root_a.min(root_b);
+            }
+        }
+    }
+    let mut by_root: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for index in 0..fruits.len() {
+        by_root
+            .entry(root(&mut parent, index))
+            .or_default()
+            .push(index);
+    }
+    let alike = by_root.into_values().filter(|members| members.len() > 1);
+
+    let cluster = |reason, members: Vec<usize>| DuplicateCluster {
+        reason,
+        fruits: members.into_iter().map(|i| fruits[i].clone()).collect(),
+    };
+    named
+        .into_iter()
+        .map(|members| cluster(DuplicateReason::SameName, members))
+        .chain(alike.map(|members| cluster(DuplicateReason::SimilarDimensions, members)))
+        .collect()
+}
+
+/// A name with its case folded and its whitespace removed.
+fn name_key(name: &str) -> String {
+    fold_case(name).split_whitespace().collect()
+}
+
+/// Whether every dimension of `a` is within `tolerance` of `b`'s.
+fn similar(a: &FruitDimensions, b: &FruitDimensions, tolerance: f32) -> bool {
+    [
+        (a.length, b.length),
+        (a.width, b.width),
+        (a.height, b.height),
+    ]
+    .into_iter()
+    .all(|(x, y)| (x - y).value().abs() <= tolerance * x.value().max(y.value()))
+}
+
+/// The representative of `index`'s cluster, shortening the path to it.
+fn root(parent: &mut [usize], index: usize) -> usize {
+    let mut current = index;
+    while parent[current] != current {
+        parent[current] = parent[parent[current]];
+        current = parent[current];
+    }
+    current
+}
@@ -0,0 +1,192 @@
+// ============================================================================
+// error.rs - Typed Errors for Catalogue Operations
+// ============================================================================
+// Loading and saving files can fail in many ways (I/O, bad JSON), and those
+// errors are passed around as `Box<dyn Error>`. Operations on the catalogue
+// itself, however, fail for a small set of well-known reasons ("that fruit
+// already exists", "that name isn't allowed"). Giving those reasons their own
+// enum lets callers `match` on them instead of comparing error strings.
+//
+// Key concept: Implementing `std::error::Error` (plus `Display` for the
+// message) means a `CatalogError` can still be returned with `?` from any
+// function that returns `Result<_, Box<dyn Error>>`.
+// ============================================================================
+
+use crate::policy::Quota;
+use std::error::Error;
+use std::fmt;
+
+/// The ways a change to the catalogue can be rejected.
+///
+/// The `Display` messages are the exact sentences the CLI prints, so the
+/// binary can simply print the error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CatalogError {
+    /// The name was empty (or only whitespace).
+    EmptyName,
+
+    /// One or more dimensions were zero, negative or not finite.
+    InvalidDimensions,
+
+    /// A fruit with this name (compared case-insensitively) already exists.
+    DuplicateName(String),
+
+    /// No fruit with this name exists.
+    NotFound(String),
+
+    /// A fruit with this ID already exists.
+    DuplicateId(u64),
+
+    /// Several fruits share this name (ID mode); an ID is needed to pick one.
+    /// `ids` are those of the fruits that have one.
+    AmbiguousName { name: String, ids: Vec<u64> },
+
+    /// The name was rejected by the configured `NamePolicy`.
+    InvalidName { name: String, reason: String },
+
+    /// The dimensions are outside the `PlausibleRange`, whose policy is to
+    /// reject them.
+    ImplausibleDimensions { name: String, reason: String },
+
+    /// The fruit failed one of the config's `pre_add` hooks (see hooks.rs).
+    HookRejected { name: String, reason: String },
+
+    /// The change would take the catalogue past one of its configured `Limits`.
+    QuotaExceeded {
+        quota: Quota,
+        limit: u64,
+        actual: u64,
+    },
+
+    /// The file was changed by another process since it was loaded.
+    ConcurrentModification,
+
+    /// A merge with another process's changes failed; these fruits were
+    /// changed differently on both sides.
+    MergeConflict(Vec<String>),
+
+    /// The catalogue is read-only and can't be changed.
+    ReadOnly,
+
+    /// Strict loading found keys or values the catalogue schema doesn't
+    /// allow; each entry is a path into the file and what's wrong there.
+    SchemaViolations(Vec<String>),
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::EmptyName => write!(f, "Name must not be empty."),
+            CatalogError::InvalidDimensions => write!(f, "Dimensions must be positive numbers."),
+            CatalogError::DuplicateName(name) => write!(f, "Fruit '{}' already exists.", name),
+            CatalogError::NotFound(name) => write!(f, "Fruit '{}' not found.", name),
+            CatalogError::DuplicateId(id) => write!(f, "A fruit with id {} already exists.", id),
+            CatalogError::AmbiguousName { name, ids } if ids.is_empty() => write!(
+                f,
+                "Several fruits are named '{}' and none has an id; use --case-sensitive \
+                 with the exact name to choose one.",
+                name
+            ),
+            CatalogError::AmbiguousName { name, ids } => {
+                let ids: Vec<String> = ids.iter().map(u64::to_string).collect();
+                write!(
+                    f,
+                    "Several fruits are named '{}' (ids {}); use --id to choose one.",
+                    name,
+                    ids.join(", ")
+                )
+            }
+            CatalogError::InvalidName { name, reason } => {
+                write!(f, "Name '{}' is not allowed: {}.", name, reason)
+            }
+            CatalogError::ImplausibleDimensions { name, reason } => write!(
+                f,
+                "Fruit '{}' looks mis-measured: {} (is it in the wrong unit?).",
+                name, reason
+            ),
+            CatalogError::HookRejected { name, reason } => {
+                write!(f, "Fruit '{}' was rejected: {}.", name, reason)
+            }
+            CatalogError::QuotaExceeded {
+                quota,
+                limit,
+                actual,
+            } => write!(
+                f,
+                "Quota exceeded: {} would be {} (limit is {}).",
+                quota, actual, limit
+            ),
+            CatalogError::ConcurrentModification => write!(
+                f,
+                "The catalogue file was modified by another process since it was loaded; \
+                 use --force to overwrite it or --on-conflict merge to combine the changes."
+            ),
+            CatalogError::MergeConflict(names) => write!(
+                f,
+                "Could not merge with the changes on disk; both sides changed: {}.",
+                names.join(", ")
+            ),
+            CatalogError::ReadOnly => write!(f, "The catalogue is read-only."),
+            CatalogError::SchemaViolations(problems) => {
+                write!(f, "The file doesn't match the catalogue schema:")?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for CatalogError {}
+
+/// A fruit in a file that couldn't be read, reported by partial loading
+/// (`load_catalogue_partial`) instead of failing the whole load.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordError {
+    /// The record's position in the fruit list, from 0.
+    pub index: usize,
+    /// The record's name, if it has a readable one.
+    pub name: Option<String>,
+    /// What is wrong with it, e.g. `length: expected a number, found a
+    /// string`.
+    pub message: String,
+    /// Each bad field of the record, in field order; empty if the record
+    /// isn't even an object.
+    pub problems: Vec<FieldError>,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(
+                f,
+                "Record {} ('{}'): {}",
+                self.index + 1,
+                name,
+                self.message
+            ),
+            None => write!(f, "Record {}: {}", self.index + 1, self.message),
+        }
+    }
+}
+
+impl Error for RecordError {}
+
+/// A field of a fruit record whose value isn't valid, found when turning a
+/// `RawFruitRecord` into a `FruitDimensions` (see raw.rs).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    /// The field's name (its current one, if the file used an alias).
+    pub field: &'static str,
+    /// What is wrong with the value, e.g. `missing`.
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl Error for FieldError {}
@@ -0,0 +1,251 @@
+// ============================================================================
+// events.rs - Descriptions of Changes to a Catalogue
+// ============================================================================
+// Every successful mutation of a `Catalogue` (add, remove, rename, patch)
+// produces a `CatalogEvent` describing exactly what changed, including the
+// fruit's data before and/or after the change. Events are the raw material
+// for the change journal (see journal.rs) and anything else that needs to
+// know "what just happened" without diffing whole catalogues.
+//
+// Going the other way, `diff` turns two versions of a catalogue into the
+// events between them, and `CatalogueDiff` groups those for a reviewer
+// (`fruitdata diff old.json new.json`).
+// ============================================================================
+
+use crate::merge::record_key_in;
+use crate::models::{FieldChange, FruitDimensions};
+use crate::names::CaseSensitivity;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A single change made to a catalogue.
+///
+/// Serialized with a `"type"` tag, e.g.
+/// `{"type": "removed", "fruit": {"name": "Durian", ...}}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum CatalogEvent {
+    /// A fruit was added; `fruit` is the entry as stored (trimmed, with ID).
+    Added { fruit: FruitDimensions },
+
+    /// A fruit was removed; `fruit` is the entry as it was.
+    Removed { fruit: FruitDimensions },
+
+    /// A fruit was renamed or had its dimensions changed.
+    Updated {
+        before: FruitDimensions,
+        after: FruitDimensions,
+    },
+}
+
+impl CatalogEvent {
+    /// The kind of change, as used by `fruitdata audit --action`.
+    pub fn action(&self) -> Action {
+        match self {
+            CatalogEvent::Added { .. } => Action::Add,
+            CatalogEvent::Removed { .. } => Action::Remove,
+            CatalogEvent::Updated { before, after } if before.name != after.name => Action::Rename,
+            CatalogEvent::Updated { .. } => Action::Update,
+        }
+    }
+
+    /// The fruit's name after the change (or before it, for removals).
+    pub fn name(&self) -> &str {
+        match self {
+            CatalogEvent::Added { fruit } | CatalogEvent::Removed { fruit } => &fruit.name,
+            CatalogEvent::Updated { after, .. } => &after.name,
+        }
+    }
+
+    /// The fruit's state before the change (`None` for additions).
+    pub fn before(&self) -> Option<&FruitDimensions> {
+        match self {
+            CatalogEvent::Added { .. } => None,
+            CatalogEvent::Removed { fruit } => Some(fruit),
+            CatalogEvent::Updated { before, .. } => Some(before),
+        }
+    }
+
+    /// The fruit's state after the change (`None` for removals).
+    pub fn after(&self) -> Option<&FruitDimensions> {
+        match self {
+            CatalogEvent::Added { fruit } => Some(fruit),
+            CatalogEvent::Removed { .. } => None,
+            CatalogEvent::Updated { after, .. } => Some(after),
+        }
+    }
+}
+
+/// The events that turn the catalogue `before` into `after`.
+///
+/// Fruits are matched by ID, or by name (case-insensitively) when they have
+/// no ID. Removals come first, then updates and additions in `after` order.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::events::{diff, Action};
+/// use fruitdata::models::FruitDimensions;
+///
+/// let before = vec![
+///     FruitDimensions::new("Apple", 4.0, 2.5, 1.5),
+///     FruitDimensions::new("Pear", 6.0, 3.5, 2.5),
+/// ];
+/// let after = vec![FruitDimensions::new("Apple", 4.0, 2.8, 1.5)];
+///
+/// let actions: Vec<Action> = diff(&before, &after).iter().map(|e| e.action()).collect();
+/// assert_eq!(actions, vec![Action::Remove, Action::Update]);
+/// ```
+pub fn diff(before: &[FruitDimensions], after: &[FruitDimensions]) -> Vec<CatalogEvent> {
+    diff_in(before, after, CaseSensitivity::Insensitive)
+}
+
+/// `diff` for a catalogue whose names match with `case`.
+pub(crate) fn diff_in(
+    before: &[FruitDimensions],
+    after: &[FruitDimensions],
+    case: CaseSensitivity,
+) -> Vec<CatalogEvent> {
+    let record_key = |fruit: &FruitDimensions| record_key_in(fruit, case);
+    let find = |fruits: &[FruitDimensions], key: &str| {
+        fruits.iter().find(|f| record_key(f) == key).cloned()
+    };
+
+    let mut events: Vec<CatalogEvent> = before
+        .iter()
+        .filter(|f| find(after, &record_key(f)).is_none())
+        .map(|f| CatalogEvent::Removed { fruit: f.clone() })
+        .collect();
+
+    for fruit in after {
+        match find(before, &record_key(fruit)) {
+            None => events.push(CatalogEvent::Added {
+                fruit: fruit.clone(),
+            }),
+            Some(old) if &old != fruit => events.push(CatalogEvent::Updated {
+                before: old,
+                after: fruit.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    events
+}
+
+/// What differs between two catalogues, grouped for review: the fruits only
+/// in the new one, those only in the old one, and those in both whose fields
+/// differ.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::events::CatalogueDiff;
+/// use fruitdata::models::FruitDimensions;
+///
+/// let before = vec![
+///     FruitDimensions::new("Apple", 4.0, 2.5, 1.5),
+///     FruitDimensions::new("Durian", 20.0, 15.0, 15.0),
+/// ];
+/// let after = vec![
+///     FruitDimensions::new("Apple", 4.0, 2.8, 1.5),
+///     FruitDimensions::new("Kiwi", 3.0, 2.0, 2.0),
+/// ];
+///
+/// let diff = CatalogueDiff::between(&before, &after);
+/// assert_eq!(diff.added[0].name, "Kiwi");
+/// assert_eq!(diff.removed[0].name, "Durian");
+/// assert_eq!(diff.changed[0].changes[0].to_string(), "width 2.5 -> 2.8");
+/// assert!(CatalogueDiff::between(&after, &after).is_empty());
+/// ```
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CatalogueDiff {
+    /// Fruits only in the new catalogue, in its order.
+    pub added: Vec<FruitDimensions>,
+    /// Fruits only in the old catalogue, in its order.
+    pub removed: Vec<FruitDimensions>,
+    /// Fruits in both whose data differs, in the new catalogue's order.
+    pub changed: Vec<ChangedFruit>,
+}
+
+/// A fruit in both catalogues of a `CatalogueDiff` whose data differs.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChangedFruit {
+    /// The fruit's name in the new catalogue.
+    pub name: String,
+    /// The name and dimension fields that differ; empty when only other
+    /// data (tags, custom fields, ...) does.
+    pub changes: Vec<FieldChange>,
+}
+
+impl CatalogueDiff {
+    /// What changed from `before` to `after`, matching fruits as `diff`
+    /// does.
+    pub fn between(before: &[FruitDimensions], after: &[FruitDimensions]) -> Self {
+        CatalogueDiff::between_in(before, after, CaseSensitivity::Insensitive)
+    }
+
+    /// `between` for catalogues whose names match with `case`.
+    pub(crate) fn between_in(
+        before: &[FruitDimensions],
+        after: &[FruitDimensions],
+        case: CaseSensitivity,
+    ) -> Self {
+        let mut grouped = CatalogueDiff::default();
+        for event in diff_in(before, after, case) {
+            match event {
+                CatalogEvent::Added { fruit } => grouped.added.push(fruit),
+                CatalogEvent::Removed { fruit } => grouped.removed.push(fruit),
+                CatalogEvent::Updated { before, after } => grouped.changed.push(ChangedFruit {
+                    changes: before.changes_to(&after),
+                    name: after.name,
+                }),
+            }
+        }
+        grouped
+    }
+
+    /// Whether the two catalogues hold the same fruits.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The kinds of change a `CatalogEvent` can represent.
+///
+/// A rename is an update whose name changed; it gets its own action so that
+/// "who renamed X?" can be asked directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Add,
+    Remove,
+    Update,
+    Rename,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "add" => Ok(Action::Add),
+            "remove" => Ok(Action::Remove),
+            "update" => Ok(Action::Update),
+            "rename" => Ok(Action::Rename),
+            other => Err(format!(
+                "unknown action '{}' (expected add, remove, update or rename)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Action::Add => "add",
+            Action::Remove => "remove",
+            Action::Update => "update",
+            Action::Rename => "rename",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,258 @@
+// ============================================================================
+// expr.rs - Arithmetic on a Fruit's Dimensions
+// ============================================================================
+// Some numbers people want from a catalogue are simple formulas over a
+// fruit's dimensions: an aspect ratio, a surface estimate, a volume that
+// models the fruit better than a box does. An `Expression` is such a
+// formula, written the way you would on paper:
+//
+//     length / width
+//     0.52 * l * w * h
+//
+// It may use numbers, `+ - * /`, parentheses, and the names `length`,
+// `width`, `height` (or `l`, `w`, `h`) and `volume`. Expressions are parsed
+// once, when read, so a typo is reported before anything is computed.
+//
+// Catalogue files in the object layout can define computed fields in their
+// header, shown by `get` and available to `list --fields`:
+//
+//     { "computed": { "aspect_ratio": "length / width" }, "fruits": [ ... ] }
+//
+// and a single fruit can replace the box volume with its own formula:
+//
+//     { "name": "Melon", ..., "volume_formula": "0.52 * l * w * h" }
+// ============================================================================
+
+use crate::models::FruitDimensions;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A value of the fruit an expression can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variable {
+    Length,
+    Width,
+    Height,
+    Volume,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f32),
+    Variable(Variable),
+    Negate(Box<Node>),
+    Binary(char, Box<Node>, Box<Node>),
+}
+
+/// A formula over a fruit's dimensions, e.g. `length / width`.
+///
+/// Serialized as its text.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::expr::Expression;
+/// use fruitdata::models::FruitDimensions;
+///
+/// let ratio: Expression = "length / width".parse().unwrap();
+/// let apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+/// assert_eq!(ratio.evaluate(&apple), Ok(1.6));
+///
+/// assert!("length / colour".parse::<Expression>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    /// Compute the expression for `fruit`.
+    ///
+    /// # Error Cases
+    /// Dividing by zero.
+    pub fn evaluate(&self, fruit: &FruitDimensions) -> Result<f32, String> {
+        evaluate(&self.root, fruit, fruit.volume())
+    }
+
+    /// Compute the expression for `fruit`, with `volume` standing for the
+    /// given value. This is how a fruit's own `volume_formula` is computed
+    /// without asking `volume()` (and so itself) for the volume.
+    pub(crate) fn evaluate_with_volume(
+        &self,
+        fruit: &FruitDimensions,
+        volume: f32,
+    ) -> Result<f32, String> {
+        evaluate(&self.root, fruit, volume)
+    }
+}
+
+fn evaluate(node: &Node, fruit: &FruitDimensions, volume: f32) -> Result<f32, String> {
+    Ok(match node {
+        Node::Number(n) => *n,
+        Node::Variable(Variable::Length) => fruit.length.value(),
+        Node::Variable(Variable::Width) => fruit.width.value(),
+        Node::Variable(Variable::Height) => fruit.height.value(),
+        Node::Variable(Variable::Volume) => volume,
+        Node::Negate(inner) => -evaluate(inner, fruit, volume)?,
+        Node::Binary(op, left, right) => {
+            let (a, b) = (
+                evaluate(left, fruit, volume)?,
+                evaluate(right, fruit, volume)?,
+            );
+            match op {
+                '+' => a + b,
+                '-' => a - b,
+                '*' => a * b,
+                _ if b == 0.0 => return Err("division by zero".to_string()),
+                _ => a / b,
+            }
+        }
+    })
+}
+
+impl FromStr for Expression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { source: s, rest: s };
+        let root = parser.sum()?;
+        if let Some(c) = parser.peek() {
+            return Err(format!("unexpected '{}' in expression '{}'", c, s));
+        }
+        Ok(Expression {
+            source: s.trim().to_string(),
+            root,
+        })
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Expression> for String {
+    fn from(expression: Expression) -> Self {
+        expression.source
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// A recursive-descent parser working straight on the text: `sum` handles
+/// `+ -`, `product` handles `* /`, and `factor` the rest.
+struct Parser<'a> {
+    source: &'a str,
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    /// The next character that isn't whitespace.
+    fn peek(&mut self) -> Option<char> {
+        self.rest = self.rest.trim_start();
+        self.rest.chars().next()
+    }
+
+    fn bump(&mut self) {
+        let mut chars = self.rest.chars();
+        chars.next();
+        self.rest = chars.as_str();
+    }
+
+    fn sum(&mut self) -> Result<Node, String> {
+        let mut node = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.bump();
+            node = Node::Binary(op, Box::new(node), Box::new(self.product()?));
+        }
+        Ok(node)
+    }
+
+    fn product(&mut self) -> Result<Node, String> {
+        let mut node = self.factor()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.bump();
+            node = Node::Binary(op, Box::new(node), Box::new(self.factor()?));
+        }
+        Ok(node)
+    }
+
+    fn factor(&mut self) -> Result<Node, String> {
+        match self.peek() {
+            Some('-') => {
+                self.bump();
+                Ok(Node::Negate(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.bump();
+                let node = self.sum()?;
+                if self.peek() != Some(')') {
+                    return Err(format!("missing ')' in expression '{}'", self.source));
+                }
+                self.bump();
+                Ok(node)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let end = self
+                    .rest
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(self.rest.len());
+                let text = &self.rest[..end];
+                self.rest = &self.rest[end..];
+                text.parse()
+                    .map(Node::Number)
+                    .map_err(|_| format!("invalid number '{}'", text))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let end = self
+                    .rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(self.rest.len());
+                let name = &self.rest[..end];
+                self.rest = &self.rest[end..];
+                let variable = match name.to_ascii_lowercase().as_str() {
+                    "length" | "l" => Variable::Length,
+                    "width" | "w" => Variable::Width,
+                    "height" | "h" => Variable::Height,
+                    "volume" => Variable::Volume,
+                    _ => {
+                        return Err(format!(
+                            "unknown name '{}' in expression '{}' (expected length, width, height or volume)",
+                            name, self.source
+                        ))
+                    }
+                };
+                Ok(Node::Variable(variable))
+            }
+            Some(c) => Err(format!(
+                "unexpected '{}' in expression '{}'",
+                c, self.source
+            )),
+            None => Err(format!("incomplete expression '{}'", self.source)),
+        }
+    }
+}
+
+/// A named value computed for every fruit, defined in the catalogue file's
+/// `"computed"` header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputedField {
+    pub name: String,
+    pub expression: Expression,
+}
+
+impl ComputedField {
+    /// The field's value for `fruit`.
+    pub fn evaluate(&self, fruit: &FruitDimensions) -> Result<f32, String> {
+        self.expression.evaluate(fruit)
+    }
+}
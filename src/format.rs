@@ -0,0 +1,484 @@
+// ============================================================================
+// format.rs - Catalogue File Formats
+// ============================================================================
+// A catalogue can be stored in more than one format:
+//
+// - JSON (`.json`): the usual layout, a single array (or object, see
+//   document.rs) holding every fruit
+// - JSON Lines (`.jsonl`, `.ndjson`): one fruit per line, which line-based
+//   tools (grep, split, `jq -c`) handle well and which can be read and
+//   written one fruit at a time
+// - CSV (`.csv`): one fruit per row, for spreadsheets. The delimiter and
+//   header row are the `csv` settings of `SaveOptions` (see csv.rs), and
+//   measurements are left out
+// - TOML (`.toml`) and YAML (`.yaml`, `.yml`), with the `toml` and `yaml`
+//   features: the same document as a JSON file, in a form that is easier
+//   to edit by hand (see toml.rs and yaml.rs)
+//
+// `convert` moves a catalogue from one format to another. When the input is
+// JSON Lines it is streamed: each fruit is written as soon as it is read, so
+// even a very large file never has to fit in memory. A JSON input has to be
+// parsed as a whole first, and a JSON output is written in one piece so that
+// it can follow the `SaveOptions` layout.
+//
+// `convert_calibrated` (`convert --calibrate`) also corrects every fruit with
+// the calibration recorded in a JSON input's header (see calibration.rs).
+//
+// A catalogue file given with `--file` is read and written in TOML or YAML
+// when its extension says so (see `Format::for_catalogue`), and as JSON
+// otherwise.
+//
+// `export` writes fruits already in memory, such as the subset of the
+// catalogue picked by `fruitdata export --where ...`.
+// ============================================================================
+
+use crate::calibration::Calibration;
+use crate::catalog::SaveOptions;
+use crate::csv::{self, CsvOptions};
+use crate::document::CatalogueDocument;
+use crate::models::FruitDimensions;
+#[cfg(any(feature = "toml", feature = "yaml"))]
+use crate::tree::Tree;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// A format a catalogue can be read from and written to.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A JSON array of fruits (or a `{"fruits": [...]}` object).
+    #[serde(rename = "json")]
+    Json,
+    /// One JSON fruit per line.
+    #[serde(rename = "jsonl")]
+    JsonLines,
+    /// One fruit per row of comma- (or otherwise) separated values.
+    #[serde(rename = "csv")]
+    Csv,
+    /// A TOML document, laid out like a JSON one.
+    #[cfg(feature = "toml")]
+    #[serde(rename = "toml")]
+    Toml,
+    /// A YAML document, laid out like a JSON one.
+    #[cfg(feature = "yaml")]
+    #[serde(rename = "yaml")]
+    Yaml,
+}
+
+/// The names of the formats, for messages.
+const NAMES: &[&str] = &[
+    "json",
+    "jsonl",
+    "csv",
+    #[cfg(feature = "toml")]
+    "toml",
+    #[cfg(feature = "yaml")]
+    "yaml",
+];
+
+impl Format {
+    /// Guess a file's format from its extension (`.json`, `.jsonl`,
+    /// `.ndjson`, `.csv`, and `.toml`, `.yaml` and `.yml` with their
+    /// features), ignoring case. Returns `None` for anything else.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::format::Format;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(Format::from_path(Path::new("fruits.JSONL")), Some(Format::JsonLines));
+    /// assert_eq!(Format::from_path(Path::new("fruits.txt")), None);
+    /// ```
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(Format::Json),
+            "jsonl" | "ndjson" => Some(Format::JsonLines),
+            "csv" => Some(Format::Csv),
+            #[cfg(feature = "toml")]
+            "toml" => Some(Format::Toml),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+
+    /// The format of the catalogue file at `path`: TOML or YAML if its
+    /// extension says so, and JSON otherwise (whatever the extension, as
+    /// catalogues have always been read).
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::format::Format;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(Format::for_catalogue(Path::new("fruits.json")), Format::Json);
+    /// assert_eq!(Format::for_catalogue(Path::new("fruits.db")), Format::Json);
+    /// ```
+    pub fn for_catalogue(path: &Path) -> Format {
+        match Format::from_path(path) {
+            #[cfg(feature = "toml")]
+            Some(Format::Toml) => Format::Toml,
+            #[cfg(feature = "yaml")]
+            Some(Format::Yaml) => Format::Yaml,
+            _ => Format::Json,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "jsonl" | "ndjson" => Ok(Format::JsonLines),
+            "csv" => Ok(Format::Csv),
+            #[cfg(feature = "toml")]
+            "toml" => Ok(Format::Toml),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Ok(Format::Yaml),
+            other => Err(format!(
+                "unknown format '{}' (expected one of {})",
+                other,
+                NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Json => write!(f, "json"),
+            Format::JsonLines => write!(f, "jsonl"),
+            Format::Csv => write!(f, "csv"),
+            #[cfg(feature = "toml")]
+            Format::Toml => write!(f, "toml"),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => write!(f, "yaml"),
+        }
+    }
+}
+
+/// Copy every fruit from `input` (in format `from`) to `output` (in format
+/// `to`), returning how many fruits were copied.
+///
+/// Fruits are copied as they are, without validation, including any
+/// unknown fields they carry. The file-level keys of an object-layout JSON
+/// file are not copied; JSON Lines has nowhere to keep them.
+///
+/// # Error Cases
+/// - The input isn't valid in format `from` (JSON Lines errors name the line)
+/// - Reading or writing fails
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::SaveOptions;
+/// use fruitdata::format::{convert, Format};
+///
+/// let json = br#"[{"name": "Apple", "length": 4.0, "width": 2.5, "height": 1.5}]"#;
+/// let mut output = Vec::new();
+/// let count = convert(&json[..], Format::Json, &mut output, Format::JsonLines, &SaveOptions::default()).unwrap();
+///
+/// assert_eq!(count, 1);
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "{\"name\":\"Apple\",\"length\":4.0,\"width\":2.5,\"height\":1.5}\n"
+/// );
+/// ```
+pub fn convert<R: BufRead, W: Write>(
+    input: R,
+    from: Format,
+    output: W,
+    to: Format,
+    options: &SaveOptions,
+) -> Result<usize, Box<dyn Error>> {
+    write_fruits(
+        read_fruits_with(input, from, &options.csv)?,
+        output,
+        to,
+        options,
+    )
+}
+
+/// Copy every fruit like `convert`, correcting each one with the
+/// calibration in the input's header (see calibration.rs). Returns how many
+/// fruits were copied and the input's calibration.
+///
+/// JSON, TOML and YAML output keeps the input's header, saying the
+/// calibration has been applied, so converting the output again changes
+/// nothing (and returns a calibration that `is_identity`).
+///
+/// # Error Cases
+/// The same as `convert`, plus: the input is JSON Lines or CSV (which have
+/// no header), it has no valid `"calibration"` header, or a corrected
+/// dimension isn't positive (nothing is written).
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::SaveOptions;
+/// use fruitdata::format::{convert_calibrated, Format};
+///
+/// let json = br#"{"calibration": {"unit": "mm", "offset": 0.5}, "fruits": [
+///     {"name": "Apple", "length": 40.5, "width": 25.5, "height": 15.5}
+/// ]}"#;
+/// let mut output = Vec::new();
+/// let (count, _) = convert_calibrated(&json[..], Format::Json, &mut output, Format::JsonLines, &SaveOptions::default()).unwrap();
+///
+/// assert_eq!(count, 1);
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "{\"name\":\"Apple\",\"length\":4.0,\"width\":2.5,\"height\":1.5}\n"
+/// );
+///
+/// let mut once = Vec::new();
+/// convert_calibrated(&json[..], Format::Json, &mut once, Format::Json, &SaveOptions::default()).unwrap();
+/// let mut twice = Vec::new();
+/// let (_, calibration) = convert_calibrated(&once[..], Format::Json, &mut twice, Format::Json, &SaveOptions::default()).unwrap();
+/// assert!(calibration.is_identity());
+/// assert_eq!(once, twice);
+/// ```
+pub fn convert_calibrated<R: BufRead, W: Write>(
+    mut input: R,
+    from: Format,
+    mut output: W,
+    to: Format,
+    options: &SaveOptions,
+) -> Result<(usize, Calibration), Box<dyn Error>> {
+    if matches!(from, Format::JsonLines | Format::Csv) {
+        return Err(format!("{} files have no header to hold a calibration", from).into());
+    }
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    let mut document = CatalogueDocument::from_slice(&document_json(&bytes, from)?)?;
+    let calibration = document
+        .calibration()?
+        .ok_or("the input has no \"calibration\" header")?;
+    document.calibrate()?;
+
+    if matches!(to, Format::JsonLines | Format::Csv) {
+        let fruits: Fruits = Box::new(document.fruits.into_iter().map(Ok));
+        let count = write_fruits(fruits, output, to, options)?;
+        return Ok((count, calibration));
+    }
+    let json = options.document_to_json(&document.fruits, document.extra.as_ref())?;
+    output.write_all(document_text(json, to)?.as_bytes())?;
+    output.flush()?;
+    Ok((document.fruits.len(), calibration))
+}
+
+/// Write `fruits` to `output` in format `to` (for `fruitdata export`),
+/// returning how many were written.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::SaveOptions;
+/// use fruitdata::csv::CsvOptions;
+/// use fruitdata::format::{export, Format};
+/// use fruitdata::models::FruitDimensions;
+///
+/// let mut lemon = FruitDimensions::new("Lemon", 5.0, 4.0, 4.0);
+/// lemon.id = Some(7);
+/// let options = SaveOptions { csv: CsvOptions::default().with_header(false), ..SaveOptions::default() };
+/// let mut output = Vec::new();
+/// export(&[lemon], &mut output, Format::Csv, &options).unwrap();
+/// assert_eq!(String::from_utf8(output).unwrap(), "Lemon,5,4,4,7\n");
+/// ```
+///
+/// ```
+/// use fruitdata::catalog::SaveOptions;
+/// use fruitdata::format::{export, Format};
+/// use fruitdata::models::FruitDimensions;
+///
+/// let lemon = FruitDimensions::new("Lemon", 5.0, 4.0, 4.0);
+/// let mut output = Vec::new();
+/// export(&[lemon], &mut output, Format::JsonLines, &SaveOptions::default()).unwrap();
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "{\"name\":\"Lemon\",\"length\":5.0,\"width\":4.0,\"height\":4.0}\n"
+/// );
+/// ```
+pub fn export<W: Write>(
+    fruits: &[FruitDimensions],
+    output: W,
+    to: Format,
+    options: &SaveOptions,
+) -> Result<usize, Box<dyn Error>> {
+    write_fruits(
+        Box::new(fruits.iter().cloned().map(Ok)),
+        output,
+        to,
+        options,
+    )
+}
+
+/// Write `fruits` to `output` in format `to`, returning how many there were.
+fn write_fruits<W: Write>(
+    fruits: Fruits<'_>,
+    mut output: W,
+    to: Format,
+    options: &SaveOptions,
+) -> Result<usize, Box<dyn Error>> {
+    let mut count = 0;
+    match to {
+        Format::JsonLines => {
+            for fruit in fruits {
+                writeln!(output, "{}", to_json_line(&fruit?, options)?)?;
+                count += 1;
+            }
+        }
+        // Columns come from every fruit's fields, so this is one piece too
+        Format::Csv => {
+            let fruits = fruits.collect::<Result<Vec<_>, _>>()?;
+            count = fruits.len();
+            output.write_all(csv::from_fruits(&fruits, &options.csv)?.as_bytes())?;
+        }
+        // JSON, TOML and YAML are written in one piece, so the whole
+        // layout can be applied
+        _ => {
+            let fruits = fruits.collect::<Result<Vec<_>, _>>()?;
+            count = fruits.len();
+            output.write_all(document_text(options.to_json(&fruits)?, to)?.as_bytes())?;
+        }
+    }
+    output.flush()?;
+    Ok(count)
+}
+
+/// The fruits of a file, read one at a time (see `read_fruits`).
+pub type Fruits<'r> = Box<dyn Iterator<Item = Result<FruitDimensions, Box<dyn Error>>> + 'r>;
+
+/// Read the fruits of `input`, which is in `format`.
+///
+/// JSON Lines input is read lazily, one line per fruit, skipping blank
+/// lines. JSON and CSV input is parsed immediately; CSV is expected in the
+/// default layout (see `read_fruits_with`).
+pub fn read_fruits<'r, R: BufRead + 'r>(
+    input: R,
+    format: Format,
+) -> Result<Fruits<'r>, Box<dyn Error>> {
+    read_fruits_with(input, format, &CsvOptions::default())
+}
+
+/// Read the fruits of `input` like `read_fruits`, with CSV input laid out
+/// as `options` say.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::csv::CsvOptions;
+/// use fruitdata::format::{read_fruits_with, Format};
+///
+/// let options = CsvOptions::default().with_delimiter('\t');
+/// let text = "name\tlength\twidth\theight\nKiwi\t3\t2\t2\n";
+/// let fruits: Vec<_> = read_fruits_with(text.as_bytes(), Format::Csv, &options)
+///     .unwrap()
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(fruits[0].name, "Kiwi");
+/// ```
+pub fn read_fruits_with<'r, R: BufRead + 'r>(
+    input: R,
+    format: Format,
+    options: &CsvOptions,
+) -> Result<Fruits<'r>, Box<dyn Error>> {
+    match format {
+        Format::Csv => {
+            let mut text = String::new();
+            let mut input = input;
+            input.read_to_string(&mut text)?;
+            let fruits = csv::to_fruits(&text, options)?;
+            Ok(Box::new(fruits.into_iter().map(Ok)))
+        }
+        Format::JsonLines => Ok(Box::new(
+            input
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.as_ref().is_ok_and(|l| l.trim().is_empty()))
+                .map(|(number, line)| {
+                    serde_json::from_str(&line?)
+                        .map_err(|e| format!("line {}: {}", number + 1, e).into())
+                }),
+        )),
+        // JSON, TOML and YAML: one document holding every fruit
+        _ => {
+            let mut bytes = Vec::new();
+            let mut input = input;
+            input.read_to_end(&mut bytes)?;
+            let document = CatalogueDocument::from_slice(&document_json(&bytes, format)?)?;
+            Ok(Box::new(document.fruits.into_iter().map(Ok)))
+        }
+    }
+}
+
+/// One fruit as a single line of JSON, using the naming and key order of
+/// `options` (its indentation settings don't apply to a single line).
+pub fn to_json_line(
+    fruit: &FruitDimensions,
+    options: &SaveOptions,
+) -> Result<String, serde_json::Error> {
+    let named = options.field_naming.named(fruit);
+    if options.sort_keys {
+        // Through text rather than `to_value`, which would widen each f32
+        // (see `SaveOptions`)
+        let sorted: serde_json::Value = serde_json::from_str(&serde_json::to_string(&named)?)?;
+        serde_json::to_string(&sorted)
+    } else {
+        serde_json::to_string(&named)
+    }
+}
+
+/// The JSON of a catalogue document (see document.rs) whose file, in
+/// `format`, holds `bytes`. JSON is returned as it is; TOML and YAML are
+/// translated.
+///
+/// # Error Cases
+/// - The text isn't valid in `format` (the error names the line)
+/// - `format` doesn't hold documents (JSON Lines, CSV)
+pub(crate) fn document_json(bytes: &[u8], format: Format) -> Result<Cow<'_, [u8]>, String> {
+    match format {
+        Format::Json => Ok(Cow::Borrowed(bytes)),
+        #[cfg(feature = "toml")]
+        Format::Toml => {
+            let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+            Ok(Cow::Owned(
+                crate::toml::parse(text)?.to_string().into_bytes(),
+            ))
+        }
+        #[cfg(feature = "yaml")]
+        Format::Yaml => {
+            let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+            Ok(Cow::Owned(
+                crate::yaml::parse(text)?.to_string().into_bytes(),
+            ))
+        }
+        other => Err(format!("{} files don't hold a catalogue document", other)),
+    }
+}
+
+/// `json`, the text of a catalogue document, as a file in `format`.
+///
+/// A TOML file can't be a bare list, so a catalogue in the array layout is
+/// written as its `fruits` key, which reads back the same.
+pub(crate) fn document_text(json: String, format: Format) -> Result<String, String> {
+    match format {
+        Format::Json => Ok(json),
+        #[cfg(feature = "toml")]
+        Format::Toml => {
+            let tree = match Tree::from_json(&json).map_err(|e| e.to_string())? {
+                Tree::Array(fruits) => Tree::Table(vec![("fruits".into(), Tree::Array(fruits))]),
+                tree => tree,
+            };
+            crate::toml::write(&tree)
+        }
+        #[cfg(feature = "yaml")]
+        Format::Yaml => Ok(crate::yaml::write(
+            &Tree::from_json(&json).map_err(|e| e.to_string())?,
+        )),
+        other => Err(format!("{} files don't hold a catalogue document", other)),
+    }
+}
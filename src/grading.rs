@@ -0,0 +1,220 @@
+// ============================================================================
+// grading.rs - Sorting Fruits into Grades
+// ============================================================================
+// Packhouses sell fruit by grade: "Class A" is everything with a volume of
+// at least 30 cm³, "Class B" at least 15, and so on. The grades are
+// defined once, in the config file's `grading` section:
+//
+//     { "grading": {
+//         "by": "volume",
+//         "grades": [
+//           { "name": "Class A", "min": 30 },
+//           { "name": "Class B", "min": 15, "max": 30 },
+//           { "name": "Class C" }
+//         ] } }
+//
+// `by` is an `Expression` over the fruit's dimensions (see expr.rs), so a
+// grade can go by length or `l * w` as well. Each grade is a band of its
+// values, from `min` (inclusive) up to `max` (exclusive), either of which
+// may be left open. A fruit gets the first grade whose band holds its
+// value, or none if no band does.
+//
+// `fruitdata grade` grades every fruit and stores the grade in the fruit's
+// `"grade"` field (or the `field` the config names), where `list --fields`
+// and `export --where` can see it. A fruit that fits no grade loses any
+// grade it had. The changes are recorded in the journal like any other.
+// ============================================================================
+
+use crate::catalog::Catalogue;
+use crate::expr::Expression;
+use crate::models::{FruitDimensions, FRUIT_KEYS};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How fruits are graded, from the config file's `grading` section.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct GradingSettings {
+    /// What is graded (default: `volume`).
+    pub by: Expression,
+    /// The grades, best first; a fruit gets the first one it fits.
+    pub grades: Vec<Grade>,
+    /// The fruit field the grade is stored in (default: `grade`).
+    pub field: String,
+}
+
+impl Default for GradingSettings {
+    fn default() -> Self {
+        GradingSettings {
+            by: "volume".parse().expect("\"volume\" is a valid expression"),
+            grades: Vec::new(),
+            field: "grade".to_string(),
+        }
+    }
+}
+
+/// One grade: a name, and the band of values it covers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Grade {
+    pub name: String,
+    /// The smallest value in the grade (no minimum if not given).
+    #[serde(default)]
+    pub min: Option<f32>,
+    /// The value the grade stops at, itself not included (no maximum if
+    /// not given).
+    #[serde(default)]
+    pub max: Option<f32>,
+}
+
+impl Grade {
+    /// Whether `value` is in the grade's band.
+    pub fn contains(&self, value: f32) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value < max)
+    }
+}
+
+impl GradingSettings {
+    /// The grade `fruit` gets, if any.
+    ///
+    /// # Error Cases
+    /// `by` can't be computed for the fruit (it divides by zero).
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::grading::{Grade, GradingSettings};
+    /// use fruitdata::models::FruitDimensions;
+    ///
+    /// let band = |name: &str, min| Grade { name: name.into(), min: Some(min), max: None };
+    /// let grading = GradingSettings {
+    ///     grades: vec![band("Class A", 30.0), band("Class B", 15.0)],
+    ///     ..GradingSettings::default()
+    /// };
+    ///
+    /// let banana = FruitDimensions::new("Banana", 6.0, 3.5, 2.5); // 52.5 cm³
+    /// let apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5); // 15 cm³
+    /// let grape = FruitDimensions::new("Grape", 2.0, 1.5, 1.5); // 4.5 cm³
+    /// assert_eq!(grading.grade_of(&banana).unwrap().unwrap().name, "Class A");
+    /// assert_eq!(grading.grade_of(&apple).unwrap().unwrap().name, "Class B");
+    /// assert_eq!(grading.grade_of(&grape).unwrap(), None);
+    /// ```
+    pub fn grade_of(&self, fruit: &FruitDimensions) -> Result<Option<&Grade>, String> {
+        let value = self
+            .by
+            .evaluate(fruit)
+            .map_err(|e| format!("can't grade '{}' by {}: {}", fruit.name, self.by, e))?;
+        Ok(self.grades.iter().find(|grade| grade.contains(value)))
+    }
+}
+
+/// How many fruits `grade` put in each grade.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GradeSummary {
+    /// The field the grades were stored in.
+    pub field: String,
+    /// What was graded.
+    pub by: String,
+    /// Each grade, in the configured order, with how many fruits it got.
+    pub grades: Vec<GradeCount>,
+    /// How many fruits fit no grade.
+    pub ungraded: usize,
+    /// How many fruits' grades changed.
+    pub changed: usize,
+}
+
+/// A grade and how many fruits are in it.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct GradeCount {
+    pub grade: String,
+    pub count: usize,
+}
+
+/// Grade every fruit in `catalogue` as `settings` say, storing each grade
+/// in the fruit's `settings.field` (and removing it from fruits without
+/// one). The catalogue isn't saved.
+///
+/// # Error Cases
+/// - No grades are configured
+/// - `settings.field` is one of the fruit's own fields, like `length`
+/// - A fruit can't be graded (see `GradingSettings::grade_of`); nothing is
+///   changed then
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::grading::{grade, Grade, GradingSettings};
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+/// let grading = GradingSettings {
+///     grades: vec![Grade { name: "A".into(), min: Some(30.0), max: None }],
+///     ..GradingSettings::default()
+/// };
+///
+/// let summary = grade(&mut catalogue, &grading).unwrap();
+/// assert_eq!(summary.grades[0].count, 3); // Orange, Banana and Pear
+/// assert_eq!(summary.ungraded, 1); // Apple
+/// assert_eq!(catalogue.get("Pear").unwrap().extra["grade"], "A");
+/// assert!(!catalogue.get("Apple").unwrap().extra.contains_key("grade"));
+/// ```
+pub fn grade(
+    catalogue: &mut Catalogue,
+    settings: &GradingSettings,
+) -> Result<GradeSummary, String> {
+    if settings.grades.is_empty() {
+        return Err(
+            "no grades are configured (add them to the \"grading\" section of the config file)"
+                .to_string(),
+        );
+    }
+    if FRUIT_KEYS.contains(&settings.field.as_str()) || settings.field.trim().is_empty() {
+        return Err(format!(
+            "grades can't be stored in '{}' (choose a field of your own, e.g. \"grade\")",
+            settings.field
+        ));
+    }
+
+    let mut summary = GradeSummary {
+        field: settings.field.clone(),
+        by: settings.by.to_string(),
+        grades: settings
+            .grades
+            .iter()
+            .map(|grade| GradeCount {
+                grade: grade.name.clone(),
+                count: 0,
+            })
+            .collect(),
+        ungraded: 0,
+        changed: 0,
+    };
+    let mut graded = Vec::with_capacity(catalogue.fruits().len());
+    for fruit in catalogue.fruits() {
+        let mut fruit = fruit.clone();
+        let before = fruit.extra.get(&settings.field).cloned();
+        match settings.grade_of(&fruit)? {
+            Some(grade) => {
+                let index = settings
+                    .grades
+                    .iter()
+                    .position(|g| std::ptr::eq(g, grade))
+                    .expect("grade_of returns one of the grades");
+                summary.grades[index].count += 1;
+                fruit
+                    .extra
+                    .insert(settings.field.clone(), Value::String(grade.name.clone()));
+            }
+            None => {
+                summary.ungraded += 1;
+                fruit.extra.remove(&settings.field);
+            }
+        }
+        if fruit.extra.get(&settings.field) != before.as_ref() {
+            summary.changed += 1;
+        }
+        graded.push(fruit);
+    }
+
+    if summary.changed > 0 {
+        catalogue.replace_all(graded);
+    }
+    Ok(summary)
+}
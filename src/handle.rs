@@ -0,0 +1,189 @@
+// ============================================================================
+// handle.rs - A Catalogue Shared Between Threads
+// ============================================================================
+// `Catalogue` itself is a plain value: one owner changes it, then saves it.
+// A multi-threaded program (a server, a TUI with a background worker) wants
+// one catalogue that every thread can use. `CatalogHandle` owns the
+// catalogue behind an `RwLock`, together with the store it came from:
+//
+//     let handle = CatalogHandle::open(FileStore::new("fruits.json"))?;
+//     let volume = handle.read(|c| c.get("Apple").map(|f| f.volume()));
+//     handle.write(|c| c.rename("Pear", "Nashi"))?;
+//
+// Any number of threads can `read` at once; a `write` waits for them and
+// runs alone. Each write is all-or-nothing: the closure works on a copy,
+// which only replaces the catalogue if the closure succeeds. The handle's
+// `Autosave` policy decides when changes are written back to the store.
+// ============================================================================
+
+use crate::catalog::Catalogue;
+use crate::store::CatalogStore;
+use std::error::Error;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// When a `CatalogHandle` saves its catalogue to the store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Autosave {
+    /// Only when `CatalogHandle::save` is called.
+    Never,
+    /// After every successful write (the default).
+    #[default]
+    EveryWrite,
+    /// After every `n` successful writes, so that bursts of small changes
+    /// don't rewrite the store each time. Call `save` before exiting to
+    /// keep the rest.
+    Every(u32),
+}
+
+/// The catalogue and how many writes it has had since it was last saved.
+#[derive(Debug)]
+struct State {
+    catalogue: Catalogue,
+    unsaved: u32,
+}
+
+/// A catalogue that many threads can read and write safely.
+///
+/// Changes are saved to the store, but (unlike `cli::run_command`) not
+/// recorded in its journal.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::handle::CatalogHandle;
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::store::MemoryStore;
+/// use std::thread;
+///
+/// let handle = CatalogHandle::open(MemoryStore::default()).unwrap();
+/// thread::scope(|s| {
+///     for name in ["Kiwi", "Lime", "Plum"] {
+///         let handle = &handle;
+///         s.spawn(move || {
+///             handle.write(|c| c.add(FruitDimensions::new(name, 3.0, 2.0, 2.0))).unwrap();
+///         });
+///     }
+/// });
+///
+/// assert_eq!(handle.read(|c| c.fruits().len()), 3);
+/// assert_eq!(handle.store().fruits().len(), 3); // saved after every write
+/// ```
+#[derive(Debug)]
+pub struct CatalogHandle<S> {
+    store: S,
+    autosave: Autosave,
+    state: RwLock<State>,
+}
+
+impl<S: CatalogStore> CatalogHandle<S> {
+    /// Load the catalogue from `store`.
+    ///
+    /// # Error Cases
+    /// Whatever `store.load()` fails with.
+    pub fn open(store: S) -> Result<Self, Box<dyn Error>> {
+        let catalogue = store.load()?;
+        Ok(CatalogHandle::new(catalogue, store))
+    }
+
+    /// Share `catalogue`, saving it to `store`. The catalogue keeps its
+    /// rules, so configure it (`with_name_policy`, ...) first.
+    pub fn new(catalogue: Catalogue, store: S) -> Self {
+        CatalogHandle {
+            store,
+            autosave: Autosave::default(),
+            state: RwLock::new(State {
+                catalogue,
+                unsaved: 0,
+            }),
+        }
+    }
+
+    /// Choose when changes are saved.
+    pub fn with_autosave(mut self, autosave: Autosave) -> Self {
+        self.autosave = autosave;
+        self
+    }
+
+    /// The store the catalogue is saved to.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Run `f` with the catalogue. Other readers may run at the same time;
+    /// writers wait until `f` returns.
+    pub fn read<T>(&self, f: impl FnOnce(&Catalogue) -> T) -> T {
+        f(&self.read_state().catalogue)
+    }
+
+    /// Change the catalogue with `f`, then save it if the autosave policy
+    /// says so.
+    ///
+    /// `f` works on a copy of the catalogue. If it fails, the copy is
+    /// dropped, so a closure making several changes never leaves only some
+    /// of them behind.
+    ///
+    /// # Error Cases
+    /// - Whatever `f` fails with (e.g. a `CatalogError`)
+    /// - The autosave failed; the change is kept, and saved by the next
+    ///   successful save
+    pub fn write<T, E: Into<Box<dyn Error>>>(
+        &self,
+        f: impl FnOnce(&mut Catalogue) -> Result<T, E>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut state = self.write_state();
+        let mut draft = state.catalogue.clone();
+        let value = f(&mut draft).map_err(Into::into)?;
+        state.catalogue.commit(draft);
+        state.unsaved += 1;
+
+        let due = match self.autosave {
+            Autosave::Never => false,
+            Autosave::EveryWrite => true,
+            Autosave::Every(n) => state.unsaved >= n,
+        };
+        if due {
+            self.save_state(&mut state)?;
+        }
+        Ok(value)
+    }
+
+    /// Save the catalogue now, if it has changed since it was last saved.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let mut state = self.write_state();
+        if state.unsaved > 0 {
+            self.save_state(&mut state)?;
+        }
+        Ok(())
+    }
+
+    /// Whether there are writes that haven't been saved yet.
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.read_state().unsaved > 0
+    }
+
+    /// Give back the catalogue and the store, e.g. to save one last time.
+    pub fn into_parts(self) -> (Catalogue, S) {
+        let state = self.state.into_inner().unwrap_or_else(|e| e.into_inner());
+        (state.catalogue, self.store)
+    }
+
+    fn save_state(&self, state: &mut State) -> Result<(), Box<dyn Error>> {
+        self.store.save(&mut state.catalogue)?;
+        // There is no journal to record the events in, so don't let them
+        // pile up
+        state.catalogue.take_changes();
+        state.unsaved = 0;
+        Ok(())
+    }
+
+    // A thread can only panic while holding the lock inside `f` (working
+    // on a draft) or inside `save`, and neither leaves `State` half
+    // changed, so a poisoned lock is still safe to use.
+
+    fn read_state(&self) -> RwLockReadGuard<'_, State> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write_state(&self) -> RwLockWriteGuard<'_, State> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,245 @@
+// ============================================================================
+// history.rs - Reconstructing a Fruit's Past from the Journal
+// ============================================================================
+// The journal (see journal.rs) is a flat list of changes to *all* fruits.
+// This module turns it into the history of *one* fruit: every version it
+// has had, from the first time the journal saw it until now (or until it was
+// removed).
+//
+// A fruit is followed through renames: once "Pear" is renamed to "Nashi",
+// later changes to "Nashi" belong to the same history. In ID mode fruits are
+// followed by ID instead, so two same-named entries are never mixed up.
+//
+// Replaying the journal up to a chosen moment also gives the state of the
+// whole catalogue at that time (`state_at`), which is how restores work.
+// ============================================================================
+
+use crate::catalog::FruitKey;
+use crate::events::CatalogEvent;
+use crate::journal::JournalEntry;
+use crate::merge::record_key;
+use crate::models::FruitDimensions;
+use crate::names::names_equal;
+use crate::timestamp::Timestamp;
+use serde::Serialize;
+
+/// One version of a record.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Revision<T> {
+    /// When this version came into being.
+    pub timestamp: Timestamp,
+    /// Who made the change, or `None` for a version taken from a snapshot.
+    pub actor: Option<String>,
+    /// The record as of this revision, or `None` if it was removed.
+    pub value: Option<T>,
+}
+
+/// The successive versions of one fruit, while we follow it through the
+/// journal.
+struct Lineage {
+    /// The fruit's current identity (`merge::record_key`), or `None` once it
+    /// has been removed and no later change can refer to it.
+    key: Option<String>,
+    revisions: Vec<Revision<FruitDimensions>>,
+}
+
+impl Lineage {
+    fn current(&self) -> Option<&FruitDimensions> {
+        self.revisions.last().and_then(|r| r.value.as_ref())
+    }
+}
+
+/// Find the live lineage for a fruit, starting one if the journal never
+/// mentioned it before (which happens when the journal was started after
+/// the fruit was created).
+fn lineage_for<'l>(lineages: &'l mut Vec<Lineage>, key: &str) -> &'l mut Lineage {
+    match lineages.iter().position(|l| l.key.as_deref() == Some(key)) {
+        Some(index) => &mut lineages[index],
+        None => {
+            lineages.push(Lineage {
+                key: Some(key.to_string()),
+                revisions: Vec::new(),
+            });
+            lineages.last_mut().unwrap()
+        }
+    }
+}
+
+/// Every revision of the fruit identified by `key`, oldest first.
+///
+/// A name matches every fruit that has *ever* had that name, so the history
+/// of "Nashi" includes the time it was called "Pear", and a fruit that was
+/// removed and later re-added under the same name shows both lives. An ID
+/// matches only the fruit with that ID.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::history::fruit_history;
+/// use fruitdata::journal::{ChangeRecord, JournalEntry};
+/// use fruitdata::timestamp::Timestamp;
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+/// let mut entries = vec![JournalEntry::Snapshot {
+///     timestamp: Timestamp(0),
+///     fruits: catalogue.fruits().to_vec(),
+/// }];
+/// catalogue.rename("Pear", "Nashi").unwrap();
+/// catalogue.remove("Nashi").unwrap();
+/// for event in catalogue.take_changes() {
+///     let actor = "alice".to_string();
+///     entries.push(JournalEntry::Change(Box::new(ChangeRecord { timestamp: Timestamp(60), actor, event })));
+/// }
+///
+/// let history = fruit_history(&entries, "Pear");
+/// assert_eq!(history.len(), 3); // as found, renamed, removed
+/// assert_eq!(history[1].value.as_ref().unwrap().name, "Nashi");
+/// assert_eq!(history[2].value, None);
+/// ```
+pub fn fruit_history<'a>(
+    entries: &[JournalEntry],
+    key: impl Into<FruitKey<'a>>,
+) -> Vec<Revision<FruitDimensions>> {
+    let mut lineages: Vec<Lineage> = Vec::new();
+
+    for entry in entries {
+        match entry {
+            // A snapshot states the whole catalogue: fruits that changed are
+            // new revisions, and fruits missing from it have been removed
+            JournalEntry::Snapshot { timestamp, fruits } => {
+                let keys: Vec<String> = fruits.iter().map(record_key).collect();
+                for lineage in lineages.iter_mut() {
+                    if lineage.key.as_ref().is_some_and(|k| !keys.contains(k)) {
+                        lineage.key = None;
+                        lineage.revisions.push(Revision {
+                            timestamp: *timestamp,
+                            actor: None,
+                            value: None,
+                        });
+                    }
+                }
+                for (fruit, key) in fruits.iter().zip(&keys) {
+                    let lineage = lineage_for(&mut lineages, key);
+                    if lineage.current() != Some(fruit) {
+                        lineage.revisions.push(Revision {
+                            timestamp: *timestamp,
+                            actor: None,
+                            value: Some(fruit.clone()),
+                        });
+                    }
+                }
+            }
+
+            JournalEntry::Change(record) => {
+                let revision = |value: Option<&FruitDimensions>| Revision {
+                    timestamp: record.timestamp,
+                    actor: Some(record.actor.clone()),
+                    value: value.cloned(),
+                };
+                match &record.event {
+                    CatalogEvent::Added { fruit } => lineages.push(Lineage {
+                        key: Some(record_key(fruit)),
+                        revisions: vec![revision(Some(fruit))],
+                    }),
+                    CatalogEvent::Removed { fruit } => {
+                        let lineage = lineage_for(&mut lineages, &record_key(fruit));
+                        lineage.key = None;
+                        lineage.revisions.push(revision(None));
+                    }
+                    CatalogEvent::Updated { before, after } => {
+                        let lineage = lineage_for(&mut lineages, &record_key(before));
+                        lineage.key = Some(record_key(after));
+                        lineage.revisions.push(revision(Some(after)));
+                    }
+                }
+            }
+        }
+    }
+
+    // Keep the lineages that match the key at any point in their life
+    let key = key.into();
+    let matches = |fruit: &FruitDimensions| match key {
+        FruitKey::Name(name) => names_equal(&fruit.name, name.trim()),
+        FruitKey::Id(id) => fruit.id == Some(id),
+    };
+    let mut revisions: Vec<Revision<FruitDimensions>> = lineages
+        .into_iter()
+        .filter(|l| {
+            l.revisions
+                .iter()
+                .any(|r| r.value.as_ref().is_some_and(matches))
+        })
+        .flat_map(|l| l.revisions)
+        .collect();
+
+    // A stable sort keeps each lineage's own revisions in journal order
+    revisions.sort_by_key(|r| r.timestamp);
+    revisions
+}
+
+/// The whole catalogue as it was at time `at`, rebuilt from the journal.
+///
+/// Starts from the latest snapshot taken at or before `at` and replays every
+/// later change made at or before `at`. Returns `None` if the journal has no
+/// snapshot that early, i.e. it knows nothing about the catalogue then.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::history::state_at;
+/// use fruitdata::journal::{ChangeRecord, JournalEntry};
+/// use fruitdata::timestamp::Timestamp;
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+/// let mut entries = vec![JournalEntry::Snapshot {
+///     timestamp: Timestamp(100),
+///     fruits: catalogue.fruits().to_vec(),
+/// }];
+/// catalogue.remove("Pear").unwrap();
+/// for event in catalogue.take_changes() {
+///     let actor = "alice".to_string();
+///     entries.push(JournalEntry::Change(Box::new(ChangeRecord { timestamp: Timestamp(200), actor, event })));
+/// }
+///
+/// assert_eq!(state_at(&entries, Timestamp(150)).unwrap().len(), 4); // Pear still there
+/// assert_eq!(state_at(&entries, Timestamp(250)).unwrap().len(), 3);
+/// assert_eq!(state_at(&entries, Timestamp(50)), None); // before the journal began
+/// ```
+pub fn state_at(entries: &[JournalEntry], at: Timestamp) -> Option<Vec<FruitDimensions>> {
+    let start = entries
+        .iter()
+        .rposition(|e| matches!(e, JournalEntry::Snapshot { timestamp, .. } if *timestamp <= at))?;
+
+    let mut fruits = match &entries[start] {
+        JournalEntry::Snapshot { fruits, .. } => fruits.clone(),
+        JournalEntry::Change(_) => unreachable!("`start` is the position of a snapshot"),
+    };
+
+    for entry in &entries[start + 1..] {
+        let JournalEntry::Change(record) = entry else {
+            continue;
+        };
+        if record.timestamp > at {
+            break;
+        }
+        let position = |fruits: &[FruitDimensions], fruit: &FruitDimensions| {
+            let key = record_key(fruit);
+            fruits.iter().position(|f| record_key(f) == key)
+        };
+        match &record.event {
+            CatalogEvent::Added { fruit } => fruits.push(fruit.clone()),
+            CatalogEvent::Removed { fruit } => {
+                if let Some(index) = position(&fruits, fruit) {
+                    fruits.remove(index);
+                }
+            }
+            CatalogEvent::Updated { before, after } => {
+                if let Some(index) = position(&fruits, before) {
+                    fruits[index] = after.clone();
+                }
+            }
+        }
+    }
+
+    Some(fruits)
+}
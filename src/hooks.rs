@@ -0,0 +1,188 @@
+// ============================================================================
+// hooks.rs - Site Rules Run on Added and Imported Fruits
+// ============================================================================
+// Every site has rules of its own that don't belong in fruitdata itself:
+// "nothing we stock is longer than 30 cm", "the supplier's export is in
+// millimetres". Rather than a flag (and a rebuild) for each, the config
+// file can register hooks, written as jq-style queries (see query.rs) over
+// a fruit as JSON, that run on two events:
+//
+// - pre_add: before a fruit is added, by `add` or by `import`, and before
+//   its dimensions change, by `update` or `measure add` (see
+//   `Catalogue::with_hooks`). The fruit must pass every `require`
+//   condition, or it is rejected with the hook's `message`.
+// - post_import: once an imported record has been read as a fruit (and
+//   its name cleaned up, see sanitize.rs). Each hook whose `where`
+//   condition the fruit passes (every fruit, without one) sets the fields
+//   in `set` to the first value their query produces.
+//
+// Example `fruitdata.config.json`:
+//
+//     {
+//       "hooks": {
+//         "pre_add": [
+//           { "require": ".length <= 30", "message": "nothing we stock is longer than 30 cm" }
+//         ],
+//         "post_import": [
+//           { "where": ".length > 100",
+//             "set": { "length": ".length / 10", "width": ".width / 10", "height": ".height / 10" } }
+//         ]
+//       }
+//     }
+//
+// Post-import hooks run in order, each seeing the fruit as the hooks
+// before it left it; the queries within one `set` all see the fruit as it
+// was before that hook. Pre-add hooks run after them, so an imported fruit
+// is checked in its final shape. An import with any record that fails is
+// rejected as a whole, and the catalogue is left as it was.
+//
+// Key concept: the rules are data, not code. They use the same small query
+// language as `fruitdata query`, so they can be tried out there first, and
+// they can't touch anything but the fruit they are given.
+// ============================================================================
+
+use crate::error::CatalogError;
+use crate::models::FruitDimensions;
+use crate::query::Query;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The hooks registered in the config file (see the module comment). None
+/// by default.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::hooks::HookSettings;
+/// use fruitdata::models::FruitDimensions;
+///
+/// let hooks: HookSettings = serde_json::from_str(r#"{
+///     "pre_add": [{ "require": ".length <= 30", "message": "too long to stock" }],
+///     "post_import": [{ "where": ".length > 100", "set": { "length": ".length / 10" } }]
+/// }"#).unwrap();
+///
+/// let imported = hooks.transform(&FruitDimensions::new("Melon", 250.0, 20.0, 20.0)).unwrap();
+/// assert_eq!(imported.length.value(), 25.0);
+/// assert!(hooks.check(&imported).is_ok());
+///
+/// let error = hooks.check(&FruitDimensions::new("Jackfruit", 90.0, 40.0, 40.0)).unwrap_err();
+/// assert_eq!(error.to_string(), "Fruit 'Jackfruit' was rejected: too long to stock.");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct HookSettings {
+    /// Conditions every fruit must pass before it is added or its
+    /// dimensions change.
+    pub pre_add: Vec<PreAddHook>,
+    /// Changes made to every imported fruit, in order.
+    pub post_import: Vec<PostImportHook>,
+}
+
+/// A condition a fruit must pass to be added.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PreAddHook {
+    /// The condition, e.g. `.length <= 30`.
+    pub require: Query,
+    /// Why a fruit that fails is rejected; without one, the message names
+    /// the condition.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Fields to change on the imported fruits that pass a condition.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PostImportHook {
+    /// Which fruits the hook applies to; every fruit, without one.
+    #[serde(rename = "where", default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Query>,
+    /// Each field to set, and the query that computes its new value.
+    pub set: BTreeMap<String, Query>,
+}
+
+impl HookSettings {
+    /// Run the pre-add hooks on `fruit`.
+    ///
+    /// # Error Cases
+    /// - `CatalogError::HookRejected` - The fruit failed a hook's condition,
+    ///   or the condition couldn't be run on it
+    pub fn check(&self, fruit: &FruitDimensions) -> Result<(), CatalogError> {
+        if self.pre_add.is_empty() {
+            return Ok(());
+        }
+        let value = serde_json::to_value(fruit).map_err(|e| rejected(fruit, e.to_string()))?;
+        for hook in &self.pre_add {
+            match hook.require.matches(&value) {
+                Ok(true) => {}
+                Ok(false) => {
+                    let reason = match &hook.message {
+                        Some(message) => message.clone(),
+                        None => format!("it doesn't pass '{}'", hook.require),
+                    };
+                    return Err(rejected(fruit, reason));
+                }
+                Err(e) => return Err(rejected(fruit, format!("'{}': {}", hook.require, e))),
+            }
+        }
+        Ok(())
+    }
+
+    /// `fruit` after the post-import hooks.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::hooks::HookSettings;
+    /// use fruitdata::models::FruitDimensions;
+    ///
+    /// let hooks: HookSettings = serde_json::from_str(r#"{
+    ///     "post_import": [{ "set": { "height": ".width", "width": ".height" } }]
+    /// }"#).unwrap();
+    /// let fruit = hooks.transform(&FruitDimensions::new("Pear", 6.0, 3.5, 2.5)).unwrap();
+    /// assert_eq!((fruit.width.value(), fruit.height.value()), (2.5, 3.5));
+    ///
+    /// let broken: HookSettings = serde_json::from_str(r#"{
+    ///     "post_import": [{ "set": { "length": ".name" } }]
+    /// }"#).unwrap();
+    /// assert!(broken.transform(&fruit).is_err());
+    /// ```
+    ///
+    /// # Error Cases
+    /// A hook's query couldn't be run on the fruit, or the fields it set
+    /// no longer make a fruit (say, a name for a length). The message
+    /// names the fruit.
+    pub fn transform(&self, fruit: &FruitDimensions) -> Result<FruitDimensions, String> {
+        if self.post_import.is_empty() {
+            return Ok(fruit.clone());
+        }
+        let problem = |e: String| format!("{}: {}", fruit.name, e);
+        let mut value = serde_json::to_value(fruit).map_err(|e| problem(e.to_string()))?;
+        for hook in &self.post_import {
+            if let Some(condition) = &hook.condition {
+                let applies = condition
+                    .matches(&value)
+                    .map_err(|e| problem(format!("'{}': {}", condition, e)))?;
+                if !applies {
+                    continue;
+                }
+            }
+            let mut changed = value.clone();
+            for (field, query) in &hook.set {
+                let results = query
+                    .run(&value)
+                    .map_err(|e| problem(format!("'{}': {}", query, e)))?;
+                if let (Some(result), Some(fields)) =
+                    (results.into_iter().next(), changed.as_object_mut())
+                {
+                    fields.insert(field.clone(), result);
+                }
+            }
+            value = changed;
+        }
+        serde_json::from_value(value).map_err(|e| problem(e.to_string()))
+    }
+}
+
+fn rejected(fruit: &FruitDimensions, reason: String) -> CatalogError {
+    CatalogError::HookRejected {
+        name: fruit.name.clone(),
+        reason,
+    }
+}
@@ -0,0 +1,687 @@
+// ============================================================================
+// import.rs - Bringing Arbitrary JSON Data into a Catalogue
+// ============================================================================
+// Data from other sources rarely uses fruitdata's field names. A dataset might
+// look like this:
+//
+//     [ {"fruitName": "Apple", "dims": {"l": 4.0, "w": 2.5, "h": 1.5}}, ... ]
+//
+// A `FieldMapping` says where each fruit field is found in such a record,
+// using dotted paths (`name=fruitName`, `length=dims.l`). When no mapping is
+// given for a field, `FieldMapping::infer` guesses one from the record's keys
+// ("fruitName" ends in "name", "lengthCm" starts with "length", ...).
+//
+// Each record is then turned into a `FruitDimensions`. Numbers written as
+// strings ("4.5") are accepted, since exported spreadsheets often do that.
+//
+// Spreadsheet exports (CSV, read into flat records by csv.rs) are mapped by
+// column name instead, with a `ColumnMapping` such as
+// `name=FruitName,length=L_mm:mm,width=W_mm:mm,height=H_mm:mm`. Any mapping
+// of a dimension may name the unit it is given in (`cm`, `mm` or `in`);
+// the value is converted to centimetres as it is read.
+//
+// `import_into` adds those fruits to a catalogue. The `ImportMode` says how
+// they are combined with the fruits already there (append, merge by name, or
+// replace them all), and `OnDuplicate` what happens to a record whose name
+// is taken (fail, skip it, or overwrite the fruit's dimensions).
+// ============================================================================
+
+use crate::calibration::LengthUnit;
+use crate::catalog::{Catalogue, FruitKey};
+use crate::compat::canonical_name;
+use crate::error::CatalogError;
+use crate::models::{FruitDimensions, FruitPatch};
+use crate::progress::Progress;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// A location inside a JSON value: object keys and array indexes separated
+/// by dots, e.g. `dims.l` or `sizes.0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath(Vec<String>);
+
+impl JsonPath {
+    /// The path to a single key, taken as it is (a CSV column name may
+    /// itself contain dots).
+    pub fn key(key: &str) -> JsonPath {
+        JsonPath(vec![key.trim().to_string()])
+    }
+
+    /// The keys and indexes along the path, outermost first.
+    pub fn segments(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Follow the path into `value`, returning `None` if it leads nowhere.
+    pub fn get<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        self.0
+            .iter()
+            .try_fold(value, |current, segment| match current {
+                Value::Object(fields) => fields.get(segment),
+                Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                _ => None,
+            })
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let segments: Vec<String> = s.trim().split('.').map(str::to_string).collect();
+        if segments.iter().any(|segment| segment.is_empty()) {
+            return Err(format!("invalid path '{}' (expected e.g. dims.length)", s));
+        }
+        Ok(JsonPath(segments))
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join("."))
+    }
+}
+
+/// The fruit fields that can be mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappedField {
+    Name,
+    Length,
+    Width,
+    Height,
+    Id,
+}
+
+impl MappedField {
+    const ALL: [MappedField; 5] = [
+        MappedField::Name,
+        MappedField::Length,
+        MappedField::Width,
+        MappedField::Height,
+        MappedField::Id,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            MappedField::Name => "name",
+            MappedField::Length => "length",
+            MappedField::Width => "width",
+            MappedField::Height => "height",
+            MappedField::Id => "id",
+        }
+    }
+}
+
+impl FromStr for MappedField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MappedField::ALL
+            .into_iter()
+            .find(|field| field.as_str() == s.trim().to_ascii_lowercase())
+            .ok_or_else(|| {
+                format!(
+                    "unknown field '{}' (expected name, length, width, height or id)",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for MappedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// One `--map field=path` argument, optionally with the unit a dimension is
+/// given in: `length=dims.l:mm`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMap {
+    pub field: MappedField,
+    pub path: JsonPath,
+    /// The unit of the value (centimetres if not given).
+    pub unit: Option<LengthUnit>,
+}
+
+impl FieldMap {
+    /// Split `field=source[:unit]`, leaving the source to `parse_source`.
+    fn parse_with(
+        s: &str,
+        parse_source: impl Fn(&str) -> Result<JsonPath, String>,
+    ) -> Result<FieldMap, String> {
+        let (field, source) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid mapping '{}' (expected field=path)", s))?;
+        let field: MappedField = field.parse()?;
+        let (source, unit) = match source.rsplit_once(':') {
+            Some((source, unit)) => (source, Some(unit.parse::<LengthUnit>()?)),
+            None => (source, None),
+        };
+        if unit.is_some() && matches!(field, MappedField::Name | MappedField::Id) {
+            return Err(format!(
+                "invalid mapping '{}' (only length, width and height have units)",
+                s
+            ));
+        }
+        Ok(FieldMap {
+            field,
+            path: parse_source(source)?,
+            unit,
+        })
+    }
+}
+
+impl FromStr for FieldMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FieldMap::parse_with(s, str::parse)
+    }
+}
+
+/// Where each fruit field is found in a spreadsheet: `field=column[:unit]`
+/// pairs separated by commas or line breaks, as given to `--columns` or
+/// kept in a mapping file. Column names are taken as they are (no dotted
+/// paths); blank lines and `#` comments are ignored.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::csv::records;
+/// use fruitdata::import::{ColumnMapping, FieldMapping};
+///
+/// let records = records("FruitName,L (mm),W (mm),H (in)\nApple,40,25,0.6\n").unwrap();
+/// let columns: ColumnMapping = "
+///     name=FruitName  # the 2024 harvest sheet
+///     length=L (mm):mm, width=W (mm):mm, height=H (in):in
+/// "
+/// .parse()
+/// .unwrap();
+///
+/// let fruits = FieldMapping::default().with(&columns.0).apply(&records).unwrap();
+/// assert_eq!(fruits[0].length, 4.0);
+/// assert!((fruits[0].height.value() - 1.524).abs() < 1e-6);
+///
+/// assert!("name=FruitName:mm".parse::<ColumnMapping>().is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnMapping(pub Vec<FieldMap>);
+
+impl FromStr for ColumnMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.lines()
+            .map(|line| line.split_once('#').map_or(line, |(before, _)| before))
+            .flat_map(|line| line.split(','))
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| FieldMap::parse_with(entry.trim(), |column| Ok(JsonPath::key(column))))
+            .collect::<Result<_, _>>()
+            .map(ColumnMapping)
+    }
+}
+
+/// Where each fruit field is found in the records being imported.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::import::{FieldMap, FieldMapping};
+/// use serde_json::json;
+///
+/// let records = vec![json!({"fruitName": "Apple", "dims": {"l": 4.0, "w": 2.5, "h": "1.5"}})];
+///
+/// let maps: Vec<FieldMap> = ["length=dims.l", "width=dims.w", "height=dims.h"]
+///     .iter()
+///     .map(|m| m.parse().unwrap())
+///     .collect();
+/// let mapping = FieldMapping::infer(&records[0]).with(&maps); // name is inferred
+///
+/// let fruits = mapping.apply(&records).unwrap();
+/// assert_eq!(fruits[0].name, "Apple");
+/// assert_eq!(fruits[0].height, 1.5);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldMapping {
+    pub name: Option<JsonPath>,
+    pub length: Option<JsonPath>,
+    pub width: Option<JsonPath>,
+    pub height: Option<JsonPath>,
+    /// Optional: records without an ID are imported without one.
+    pub id: Option<JsonPath>,
+    /// The units the dimensions are given in (centimetres by default).
+    pub length_unit: LengthUnit,
+    pub width_unit: LengthUnit,
+    pub height_unit: LengthUnit,
+}
+
+impl FieldMapping {
+    /// Guess a mapping from the keys of a sample record.
+    ///
+    /// Every leaf of the record is considered. A key matches a field if,
+    /// ignoring case, `_` and `-`, it equals the field's name, or starts or
+    /// ends with it (`lengthCm`, `fruit_name`); the old names of compat.rs
+    /// (`len`, `w`, `h`) count as exact matches. Exact matches win, then
+    /// shallower paths. The ID is only ever matched exactly (so that `valid`
+    /// isn't taken for one). Fields nothing matches stay unmapped.
+    pub fn infer(sample: &Value) -> FieldMapping {
+        let mut leaves = Vec::new();
+        collect_leaves(sample, &mut Vec::new(), &mut leaves);
+
+        let guess = |field: MappedField| -> Option<JsonPath> {
+            let target = field.as_str();
+            leaves
+                .iter()
+                .filter_map(|segments| {
+                    let last = segments.last()?;
+                    let key = normalise(last);
+                    let rank = if key == target || canonical_name(last) == Some(target) {
+                        0
+                    } else if field != MappedField::Id
+                        && (key.starts_with(target) || key.ends_with(target))
+                    {
+                        1
+                    } else {
+                        return None;
+                    };
+                    Some(((rank, segments.len()), segments))
+                })
+                .min_by_key(|(rank, _)| *rank)
+                .map(|(_, segments)| JsonPath(segments.clone()))
+        };
+
+        FieldMapping {
+            name: guess(MappedField::Name),
+            length: guess(MappedField::Length),
+            width: guess(MappedField::Width),
+            height: guess(MappedField::Height),
+            id: guess(MappedField::Id),
+            ..FieldMapping::default()
+        }
+    }
+
+    /// Override some fields with explicit `field=path` mappings (and the
+    /// units they give).
+    pub fn with(mut self, maps: &[FieldMap]) -> FieldMapping {
+        for map in maps {
+            let (slot, unit) = match map.field {
+                MappedField::Name => (&mut self.name, None),
+                MappedField::Length => (&mut self.length, Some(&mut self.length_unit)),
+                MappedField::Width => (&mut self.width, Some(&mut self.width_unit)),
+                MappedField::Height => (&mut self.height, Some(&mut self.height_unit)),
+                MappedField::Id => (&mut self.id, None),
+            };
+            *slot = Some(map.path.clone());
+            if let Some(unit) = unit {
+                *unit = map.unit.unwrap_or_default();
+            }
+        }
+        self
+    }
+
+    /// The mapping as `field=path` pairs (`field=path:unit` for a dimension
+    /// not in centimetres), e.g. for showing the user what was inferred.
+    /// Unmapped fields are left out.
+    pub fn describe(&self) -> Vec<String> {
+        MappedField::ALL
+            .into_iter()
+            .filter_map(|field| {
+                let path = self.path_for(field)?;
+                Some(match self.unit_for(field) {
+                    LengthUnit::Cm => format!("{}={}", field, path),
+                    unit => format!("{}={}:{}", field, path, unit),
+                })
+            })
+            .collect()
+    }
+
+    /// The unit `field` is given in (centimetres for the name and ID).
+    fn unit_for(&self, field: MappedField) -> LengthUnit {
+        match field {
+            MappedField::Length => self.length_unit,
+            MappedField::Width => self.width_unit,
+            MappedField::Height => self.height_unit,
+            MappedField::Name | MappedField::Id => LengthUnit::Cm,
+        }
+    }
+
+    fn path_for(&self, field: MappedField) -> Option<&JsonPath> {
+        match field {
+            MappedField::Name => self.name.as_ref(),
+            MappedField::Length => self.length.as_ref(),
+            MappedField::Width => self.width.as_ref(),
+            MappedField::Height => self.height.as_ref(),
+            MappedField::Id => self.id.as_ref(),
+        }
+    }
+
+    /// Turn every record into a fruit.
+    ///
+    /// The fruits are not validated here (that's `Catalogue::add`'s job);
+    /// this only checks that each value exists and has a usable type.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<FruitDimensions>)` - One fruit per record, in order
+    /// - `Err(Vec<String>)` - One message per problem, e.g.
+    ///   `record 3: width (dims.w) is missing`
+    pub fn apply(&self, records: &[Value]) -> Result<Vec<FruitDimensions>, Vec<String>> {
+        // A field without a path can't be found in any record
+        let unmapped: Vec<String> = MappedField::ALL
+            .into_iter()
+            .filter(|field| *field != MappedField::Id && self.path_for(*field).is_none())
+            .map(|field| format!("{} isn't mapped (use --map {}=PATH)", field, field))
+            .collect();
+        if !unmapped.is_empty() {
+            return Err(unmapped);
+        }
+
+        let mut fruits = Vec::new();
+        let mut problems = Vec::new();
+
+        for (index, record) in records.iter().enumerate() {
+            match self.map_record(record) {
+                Ok(fruit) => fruits.push(fruit),
+                Err(problem) => problems.extend(
+                    problem
+                        .into_iter()
+                        .map(|p| format!("record {}: {}", index + 1, p)),
+                ),
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(fruits)
+        } else {
+            Err(problems)
+        }
+    }
+
+    fn map_record(&self, record: &Value) -> Result<FruitDimensions, Vec<String>> {
+        let mut problems = Vec::new();
+
+        let mut lookup = |field: MappedField| -> Option<&Value> {
+            // Only the optional ID can be unmapped by now
+            let path = self.path_for(field)?;
+            let value = path.get(record).filter(|v| !v.is_null());
+            if value.is_none() && field != MappedField::Id {
+                problems.push(format!("{} ({}) is missing", field, path));
+            }
+            value
+        };
+
+        let name = lookup(MappedField::Name).cloned();
+        let dimensions = [MappedField::Length, MappedField::Width, MappedField::Height]
+            .map(|field| (field, lookup(field).cloned()));
+        let id = lookup(MappedField::Id).cloned();
+
+        let name = match name {
+            Some(Value::String(s)) => Some(s),
+            Some(Value::Number(n)) => Some(n.to_string()),
+            Some(other) => {
+                problems.push(format!("name should be text, found {}", other));
+                None
+            }
+            None => None,
+        };
+        let [length, width, height] = dimensions.map(|(field, value)| {
+            let value = value?;
+            let number = match &value {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().parse::<f64>().ok(),
+                _ => None,
+            };
+            if number.is_none() {
+                problems.push(format!("{} should be a number, found {}", field, value));
+            }
+            number.map(|n| (n * self.unit_for(field).in_cm()) as f32)
+        });
+        let id = match id {
+            None => None,
+            Some(value) => {
+                let number = match &value {
+                    Value::Number(n) => n.as_u64(),
+                    Value::String(s) => s.trim().parse::<u64>().ok(),
+                    _ => None,
+                };
+                if number.is_none() {
+                    problems.push(format!(
+                        "id should be a non-negative integer, found {}",
+                        value
+                    ));
+                }
+                number
+            }
+        };
+
+        match (name, length, width, height) {
+            (Some(name), Some(length), Some(width), Some(height)) if problems.is_empty() => {
+                let mut fruit = FruitDimensions::new(name, length, width, height);
+                fruit.id = id;
+                Ok(fruit)
+            }
+            _ => Err(problems),
+        }
+    }
+}
+
+/// Lower-case a key and drop `_` and `-`, so `Fruit_Name` matches `name`.
+fn normalise(key: &str) -> String {
+    key.chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Record the path of every non-container value inside `value`.
+fn collect_leaves(value: &Value, path: &mut Vec<String>, leaves: &mut Vec<Vec<String>>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, child) in fields {
+                path.push(key.clone());
+                collect_leaves(child, path, leaves);
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (index, child) in items.iter().enumerate() {
+                path.push(index.to_string());
+                collect_leaves(child, path, leaves);
+                path.pop();
+            }
+        }
+        _ => leaves.push(path.clone()),
+    }
+}
+
+// ============================================================================
+// IMPORT MODES
+// ============================================================================
+
+/// How `import_into` combines the imported fruits with the catalogue's.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Add every record as a new fruit; a record whose name is taken (in
+    /// name mode) is a duplicate.
+    #[default]
+    Append,
+    /// Match records to existing fruits by name (case-insensitive, in ID
+    /// mode too); a matching record is a duplicate, the others are added.
+    Merge,
+    /// Remove every fruit first, so that only duplicates within the
+    /// records themselves are possible.
+    Replace,
+}
+
+impl ImportMode {
+    /// What to do with duplicates unless told otherwise: overwrite them
+    /// when merging, fail otherwise.
+    pub fn default_on_duplicate(self) -> OnDuplicate {
+        match self {
+            ImportMode::Merge => OnDuplicate::Overwrite,
+            ImportMode::Append | ImportMode::Replace => OnDuplicate::Error,
+        }
+    }
+}
+
+impl FromStr for ImportMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "append" => Ok(ImportMode::Append),
+            "merge" => Ok(ImportMode::Merge),
+            "replace" => Ok(ImportMode::Replace),
+            other => Err(format!(
+                "unknown import mode '{}' (expected append, merge or replace)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ImportMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportMode::Append => write!(f, "append"),
+            ImportMode::Merge => write!(f, "merge"),
+            ImportMode::Replace => write!(f, "replace"),
+        }
+    }
+}
+
+/// What `import_into` does with a record whose name is already taken.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnDuplicate {
+    /// Reject the whole import.
+    Error,
+    /// Leave the existing fruit alone and carry on.
+    Skip,
+    /// Give the existing fruit the record's dimensions (keeping its name,
+    /// ID and other fields).
+    Overwrite,
+}
+
+impl FromStr for OnDuplicate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "error" => Ok(OnDuplicate::Error),
+            "skip" => Ok(OnDuplicate::Skip),
+            "overwrite" => Ok(OnDuplicate::Overwrite),
+            other => Err(format!(
+                "unknown duplicate policy '{}' (expected error, skip or overwrite)",
+                other
+            )),
+        }
+    }
+}
+
+/// What `import_into` did with each record.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ImportSummary {
+    /// The fruits added, as stored (trimmed, with IDs in ID mode).
+    pub inserted: Vec<FruitDimensions>,
+    /// The existing fruits overwritten, as they are now.
+    pub updated: Vec<FruitDimensions>,
+    /// The names of the records skipped as duplicates.
+    pub skipped: Vec<String>,
+    /// How many fruits `ImportMode::Replace` removed.
+    pub removed: usize,
+}
+
+/// Add `fruits` (e.g. from `FieldMapping::apply`) to `catalogue`, combining
+/// them with its fruits as `mode` says and handling duplicates as
+/// `on_duplicate` says. Reports each record to `progress`.
+///
+/// The catalogue validates every fruit as usual. The first record it
+/// rejects ends the import, with the records before it already applied, so
+/// import into a copy (as `fruitdata import` does) to get all or nothing.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::import::{import_into, ImportMode, OnDuplicate};
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::progress::NoProgress;
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+/// let records = vec![
+///     FruitDimensions::new("apple", 4.4, 2.7, 1.6),
+///     FruitDimensions::new("Kiwi", 3.0, 2.0, 2.0),
+/// ];
+/// let summary = import_into(
+///     &mut catalogue,
+///     records,
+///     ImportMode::Merge,
+///     OnDuplicate::Overwrite,
+///     &mut NoProgress,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(summary.inserted[0].name, "Kiwi");
+/// assert_eq!(summary.updated[0].name, "Apple"); // keeps its own spelling
+/// assert_eq!(catalogue.get("Apple").unwrap().length, 4.4);
+/// ```
+///
+/// # Error Cases
+/// - `CatalogError::DuplicateName` - A duplicate, with `OnDuplicate::Error`
+/// - Anything `Catalogue::add` or `Catalogue::apply_patch` rejects
+pub fn import_into(
+    catalogue: &mut Catalogue,
+    fruits: Vec<FruitDimensions>,
+    mode: ImportMode,
+    on_duplicate: OnDuplicate,
+    progress: &mut dyn Progress,
+) -> Result<ImportSummary, CatalogError> {
+    // Replacing builds the new fruits on an emptied copy, then swaps them
+    // in at once, so the change is recorded as what really differs
+    if mode == ImportMode::Replace {
+        let mut fresh = catalogue.clone();
+        fresh.replace_all(Vec::new());
+        let mut summary = import_into(
+            &mut fresh,
+            fruits,
+            ImportMode::Append,
+            on_duplicate,
+            progress,
+        )?;
+        summary.removed = catalogue.fruits().len();
+        catalogue.replace_all(fresh.into_fruits());
+        return Ok(summary);
+    }
+
+    let mut summary = ImportSummary::default();
+    progress.start("Importing", Some(fruits.len() as u64));
+    let result = fruits.into_iter().try_for_each(|fruit| {
+        let added = if mode == ImportMode::Merge && !catalogue.find_all(&fruit.name).is_empty() {
+            Err(CatalogError::DuplicateName(fruit.name.trim().to_string()))
+        } else {
+            catalogue.add(fruit.clone())
+        };
+        progress.advance(1);
+        match (added, on_duplicate) {
+            (Ok(()), _) => summary.inserted.extend(catalogue.fruits().last().cloned()),
+            (Err(CatalogError::DuplicateName(name)), OnDuplicate::Skip) => {
+                summary.skipped.push(name)
+            }
+            (Err(CatalogError::DuplicateName(name)), OnDuplicate::Overwrite) => {
+                let dimensions = FruitPatch {
+                    length: Some(fruit.length.value()),
+                    width: Some(fruit.width.value()),
+                    height: Some(fruit.height.value()),
+                    ..FruitPatch::default()
+                };
+                catalogue.apply_patch(FruitKey::Name(&name), &dimensions)?;
+                summary.updated.push(catalogue.find(name.as_str())?.clone());
+            }
+            (Err(e), _) => return Err(e),
+        }
+        Ok(())
+    });
+    progress.finish();
+    result.map(|()| summary)
+}
@@ -0,0 +1,124 @@
+// ============================================================================
+// ingest.rs - Readings From Automated Measuring Stations
+// ============================================================================
+// Our caliper rigs measure fruit without anyone typing at a terminal. Each
+// reading is a small JSON message:
+//
+//     {"name": "Apple", "length": 4.4, "width": 2.7, "height": 1.6,
+//      "taken": "2024-06-15T08:30:00Z"}
+//
+// `taken` is optional (the time the message arrived is used instead), and
+// `id` picks one fruit when several share a name. A reading of a known
+// fruit is recorded as a measurement (the newest one also sets its
+// dimensions, as with `fruitdata measure add`); a reading of an unknown
+// fruit adds it, with the reading as its first measurement.
+//
+// Readings are checked like any other change: the dimensions must be
+// positive finite numbers, within the plausible range if one is set, and
+// new names must follow the naming rules. This module only turns messages
+// into catalogue changes; how they arrive (MQTT, see mqtt.rs) is separate.
+// ============================================================================
+
+use crate::catalog::{Catalogue, FruitKey};
+use crate::length::Centimeters;
+use crate::measurements::Measurement;
+use crate::models::{finite, FruitDimensions};
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// One reading published by a measuring station.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct StationReading {
+    name: String,
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(default)]
+    taken: Option<Timestamp>,
+    #[serde(deserialize_with = "finite")]
+    length: Centimeters,
+    #[serde(deserialize_with = "finite")]
+    width: Centimeters,
+    #[serde(deserialize_with = "finite")]
+    height: Centimeters,
+}
+
+/// What a reading did to the catalogue.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Ingested {
+    /// The reading was recorded as a measurement of an existing fruit.
+    Measured { name: String },
+    /// The fruit was new, and was added.
+    Added { name: String },
+}
+
+/// Apply one station message to `catalogue`, using `received` as the time
+/// of a reading that doesn't say when it was taken. The catalogue isn't
+/// saved.
+///
+/// # Error Cases
+/// - The message isn't a JSON reading (a field missing or of the wrong
+///   type, an unknown field, a dimension that isn't a finite number)
+/// - `CatalogError` - The catalogue rejected the change (a non-positive or
+///   implausible dimension, a name against the naming rules, an unknown ID)
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::ingest::{ingest, Ingested};
+/// use fruitdata::timestamp::Timestamp;
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+/// let now = Timestamp(1_718_000_000);
+///
+/// let reading = br#"{"name": "Apple", "length": 4.4, "width": 2.7, "height": 1.6}"#;
+/// assert_eq!(ingest(&mut catalogue, reading, now).unwrap(), Ingested::Measured { name: "Apple".into() });
+/// assert_eq!(catalogue.get("Apple").unwrap().length, 4.4);
+///
+/// let reading = br#"{"name": "Quince", "length": 8, "width": 7, "height": 7, "taken": "2024-06-10"}"#;
+/// assert_eq!(ingest(&mut catalogue, reading, now).unwrap(), Ingested::Added { name: "Quince".into() });
+/// assert_eq!(catalogue.get("Quince").unwrap().measurements.len(), 1);
+///
+/// assert!(ingest(&mut catalogue, br#"{"name": "Pear", "length": -1, "width": 1, "height": 1}"#, now).is_err());
+/// assert!(ingest(&mut catalogue, b"{\"name\": \"Pear\"}", now).is_err());
+/// ```
+pub fn ingest(
+    catalogue: &mut Catalogue,
+    message: &[u8],
+    received: Timestamp,
+) -> Result<Ingested, Box<dyn Error>> {
+    let reading: StationReading = serde_json::from_slice(message)?;
+    let name = reading.name.trim();
+    let measurement = Measurement::new(
+        reading.taken.unwrap_or(received),
+        reading.length,
+        reading.width,
+        reading.height,
+    );
+
+    let key = match reading.id {
+        Some(id) => FruitKey::Id(id),
+        None => FruitKey::Name(name),
+    };
+    if reading.id.is_some() || !catalogue.find_all(name).is_empty() {
+        catalogue.add_measurement(key, measurement)?;
+        let fruit = catalogue.find(key)?;
+        return Ok(Ingested::Measured {
+            name: fruit.name.clone(),
+        });
+    }
+
+    let mut fruit = FruitDimensions::new(
+        name,
+        measurement.length,
+        measurement.width,
+        measurement.height,
+    );
+    fruit.measurements.push(measurement);
+    catalogue.add(fruit)?;
+    Ok(Ingested::Added {
+        name: name.to_string(),
+    })
+}
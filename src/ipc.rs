@@ -0,0 +1,214 @@
+// ============================================================================
+// ipc.rs - JSON-RPC Control Interface over a Unix Socket
+// ============================================================================
+// Editor plugins and local GUIs want to ask the catalogue questions and
+// change it without starting a `fruitdata` process for every keystroke.
+// `fruitdata ipc` listens on a Unix socket and speaks JSON-RPC 2.0, one
+// message per line:
+//
+//     --> {"jsonrpc": "2.0", "id": 1, "method": "get", "params": ["Apple"]}
+//     <-- {"jsonrpc": "2.0", "id": 1, "result": {"status": 0, "report": {...},
+//          "notes": [], "hints": []}}
+//
+// The method is a command's name and the params are its arguments, by name
+// (`{"name": "Kiwi", "length": 3, ...}` for `add`) or as typed after it
+// (`["Kiwi", 3, 2, 2]`). The result holds the command's report as JSON
+// (what `--format json` would print), not text to be scraped. rpc.rs
+// describes the protocol. The `shutdown` method stops the server.
+//
+// Unlike `fruitdata daemon`, nothing is cached: every request loads the
+// catalogue from its store and every change is saved at once, so the file
+// is always up to date for other programs. Several clients can be
+// connected at a time; their requests are run one at a time, in the order
+// they arrive.
+//
+// Windows named pipes need system calls the standard library doesn't
+// offer, so the interface is only built on Unix.
+// ============================================================================
+
+use crate::cli::Context;
+use crate::present::RenderOptions;
+use crate::rpc::{self, CommandResult};
+use crate::store::CatalogStore;
+use serde::Serialize;
+use serde_json::json;
+use std::error::Error;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// How long the server sleeps when no client has anything to say.
+const IDLE: Duration = Duration::from_millis(10);
+
+/// What the server did before it was stopped.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IpcSummary {
+    /// How many clients connected.
+    pub clients: u64,
+    /// How many requests were run.
+    pub requests: u64,
+}
+
+/// Whether the server should go on after a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Next {
+    Continue,
+    Stop,
+}
+
+/// One connected client, and what it has sent that isn't a whole line yet.
+struct Client {
+    stream: UnixStream,
+    pending: Vec<u8>,
+    open: bool,
+}
+
+/// The running server.
+struct Server<'a, 'c> {
+    store: &'a dyn CatalogStore,
+    context: &'c mut Context,
+    output: RenderOptions,
+    summary: IpcSummary,
+}
+
+impl Server<'_, '_> {
+    /// Answer one line: a request or a batch of them.
+    fn handle(&mut self, line: &str) -> (Option<String>, Next) {
+        let mut next = Next::Continue;
+        let reply = rpc::handle(line, |request| {
+            self.summary.requests += 1;
+            if request.method == "shutdown" {
+                next = Next::Stop;
+                return Ok(CommandResult {
+                    report: json!({ "outcome": "stopped" }),
+                    ..CommandResult::default()
+                });
+            }
+            let command = request.command()?;
+            rpc::run(&command, self.store, self.context, &self.output)
+        });
+        (reply, next)
+    }
+
+    /// Read whatever `client` has sent and answer each whole line.
+    fn serve(&mut self, client: &mut Client) -> Result<(bool, Next), Box<dyn Error>> {
+        let mut busy = false;
+        let mut buffer = [0; 4096];
+        loop {
+            match client.stream.read(&mut buffer) {
+                Ok(0) => {
+                    client.open = false;
+                    break;
+                }
+                Ok(read) => {
+                    busy = true;
+                    client.pending.extend_from_slice(&buffer[..read]);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    client.open = false;
+                    break;
+                }
+            }
+        }
+
+        while let Some(end) = client.pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = client.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (reply, next) = self.handle(&line);
+            if let Some(mut reply) = reply {
+                reply.push('\n');
+                // Answers are written whole, however slowly the client reads
+                client.stream.set_nonblocking(false)?;
+                let written = client.stream.write_all(reply.as_bytes());
+                client.stream.set_nonblocking(true)?;
+                // A client that went away doesn't stop the server
+                if written.is_err() {
+                    client.open = false;
+                }
+            }
+            if next == Next::Stop {
+                return Ok((busy, Next::Stop));
+            }
+        }
+        Ok((busy, Next::Continue))
+    }
+}
+
+/// Listen on `socket` and answer JSON-RPC requests (see the module comment)
+/// by running them against `store`, until a client calls `shutdown`.
+///
+/// A socket file left behind by a server that is no longer running is
+/// replaced; the socket file is removed when the server stops.
+///
+/// # Error Cases
+/// - Something is already listening on `socket`
+/// - The socket can't be created
+pub fn serve(
+    socket: &Path,
+    store: &dyn CatalogStore,
+    context: &mut Context,
+    output: RenderOptions,
+) -> Result<IpcSummary, Box<dyn Error>> {
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(format!("a server is already listening on '{}'", socket.display()).into());
+        }
+        fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    listener.set_nonblocking(true)?;
+
+    let mut server = Server {
+        store,
+        context,
+        output,
+        summary: IpcSummary::default(),
+    };
+    let mut clients: Vec<Client> = Vec::new();
+    let result = 'serving: loop {
+        let mut busy = false;
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = stream.set_nonblocking(true) {
+                        break 'serving Err(e.into());
+                    }
+                    server.summary.clients += 1;
+                    busy = true;
+                    clients.push(Client {
+                        stream,
+                        pending: Vec::new(),
+                        open: true,
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break 'serving Err(e.into()),
+            }
+        }
+
+        for client in &mut clients {
+            match server.serve(client) {
+                Ok((read, Next::Continue)) => busy |= read,
+                Ok((_, Next::Stop)) => break 'serving Ok(server.summary),
+                Err(e) => break 'serving Err(e),
+            }
+        }
+        clients.retain(|client| client.open);
+
+        if !busy {
+            thread::sleep(IDLE);
+        }
+    };
+
+    let _ = fs::remove_file(socket);
+    result
+}
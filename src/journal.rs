@@ -0,0 +1,388 @@
+// ============================================================================
+// journal.rs - Append-Only Change Log
+// ============================================================================
+// The catalogue file only ever holds the *current* fruits. To answer
+// questions like "who deleted Durian, and when?" we also keep a journal: a
+// JSON Lines file (one JSON object per line) next to the catalogue, to which
+// every successful change is appended.
+//
+// The journal for `fruits.json` is `fruits.json.journal`. Its first line is
+// a snapshot of the catalogue as it was before the first recorded change;
+// every later line is one change, with the time it happened, who made it,
+// and the `CatalogEvent` describing it:
+//
+//     {"kind":"snapshot","timestamp":"2024-05-01T09:00:00Z","fruits":[...]}
+//     {"kind":"change","timestamp":"2024-05-02T14:31:07Z","actor":"alice",
+//      "event":{"type":"removed","fruit":{"name":"Durian",...}}}
+//
+// Key concept: Appending a line never rewrites what is already there, so the
+// journal stays cheap to update however long it grows, and a crash while
+// writing can at worst damage the last line.
+//
+// Left alone, a journal grows forever. `Journal::compact` folds everything
+// older than a cutoff into a single snapshot, keeping the catalogue's state
+// at the cutoff (and every change since) while forgetting the details of how
+// it got there.
+// ============================================================================
+
+use crate::catalog::FruitKey;
+use crate::events::{Action, CatalogEvent};
+use crate::history::{fruit_history, state_at, Revision};
+use crate::models::FruitDimensions;
+use crate::names::names_equal;
+use crate::timestamp::{Period, Timestamp};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+
+/// Journal settings from the config file.
+///
+/// # Config file example
+/// ```json
+/// { "journal": { "enabled": true, "keep": "90d" } }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct JournalSettings {
+    /// Whether the CLI records changes in the journal (default: `true`).
+    pub enabled: bool,
+
+    /// How much history `fruitdata history gc` keeps when no `--keep` is
+    /// given. `None` (the default) means there is no retention policy.
+    pub keep: Option<Period>,
+}
+
+impl Default for JournalSettings {
+    fn default() -> Self {
+        JournalSettings {
+            enabled: true,
+            keep: None,
+        }
+    }
+}
+
+/// What `Journal::compact` did.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// How many entries were folded into the new snapshot.
+    pub entries_removed: usize,
+    /// The journal's size before compacting, in bytes.
+    pub bytes_before: u64,
+    /// The journal's size afterwards, in bytes.
+    pub bytes_after: u64,
+}
+
+impl CompactionReport {
+    /// The number of bytes freed (zero if the journal didn't shrink).
+    pub fn reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// One change, as recorded in the journal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChangeRecord {
+    /// When the change was saved.
+    pub timestamp: Timestamp,
+    /// Who made it (the `--actor` flag, or the user running the command).
+    pub actor: String,
+    /// What changed.
+    pub event: CatalogEvent,
+}
+
+/// One line of the journal file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum JournalEntry {
+    /// The complete catalogue at a point in time; changes after it build on it.
+    Snapshot {
+        timestamp: Timestamp,
+        fruits: Vec<FruitDimensions>,
+    },
+    /// A single change. Boxed, as a change holds up to two whole fruits.
+    Change(Box<ChangeRecord>),
+}
+
+impl JournalEntry {
+    /// When the snapshot was taken or the change was made.
+    pub fn timestamp(&self) -> Timestamp {
+        match self {
+            JournalEntry::Snapshot { timestamp, .. } => *timestamp,
+            JournalEntry::Change(record) => record.timestamp,
+        }
+    }
+}
+
+/// The path of the journal that belongs to a catalogue file.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::journal::journal_path;
+/// assert_eq!(journal_path("data/fruits.json"), "data/fruits.json.journal");
+/// ```
+pub fn journal_path(catalogue_path: &str) -> String {
+    format!("{}.journal", catalogue_path)
+}
+
+/// A handle on a journal file.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::events::Action;
+/// use fruitdata::journal::{AuditFilter, Journal};
+/// use fruitdata::timestamp::Timestamp;
+///
+/// let path = std::env::temp_dir().join("fruitdata-journal-doc.journal");
+/// let journal = Journal::new(path.to_str().unwrap());
+/// # let _ = std::fs::remove_file(&path);
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+/// let before = catalogue.fruits().to_vec();
+/// catalogue.remove("Pear").unwrap();
+/// journal
+///     .record(&before, "alice", Timestamp::now(), catalogue.take_changes())
+///     .unwrap();
+///
+/// let filter = AuditFilter { action: Some(Action::Remove), ..AuditFilter::default() };
+/// let entries = journal.read().unwrap();
+/// let removals = filter.apply(&entries);
+/// assert_eq!(removals[0].actor, "alice");
+/// assert_eq!(removals[0].event.name(), "Pear");
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Journal {
+    path: String,
+}
+
+impl Journal {
+    /// Use the journal file at `path` (which need not exist yet).
+    pub fn new(path: &str) -> Self {
+        Journal {
+            path: path.to_string(),
+        }
+    }
+
+    /// The journal belonging to the catalogue file at `catalogue_path`.
+    pub fn for_catalogue(catalogue_path: &str) -> Self {
+        Journal::new(&journal_path(catalogue_path))
+    }
+
+    /// The journal file's path.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Whether anything has been recorded yet.
+    pub fn exists(&self) -> bool {
+        fs::metadata(&self.path).is_ok()
+    }
+
+    /// Read every entry, oldest first. A missing journal has no entries.
+    ///
+    /// # Error Cases
+    /// - The file can't be read
+    /// - A line isn't a valid journal entry (the error names the line)
+    pub fn read(&self) -> Result<Vec<JournalEntry>, Box<dyn Error>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| format!("{} line {}: {}", self.path, number + 1, e).into())
+            })
+            .collect()
+    }
+
+    /// Append entries to the end of the journal, creating it if needed.
+    pub fn append(&self, entries: &[JournalEntry]) -> Result<(), Box<dyn Error>> {
+        let mut lines = String::new();
+        for entry in entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        Ok(())
+    }
+
+    /// Record a batch of changes made by `actor` at `timestamp`.
+    ///
+    /// `before` is the catalogue as it was before these changes. It is only
+    /// used when the journal doesn't exist yet, to write the initial snapshot
+    /// that later changes build on. Recording no changes does nothing.
+    pub fn record(
+        &self,
+        before: &[FruitDimensions],
+        actor: &str,
+        timestamp: Timestamp,
+        events: Vec<CatalogEvent>,
+    ) -> Result<(), Box<dyn Error>> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut entries = Vec::with_capacity(events.len() + 1);
+        if !self.exists() {
+            entries.push(JournalEntry::Snapshot {
+                timestamp,
+                fruits: before.to_vec(),
+            });
+        }
+        entries.extend(events.into_iter().map(|event| {
+            JournalEntry::Change(Box::new(ChangeRecord {
+                timestamp,
+                actor: actor.to_string(),
+                event,
+            }))
+        }));
+
+        self.append(&entries)
+    }
+
+    /// Forget the details of every change made at or before `cutoff`.
+    ///
+    /// Those entries are replaced by one snapshot of the catalogue as it was
+    /// at `cutoff`, so later changes still replay correctly and `restore`
+    /// works for any time from `cutoff` on. The journal is rewritten through
+    /// a temporary file, so a crash leaves either the old or the new journal.
+    ///
+    /// Nothing is rewritten when there is nothing to fold (no entries that
+    /// old, or just the one snapshot).
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+    /// use fruitdata::journal::{Journal, JournalEntry};
+    /// use fruitdata::timestamp::Timestamp;
+    ///
+    /// let path = std::env::temp_dir().join("fruitdata-compact-doc.journal");
+    /// let journal = Journal::new(path.to_str().unwrap());
+    /// # let _ = std::fs::remove_file(&path);
+    ///
+    /// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+    /// let before = catalogue.fruits().to_vec();
+    /// catalogue.remove("Pear").unwrap();
+    /// journal.record(&before, "alice", Timestamp(100), catalogue.take_changes()).unwrap();
+    /// catalogue.remove("Apple").unwrap();
+    /// journal.record(&before, "bob", Timestamp(200), catalogue.take_changes()).unwrap();
+    ///
+    /// let report = journal.compact(Timestamp(150)).unwrap();
+    /// assert_eq!(report.entries_removed, 2); // first snapshot and alice's change
+    /// assert!(report.reclaimed() > 0);
+    ///
+    /// let entries = journal.read().unwrap();
+    /// assert_eq!(entries.len(), 2); // new snapshot and bob's change
+    /// assert!(matches!(&entries[0], JournalEntry::Snapshot { fruits, .. } if fruits.len() == 3));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn compact(&self, cutoff: Timestamp) -> Result<CompactionReport, Box<dyn Error>> {
+        let bytes_before = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        let unchanged = CompactionReport {
+            entries_removed: 0,
+            bytes_before,
+            bytes_after: bytes_before,
+        };
+
+        let entries = self.read()?;
+        let old = entries.iter().filter(|e| e.timestamp() <= cutoff).count();
+        let Some(fruits) = state_at(&entries, cutoff) else {
+            return Ok(unchanged);
+        };
+        if old <= 1 {
+            return Ok(unchanged);
+        }
+
+        let mut kept = vec![JournalEntry::Snapshot {
+            timestamp: cutoff,
+            fruits,
+        }];
+        kept.extend(entries.into_iter().filter(|e| e.timestamp() > cutoff));
+
+        // Write the new journal next to the old one, then swap it in
+        let temporary = Journal::new(&format!("{}.tmp", self.path));
+        let _ = fs::remove_file(&temporary.path);
+        temporary.append(&kept)?;
+        fs::rename(&temporary.path, &self.path)?;
+
+        Ok(CompactionReport {
+            entries_removed: old,
+            bytes_before,
+            bytes_after: fs::metadata(&self.path)?.len(),
+        })
+    }
+
+    /// Every revision of one fruit, oldest first (see `history::fruit_history`).
+    pub fn history<'a>(
+        &self,
+        key: impl Into<FruitKey<'a>>,
+    ) -> Result<Vec<Revision<FruitDimensions>>, Box<dyn Error>> {
+        Ok(fruit_history(&self.read()?, key))
+    }
+}
+
+/// Criteria for picking changes out of the journal (`fruitdata audit`).
+///
+/// Every criterion is optional; the default filter matches every change.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditFilter {
+    /// Only changes made at or after this time.
+    pub since: Option<Timestamp>,
+    /// Only changes made before this time.
+    pub until: Option<Timestamp>,
+    /// Only changes made by this actor (exact match).
+    pub actor: Option<String>,
+    /// Only changes of this kind.
+    pub action: Option<Action>,
+    /// Only changes to a fruit with this name, before or after the change
+    /// (case-insensitive).
+    pub name: Option<String>,
+}
+
+impl AuditFilter {
+    /// Returns `true` if the change satisfies every criterion.
+    pub fn matches(&self, record: &ChangeRecord) -> bool {
+        let event = &record.event;
+        self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp < until)
+            && self
+                .actor
+                .as_ref()
+                .is_none_or(|actor| &record.actor == actor)
+            && self.action.is_none_or(|action| event.action() == action)
+            && self.name.as_ref().is_none_or(|name| {
+                event
+                    .before()
+                    .into_iter()
+                    .chain(event.after())
+                    .any(|fruit| names_equal(&fruit.name, name))
+            })
+    }
+
+    /// The matching changes among `entries`, oldest first.
+    pub fn apply<'a>(&self, entries: &'a [JournalEntry]) -> Vec<&'a ChangeRecord> {
+        entries
+            .iter()
+            .filter_map(|entry| match entry {
+                JournalEntry::Change(record) if self.matches(record) => Some(&**record),
+                _ => None,
+            })
+            .collect()
+    }
+}
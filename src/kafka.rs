@@ -0,0 +1,410 @@
+// ============================================================================
+// kafka.rs - Publishing Messages to Kafka (feature "kafka")
+// ============================================================================
+// Changes to the catalogue are published to a Kafka topic (see publish.rs
+// for what the messages contain). This is the small part of the Kafka
+// protocol a producer needs, over plain `TcpStream`s:
+//
+// 1. Ask one of the configured brokers for the topic's metadata (Metadata
+//    v4): its partitions, and which broker leads each.
+// 2. Pick a partition for each message the way the Java client does, from
+//    the murmur2 hash of its key, so other producers and consumers agree on
+//    where a fruit's changes go.
+// 3. Send each leader its messages as one record batch (Produce v3, the
+//    oldest version current brokers accept), and wait for the leader to
+//    write them (acks = 1).
+//
+// Every request is an i32 size, then a header (API key, version,
+// correlation ID, client ID), then the body; integers are big-endian and
+// strings have an i16 length. There is no TLS or SASL, and no retrying
+// beyond what `publish` callers do.
+// ============================================================================
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const PRODUCE: i16 = 0;
+const METADATA: i16 = 3;
+
+/// A message's key and value.
+pub type Message = (Vec<u8>, Vec<u8>);
+
+/// Messages by the partition they go to.
+type Partitions<'a> = BTreeMap<i32, Vec<&'a Message>>;
+
+/// How long to wait for a broker to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Publish `messages` to `topic`, using the first
+/// of `brokers` that answers to find the partition leaders.
+///
+/// # Error Cases
+/// - None of the brokers can be reached
+/// - The topic doesn't exist, or a partition has no leader
+/// - A leader rejected the messages (the Kafka error code is reported)
+pub fn publish(
+    brokers: &[String],
+    client_id: &str,
+    topic: &str,
+    messages: &[Message],
+) -> io::Result<()> {
+    if messages.is_empty() {
+        return Ok(());
+    }
+
+    let mut last_error = io::Error::new(io::ErrorKind::InvalidInput, "no Kafka brokers configured");
+    let mut metadata = None;
+    for broker in brokers {
+        match Connection::open(broker, client_id).and_then(|mut c| c.metadata(topic)) {
+            Ok(found) => {
+                metadata = Some(found);
+                break;
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    let metadata = metadata.ok_or(last_error)?;
+
+    // Group the messages by the broker leading their partition
+    let mut batches: BTreeMap<i32, Partitions> = BTreeMap::new();
+    for message in messages {
+        let hash = murmur2(&message.0) & 0x7FFF_FFFF;
+        let (partition, leader) = metadata.partitions[hash as usize % metadata.partitions.len()];
+        batches
+            .entry(leader)
+            .or_default()
+            .entry(partition)
+            .or_default()
+            .push(message);
+    }
+
+    for (leader, partitions) in batches {
+        let address = metadata
+            .brokers
+            .get(&leader)
+            .ok_or_else(|| invalid(&format!("unknown leader broker {}", leader)))?;
+        let mut connection = Connection::open(address, client_id)?;
+        connection.produce(topic, &partitions)?;
+    }
+    Ok(())
+}
+
+/// A topic's partitions (with their leaders) and where the brokers are.
+#[derive(Debug)]
+struct Metadata {
+    brokers: BTreeMap<i32, String>,
+    /// Partition number and leader, in partition order.
+    partitions: Vec<(i32, i32)>,
+}
+
+#[derive(Debug)]
+struct Connection {
+    stream: TcpStream,
+    client_id: String,
+    correlation_id: i32,
+}
+
+impl Connection {
+    fn open(address: &str, client_id: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        Ok(Connection {
+            stream,
+            client_id: client_id.to_string(),
+            correlation_id: 0,
+        })
+    }
+
+    /// Send a request, and return the body of its response.
+    fn request(&mut self, api_key: i16, version: i16, body: &[u8]) -> io::Result<Vec<u8>> {
+        self.correlation_id += 1;
+        let mut request = Vec::new();
+        put_i16(&mut request, api_key);
+        put_i16(&mut request, version);
+        put_i32(&mut request, self.correlation_id);
+        put_string(&mut request, &self.client_id);
+        request.extend_from_slice(body);
+
+        let mut framed = Vec::with_capacity(request.len() + 4);
+        put_i32(&mut framed, request.len() as i32);
+        framed.extend_from_slice(&request);
+        self.stream.write_all(&framed)?;
+
+        let mut size = [0; 4];
+        self.stream.read_exact(&mut size)?;
+        let mut response = vec![0; i32::from_be_bytes(size).max(0) as usize];
+        self.stream.read_exact(&mut response)?;
+        let mut reader = Reader(&response);
+        if reader.i32()? != self.correlation_id {
+            return Err(invalid("response to a different request"));
+        }
+        Ok(reader.0.to_vec())
+    }
+
+    fn metadata(&mut self, topic: &str) -> io::Result<Metadata> {
+        let mut body = Vec::new();
+        put_i32(&mut body, 1);
+        put_string(&mut body, topic);
+        body.push(0); // don't create the topic
+        let response = self.request(METADATA, 4, &body)?;
+
+        let mut reader = Reader(&response);
+        reader.i32()?; // throttle time
+        let mut brokers = BTreeMap::new();
+        for _ in 0..reader.i32()? {
+            let node = reader.i32()?;
+            let host = reader.string()?.unwrap_or_default();
+            let port = reader.i32()?;
+            reader.string()?; // rack
+            brokers.insert(node, format!("{}:{}", host, port));
+        }
+        reader.string()?; // cluster ID
+        reader.i32()?; // controller
+
+        for _ in 0..reader.i32()? {
+            let error = reader.i16()?;
+            let name = reader.string()?;
+            reader.take(1)?; // is internal
+            let mut partitions = Vec::new();
+            for _ in 0..reader.i32()? {
+                let partition_error = reader.i16()?;
+                let partition = reader.i32()?;
+                let leader = reader.i32()?;
+                for _ in 0..2 {
+                    // replicas, in-sync replicas
+                    let count = reader.i32()?.max(0) as usize;
+                    reader.take(count * 4)?;
+                }
+                if partition_error != 0 || leader < 0 {
+                    return Err(kafka_error(
+                        partition_error,
+                        &format!("{}/{}", topic, partition),
+                    ));
+                }
+                partitions.push((partition, leader));
+            }
+            if name.as_deref() == Some(topic) {
+                if error != 0 {
+                    return Err(kafka_error(error, topic));
+                }
+                if partitions.is_empty() {
+                    return Err(invalid(&format!("topic '{}' has no partitions", topic)));
+                }
+                partitions.sort_unstable();
+                return Ok(Metadata {
+                    brokers,
+                    partitions,
+                });
+            }
+        }
+        Err(invalid(&format!("no metadata for topic '{}'", topic)))
+    }
+
+    fn produce(&mut self, topic: &str, partitions: &Partitions) -> io::Result<()> {
+        let mut body = Vec::new();
+        put_i16(&mut body, -1); // no transactional ID
+        put_i16(&mut body, 1); // acks: the leader
+        put_i32(&mut body, TIMEOUT.as_millis() as i32);
+        put_i32(&mut body, 1);
+        put_string(&mut body, topic);
+        put_i32(&mut body, partitions.len() as i32);
+        for (partition, messages) in partitions {
+            put_i32(&mut body, *partition);
+            let batch = record_batch(messages);
+            put_i32(&mut body, batch.len() as i32);
+            body.extend_from_slice(&batch);
+        }
+        let response = self.request(PRODUCE, 3, &body)?;
+
+        let mut reader = Reader(&response);
+        for _ in 0..reader.i32()? {
+            reader.string()?;
+            for _ in 0..reader.i32()? {
+                let partition = reader.i32()?;
+                let error = reader.i16()?;
+                reader.take(16)?; // base offset, log append time
+                if error != 0 {
+                    return Err(kafka_error(error, &format!("{}/{}", topic, partition)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `messages` as a record batch (message format v2), all stamped with the
+/// current time.
+fn record_batch(messages: &[&Message]) -> Vec<u8> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64);
+
+    let mut records = Vec::new();
+    for (offset, (key, value)) in messages.iter().enumerate() {
+        let mut record = vec![0]; // attributes
+        put_varint(&mut record, 0); // timestamp delta
+        put_varint(&mut record, offset as i64);
+        put_varint(&mut record, key.len() as i64);
+        record.extend_from_slice(key);
+        put_varint(&mut record, value.len() as i64);
+        record.extend_from_slice(value);
+        put_varint(&mut record, 0); // headers
+        put_varint(&mut records, record.len() as i64);
+        records.extend_from_slice(&record);
+    }
+
+    // The part of the batch the CRC covers
+    let mut checked = Vec::new();
+    put_i16(&mut checked, 0); // attributes: no compression
+    put_i32(&mut checked, messages.len() as i32 - 1); // last offset delta
+    put_i64(&mut checked, now); // first timestamp
+    put_i64(&mut checked, now); // max timestamp
+    put_i64(&mut checked, -1); // producer ID
+    put_i16(&mut checked, -1); // producer epoch
+    put_i32(&mut checked, -1); // base sequence
+    put_i32(&mut checked, messages.len() as i32);
+    checked.extend_from_slice(&records);
+
+    let mut batch = Vec::new();
+    put_i64(&mut batch, 0); // base offset
+    put_i32(&mut batch, (4 + 1 + 4 + checked.len()) as i32); // batch length
+    put_i32(&mut batch, -1); // partition leader epoch
+    batch.push(2); // magic
+    batch.extend_from_slice(&crc32c(&checked).to_be_bytes());
+    batch.extend_from_slice(&checked);
+    batch
+}
+
+/// The hash the Java client's default partitioner uses for keys.
+///
+/// ```
+/// # use fruitdata::kafka::murmur2;
+/// assert_eq!(murmur2(b"21"), -973932308);
+/// assert_eq!(murmur2(b"foobar"), -790332482);
+/// ```
+pub fn murmur2(data: &[u8]) -> i32 {
+    const M: u32 = 0x5bd1_e995;
+    const R: u32 = 24;
+    let mut h: u32 = 0x9747_b28c ^ data.len() as u32;
+
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+    let rest = chunks.remainder();
+    if rest.len() >= 3 {
+        h ^= u32::from(rest[2]) << 16;
+    }
+    if rest.len() >= 2 {
+        h ^= u32::from(rest[1]) << 8;
+    }
+    if !rest.is_empty() {
+        h ^= u32::from(rest[0]);
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+    h as i32
+}
+
+/// CRC-32C (Castagnoli), the checksum of a record batch.
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0x82F6_3B78 & mask);
+        }
+    }
+    !crc
+}
+
+/// Reads the fields of a response in order.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < n {
+            return Err(invalid("truncated response"));
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn i16(&mut self) -> io::Result<i16> {
+        let bytes = self.take(2)?;
+        Ok(i16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// A nullable string (`None` for null).
+    fn string(&mut self) -> io::Result<Option<String>> {
+        let length = self.i16()?;
+        if length < 0 {
+            return Ok(None);
+        }
+        let bytes = self.take(length as usize)?;
+        Ok(Some(String::from_utf8_lossy(bytes).into_owned()))
+    }
+}
+
+fn put_i16(out: &mut Vec<u8>, n: i16) {
+    out.extend_from_slice(&n.to_be_bytes());
+}
+
+fn put_i32(out: &mut Vec<u8>, n: i32) {
+    out.extend_from_slice(&n.to_be_bytes());
+}
+
+fn put_i64(out: &mut Vec<u8>, n: i64) {
+    out.extend_from_slice(&n.to_be_bytes());
+}
+
+fn put_string(out: &mut Vec<u8>, text: &str) {
+    put_i16(out, text.len() as i16);
+    out.extend_from_slice(text.as_bytes());
+}
+
+/// A zig-zag varint, as in record batches.
+fn put_varint(out: &mut Vec<u8>, n: i64) {
+    let mut n = ((n << 1) ^ (n >> 63)) as u64;
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Kafka: {}", message))
+}
+
+/// A Kafka error code, with the name of the common ones.
+fn kafka_error(code: i16, subject: &str) -> io::Error {
+    let name = match code {
+        3 => "unknown topic or partition",
+        5 => "leader not available",
+        6 => "not leader for partition",
+        7 => "request timed out",
+        10 => "message too large",
+        29 => "topic authorization failed",
+        _ => "error",
+    };
+    invalid(&format!("{} ({}): {}", name, code, subject))
+}
@@ -0,0 +1,227 @@
+// ============================================================================
+// label.rs - Printer-Ready Labels
+// ============================================================================
+// The warehouse prints a label for every crate: the fruit's name, its
+// dimensions, its PLU (the price look-up code on supermarket stickers, kept
+// in the fruit's `"plu"` field) and a Code 128 barcode. `fruitdata label`
+// writes such a label in the language of the printer:
+//
+// - zpl: Zebra Programming Language, for Zebra and compatible thermal
+//   label printers. The whole label is a template, so the layout can be
+//   changed to suit the label stock.
+// - escpos: ESC/POS, for receipt-style printers. The template gives the
+//   text lines; the barcode, a paper feed and a cut are added after them.
+//
+// Templates use the `{{field}}` placeholders of template.rs, and can be
+// replaced in the config file's `label` section:
+//
+//     { "label": { "zpl": "^XA^FO40,40^A0N,60,60^FH^FD{{name}}^FS^XZ" } }
+//
+// The barcode holds the fruit's PLU, or its ID if it has no PLU
+// (`{{barcode}}` in a template). A fruit with neither gets no barcode: a
+// template line with a placeholder that has no value is left out, so a
+// fruit without a PLU gets no "PLU" line either.
+//
+// Key concept: names and PLUs are data, and must never become printer
+// commands. In ZPL, `^` and `~` start commands, so every value is written
+// with `^`, `~` and `_` hex-encoded (`_5E`, `_7E`, `_5F`), which the
+// printer decodes in fields that start with `^FH`. A custom template
+// should put `^FH` before each `^FD` that holds a placeholder. In ESC/POS,
+// control characters in a value are printed as `?`.
+// ============================================================================
+
+use crate::models::FruitDimensions;
+use crate::template::Template;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+/// The longest barcode a label holds; longer ones don't fit on the stock.
+const MAX_BARCODE: usize = 48;
+
+/// The printer language of a label.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelFormat {
+    /// Zebra Programming Language.
+    Zpl,
+    /// ESC/POS.
+    EscPos,
+}
+
+impl FromStr for LabelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "zpl" => Ok(LabelFormat::Zpl),
+            "escpos" | "esc/pos" => Ok(LabelFormat::EscPos),
+            other => Err(format!(
+                "unknown label format '{}' (expected zpl or escpos)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LabelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LabelFormat::Zpl => write!(f, "zpl"),
+            LabelFormat::EscPos => write!(f, "escpos"),
+        }
+    }
+}
+
+/// The label templates, from the config file's `label` section.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LabelSettings {
+    /// The whole ZPL label.
+    pub zpl: Template,
+    /// The text lines of an ESC/POS label.
+    pub escpos: Template,
+}
+
+impl Default for LabelSettings {
+    fn default() -> Self {
+        let zpl = "^XA\n\
+                   ^CI28\n\
+                   ^FO40,30^A0N,50,50^FH^FD{{name}}^FS\n\
+                   ^FO40,95^A0N,30,30^FH^FD{{length}} x {{width}} x {{height}} cm^FS\n\
+                   ^FO40,140^A0N,30,30^FH^FDPLU {{plu}}^FS\n\
+                   ^FO40,190^BY2^BCN,80,Y,N,N^FH^FD{{barcode}}^FS\n\
+                   ^XZ\n";
+        let escpos = "{{name}}\n{{length}} x {{width}} x {{height}} cm\nPLU {{plu}}\n";
+        LabelSettings {
+            zpl: zpl
+                .parse()
+                .expect("the default ZPL label is a valid template"),
+            escpos: escpos
+                .parse()
+                .expect("the default ESC/POS label is a valid template"),
+        }
+    }
+}
+
+impl LabelSettings {
+    /// The label for `fruit` in `format`.
+    ///
+    /// ESC/POS labels are plain ASCII apart from the printer's control
+    /// codes; other characters are printed as `?`.
+    ///
+    /// # Error Cases
+    /// The barcode data is longer than 48 characters, or has characters
+    /// Code 128 can't encode (anything but printable ASCII).
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::label::{LabelFormat, LabelSettings};
+    /// use fruitdata::models::FruitDimensions;
+    ///
+    /// let mut apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+    /// apple.extra.insert("plu".into(), 4131.into());
+    ///
+    /// let zpl = LabelSettings::default().render(&apple, LabelFormat::Zpl).unwrap();
+    /// assert!(zpl.starts_with("^XA"));
+    /// assert!(zpl.contains("^FH^FDApple^FS"));
+    /// assert!(zpl.contains("^BCN,80,Y,N,N^FH^FD4131^FS"));
+    ///
+    /// let escpos = LabelSettings::default().render(&apple, LabelFormat::EscPos).unwrap();
+    /// assert!(escpos.starts_with("\x1b@Apple\n4 x 2.5 x 1.5 cm\nPLU 4131\n"));
+    /// assert!(escpos.contains("\x1dkI\x06{B4131")); // Code 128, 6 bytes of data
+    ///
+    /// // Values can't become printer commands
+    /// let evil = FruitDimensions::new("Evil^XZ~JR", 4.0, 2.5, 1.5);
+    /// let zpl = LabelSettings::default().render(&evil, LabelFormat::Zpl).unwrap();
+    /// assert!(zpl.contains("^FH^FDEvil_5EXZ_7EJR^FS"));
+    ///
+    /// // Without a PLU or ID, there is no PLU line and no barcode
+    /// assert!(!zpl.contains("PLU"));
+    /// assert!(!zpl.contains("^BC"));
+    /// ```
+    pub fn render(&self, fruit: &FruitDimensions, format: LabelFormat) -> Result<String, String> {
+        let barcode = barcode_data(fruit);
+        if let Some(data) = &barcode {
+            if data.len() > MAX_BARCODE || !data.chars().all(|c| matches!(c, ' '..='~')) {
+                return Err(format!(
+                    "'{}' can't be a barcode (up to {} printable ASCII characters)",
+                    data, MAX_BARCODE
+                ));
+            }
+        }
+
+        match format {
+            LabelFormat::Zpl => Ok(self.zpl.render_lines(fruit, |n| n.to_string(), zpl_field)),
+            LabelFormat::EscPos => {
+                // ESC @: reset the printer
+                let mut label = String::from("\x1b@");
+                let text = self.escpos.render_lines(
+                    fruit,
+                    |n| n.to_string(),
+                    |value| {
+                        value
+                            .chars()
+                            .map(|c| if c.is_control() { '?' } else { c })
+                            .collect()
+                    },
+                );
+                label.extend(text.chars().map(|c| if c.is_ascii() { c } else { '?' }));
+                if let Some(data) = barcode {
+                    // GS h / GS w: 80 dots high, 2 dots per module; GS H:
+                    // the digits below; GS k 73: Code 128, starting in
+                    // code set B. A `{` in the data is written `{{`.
+                    let data = data.replace('{', "{{");
+                    label.push_str("\x1dhP\x1dw\x02\x1dH\x02");
+                    label.push_str("\x1dkI");
+                    label.push(char::from(data.len() as u8 + 2));
+                    label.push_str("{B");
+                    label.push_str(&data);
+                    label.push('\n');
+                }
+                // ESC d 3: feed three lines; GS V 66 0: feed to the cutter and cut
+                label.push_str("\x1bd\x03\x1dVB\x00");
+                Ok(label)
+            }
+        }
+    }
+}
+
+/// `value` for a ZPL field started with `^FH`: `^`, `~` and `_` are
+/// hex-encoded.
+fn zpl_field(value: &str) -> String {
+    value.chars().fold(String::new(), |mut field, c| {
+        if matches!(c, '^' | '~' | '_') {
+            let _ = write!(field, "_{:02X}", u32::from(c));
+        } else {
+            field.push(c);
+        }
+        field
+    })
+}
+
+/// The fruit's PLU, from its `"plu"` field (a string or a number).
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::label::plu;
+/// use fruitdata::models::FruitDimensions;
+///
+/// let mut banana = FruitDimensions::new("Banana", 6.0, 3.5, 2.5);
+/// assert_eq!(plu(&banana), None);
+/// banana.extra.insert("plu".into(), "4011".into());
+/// assert_eq!(plu(&banana).as_deref(), Some("4011"));
+/// ```
+pub fn plu(fruit: &FruitDimensions) -> Option<String> {
+    match fruit.extra.get("plu")? {
+        Value::String(plu) if !plu.trim().is_empty() => Some(plu.trim().to_string()),
+        Value::Number(plu) => Some(plu.to_string()),
+        _ => None,
+    }
+}
+
+/// What the fruit's barcode holds: its PLU, or else its ID.
+pub fn barcode_data(fruit: &FruitDimensions) -> Option<String> {
+    plu(fruit).or_else(|| fruit.id.map(|id| id.to_string()))
+}
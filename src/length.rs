@@ -0,0 +1,178 @@
+// ============================================================================
+// length.rs - Lengths That Know Their Unit
+// ============================================================================
+// A fruit's dimensions used to be bare `f32`s, and so were its volume, its
+// aspect ratios and every other number computed from them. Nothing stopped
+// code from adding a length to a volume, or passing millimetres where
+// centimetres were expected.
+//
+// `Centimeters` wraps the number instead. Lengths can be added to and
+// subtracted from each other and scaled by plain numbers; anything else
+// (such as a volume) needs the number taken out with `value()` first, which
+// makes mixing them a visible decision. Conversions to and from other units
+// are spelled out (`from_millimetres`, `to_inches`, ...).
+//
+// In files, a length is just a number (`#[serde(transparent)]`), so this
+// doesn't change the file format.
+// ============================================================================
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
+
+/// Millimetres in a centimetre.
+const MILLIMETRES: f32 = 10.0;
+/// Centimetres in an inch.
+const INCH: f32 = 2.54;
+
+/// A length in centimetres, the unit of a fruit's dimensions.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::length::Centimeters;
+///
+/// let length = Centimeters(4.0) + Centimeters::from_millimetres(5.0);
+/// assert_eq!(length, Centimeters(4.5));
+/// assert_eq!(length * 2.0, Centimeters(9.0));
+/// assert_eq!(Centimeters::from_inches(1.0).value(), 2.54);
+/// assert_eq!(length.to_string(), "4.5");
+///
+/// // A length can't be added to a plain number (such as a volume):
+/// // `length + 10.0` doesn't compile.
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Centimeters(pub f32);
+
+impl Centimeters {
+    /// The length as a number of centimetres.
+    pub fn value(self) -> f32 {
+        self.0
+    }
+
+    /// A length given in millimetres.
+    pub fn from_millimetres(millimetres: f32) -> Self {
+        Centimeters(millimetres / MILLIMETRES)
+    }
+
+    /// The length in millimetres.
+    pub fn to_millimetres(self) -> f32 {
+        self.0 * MILLIMETRES
+    }
+
+    /// A length given in inches.
+    pub fn from_inches(inches: f32) -> Self {
+        Centimeters(inches * INCH)
+    }
+
+    /// The length in inches.
+    pub fn to_inches(self) -> f32 {
+        self.0 / INCH
+    }
+
+    /// Order two lengths like `f32::total_cmp`, which is total even for
+    /// NaN.
+    pub fn total_cmp(&self, other: &Centimeters) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+
+    /// Whether the length is a finite number (not NaN or infinite).
+    pub fn is_finite(self) -> bool {
+        self.0.is_finite()
+    }
+}
+
+impl From<f32> for Centimeters {
+    fn from(centimetres: f32) -> Self {
+        Centimeters(centimetres)
+    }
+}
+
+impl From<Centimeters> for f32 {
+    fn from(length: Centimeters) -> Self {
+        length.0
+    }
+}
+
+/// Compare with a number of centimetres, e.g. `fruit.length == 4.0`.
+impl PartialEq<f32> for Centimeters {
+    fn eq(&self, other: &f32) -> bool {
+        self.0 == *other
+    }
+}
+
+/// Compare with a number of centimetres, e.g. `fruit.length > 0.0`.
+impl PartialOrd<f32> for Centimeters {
+    fn partial_cmp(&self, other: &f32) -> Option<std::cmp::Ordering> {
+        self.0.partial_cmp(other)
+    }
+}
+
+impl Add for Centimeters {
+    type Output = Centimeters;
+
+    fn add(self, other: Centimeters) -> Centimeters {
+        Centimeters(self.0 + other.0)
+    }
+}
+
+impl Sub for Centimeters {
+    type Output = Centimeters;
+
+    fn sub(self, other: Centimeters) -> Centimeters {
+        Centimeters(self.0 - other.0)
+    }
+}
+
+impl AddAssign for Centimeters {
+    fn add_assign(&mut self, other: Centimeters) {
+        self.0 += other.0;
+    }
+}
+
+impl SubAssign for Centimeters {
+    fn sub_assign(&mut self, other: Centimeters) {
+        self.0 -= other.0;
+    }
+}
+
+/// Scale a length, e.g. by a calibration factor.
+impl Mul<f32> for Centimeters {
+    type Output = Centimeters;
+
+    fn mul(self, factor: f32) -> Centimeters {
+        Centimeters(self.0 * factor)
+    }
+}
+
+/// Divide a length into equal parts.
+impl Div<f32> for Centimeters {
+    type Output = Centimeters;
+
+    fn div(self, divisor: f32) -> Centimeters {
+        Centimeters(self.0 / divisor)
+    }
+}
+
+/// The ratio of two lengths, a plain number.
+impl Div for Centimeters {
+    type Output = f32;
+
+    fn div(self, other: Centimeters) -> f32 {
+        self.0 / other.0
+    }
+}
+
+impl Sum for Centimeters {
+    fn sum<I: Iterator<Item = Centimeters>>(lengths: I) -> Self {
+        Centimeters(lengths.map(|length| length.0).sum())
+    }
+}
+
+/// Written as the bare number, honouring precision (`{:.1}`).
+impl fmt::Display for Centimeters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
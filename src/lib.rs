@@ -0,0 +1,111 @@
+// ============================================================================
+// lib.rs - Library Crate Root
+// ============================================================================
+// This file turns fruitdata into a library as well as a binary. Everything
+// that isn't specific to the command line (the data model, persistence,
+// sorting, ...) lives in modules declared here, so that:
+//
+// 1. The `fruitdata` binary (main.rs) can use them via `use fruitdata::...`
+// 2. Other Rust programs can depend on fruitdata and reuse the same logic
+// 3. Doc examples in these modules are compiled and run by `cargo test`
+//
+// Key concept: A Cargo package can contain both a library crate (src/lib.rs)
+// and a binary crate (src/main.rs). The binary is just another user of the
+// library, which keeps main.rs focused on argument parsing and printing
+// (even the commands themselves are in cli.rs).
+// ============================================================================
+
+#[cfg(feature = "barcode")]
+pub mod barcode;
+#[cfg(feature = "testing")]
+pub mod bench_support;
+pub mod borrowed;
+pub mod calibration;
+pub mod catalog;
+pub mod cli;
+pub mod compact;
+pub mod compat;
+pub mod complete;
+pub mod config;
+pub mod csv;
+#[cfg(unix)]
+pub mod daemon;
+pub mod diagnostic;
+pub mod document;
+pub mod duplicates;
+pub mod error;
+pub mod events;
+pub mod expr;
+pub mod format;
+pub mod grading;
+pub mod handle;
+pub mod history;
+pub mod hooks;
+pub mod import;
+pub mod ingest;
+#[cfg(unix)]
+pub mod ipc;
+pub mod journal;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod label;
+pub mod length;
+pub mod lint;
+pub mod measurements;
+pub mod merge;
+pub mod models;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod names;
+pub mod pattern;
+pub mod pivot;
+pub mod policy;
+/// The most commonly used fruitdata types, for `use fruitdata::prelude::*`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::prelude::*;
+///
+/// let store = MemoryStore::new(vec![
+///     FruitDimensions::new("Pear", 6.0, 3.5, 2.5),
+///     FruitDimensions::new("Apple", 4.0, 2.5, 1.5),
+/// ]);
+/// let mut catalogue: Catalogue = store.load().unwrap();
+///
+/// let duplicate = catalogue.add(FruitDimensions::new("apple", 1.0, 1.0, 1.0));
+/// assert_eq!(duplicate, Err(CatalogError::DuplicateName("apple".into())));
+///
+/// let large: Query = ".length > 5".parse().unwrap();
+/// assert_eq!(catalogue.count(Some(&large)).unwrap(), 1);
+///
+/// let mut fruits = catalogue.fruits().to_vec();
+/// "name".parse::<SortSpec>().unwrap().sort(&mut fruits);
+/// assert_eq!(fruits[0].name, "Apple");
+/// assert!("P*".parse::<Glob>().unwrap().is_match("Pear"));
+/// ```
+pub mod prelude;
+pub mod present;
+pub mod progress;
+pub mod publish;
+pub mod query;
+pub mod raw;
+pub mod repair;
+pub mod retry;
+pub mod rpc;
+pub mod sanitize;
+pub mod service;
+pub mod shared;
+pub mod sort;
+pub mod stats;
+pub mod store;
+pub mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod timestamp;
+#[cfg(feature = "toml")]
+pub mod toml;
+#[cfg(any(feature = "toml", feature = "yaml"))]
+pub mod tree;
+pub mod validate;
+#[cfg(feature = "yaml")]
+pub mod yaml;
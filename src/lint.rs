@@ -0,0 +1,341 @@
+// ============================================================================
+// lint.rs - Warnings About Suspicious Catalogue Data
+// ============================================================================
+// Validation (validate.rs) rejects data that breaks the catalogue's rules.
+// Linting looks for data that is allowed but probably wrong:
+//
+// - dimension-outlier: a dimension far from what the other fruits have
+//   (usually a unit mix-up, like 45 mm typed into a centimetre catalogue)
+// - inconsistent-casing: "kiwi" in a catalogue where names are Capitalised
+// - missing-tags: a fruit without a `tags` field (off unless enabled, since
+//   only some catalogues use tags)
+//
+// Rules can be switched on and off in the config file:
+//
+//     { "lint": { "rules": { "missing-tags": true }, "outlier_threshold": 5.0 } }
+//
+// Lint findings are warnings: `fruitdata lint` reports them and succeeds,
+// unless `--deny warnings` (or `--deny <rule>`) turns them into errors.
+// ============================================================================
+
+use crate::models::FruitDimensions;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Outlier detection needs enough fruits to know what "typical" is.
+const MIN_OUTLIER_SAMPLE: usize = 5;
+
+/// A lint rule.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    DimensionOutlier,
+    InconsistentCasing,
+    MissingTags,
+}
+
+impl Rule {
+    const ALL: [Rule; 3] = [
+        Rule::DimensionOutlier,
+        Rule::InconsistentCasing,
+        Rule::MissingTags,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Rule::DimensionOutlier => "dimension-outlier",
+            Rule::InconsistentCasing => "inconsistent-casing",
+            Rule::MissingTags => "missing-tags",
+        }
+    }
+
+    /// Whether the rule runs when the config doesn't mention it.
+    pub fn enabled_by_default(self) -> bool {
+        self != Rule::MissingTags
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.as_str() == s.trim().to_ascii_lowercase())
+            .ok_or_else(|| {
+                format!(
+                    "unknown lint rule '{}' (expected dimension-outlier, \
+                     inconsistent-casing or missing-tags)",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Lint settings from the config file.
+///
+/// # Config file example
+/// ```json
+/// { "lint": { "rules": { "missing-tags": true, "dimension-outlier": false } } }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LintSettings {
+    /// Rules switched on (`true`) or off (`false`). Rules not listed use
+    /// `Rule::enabled_by_default`.
+    pub rules: BTreeMap<Rule, bool>,
+
+    /// How unusual a dimension must be to count as an outlier, as a
+    /// modified z-score (default: 3.5; higher means fewer warnings).
+    pub outlier_threshold: f32,
+}
+
+impl Default for LintSettings {
+    fn default() -> Self {
+        LintSettings {
+            rules: BTreeMap::new(),
+            outlier_threshold: 3.5,
+        }
+    }
+}
+
+impl LintSettings {
+    /// Whether `rule` should run.
+    pub fn enabled(&self, rule: Rule) -> bool {
+        self.rules
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.enabled_by_default())
+    }
+}
+
+/// One suspicious thing found by a lint rule.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub rule: Rule,
+    /// The name of the fruit the warning is about.
+    pub fruit: String,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning[{}] {}: {}", self.rule, self.fruit, self.message)
+    }
+}
+
+/// Which warnings `--deny` turns into errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deny {
+    /// Every warning.
+    Warnings,
+    /// Warnings from one rule.
+    Rule(Rule),
+}
+
+impl Deny {
+    /// Whether `warning` is denied.
+    pub fn covers(&self, warning: &Warning) -> bool {
+        match self {
+            Deny::Warnings => true,
+            Deny::Rule(rule) => warning.rule == *rule,
+        }
+    }
+}
+
+impl FromStr for Deny {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("warnings") {
+            Ok(Deny::Warnings)
+        } else {
+            s.parse()
+                .map(Deny::Rule)
+                .map_err(|e| format!("{} (or 'warnings' for all)", e))
+        }
+    }
+}
+
+/// Run every enabled rule over the fruits, returning the warnings in
+/// catalogue order.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::lint::{lint, LintSettings, Rule};
+/// use fruitdata::models::FruitDimensions;
+///
+/// let fruits = vec![
+///     FruitDimensions::new("Apple", 4.0, 2.5, 1.5),
+///     FruitDimensions::new("Pear", 6.0, 3.5, 2.5),
+///     FruitDimensions::new("Plum", 3.0, 3.0, 3.0),
+///     FruitDimensions::new("Fig", 4.0, 3.0, 3.0),
+///     FruitDimensions::new("kiwi", 50.0, 3.0, 2.0), // mm, not cm?
+/// ];
+/// let warnings = lint(&fruits, &LintSettings::default());
+///
+/// let rules: Vec<Rule> = warnings.iter().map(|w| w.rule).collect();
+/// assert_eq!(rules, vec![Rule::DimensionOutlier, Rule::InconsistentCasing]);
+/// assert_eq!(warnings[0].message, "length 50 is unusually large (median 4)");
+/// ```
+pub fn lint(fruits: &[FruitDimensions], settings: &LintSettings) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let warn = |rule: Rule, fruit: &FruitDimensions, message: String| Warning {
+        rule,
+        fruit: fruit.name.trim().to_string(),
+        message,
+    };
+
+    let outliers = if settings.enabled(Rule::DimensionOutlier) {
+        dimension_outliers(fruits, settings.outlier_threshold)
+    } else {
+        Vec::new()
+    };
+    let usual_casing = if settings.enabled(Rule::InconsistentCasing) {
+        usual_casing(fruits)
+    } else {
+        None
+    };
+
+    for (index, fruit) in fruits.iter().enumerate() {
+        for (_, message) in outliers.iter().filter(|(i, _)| *i == index) {
+            warnings.push(warn(Rule::DimensionOutlier, fruit, message.clone()));
+        }
+
+        if let Some(usual) = usual_casing {
+            if let Some(casing) = Casing::of(&fruit.name).filter(|c| *c != usual) {
+                let message = format!(
+                    "name is {}, but most names are {}",
+                    casing.describe(),
+                    usual.describe()
+                );
+                warnings.push(warn(Rule::InconsistentCasing, fruit, message));
+            }
+        }
+
+        if settings.enabled(Rule::MissingTags) {
+            let tagged = match fruit.extra.get("tags") {
+                Some(Value::Array(tags)) => !tags.is_empty(),
+                Some(Value::String(tags)) => !tags.trim().is_empty(),
+                _ => false,
+            };
+            if !tagged {
+                warnings.push(warn(Rule::MissingTags, fruit, "no tags".to_string()));
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Find dimensions whose modified z-score (based on the median and the
+/// median absolute deviation, which a single wild value can't skew) is above
+/// `threshold`. Returns `(fruit index, message)` pairs.
+fn dimension_outliers(fruits: &[FruitDimensions], threshold: f32) -> Vec<(usize, String)> {
+    if fruits.len() < MIN_OUTLIER_SAMPLE {
+        return Vec::new();
+    }
+
+    let mut outliers = Vec::new();
+    for field in ["length", "width", "height"] {
+        let values: Vec<f32> = fruits
+            .iter()
+            .map(|f| match field {
+                "length" => f.length.value(),
+                "width" => f.width.value(),
+                _ => f.height.value(),
+            })
+            .collect();
+        let centre = median(&values);
+        let spread = median(
+            &values
+                .iter()
+                .map(|v| (v - centre).abs())
+                .collect::<Vec<_>>(),
+        );
+        // Most fruits agree exactly; nothing to measure against
+        if spread == 0.0 {
+            continue;
+        }
+
+        for (index, v) in values.iter().enumerate() {
+            let score = 0.6745 * (v - centre) / spread;
+            if score.abs() > threshold {
+                let size = if score > 0.0 { "large" } else { "small" };
+                outliers.push((
+                    index,
+                    format!("{} {} is unusually {} (median {})", field, v, size, centre),
+                ));
+            }
+        }
+    }
+    outliers
+}
+
+fn median(values: &[f32]) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+/// How a name uses capital letters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Casing {
+    /// "kiwi", "blood orange"
+    Lower,
+    /// "KIWI"
+    Upper,
+    /// "Kiwi", "Blood orange", "Blood Orange"
+    Capitalised,
+}
+
+impl Casing {
+    /// The casing of a name, or `None` if it has no letters (or only one,
+    /// which can't be told apart from "capitalised").
+    fn of(name: &str) -> Option<Casing> {
+        let letters: Vec<char> = name.chars().filter(|c| c.is_alphabetic()).collect();
+        if letters.len() < 2 {
+            return None;
+        }
+        if letters.iter().all(|c| !c.is_uppercase()) {
+            Some(Casing::Lower)
+        } else if letters.iter().all(|c| !c.is_lowercase()) {
+            Some(Casing::Upper)
+        } else if letters[0].is_uppercase() {
+            Some(Casing::Capitalised)
+        } else {
+            // "iPhone-style" names are deliberate; leave them alone
+            None
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Casing::Lower => "lower case",
+            Casing::Upper => "upper case",
+            Casing::Capitalised => "capitalised",
+        }
+    }
+}
+
+/// The casing used by more than half of the names, if any.
+fn usual_casing(fruits: &[FruitDimensions]) -> Option<Casing> {
+    let casings: Vec<Casing> = fruits.iter().filter_map(|f| Casing::of(&f.name)).collect();
+    [Casing::Lower, Casing::Upper, Casing::Capitalised]
+        .into_iter()
+        .find(|casing| casings.iter().filter(|c| *c == casing).count() * 2 > casings.len())
+}
@@ -4,26 +4,31 @@
 // This is the main entry point for the fruitdata CLI application.
 // It handles:
 // 1. Parsing command-line arguments (using the `clap` crate)
-// 2. Loading/initializing the fruit catalogue from a JSON file
-// 3. Dispatching to the appropriate command handler (list, get, add, remove)
-// 4. Persisting changes back to the JSON file
+// 2. Loading the config file and choosing the catalogue file (the store)
+// 3. Handing the command to `fruitdata::cli::run_command`, which does the work
+// 4. Printing the command's report (see src/present.rs) and the exit status
+//
+// The commands themselves live in the library (src/cli.rs), so that they can
+// be tested and reused; this file is only the terminal front end.
 //
 // Key concepts:
 // - CLI parsing: Converting strings from the command line into structured data
-// - Pattern matching: Using Rust's `match` to handle different commands
 // - Error handling: Using `Result<T, E>` for functions that can fail
-// - String matching: Case-insensitive fruit name lookups
 // ============================================================================
 
-// Import modules defined in this crate
-mod catalog;
-mod models;
-
-// Import specific items from modules for convenience (saves typing catalog::, models::, etc.)
-use catalog::{initialise_fruit_catalogue, load_catalogue, save_catalogue};
-use clap::{Parser, Subcommand};
-use models::FruitDimensions;
+// Import specific items from the fruitdata library (src/lib.rs) for convenience
+// (saves typing fruitdata::cli::, fruitdata::config::, etc.)
+use clap::{CommandFactory, Parser};
+use fruitdata::cli::{run_command, Commands, Context};
+use fruitdata::complete;
+use fruitdata::config::Config;
+use fruitdata::policy::ConflictPolicy;
+use fruitdata::present::{render_with, Units};
+use fruitdata::progress::ProgressBar;
+use fruitdata::retry::Retrying;
+use fruitdata::store::FileStore;
 use std::error::Error;
+use std::io::{self, BufRead, Write};
 use std::path::PathBuf;
 
 // ============================================================================
@@ -56,7 +61,8 @@ use std::path::PathBuf;
 #[derive(Parser)]
 #[command(author, version, about)] // Auto-generate author/version from Cargo.toml
 struct Cli {
-    /// Path to the fruit catalogue JSON file.
+    /// Path to the fruit catalogue JSON file (or TOML or YAML file, by its
+    /// extension, with the `toml` and `yaml` features).
     /// - Short form: `-f`
     /// - Long form: `--file`
     /// - Default value: `"fruits.json"` if not provided
@@ -68,134 +74,149 @@ struct Cli {
     #[arg(short, long, default_value = "fruits.json")]
     file: PathBuf,
 
-    /// The subcommand to execute (list, get, add, or remove)
+    /// Path to the fruitdata config file (naming rules and other settings).
+    /// A missing file means "use the defaults"; a broken one is an error.
+    #[arg(long, default_value = "fruitdata.config.json")]
+    config: PathBuf,
+
+    /// What to do if another process changed the file while this command ran:
+    /// `abort` (default, keep their changes), `force` (overwrite them), or
+    /// `merge` (combine both, failing if the same fruit was changed twice).
+    #[arg(long, value_name = "POLICY", default_value = "abort")]
+    on_conflict: ConflictPolicy,
+
+    /// Overwrite the file even if another process changed it (same as
+    /// `--on-conflict force`).
+    #[arg(long)]
+    force: bool,
+
+    /// Refuse every command that would change the catalogue. Useful when
+    /// pointing at a published catalogue that only CI may modify.
+    #[arg(long)]
+    read_only: bool,
+
+    /// Fail if the catalogue file has unknown keys or wrongly typed values,
+    /// listing each problem with its path (e.g. in CI, to catch schema
+    /// drift). Without it, unknown keys are kept. A file that can't be read
+    /// at all is an error either way; `fruitdata repair` recovers it.
+    #[arg(long)]
+    strict: bool,
+
+    /// Start the default catalogue if the file doesn't exist, instead of
+    /// failing (so that a mistyped `--file` is noticed). `fruitdata init`
+    /// creates one explicitly.
+    #[arg(long)]
+    create: bool,
+
+    /// Match fruit names exactly, so that "Kiwi" and "KIWI" are different
+    /// fruits. A catalogue can ask for this itself with the
+    /// `"case_sensitive": true` header, which a change made with this flag
+    /// sets.
+    #[arg(long)]
+    case_sensitive: bool,
+
+    /// When `get`, `remove` or `update` names no fruit, offer to use each
+    /// of the closest names instead (without it, they are only suggested).
+    #[arg(long)]
+    interactive: bool,
+
+    /// Who is making the changes, as recorded in the change journal.
+    /// Defaults to the `USER` (or `USERNAME`) environment variable.
+    #[arg(long)]
+    actor: Option<String>,
+
+    /// Don't draw progress bars for long operations (`convert`, `import`).
+    /// They are only drawn when stderr is a terminal anyway.
+    #[arg(long)]
+    no_progress: bool,
+
+    /// Show lengths and volumes in `metric` (cm, cm³, the default) or
+    /// `imperial` (in, in³) units. The catalogue is always stored in
+    /// centimetres, and JSON output stays in them.
+    #[arg(long, value_name = "SYSTEM")]
+    units: Option<Units>,
+
+    /// Show numbers in text and CSV output with this many decimal places
+    /// (default: as many as each number needs).
+    #[arg(long, value_name = "N")]
+    precision: Option<usize>,
+
+    /// The subcommand to execute (list, get, add, remove, or rename)
     /// Subcommands are positional arguments that determine which action to perform
     #[command(subcommand)]
     command: Commands,
 }
 
-/// An enum representing all possible subcommands (actions) the user can request.
-///
-/// In Rust, an `enum` is a type that can have multiple variants (possibilities).
-/// Each variant can have associated data. For example, `Get { name: String }`
-/// means the `Get` variant carries a String containing the fruit name.
-///
-/// # Why use an enum here?
-/// This structure ensures:
-/// - Type safety: The compiler ensures a command variant exists before we use it
-/// - Exhaustiveness: We must handle all possible commands in our match statement
-/// - Clear semantics: The code explicitly shows what actions are possible
-#[derive(Subcommand)]
-enum Commands {
-    /// List all available fruits in the catalogue.
-    /// Command: `fruitdata list`
-    /// Does not require any additional arguments.
-    List,
+/// Ask a yes/no question on the terminal; anything but "y"/"yes" means no.
+fn confirm(question: &str) -> Result<bool, Box<dyn Error>> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
 
-    /// Show detailed information for a specific fruit.
-    /// Command: `fruitdata get AppleName`
-    ///
-    /// The `name` field will be populated with the fruit name provided by the user.
-    /// Example: `fruitdata get Apple` → Get { name: "Apple" }
-    Get {
-        /// The name of the fruit to look up
-        name: String,
-    },
-
-    /// Add a new fruit to the catalogue.
-    /// Command: `fruitdata add "FruitName" 4.0 2.5 1.5`
-    ///
-    /// All fields must be provided in order: name, length, width, height
-    /// The name can contain spaces if quoted (e.g., "Dragon Fruit")
-    Add {
-        /// Name of the fruit (e.g., "Apple", "Dragonfruit")
-        name: String,
-        /// Length dimension (must be a positive number)
-        length: f32,
-        /// Width dimension (must be a positive number)
-        width: f32,
-        /// Height dimension (must be a positive number)
-        height: f32,
-    },
-
-    /// Remove a fruit from the catalogue by name.
-    /// Command: `fruitdata remove AppleName`
-    ///
-    /// After removal, the catalogue is saved back to the JSON file.
-    Remove {
-        /// The name of the fruit to remove
-        name: String,
-    },
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim().to_ascii_lowercase();
+    Ok(answer == "y" || answer == "yes")
 }
 
 // ============================================================================
-// HELPER FUNCTIONS
+// MAIN FUNCTION - Program Entry Point
 // ============================================================================
 
-/// Display detailed information about a fruit in a human-readable format.
-///
-/// This helper function formats a FruitDimensions struct nicely for console output.
-/// It's used by the `Get` command to show fruit details to the user.
-///
-/// # Arguments
-/// - `fruit: &FruitDimensions` - A reference to the fruit to display
-///   (We use & to borrow the data without taking ownership)
+/// The main function is the entry point where the program starts execution.
 ///
-/// # Output format
-/// ```
-/// Name: Apple
-/// Dimensions: 4.0 x 2.5 x 1.5
-/// Volume: 15.0
-/// ```
+/// All the real work happens in `run()`. `main()` only decides how a failure
+/// is reported: it prints the error's human-readable message (its `Display`
+/// form, e.g. "Quota exceeded: ...") and exits with status code 1.
 ///
-/// # Example
-/// ```
-/// let apple = FruitDimensions {
-///     name: "Apple".to_string(),
-///     length: 4.0,
-///     width: 2.5,
-///     height: 1.5,
-/// };
-/// display_fruit_info(&apple);
-/// ```
-fn display_fruit_info(fruit: &FruitDimensions) {
-    println!("Name: {}", fruit.name);
-    println!(
-        "Dimensions: {} x {} x {}",
-        fruit.length, fruit.width, fruit.height
-    );
-    println!("Volume: {}", fruit.volume());
+/// If `main()` returned the `Result` itself, Rust would print the error's
+/// `Debug` form instead, which is meant for programmers rather than users.
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 }
 
-// ============================================================================
-// MAIN FUNCTION - Program Entry Point
-// ============================================================================
-
-/// The main function is the entry point where the program starts execution.
+/// Parse the command line, run the requested command and print its outcome.
 ///
-/// # Why does main() return Result?
+/// # Why does run() return Result?
 /// `Result<(), Box<dyn Error>>` means:
 /// - `Ok(())` - Program executed successfully (no data to return, just success)
 /// - `Err(...)` - An error occurred; the error is boxed (stored on the heap)
 ///
-/// Returning Result from main() is a Rust best practice because:
+/// Returning Result is a Rust best practice because:
 /// 1. It allows us to use the `?` operator for error propagation
-/// 2. If main() returns an error, Rust automatically exits with status code 1
+/// 2. `main()` can report any error in one place and exit with status code 1
 /// 3. It makes error handling cleaner and less verbose
 ///
 /// # Program flow
 /// 1. Parse CLI arguments into a Cli struct
 /// 2. Convert the file path (PathBuf) to a string
-/// 3. Load catalogue from JSON (or initialize a new one if file doesn't exist)
-/// 4. Match on the command and execute the appropriate action
-/// 5. Return Ok(()) on success or propagate errors with ?
-fn main() -> Result<(), Box<dyn Error>> {
+/// 3. Load the config file (or use defaults if it doesn't exist)
+/// 4. Collect the global flags into a `Context`
+/// 5. Run the command (see `fruitdata::cli::run_command`)
+/// 6. Print its output, and fail if the command's verdict was negative
+fn run() -> Result<(), Box<dyn Error>> {
     // ========================================================================
     // STEP 1: Parse command-line arguments
     // ========================================================================
     // Cli::parse() reads std::env::args() and constructs a Cli struct.
     // If parsing fails (e.g., invalid arguments), clap prints an error and exits.
     // If parsing succeeds, we have a fully populated Cli struct.
+    //
+    // `COMPLETE=bash fruitdata` is a request from the shell's completion
+    // instead (see src/complete.rs), with the words to complete after `--`.
+    if let Some(shell) = complete::requested_shell() {
+        let words: Vec<String> = std::env::args().skip_while(|a| a != "--").skip(1).collect();
+        let index = std::env::var(complete::INDEX_VAR)
+            .ok()
+            .and_then(|index| index.parse().ok());
+        println!(
+            "{}",
+            complete::respond(&shell, &Cli::command(), &words, index)?
+        );
+        return Ok(());
+    }
     let cli = Cli::parse();
 
     // ========================================================================
@@ -208,165 +229,86 @@ fn main() -> Result<(), Box<dyn Error>> {
     // - PathBuf might contain invalid UTF-8 characters (rare but possible)
     // - .to_str() returns Option<&str>, which is Some(s) if valid, None if invalid
     // - We use .ok_or_else() to convert None into an error
-    // - Then .to_string() converts &str to String for storage
     let file_path = cli
         .file
         .to_str()
-        .ok_or_else(|| "invalid file path".to_string())?
-        .to_string();
+        .ok_or_else(|| "invalid file path".to_string())?;
 
     // ========================================================================
-    // STEP 3: Load or initialize the catalogue
+    // STEP 3: Load the config file
     // ========================================================================
-    // Try to load the catalogue from the JSON file.
-    // If loading fails (file doesn't exist, corrupted JSON, etc.),
-    // fall back to initializing a new default catalogue.
-    //
-    // After this point, `fruits` contains a list of FruitDimensions structs,
-    // either loaded from disk or freshly initialized.
-    //
-    // We use `mut` (mutable) because some commands (Add, Remove) will modify it.
-    let mut fruits = match load_catalogue(&file_path) {
-        Ok(f) => {
-            // Successfully loaded catalogue from file
-            f
-        }
-        Err(_) => {
-            // File doesn't exist or is corrupted; create a default catalogue
-            eprintln!("Could not load catalogue, initialising a new one.");
-            initialise_fruit_catalogue()
-        }
-    };
+    // The config holds organisation-wide rules such as the name policy.
+    // A missing file gives the defaults; a malformed one stops the program.
+    let config_path = cli
+        .config
+        .to_str()
+        .ok_or_else(|| "invalid config path".to_string())?;
+    let mut config = Config::load_or_default(config_path)?;
 
     // ========================================================================
-    // STEP 4: Dispatch to the appropriate command handler
+    // STEP 4: Collect the global flags
     // ========================================================================
-    // We use Rust's `match` statement to handle each possible command.
-    // The match statement is exhaustive - we must handle all enum variants.
-    // This is part of Rust's safety: the compiler ensures we don't forget a case.
-    //
-    // We match on `&cli.command` (a reference) so we don't move/consume the data.
-    match &cli.command {
-        // ====================================================================
-        // COMMAND: list
-        // ====================================================================
-        // List all fruits in the catalogue (just their names)
-        Commands::List => {
-            println!("--- Available Fruits ---");
-            // Iterate over all fruits; `&fruits` gives us references to each
-            for f in &fruits {
-                println!("{}", f.name);
-            }
-        }
-
-        // ====================================================================
-        // COMMAND: get <name>
-        // ====================================================================
-        // Find and display details for a specific fruit by name
-        Commands::Get { name } => {
-            // Use `iter().find()` to locate the first fruit matching the name.
-            // .find() takes a closure (a small anonymous function) and returns
-            // an Option: Some(fruit) if found, None if not found.
-            //
-            // `eq_ignore_ascii_case()` compares names case-insensitively:
-            // "apple", "Apple", "APPLE" all match.
-            if let Some(fruit) = fruits.iter().find(|f| f.name.eq_ignore_ascii_case(name)) {
-                // Found a matching fruit; display its details
-                display_fruit_info(fruit);
-            } else {
-                // No matching fruit found; inform the user
-                println!("Fruit '{}' not found.", name);
-            }
-        }
-
-        // ====================================================================
-        // COMMAND: add <name> <length> <width> <height>
-        // ====================================================================
-        // Add a new fruit to the catalogue with the given dimensions
-        Commands::Add {
-            name,
-            length,
-            width,
-            height,
-        } => {
-            // Validation 1: Ensure the name is not empty (after trimming whitespace)
-            let name_trimmed = name.trim();
-            if name_trimmed.is_empty() {
-                println!("Name must not be empty.");
-                return Ok(()); // Exit the command; don't add anything
-            }
-
-            // Validation 2: Ensure all dimensions are positive numbers
-            // f32 can be zero or negative, which doesn't make physical sense
-            if *length <= 0.0 || *width <= 0.0 || *height <= 0.0 {
-                println!("Dimensions must be positive numbers.");
-                return Ok(());
-            }
-
-            // Validation 3: Ensure the fruit doesn't already exist (case-insensitive)
-            // We don't allow duplicate fruits in the catalogue
-            if fruits
-                .iter()
-                .any(|f| f.name.eq_ignore_ascii_case(name_trimmed))
-            {
-                println!("Fruit '{}' already exists.", name_trimmed);
-                return Ok(());
-            }
-
-            // All validations passed; create the new fruit struct
-            let fruit = FruitDimensions {
-                name: name_trimmed.to_string(),
-                length: *length, // Dereference (convert &f32 to f32)
-                width: *width,
-                height: *height,
-            };
-
-            // Add the fruit to our in-memory catalogue
-            fruits.push(fruit);
-
-            // Persist the changes to the JSON file
-            // If saving fails, the ? operator will return the error
-            save_catalogue(&fruits, &file_path)?;
-
-            println!("Added '{}'.", name_trimmed);
-        }
-
-        // ====================================================================
-        // COMMAND: remove <name>
-        // ====================================================================
-        // Remove a fruit from the catalogue by name (case-insensitive)
-        Commands::Remove { name } => {
-            // Validation: Ensure the name is not empty (after trimming)
-            let name_trimmed = name.trim();
-            if name_trimmed.is_empty() {
-                println!("Name must not be empty.");
-                return Ok(());
-            }
-
-            // Remember how many fruits we had before removal
-            let before = fruits.len();
-
-            // Remove all fruits matching the name (case-insensitive)
-            // `.retain()` keeps only the fruits for which the closure returns true.
-            // Here, we keep only fruits that DON'T match the name.
-            // This effectively removes matching fruits.
-            fruits.retain(|f| !f.name.eq_ignore_ascii_case(name_trimmed));
+    // `--force` is shorthand for `--on-conflict force`
+    // `--units` and `--precision` override the config's `output` section
+    // (which `export` reads too)
+    config.output.units = cli.units.unwrap_or(config.output.units);
+    config.output.precision = cli.precision.or(config.output.precision);
+    let output = config.output.clone();
+    let conflict_policy = if cli.force {
+        ConflictPolicy::Force
+    } else {
+        cli.on_conflict
+    };
+    let actor = cli
+        .actor
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string());
 
-            // Check if we actually removed anything
-            if fruits.len() < before {
-                // At least one fruit was removed
+    // With `--strict`, a file that exists but doesn't match the schema is an
+    // error as well as one that can't be read at all. Transient
+    // I/O errors (e.g. on a network share) are retried as the config says.
+    let store = Retrying::new(
+        FileStore::new(file_path).with_strict(cli.strict),
+        config.retry.clone(),
+    );
+    let mut context = Context::new(config)
+        .with_actor(actor)
+        .with_read_only(cli.read_only)
+        .with_strict(cli.strict)
+        .with_create(cli.create)
+        .with_interactive(cli.interactive)
+        .with_case_sensitive(cli.case_sensitive)
+        .with_conflict_policy(conflict_policy)
+        .with_confirm(confirm);
+    if let Some(bar) = ProgressBar::stderr().filter(|_| !cli.no_progress) {
+        context = context.with_progress(bar);
+    }
 
-                // Persist the changes to the JSON file
-                save_catalogue(&fruits, &file_path)?;
+    // ========================================================================
+    // STEP 5: Run the command
+    // ========================================================================
+    let outcome = run_command(&cli.command, &store, &mut context)?;
 
-                println!("Removed '{}'.", name_trimmed);
-            } else {
-                // No fruit matched; nothing was removed
-                println!("Fruit '{}' not found.", name_trimmed);
-            }
-        }
+    // ========================================================================
+    // STEP 6: Show the outcome
+    // ========================================================================
+    for note in &outcome.notes {
+        eprintln!("{}", note);
+    }
+    // What the command did is data; `render_with` turns it into the text
+    // (or, with `--format json`, the JSON) to print; `exists` prints nothing
+    let rendered = render_with(&outcome.report, cli.command.output_format(), &output)?;
+    if !rendered.is_empty() {
+        println!("{}", rendered);
+    }
+    for hint in &outcome.hints {
+        eprintln!("{}", hint);
+    }
+    match outcome.failure {
+        Some(failure) => Err(failure.into()),
+        // `exists` answers "no" with its status alone
+        None if outcome.status != 0 => std::process::exit(outcome.status),
+        None => Ok(()),
     }
-
-    // All commands completed successfully
-    Ok(())
 }
@@ -0,0 +1,202 @@
+// ============================================================================
+// measurements.rs - Repeated Measurements of One Fruit
+// ============================================================================
+// A fruit's dimensions are a single reading. Growers tracking fruit on the
+// tree measure the same one again and again, and want each reading kept
+// with its date. A fruit's `measurements` list holds them, oldest first:
+//
+//     { "name": "Apple", "length": 4.4, ...,
+//       "measurements": [
+//         { "taken": "2024-06-01T00:00:00Z", "length": 4.0, "width": 2.5, "height": 1.5 },
+//         { "taken": "2024-06-15T00:00:00Z", "length": 4.4, "width": 2.7, "height": 1.6 }
+//       ] }
+//
+// `fruitdata measure add` records one (the newest also becomes the fruit's
+// dimensions), and `fruitdata growth` shows how the fruit changed between
+// them. The list is left out of files for fruits that have no measurements.
+//
+// `summary` condenses a series into the mean, variance and trend of each
+// dimension, for `fruitdata get --measurements` and for analysing orchard
+// logs in code. Like `stats`, it accumulates in f64.
+// ============================================================================
+
+use crate::length::Centimeters;
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+
+/// One dated reading of a fruit's dimensions.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    /// When the fruit was measured.
+    pub taken: Timestamp,
+    #[serde(deserialize_with = "crate::models::finite")]
+    pub length: Centimeters,
+    #[serde(deserialize_with = "crate::models::finite")]
+    pub width: Centimeters,
+    #[serde(deserialize_with = "crate::models::finite")]
+    pub height: Centimeters,
+}
+
+impl Measurement {
+    /// A measurement taken at `taken`, with dimensions in centimetres.
+    pub fn new(
+        taken: Timestamp,
+        length: impl Into<Centimeters>,
+        width: impl Into<Centimeters>,
+        height: impl Into<Centimeters>,
+    ) -> Self {
+        Measurement {
+            taken,
+            length: length.into(),
+            width: width.into(),
+            height: height.into(),
+        }
+    }
+
+    /// The volume at the time, computed as for `FruitDimensions::volume`
+    /// without a formula.
+    pub fn volume(&self) -> f32 {
+        self.length.value() * self.width.value() * self.height.value()
+    }
+}
+
+/// One step of a fruit's growth: a measurement, and how it differs from the
+/// one before.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct GrowthStep {
+    #[serde(flatten)]
+    pub measurement: Measurement,
+    pub volume: f32,
+    /// The change in volume since the previous measurement, as a fraction
+    /// (0.1 for 10% bigger); `None` for the first one.
+    pub volume_change: Option<f32>,
+    /// Days since the previous measurement; `None` for the first one.
+    pub days: Option<f32>,
+}
+
+/// How a fruit grew from one measurement to the next, oldest first.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::measurements::{growth, Measurement};
+///
+/// let day = |d: u64| fruitdata::timestamp::Timestamp(d * 86_400);
+/// let series = [
+///     Measurement::new(day(0), 4.0, 2.5, 1.0),
+///     Measurement::new(day(7), 4.0, 2.5, 1.5),
+/// ];
+/// let steps = growth(&series);
+/// assert_eq!(steps[0].volume_change, None);
+/// assert_eq!(steps[1].volume, 15.0);
+/// assert_eq!(steps[1].volume_change, Some(0.5)); // 10 -> 15
+/// assert_eq!(steps[1].days, Some(7.0));
+/// ```
+pub fn growth(measurements: &[Measurement]) -> Vec<GrowthStep> {
+    let mut previous: Option<&Measurement> = None;
+    measurements
+        .iter()
+        .map(|measurement| {
+            let volume = measurement.volume();
+            let step = GrowthStep {
+                measurement: *measurement,
+                volume,
+                volume_change: previous
+                    .filter(|before| before.volume() > 0.0)
+                    .map(|before| volume / before.volume() - 1.0),
+                days: previous.map(|before| {
+                    measurement.taken.0.saturating_sub(before.taken.0) as f32 / 86_400.0
+                }),
+            };
+            previous = Some(measurement);
+            step
+        })
+        .collect()
+}
+
+/// The mean, spread and trend of one dimension over a series of
+/// measurements.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct DimensionSummary {
+    pub mean: f32,
+    /// The sample variance (dividing by n - 1); `None` for a single
+    /// measurement.
+    pub variance: Option<f32>,
+    /// The slope of the least-squares line through the measurements, in
+    /// units per day; `None` unless they were taken at different times.
+    pub trend: Option<f32>,
+}
+
+impl DimensionSummary {
+    /// Summarise the `(days, value)` points of one dimension.
+    fn of(points: &[(f64, f64)]) -> Self {
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+        for (x, y) in points {
+            sxx += (x - mean_x) * (x - mean_x);
+            sxy += (x - mean_x) * (y - mean_y);
+            syy += (y - mean_y) * (y - mean_y);
+        }
+        DimensionSummary {
+            mean: mean_y as f32,
+            variance: (points.len() > 1).then(|| (syy / (n - 1.0)) as f32),
+            trend: (sxx > 0.0).then(|| (sxy / sxx) as f32),
+        }
+    }
+}
+
+/// What a fruit's series of measurements adds up to.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::measurements::{summary, Measurement};
+///
+/// let day = |d: u64| fruitdata::timestamp::Timestamp(d * 86_400);
+/// let series = [
+///     Measurement::new(day(0), 4.0, 2.5, 1.0),
+///     Measurement::new(day(10), 5.0, 2.5, 1.5),
+///     Measurement::new(day(20), 6.0, 2.5, 2.0),
+/// ];
+/// let summary = summary(&series).unwrap();
+/// assert_eq!(summary.count, 3);
+/// assert_eq!(summary.length.mean, 5.0);
+/// assert_eq!(summary.length.variance, Some(1.0));
+/// assert_eq!(summary.length.trend, Some(0.1)); // 1 cm every 10 days
+/// assert_eq!(summary.width.trend, Some(0.0));
+/// ```
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    /// When the oldest measurement was taken.
+    pub first: Timestamp,
+    /// When the newest measurement was taken.
+    pub last: Timestamp,
+    pub length: DimensionSummary,
+    pub width: DimensionSummary,
+    pub height: DimensionSummary,
+}
+
+/// Summarise `measurements`, in any order; `None` if there are none.
+pub fn summary(measurements: &[Measurement]) -> Option<Summary> {
+    let first = measurements.iter().map(|m| m.taken).min()?;
+    let last = measurements.iter().map(|m| m.taken).max()?;
+    let dimension = |value: fn(&Measurement) -> f32| {
+        let points: Vec<(f64, f64)> = measurements
+            .iter()
+            .map(|m| {
+                let days = (m.taken.0 - first.0) as f64 / 86_400.0;
+                (days, f64::from(value(m)))
+            })
+            .collect();
+        DimensionSummary::of(&points)
+    };
+    Some(Summary {
+        count: measurements.len(),
+        first,
+        last,
+        length: dimension(|m| m.length.value()),
+        width: dimension(|m| m.width.value()),
+        height: dimension(|m| m.height.value()),
+    })
+}
@@ -0,0 +1,193 @@
+// ============================================================================
+// merge.rs - Three-Way Merge of Catalogue Edits
+// ============================================================================
+// When two processes edit the same catalogue file at once, the second save
+// would normally overwrite the first one's changes. A three-way merge avoids
+// that by comparing three versions of the data:
+//
+// - base:   the fruits as they were when *we* loaded the file
+// - ours:   the fruits as we want to save them
+// - theirs: the fruits currently in the file (written by someone else)
+//
+// For every fruit, whoever changed it relative to `base` wins. If both sides
+// changed the same fruit in different ways, that's a conflict and the merge
+// fails rather than guessing.
+//
+// In ID mode each side gives the fruits it adds the next free ID, so two
+// processes that add a fruit at once give both the same one. Those are two
+// new fruits, not one fruit changed twice, so ours is renumbered rather
+// than reported as a conflict.
+//
+// Key concept: This is the same idea `git merge` uses for lines of text,
+// applied to catalogue records identified by ID (or by name when they have
+// no ID).
+// ============================================================================
+
+use crate::models::FruitDimensions;
+use crate::names::CaseSensitivity;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+/// The identity used to match up the same fruit across the three versions.
+pub(crate) fn record_key(fruit: &FruitDimensions) -> String {
+    record_key_in(fruit, CaseSensitivity::Insensitive)
+}
+
+/// `record_key` for a catalogue whose names match with `case`.
+pub(crate) fn record_key_in(fruit: &FruitDimensions, case: CaseSensitivity) -> String {
+    match fruit.id {
+        Some(id) => format!("#{}", id),
+        None => case.fold(&fruit.name),
+    }
+}
+
+/// Merge our edits and theirs, both made on top of `base`.
+///
+/// The result keeps the order of `theirs`, with fruits that only we added
+/// appended at the end in our order.
+///
+/// # Returns
+/// - `Ok(Vec<FruitDimensions>)` - The merged fruits
+/// - `Err(Vec<String>)` - The names of the fruits both sides changed differently
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::merge::three_way_merge;
+/// use fruitdata::models::FruitDimensions;
+///
+/// let base = vec![FruitDimensions::new("Apple", 4.0, 2.5, 1.5)];
+/// let ours = vec![
+///     FruitDimensions::new("Apple", 4.0, 2.5, 1.5),
+///     FruitDimensions::new("Kiwi", 3.0, 2.0, 2.0), // we added Kiwi
+/// ];
+/// let theirs = vec![FruitDimensions::new("Apple", 4.4, 2.5, 1.5)]; // they resized Apple
+///
+/// let merged = three_way_merge(&base, &ours, &theirs).unwrap();
+/// assert_eq!(merged[0].length, 4.4);
+/// assert_eq!(merged[1].name, "Kiwi");
+/// ```
+///
+/// In ID mode, fruits both sides added with the same new ID are both kept:
+/// ```
+/// use fruitdata::merge::three_way_merge;
+/// use fruitdata::models::FruitDimensions;
+///
+/// let with_id = |name: &str, id: u64| FruitDimensions { id: Some(id), ..FruitDimensions::new(name, 3.0, 2.0, 2.0) };
+/// let base = vec![with_id("Apple", 1)];
+/// let ours = vec![with_id("Apple", 1), with_id("Kiwi", 2)];
+/// let theirs = vec![with_id("Apple", 1), with_id("Fig", 2)];
+///
+/// let merged = three_way_merge(&base, &ours, &theirs).unwrap();
+/// let entries: Vec<(&str, Option<u64>)> = merged.iter().map(|f| (f.name.as_str(), f.id)).collect();
+/// assert_eq!(entries, [("Apple", Some(1)), ("Fig", Some(2)), ("Kiwi", Some(3))]);
+/// ```
+pub fn three_way_merge(
+    base: &[FruitDimensions],
+    ours: &[FruitDimensions],
+    theirs: &[FruitDimensions],
+) -> Result<Vec<FruitDimensions>, Vec<String>> {
+    three_way_merge_in(base, ours, theirs, CaseSensitivity::Insensitive)
+}
+
+/// `three_way_merge` for a catalogue whose names match with `case`.
+pub(crate) fn three_way_merge_in(
+    base: &[FruitDimensions],
+    ours: &[FruitDimensions],
+    theirs: &[FruitDimensions],
+    case: CaseSensitivity,
+) -> Result<Vec<FruitDimensions>, Vec<String>> {
+    let ours = renumber_new_ids(base, ours, theirs);
+    let ours = ours.as_ref();
+    let record_key = |fruit: &FruitDimensions| record_key_in(fruit, case);
+    let index = |fruits: &[FruitDimensions]| -> HashMap<String, FruitDimensions> {
+        fruits.iter().map(|f| (record_key(f), f.clone())).collect()
+    };
+    let base_map = index(base);
+    let ours_map = index(ours);
+    let theirs_map = index(theirs);
+
+    // Visit every key once: theirs first (to keep the file's order), then
+    // the ones only we know about
+    let mut keys: Vec<String> = theirs.iter().map(record_key).collect();
+    for key in ours.iter().map(record_key) {
+        if !theirs_map.contains_key(&key) {
+            keys.push(key);
+        }
+    }
+
+    let mut merged = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for key in keys {
+        let b = base_map.get(&key);
+        let o = ours_map.get(&key);
+        let t = theirs_map.get(&key);
+
+        let winner = if o == b {
+            // We didn't touch it: take their version (which may be a removal)
+            t
+        } else if t == b || o == t {
+            // Only we changed it, or we both made the same change
+            o
+        } else {
+            // Both changed it differently
+            let name = o.or(t).or(b).map(|f| f.name.clone()).unwrap_or(key);
+            conflicts.push(name);
+            continue;
+        };
+
+        if let Some(fruit) = winner {
+            merged.push(fruit.clone());
+        }
+    }
+
+    if conflicts.is_empty() {
+        Ok(merged)
+    } else {
+        Err(conflicts)
+    }
+}
+
+/// `ours`, with each fruit we added renumbered if they added a different
+/// fruit with the same ID, to the IDs after the highest one in use.
+fn renumber_new_ids<'a>(
+    base: &[FruitDimensions],
+    ours: &'a [FruitDimensions],
+    theirs: &[FruitDimensions],
+) -> Cow<'a, [FruitDimensions]> {
+    let base_ids: HashSet<u64> = base.iter().filter_map(|f| f.id).collect();
+    let theirs_new: HashMap<u64, &FruitDimensions> = theirs
+        .iter()
+        .filter_map(|f| f.id.filter(|id| !base_ids.contains(id)).map(|id| (id, f)))
+        .collect();
+    let clashes = |fruit: &FruitDimensions| {
+        fruit
+            .id
+            .filter(|id| !base_ids.contains(id))
+            .and_then(|id| theirs_new.get(&id))
+            .is_some_and(|added| *added != fruit)
+    };
+    if !ours.iter().any(clashes) {
+        return Cow::Borrowed(ours);
+    }
+
+    let mut next = base
+        .iter()
+        .chain(ours)
+        .chain(theirs)
+        .filter_map(|f| f.id)
+        .max()
+        .map_or(1, |id| id + 1);
+    let renumbered = ours
+        .iter()
+        .map(|fruit| {
+            let mut fruit = fruit.clone();
+            if clashes(&fruit) {
+                fruit.id = Some(next);
+                next += 1;
+            }
+            fruit
+        })
+        .collect();
+    Cow::Owned(renumbered)
+}
@@ -3,10 +3,21 @@
 // ============================================================================
 // This module defines the core data structures used by the fruitdata CLI.
 // Specifically, it defines the `FruitDimensions` struct and implements the
-// `volume()` method to calculate the volume of a fruit.
+// `volume()` method to calculate the volume of a fruit. It also defines
+// `FruitPatch`, which describes a change to some of a fruit's fields, and
+// `FieldNaming`, which chooses the JSON field names fruits are written with.
+// The proportions of a fruit are described by its `AspectRatios`.
 // ============================================================================
 
-use serde::{Deserialize, Serialize};
+use crate::expr::Expression;
+use crate::length::Centimeters;
+use crate::measurements::Measurement;
+use crate::stats::{SizeClass, SizeClasses};
+use serde::de::Error as _;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::fmt;
 
 /// A struct that represents a single fruit's dimensions and metadata.
 ///
@@ -23,27 +34,170 @@ use serde::{Deserialize, Serialize};
 ///
 /// - `Clone`: Allows creating copies of FruitDimensions instances. Useful when
 ///   we need to pass data without moving ownership.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+///
+/// - `PartialEq`: Allows comparing two fruits with `==`, field by field.
+///
+/// The dimension fields also accept the names other systems use for them:
+/// `lengthCm` (camelCase, as produced by our JavaScript services),
+/// `length_cm` (snake_case) and the old short `len`, and likewise for width
+/// (`w`) and height (`h`); see compat.rs for the full list and the policy
+/// for renaming fields. Files are written with the plain names unless
+/// `SaveOptions::field_naming` says otherwise.
+///
+/// A dimension that isn't a finite number (NaN, infinity, or too large for
+/// an `f32`) is rejected when loading, so a loaded fruit's box volume is
+/// never NaN:
+///
+/// ```
+/// # use fruitdata::models::FruitDimensions;
+/// let json = r#"{"name": "Apple", "length": 4.0, "width": 1e39, "height": 1.5}"#;
+/// let error = serde_json::from_str::<FruitDimensions>(json).unwrap_err();
+/// assert!(error.to_string().contains("finite number"));
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FruitDimensions {
     /// The name of the fruit (e.g., "Apple", "Orange", "Banana").
     /// This is used to uniquely identify fruits in the catalogue.
-    /// Names are case-insensitive when matching (handled in main.rs).
+    /// Names are case-insensitive when matching (see `names::names_equal`).
     pub name: String,
 
-    /// The length of the fruit in arbitrary units (typically centimeters).
+    /// The length of the fruit, in centimetres (see length.rs).
     /// Used in volume calculations and displayed to the user.
-    pub length: f32,
+    #[serde(
+        alias = "len",
+        alias = "lengthCm",
+        alias = "length_cm",
+        deserialize_with = "finite"
+    )]
+    pub length: Centimeters,
 
-    /// The width of the fruit in arbitrary units (typically centimeters).
+    /// The width of the fruit, in centimetres (see length.rs).
     /// Used in volume calculations and displayed to the user.
-    pub width: f32,
+    #[serde(
+        alias = "w",
+        alias = "widthCm",
+        alias = "width_cm",
+        deserialize_with = "finite"
+    )]
+    pub width: Centimeters,
 
-    /// The height of the fruit in arbitrary units (typically centimeters).
+    /// The height of the fruit, in centimetres (see length.rs).
     /// Used in volume calculations and displayed to the user.
-    pub height: f32,
+    #[serde(
+        alias = "h",
+        alias = "heightCm",
+        alias = "height_cm",
+        deserialize_with = "finite"
+    )]
+    pub height: Centimeters,
+
+    /// An optional numeric identifier for this entry.
+    ///
+    /// Catalogues in the default mode don't need IDs, because names are
+    /// unique. In ID mode (`"uniqueness": "id"` in the config) several
+    /// entries may share a name, and the ID tells them apart; `Catalogue`
+    /// assigns one to every fruit automatically.
+    ///
+    /// `#[serde(default)]` lets files without IDs load (the field becomes
+    /// `None`), and `skip_serializing_if` keeps them out of saved files when
+    /// there is nothing to write.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+
+    /// A formula for this fruit's volume, used by `volume()` instead of the
+    /// box formula, e.g. `0.52 * l * w * h` for a near-ellipsoid. See
+    /// `expr::Expression` for what a formula may contain. Boxed, as most
+    /// fruits don't have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_formula: Option<Box<Expression>>,
+
+    /// Dated readings of the fruit's dimensions, oldest first (see
+    /// measurements.rs). Empty, and left out of the file, for most fruits.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub measurements: Vec<Measurement>,
+
+    /// Fields fruitdata doesn't know about, kept exactly as they were read.
+    ///
+    /// Richer tools may store more per fruit than fruitdata understands
+    /// (`"origin"`, `"tags"`, ...). `#[serde(flatten)]` collects every
+    /// unrecognised key here when loading and writes them back when saving,
+    /// so editing such a file with fruitdata doesn't lose that data.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Every JSON key `FruitDimensions` understands, apart from the aliases of
+/// the dimension fields (see `compat::FIELD_ALIASES`). Used by strict
+/// loading to spot unknown keys, so keep it in step with the struct's fields.
+pub(crate) const FRUIT_KEYS: &[&str] = &[
+    "name",
+    "length",
+    "width",
+    "height",
+    "id",
+    "volume_formula",
+    "measurements",
+];
+
+/// Deserialize a dimension, rejecting NaN, infinity and numbers too large
+/// for an `f32` (which would become infinity). JSON itself can't spell NaN,
+/// but other sources of a `Deserializer` can.
+pub(crate) fn finite<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: From<f32>,
+{
+    let value = f64::deserialize(deserializer)?;
+    let narrowed = value as f32;
+    if !narrowed.is_finite() {
+        return Err(D::Error::custom(format!(
+            "a dimension must be a finite number that fits in a 32-bit float, found {:e}",
+            value
+        )));
+    }
+    Ok(narrowed.into())
+}
+
+/// `finite` for an optional dimension.
+fn finite_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    #[derive(Deserialize)]
+    struct Finite(#[serde(deserialize_with = "finite")] f32);
+
+    Ok(Option::<Finite>::deserialize(deserializer)?.map(|Finite(value)| value))
 }
 
 impl FruitDimensions {
+    /// Create a fruit from its name and dimensions, with no ID.
+    ///
+    /// This is shorthand for a struct literal that leaves every optional
+    /// field (and `extra`) empty, so callers don't need updating when new optional fields
+    /// are added to the struct.
+    ///
+    /// # Example
+    /// ```
+    /// # use fruitdata::models::FruitDimensions;
+    /// let apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+    /// assert_eq!(apple.name, "Apple");
+    /// assert_eq!(apple.id, None);
+    /// ```
+    pub fn new(
+        name: impl Into<String>,
+        length: impl Into<Centimeters>,
+        width: impl Into<Centimeters>,
+        height: impl Into<Centimeters>,
+    ) -> Self {
+        FruitDimensions {
+            name: name.into(),
+            length: length.into(),
+            width: width.into(),
+            height: height.into(),
+            id: None,
+            volume_formula: None,
+            measurements: Vec::new(),
+            extra: Map::new(),
+        }
+    }
+
     /// Calculates the approximate volume of the fruit.
     ///
     /// This method computes the volume by multiplying all three dimensions:
@@ -53,20 +207,395 @@ impl FruitDimensions {
     /// approximation. In reality, fruits are irregular shapes, but this gives
     /// a rough estimate of size.
     ///
+    /// A fruit with a `volume_formula` uses that instead; in the formula,
+    /// `volume` means the box volume. If the formula can't be computed
+    /// (it divides by zero), the box volume is used.
+    ///
     /// # Returns
     /// An `f32` value representing the computed volume.
     ///
     /// # Example
     /// ```
-    /// let apple = FruitDimensions {
-    ///     name: "Apple".to_string(),
-    ///     length: 4.0,
-    ///     width: 2.5,
-    ///     height: 1.5,
-    /// };
+    /// # use fruitdata::models::FruitDimensions;
+    /// let mut apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
     /// assert_eq!(apple.volume(), 15.0); // 4.0 * 2.5 * 1.5 = 15.0
+    ///
+    /// apple.volume_formula = Some(Box::new("0.5 * volume".parse().unwrap()));
+    /// assert_eq!(apple.volume(), 7.5);
     /// ```
     pub fn volume(&self) -> f32 {
-        self.length * self.width * self.height
+        let box_volume = self.length.value() * self.width.value() * self.height.value();
+        match &self.volume_formula {
+            Some(formula) => formula
+                .evaluate_with_volume(self, box_volume)
+                .unwrap_or(box_volume),
+            None => box_volume,
+        }
+    }
+
+    /// How the fruit's dimensions compare with each other, whichever way
+    /// round it was measured.
+    ///
+    /// # Example
+    /// ```
+    /// # use fruitdata::models::FruitDimensions;
+    /// let banana = FruitDimensions::new("Banana", 3.0, 18.0, 3.6);
+    /// let ratios = banana.aspect_ratios();
+    /// assert_eq!(ratios.elongation, 5.0); // 18 / 3.6
+    /// assert!((ratios.flatness - 1.2).abs() < 1e-6); // 3.6 / 3
+    /// ```
+    pub fn aspect_ratios(&self) -> AspectRatios {
+        let mut sizes = [self.length, self.width, self.height];
+        sizes.sort_by(|a, b| b.total_cmp(a));
+        let [largest, middle, smallest] = sizes;
+        AspectRatios {
+            elongation: largest / middle,
+            flatness: middle / smallest,
+        }
+    }
+
+    /// Whether the fruit's largest dimension is at least `threshold` times
+    /// the next largest (a banana is, at 1.5; an orange isn't).
+    ///
+    /// # Example
+    /// ```
+    /// # use fruitdata::models::FruitDimensions;
+    /// let banana = FruitDimensions::new("Banana", 18.0, 3.6, 3.0);
+    /// assert!(banana.is_elongated(1.5));
+    /// assert!(!banana.is_elongated(6.0));
+    /// ```
+    pub fn is_elongated(&self, threshold: f32) -> bool {
+        self.aspect_ratios().elongation >= threshold
+    }
+
+    /// The fruit's size class by volume, with the cut-offs in `classes`
+    /// (the config file's `size_classes`).
+    ///
+    /// # Example
+    /// ```
+    /// # use fruitdata::models::FruitDimensions;
+    /// use fruitdata::stats::{SizeClass, SizeClasses};
+    ///
+    /// let classes = SizeClasses::default(); // medium from 20 cm³, large from 100 cm³
+    /// assert_eq!(FruitDimensions::new("Apple", 4.0, 2.5, 1.5).size_class(&classes), SizeClass::Small);
+    /// assert_eq!(FruitDimensions::new("Banana", 6.0, 3.5, 2.5).size_class(&classes), SizeClass::Medium);
+    /// assert_eq!(FruitDimensions::new("Melon", 20.0, 15.0, 15.0).size_class(&classes), SizeClass::Large);
+    /// ```
+    pub fn size_class(&self, classes: &SizeClasses) -> SizeClass {
+        classes.classify(self.volume())
+    }
+
+    /// List the fields (name, dimensions and any others) whose values differ
+    /// in `other`.
+    ///
+    /// # Example
+    /// ```
+    /// # use fruitdata::models::FruitDimensions;
+    /// let before = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+    /// let mut after = FruitDimensions::new("Apple", 4.0, 2.8, 1.5);
+    /// after.extra.insert("grade".into(), "A".into());
+    ///
+    /// let changes = before.changes_to(&after);
+    /// assert_eq!(changes.len(), 2);
+    /// assert_eq!(changes[0].to_string(), "width 2.5 -> 2.8");
+    /// assert_eq!(changes[1].to_string(), "grade none -> \"A\"");
+    /// assert_eq!(changes[0].after, serde_json::json!(2.8));
+    /// ```
+    pub fn changes_to(&self, other: &FruitDimensions) -> Vec<FieldChange> {
+        let mut changes = Vec::new();
+        if self.name != other.name {
+            changes.push(FieldChange::new(
+                "name",
+                self.name.as_str(),
+                other.name.as_str(),
+            ));
+        }
+        let dimensions = [
+            ("length", self.length, other.length),
+            ("width", self.width, other.width),
+            ("height", self.height, other.height),
+        ];
+        for (field, before, after) in dimensions {
+            if before != after {
+                changes.push(FieldChange::new(
+                    field,
+                    dimension_value(before),
+                    dimension_value(after),
+                ));
+            }
+        }
+        if self.volume_formula != other.volume_formula {
+            let describe = |formula: &Option<Box<Expression>>| match formula {
+                Some(formula) => Value::String(formula.to_string()),
+                None => Value::Null,
+            };
+            changes.push(FieldChange::new(
+                "volume_formula",
+                describe(&self.volume_formula),
+                describe(&other.volume_formula),
+            ));
+        }
+        if self.measurements != other.measurements {
+            changes.push(FieldChange::new(
+                "measurements",
+                self.measurements.len(),
+                other.measurements.len(),
+            ));
+        }
+        let mut keys: Vec<&String> = self.extra.keys().chain(other.extra.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            let (before, after) = (self.extra.get(key), other.extra.get(key));
+            if before != after {
+                changes.push(FieldChange::new(
+                    key.clone(),
+                    before.cloned().unwrap_or(Value::Null),
+                    after.cloned().unwrap_or(Value::Null),
+                ));
+            }
+        }
+        changes
+    }
+}
+
+/// How a fruit's dimensions compare, from `FruitDimensions::aspect_ratios`.
+/// Both ratios are at least 1; a sphere has 1 and 1.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct AspectRatios {
+    /// The largest dimension over the middle one: how elongated the fruit
+    /// is.
+    pub elongation: f32,
+    /// The middle dimension over the smallest one: how flat it is.
+    pub flatness: f32,
+}
+
+/// One field that differs between two versions of a fruit, as produced by
+/// `FruitDimensions::changes_to`. Displays as e.g. `width 2.5 -> 2.8`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// The field's name, as in the JSON file (`"name"`, `"length"`, ...).
+    pub field: String,
+    /// The old value, as in the JSON file; `null` if the fruit had none
+    /// (a custom field, or `volume_formula`). For `measurements`, how many
+    /// there were.
+    pub before: Value,
+    /// The new value, like `before`.
+    pub after: Value,
+}
+
+impl FieldChange {
+    fn new(field: impl Into<String>, before: impl Into<Value>, after: impl Into<Value>) -> Self {
+        FieldChange {
+            field: field.into(),
+            before: before.into(),
+            after: after.into(),
+        }
+    }
+}
+
+impl fmt::Display for FieldChange {
+    /// The fruit's own text fields are shown as they are, custom fields as
+    /// JSON (so `"4011"` and `4011` can be told apart), and a missing value
+    /// as `none`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Value| match value {
+            Value::Null => "none".to_string(),
+            Value::String(text) if matches!(self.field.as_str(), "name" | "volume_formula") => {
+                text.clone()
+            }
+            other => other.to_string(),
+        };
+        write!(
+            f,
+            "{} {} -> {}",
+            self.field,
+            show(&self.before),
+            show(&self.after)
+        )
+    }
+}
+
+/// A dimension as a JSON number with the f32's own digits: `to_value` would
+/// widen 2.8 to 2.799999952316284.
+fn dimension_value(cm: Centimeters) -> Value {
+    serde_json::from_str(&cm.to_string()).unwrap_or(Value::Null)
+}
+
+/// A partial update to a `FruitDimensions`: every field is optional, and only
+/// the fields that are `Some` are changed.
+///
+/// `FruitPatch` deserializes from a JSON Merge Patch document (RFC 7396),
+/// e.g. `{"length": 4.5}`. Because every field of a fruit is required, a
+/// `null` value can't delete anything and is treated the same as leaving the
+/// key out. Unknown keys are rejected, so a typo like `"lenght"` is an error
+/// rather than a silent no-op.
+///
+/// Apply a patch with `Catalogue::apply_patch`, which validates the result
+/// (positive dimensions, name rules, no duplicates) before changing anything.
+///
+/// # Example
+/// ```
+/// # use fruitdata::models::{FruitDimensions, FruitPatch};
+/// let patch: FruitPatch = serde_json::from_str(r#"{"height": 2.0}"#).unwrap();
+///
+/// let mut apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+/// patch.apply_to(&mut apple);
+/// assert_eq!(apple.height, 2.0);
+/// assert_eq!(apple.length, 4.0); // untouched
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FruitPatch {
+    /// A new name for the fruit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// A new length.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "len",
+        alias = "lengthCm",
+        alias = "length_cm",
+        deserialize_with = "finite_option"
+    )]
+    pub length: Option<f32>,
+
+    /// A new width.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "w",
+        alias = "widthCm",
+        alias = "width_cm",
+        deserialize_with = "finite_option"
+    )]
+    pub width: Option<f32>,
+
+    /// A new height.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "h",
+        alias = "heightCm",
+        alias = "height_cm",
+        deserialize_with = "finite_option"
+    )]
+    pub height: Option<f32>,
+}
+
+impl FruitPatch {
+    /// Returns `true` if the patch wouldn't change anything.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.length.is_none()
+            && self.width.is_none()
+            && self.height.is_none()
+    }
+
+    /// Copy every `Some` field of the patch onto `fruit`, without validation.
+    pub fn apply_to(&self, fruit: &mut FruitDimensions) {
+        if let Some(name) = &self.name {
+            fruit.name = name.clone();
+        }
+        if let Some(length) = self.length {
+            fruit.length = length.into();
+        }
+        if let Some(width) = self.width {
+            fruit.width = width.into();
+        }
+        if let Some(height) = self.height {
+            fruit.height = height.into();
+        }
+    }
+}
+
+/// The JSON field names used when writing fruits.
+///
+/// Reading always accepts all three styles (see `FruitDimensions`); this
+/// only affects what is written, so files can be produced for systems that
+/// expect a particular style.
+///
+/// | Style        | Dimension fields                      |
+/// |--------------|---------------------------------------|
+/// | `plain`      | `length`, `width`, `height`           |
+/// | `camelCase`  | `lengthCm`, `widthCm`, `heightCm`     |
+/// | `snake_case` | `length_cm`, `width_cm`, `height_cm`  |
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldNaming {
+    /// The field names of the `FruitDimensions` struct (the default).
+    #[default]
+    #[serde(rename = "plain")]
+    Plain,
+    #[serde(rename = "camelCase")]
+    CamelCase,
+    #[serde(rename = "snake_case")]
+    SnakeCase,
+}
+
+impl FieldNaming {
+    /// The names of the length, width and height fields in this style.
+    fn dimension_keys(self) -> [&'static str; 3] {
+        match self {
+            FieldNaming::Plain => ["length", "width", "height"],
+            FieldNaming::CamelCase => ["lengthCm", "widthCm", "heightCm"],
+            FieldNaming::SnakeCase => ["length_cm", "width_cm", "height_cm"],
+        }
+    }
+
+    /// Wrap a fruit so that it serializes with this style's field names.
+    ///
+    /// # Example
+    /// ```
+    /// # use fruitdata::models::{FieldNaming, FruitDimensions};
+    /// let apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+    /// let json = serde_json::to_string(&FieldNaming::CamelCase.named(&apple)).unwrap();
+    /// assert_eq!(json, r#"{"name":"Apple","lengthCm":4.0,"widthCm":2.5,"heightCm":1.5}"#);
+    ///
+    /// let back: FruitDimensions = serde_json::from_str(&json).unwrap();
+    /// assert_eq!(back, apple);
+    /// ```
+    pub fn named(self, fruit: &FruitDimensions) -> NamedFields<'_> {
+        NamedFields {
+            fruit,
+            naming: self,
+        }
+    }
+}
+
+/// A fruit paired with the field names to serialize it with; created by
+/// `FieldNaming::named`.
+#[derive(Debug, Clone, Copy)]
+pub struct NamedFields<'a> {
+    fruit: &'a FruitDimensions,
+    naming: FieldNaming,
+}
+
+impl Serialize for NamedFields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fruit = self.fruit;
+        let [length, width, height] = self.naming.dimension_keys();
+
+        // A map rather than a struct, because the `extra` keys are only
+        // known at runtime
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("name", &fruit.name)?;
+        map.serialize_entry(length, &fruit.length)?;
+        map.serialize_entry(width, &fruit.width)?;
+        map.serialize_entry(height, &fruit.height)?;
+        if let Some(id) = fruit.id {
+            map.serialize_entry("id", &id)?;
+        }
+        if let Some(formula) = &fruit.volume_formula {
+            map.serialize_entry("volume_formula", formula)?;
+        }
+        if !fruit.measurements.is_empty() {
+            map.serialize_entry("measurements", &fruit.measurements)?;
+        }
+        for (key, value) in &fruit.extra {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
     }
 }
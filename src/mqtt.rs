@@ -0,0 +1,355 @@
+// ============================================================================
+// mqtt.rs - Subscribing to an MQTT Broker (feature "mqtt")
+// ============================================================================
+// Measuring stations publish their readings to an MQTT broker, and
+// `fruitdata ingest-mqtt` subscribes to them (see ingest.rs for what a
+// reading does to the catalogue).
+//
+// Only the small part of MQTT 3.1.1 a subscriber needs is implemented here,
+// over a plain `TcpStream`: connect (optionally with a user name and
+// password), subscribe to one topic filter, receive messages, and keep the
+// connection alive with pings. Messages are received with at-least-once
+// delivery (QoS 1): each one is acknowledged with `acknowledge` once it has
+// been dealt with, so the broker can send it again if we stop first.
+// There is no TLS; run a local bridge (e.g. stunnel) for brokers that need
+// it.
+//
+// Every packet starts with a fixed header: one byte of packet type and
+// flags, then the length of the rest as a variable-length integer (7 bits
+// per byte, low bits first). Strings are a big-endian u16 length followed
+// by UTF-8.
+// ============================================================================
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const PINGREQ: u8 = 0xC0;
+const PINGRESP: u8 = 0xD0;
+const DISCONNECT: u8 = 0xE0;
+
+/// How to reach the broker. `MqttOptions::new` starts without credentials
+/// and with a 30-second keep-alive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttOptions {
+    broker: String,
+    client_id: String,
+    username: Option<String>,
+    password: Option<String>,
+    keep_alive: Duration,
+}
+
+impl MqttOptions {
+    /// Connect to `broker` (`host:port`) as `client_id`, which the broker
+    /// uses to tell its clients apart.
+    pub fn new(broker: impl Into<String>, client_id: impl Into<String>) -> Self {
+        MqttOptions {
+            broker: broker.into(),
+            client_id: client_id.into(),
+            username: None,
+            password: None,
+            keep_alive: Duration::from_secs(30),
+        }
+    }
+
+    /// Log in with `username` and, if given, `password`.
+    pub fn with_credentials(
+        mut self,
+        username: impl Into<String>,
+        password: Option<String>,
+    ) -> Self {
+        self.username = Some(username.into());
+        self.password = password;
+        self
+    }
+
+    /// Ping the broker when the connection has been idle this long (whole
+    /// seconds, at least one).
+    pub fn with_keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.keep_alive = keep_alive.max(Duration::from_secs(1));
+        self
+    }
+}
+
+/// A message received from the broker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// The topic it was published to.
+    pub topic: String,
+    pub payload: Vec<u8>,
+    /// The ID to acknowledge it with, for messages sent with QoS 1.
+    packet_id: Option<u16>,
+}
+
+/// A connection to an MQTT broker that receives messages.
+///
+/// # Example Usage
+/// A broker that sends one message, played by a thread:
+/// ```
+/// use fruitdata::mqtt::{MqttOptions, Subscriber};
+/// use std::io::{Read, Write};
+/// use std::net::TcpListener;
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let broker = listener.local_addr().unwrap().to_string();
+/// let thread = std::thread::spawn(move || {
+///     let (mut client, _) = listener.accept().unwrap();
+///     let mut packet = [0; 64];
+///     client.read(&mut packet).unwrap(); // CONNECT
+///     client.write_all(&[0x20, 2, 0, 0]).unwrap(); // accepted
+///     client.read(&mut packet).unwrap(); // SUBSCRIBE, packet ID 1
+///     client.write_all(&[0x90, 3, 0, 1, 1]).unwrap(); // granted QoS 1
+///     // PUBLISH with QoS 1 to "fruit/a", packet ID 7, payload "hi"
+///     client.write_all(&[0x32, 13, 0, 7, b'f', b'r', b'u', b'i', b't', b'/', b'a', 0, 7, b'h', b'i']).unwrap();
+///     let mut ack = [0; 4];
+///     client.read_exact(&mut ack).unwrap();
+///     ack
+/// });
+///
+/// let mut subscriber = Subscriber::connect(&MqttOptions::new(broker, "test")).unwrap();
+/// subscriber.subscribe("fruit/#").unwrap();
+/// let message = subscriber.next_message().unwrap().unwrap();
+/// assert_eq!(message.topic, "fruit/a");
+/// assert_eq!(message.payload, b"hi");
+/// subscriber.acknowledge(&message).unwrap();
+/// assert_eq!(thread.join().unwrap(), [0x40, 2, 0, 7]); // PUBACK
+/// ```
+#[derive(Debug)]
+pub struct Subscriber {
+    stream: TcpStream,
+    next_id: u16,
+}
+
+impl Subscriber {
+    /// Open a session with the broker.
+    ///
+    /// # Error Cases
+    /// - The broker can't be reached
+    /// - It refused the connection (e.g. bad credentials), or didn't
+    ///   answer like an MQTT broker
+    pub fn connect(options: &MqttOptions) -> io::Result<Self> {
+        let stream = TcpStream::connect(&options.broker)?;
+        // Ping before the broker gives up on us (at 1.5 times the keep-alive)
+        stream.set_read_timeout(Some(options.keep_alive))?;
+        let mut subscriber = Subscriber { stream, next_id: 1 };
+
+        let mut flags = 0x02; // clean session
+        let mut body = Vec::new();
+        put_string(&mut body, "MQTT");
+        body.push(4); // protocol level 3.1.1
+        let flags_at = body.len();
+        body.push(0);
+        let keep_alive = options.keep_alive.as_secs().min(u64::from(u16::MAX)) as u16;
+        body.extend_from_slice(&keep_alive.to_be_bytes());
+        put_string(&mut body, &options.client_id);
+        if let Some(username) = &options.username {
+            flags |= 0x80;
+            put_string(&mut body, username);
+            if let Some(password) = &options.password {
+                flags |= 0x40;
+                put_string(&mut body, password);
+            }
+        }
+        body[flags_at] = flags;
+        subscriber.send(CONNECT, &body)?;
+
+        let (header, body) = subscriber.receive()?.ok_or_else(closed)?;
+        if header != CONNACK || body.len() != 2 {
+            return Err(invalid("expected CONNACK"));
+        }
+        match body[1] {
+            0 => Ok(subscriber),
+            code => Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("broker refused the connection: {}", refusal(code)),
+            )),
+        }
+    }
+
+    /// Receive the messages published to topics matching `filter` (which
+    /// may contain the wildcards `+` and `#`).
+    pub fn subscribe(&mut self, filter: &str) -> io::Result<()> {
+        let id = self.packet_id();
+        let mut body = id.to_be_bytes().to_vec();
+        put_string(&mut body, filter);
+        body.push(1); // at least once
+        self.send(SUBSCRIBE, &body)?;
+
+        loop {
+            let (header, body) = self.receive()?.ok_or_else(closed)?;
+            match header {
+                SUBACK if body.get(..2) == Some(&id.to_be_bytes()[..]) => {
+                    return match body.get(2) {
+                        Some(0x80) | None => Err(invalid(&format!(
+                            "broker refused the subscription to '{}'",
+                            filter
+                        ))),
+                        Some(_) => Ok(()),
+                    };
+                }
+                // Nothing is published to us before the SUBACK, but
+                // tolerate brokers that answer pings in between
+                PINGRESP => {}
+                _ => return Err(invalid("expected SUBACK")),
+            }
+        }
+    }
+
+    /// Wait for the next message, or `None` if the broker closed the
+    /// connection.
+    pub fn next_message(&mut self) -> io::Result<Option<Message>> {
+        loop {
+            let Some((header, body)) = self.receive()? else {
+                return Ok(None);
+            };
+            match header & 0xF0 {
+                PUBLISH => return parse_publish(header, &body).map(Some),
+                PINGRESP => {}
+                _ => return Err(invalid("unexpected packet")),
+            }
+        }
+    }
+
+    /// Tell the broker `message` has been dealt with, so it isn't sent
+    /// again.
+    pub fn acknowledge(&mut self, message: &Message) -> io::Result<()> {
+        match message.packet_id {
+            Some(id) => self.send(PUBACK, &id.to_be_bytes()),
+            None => Ok(()),
+        }
+    }
+
+    /// End the session cleanly.
+    pub fn disconnect(mut self) -> io::Result<()> {
+        self.send(DISCONNECT, &[])
+    }
+
+    fn packet_id(&mut self) -> u16 {
+        let id = self.next_id;
+        // IDs must not be 0
+        self.next_id = self.next_id.checked_add(1).unwrap_or(1);
+        id
+    }
+
+    fn send(&mut self, header: u8, body: &[u8]) -> io::Result<()> {
+        let mut packet = vec![header];
+        let mut length = body.len();
+        loop {
+            let mut byte = (length % 128) as u8;
+            length /= 128;
+            if length > 0 {
+                byte |= 0x80;
+            }
+            packet.push(byte);
+            if length == 0 {
+                break;
+            }
+        }
+        packet.extend_from_slice(body);
+        self.stream.write_all(&packet)
+    }
+
+    /// Read the next packet's header byte and body, pinging the broker
+    /// whenever the connection is idle for the keep-alive period. `None`
+    /// if the connection was closed between packets.
+    fn receive(&mut self) -> io::Result<Option<(u8, Vec<u8>)>> {
+        let mut header = [0];
+        loop {
+            match self.stream.read(&mut header) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    self.send(PINGREQ, &[])?;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        // The rest of a packet follows its header straight away
+        let timeout = self.stream.read_timeout()?;
+        self.stream.set_read_timeout(None)?;
+        let mut length = 0usize;
+        for shift in (0..28).step_by(7) {
+            let mut byte = [0];
+            self.stream.read_exact(&mut byte)?;
+            length |= usize::from(byte[0] & 0x7F) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        self.stream.read_exact(&mut body)?;
+        self.stream.set_read_timeout(timeout)?;
+        Ok(Some((header[0], body)))
+    }
+}
+
+/// The topic, packet ID and payload of a PUBLISH packet.
+fn parse_publish(header: u8, body: &[u8]) -> io::Result<Message> {
+    let qos = (header >> 1) & 0x03;
+    let (topic, rest) = take_string(body)?;
+    let (packet_id, payload) = if qos > 0 {
+        let id = rest.get(..2).ok_or_else(|| invalid("truncated PUBLISH"))?;
+        (Some(u16::from_be_bytes([id[0], id[1]])), &rest[2..])
+    } else {
+        (None, rest)
+    };
+    Ok(Message {
+        topic,
+        payload: payload.to_vec(),
+        packet_id,
+    })
+}
+
+fn put_string(buffer: &mut Vec<u8>, text: &str) {
+    buffer.extend_from_slice(&(text.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(text.as_bytes());
+}
+
+/// A length-prefixed string at the start of `bytes`, and what follows it.
+fn take_string(bytes: &[u8]) -> io::Result<(String, &[u8])> {
+    let length = match bytes {
+        [high, low, ..] => usize::from(u16::from_be_bytes([*high, *low])),
+        _ => return Err(invalid("truncated string")),
+    };
+    let text = bytes
+        .get(2..2 + length)
+        .ok_or_else(|| invalid("truncated string"))?;
+    let text = String::from_utf8(text.to_vec()).map_err(|_| invalid("topic isn't UTF-8"))?;
+    Ok((text, &bytes[2 + length..]))
+}
+
+/// Why the broker refused a connection, from its CONNACK return code.
+fn refusal(code: u8) -> &'static str {
+    match code {
+        1 => "unsupported protocol version",
+        2 => "client ID rejected",
+        3 => "server unavailable",
+        4 => "bad user name or password",
+        5 => "not authorised",
+        _ => "unknown reason",
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("MQTT: {}", message))
+}
+
+fn closed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "MQTT: the broker closed the connection",
+    )
+}
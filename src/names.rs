@@ -0,0 +1,249 @@
+// ============================================================================
+// names.rs - Comparing and Ordering Fruit Names
+// ============================================================================
+// Every place that asks "are these two fruit names the same?" or "which name
+// comes first?" goes through this module, so the rules are identical for
+// `get`, `add`, `remove`, sorting, and anything built on the library.
+//
+// Two levels of support are available:
+//
+// 1. Default build: ASCII-only case-insensitivity. "apple" == "APPLE", but
+//    "açaí" != "AÇAÍ" because 'ç' and 'Ç' aren't ASCII letters.
+// 2. With the `unicode` cargo feature: full Unicode case folding for lookups
+//    and a collation order that sorts accented letters next to their base
+//    letter ("Açaí" sorts between "Abiu" and "Acerola", not after "Avocado").
+//
+// Enable it with: `cargo build --features unicode`
+//
+// Some datasets tell "Kiwi" (the fruit) from "KIWI" (a brand's SKU). A
+// catalogue can opt out of case-insensitivity altogether with the
+// `"case_sensitive": true` file header or `--case-sensitive`; then the
+// `CaseSensitivity` it carries compares names exactly.
+//
+// Key concept: Case folding is not the same as lowercasing. Folding maps
+// strings to a form that is only meant for comparison (e.g. German "ß" folds
+// to "ss"), whereas lowercasing is meant for display.
+// ============================================================================
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Fold a name into the form used for case-insensitive comparison.
+///
+/// Two names are considered equal when their folded forms are equal. The
+/// folded form is only for comparing and indexing; never show it to users.
+///
+/// With the `unicode` feature, `char::to_lowercase` handles every cased
+/// script; the few characters whose *folding* differs from their lowercase
+/// form (sharp s, final sigma) are special-cased.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::names::fold_case;
+///
+/// assert_eq!(fold_case("Apple"), "apple");
+/// # #[cfg(feature = "unicode")]
+/// assert_eq!(fold_case("AÇAÍ"), "açaí");
+/// ```
+pub fn fold_case(name: &str) -> String {
+    #[cfg(not(feature = "unicode"))]
+    {
+        name.to_ascii_lowercase()
+    }
+    #[cfg(feature = "unicode")]
+    {
+        let mut folded = String::with_capacity(name.len());
+        for c in name.chars() {
+            match c {
+                'ß' | 'ẞ' => folded.push_str("ss"),
+                'ς' => folded.push('σ'),
+                _ => folded.extend(c.to_lowercase()),
+            }
+        }
+        folded
+    }
+}
+
+/// Check whether two fruit names refer to the same fruit.
+///
+/// This replaces direct calls to `eq_ignore_ascii_case` so that the
+/// `unicode` feature changes matching everywhere at once.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::names::names_equal;
+///
+/// assert!(names_equal("apple", "APPLE"));
+/// assert!(!names_equal("apple", "pear"));
+/// ```
+pub fn names_equal(a: &str, b: &str) -> bool {
+    #[cfg(not(feature = "unicode"))]
+    {
+        a.eq_ignore_ascii_case(b)
+    }
+    #[cfg(feature = "unicode")]
+    {
+        fold_case(a) == fold_case(b)
+    }
+}
+
+/// Whether names that differ only in case are the same fruit.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::names::CaseSensitivity;
+///
+/// assert!(CaseSensitivity::Insensitive.names_equal("Kiwi", "KIWI"));
+/// assert!(!CaseSensitivity::Sensitive.names_equal("Kiwi", "KIWI"));
+/// assert_eq!(CaseSensitivity::Sensitive.fold("KIWI"), "KIWI");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseSensitivity {
+    /// "apple" and "APPLE" are the same fruit (see `names_equal`). This is
+    /// the default.
+    #[default]
+    Insensitive,
+    /// Names must match exactly.
+    Sensitive,
+}
+
+impl CaseSensitivity {
+    /// `Sensitive` if `sensitive` is true, `Insensitive` otherwise.
+    pub fn from_flag(sensitive: bool) -> Self {
+        if sensitive {
+            CaseSensitivity::Sensitive
+        } else {
+            CaseSensitivity::Insensitive
+        }
+    }
+
+    /// Check whether two names refer to the same fruit under this mode.
+    pub fn names_equal(self, a: &str, b: &str) -> bool {
+        match self {
+            CaseSensitivity::Insensitive => names_equal(a, b),
+            CaseSensitivity::Sensitive => a == b,
+        }
+    }
+
+    /// The form of `name` to compare and index under this mode: its
+    /// `fold_case` form, or the name itself.
+    pub fn fold(self, name: &str) -> String {
+        match self {
+            CaseSensitivity::Insensitive => fold_case(name),
+            CaseSensitivity::Sensitive => name.to_string(),
+        }
+    }
+}
+
+/// Build the key used to sort names into human (dictionary) order.
+///
+/// The default build sorts by the ASCII-folded name. With the `unicode`
+/// feature, names are decomposed (NFD) and combining accents are dropped,
+/// which is what ICU calls the "primary strength" of a collation: letters
+/// are compared by their base form first.
+#[cfg(not(feature = "unicode"))]
+fn primary_key(name: &str) -> String {
+    fold_case(name)
+}
+
+#[cfg(feature = "unicode")]
+fn primary_key(name: &str) -> String {
+    use unicode_normalization::char::is_combining_mark;
+    use unicode_normalization::UnicodeNormalization;
+
+    fold_case(name)
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect()
+}
+
+/// Compare two names for sorted output.
+///
+/// Names are ordered by their base letters first, then (on a tie) by their
+/// case-folded form including accents, and finally by the exact text so that
+/// the order is always deterministic.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::names::collate;
+/// use std::cmp::Ordering;
+///
+/// assert_eq!(collate("apple", "Banana"), Ordering::Less);
+/// # #[cfg(feature = "unicode")]
+/// assert_eq!(collate("Açaí", "Apple"), Ordering::Less);
+///
+/// # #[cfg(feature = "unicode")]
+/// # {
+/// let mut names = ["Avocado", "Acerola", "Açaí", "Abiu"];
+/// names.sort_by(|a, b| collate(a, b));
+/// assert_eq!(names, ["Abiu", "Açaí", "Acerola", "Avocado"]);
+/// # }
+/// ```
+pub fn collate(a: &str, b: &str) -> Ordering {
+    primary_key(a)
+        .cmp(&primary_key(b))
+        .then_with(|| fold_case(a).cmp(&fold_case(b)))
+        .then_with(|| a.cmp(b))
+}
+
+/// Count the single-character edits (insertions, deletions, substitutions)
+/// that turn one name into the other, ignoring case.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::names::edit_distance;
+///
+/// assert_eq!(edit_distance("Aple", "apple"), 1);
+/// assert_eq!(edit_distance("Banana", "Bandana"), 1);
+/// assert_eq!(edit_distance("Kiwi", "Pear"), 4);
+/// ```
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = fold_case(a).chars().collect();
+    let b: Vec<char> = fold_case(b).chars().collect();
+
+    // The classic dynamic programme, one row at a time: `row[j]` is the
+    // distance between the first `i` characters of `a` and the first `j`
+    // of `b`
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The `candidates` within `max_distance` edits of `name`, closest first
+/// (ties in collation order), each distinct name once.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::names::closest_names;
+///
+/// let names = ["Apple", "Maple", "Banana", "apple"];
+/// assert_eq!(closest_names("Aple", names, 2), ["Apple", "Maple"]);
+/// assert!(closest_names("Kiwi", names, 2).is_empty());
+/// ```
+pub fn closest_names<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    max_distance: usize,
+) -> Vec<&'a str> {
+    let mut close: Vec<(usize, &str)> = Vec::new();
+    for candidate in candidates {
+        if close.iter().any(|(_, seen)| names_equal(seen, candidate)) {
+            continue;
+        }
+        let distance = edit_distance(name, candidate);
+        if distance <= max_distance {
+            close.push((distance, candidate));
+        }
+    }
+    close.sort_by(|(da, a), (db, b)| da.cmp(db).then_with(|| collate(a, b)));
+    close.into_iter().map(|(_, candidate)| candidate).collect()
+}
@@ -0,0 +1,655 @@
+// ============================================================================
+// pattern.rs - Glob and Regex Patterns over Fruit Names
+// ============================================================================
+// `fruitdata list --glob 'P*'` and `--regex '^.{3}$'` narrow a listing by
+// name without the query language. Both compile to the same small matcher:
+//
+// - a `Glob` matches the whole name: `*` is any run of characters, `?` any
+//   one character, `[abc]`, `[a-z]` and `[!abc]` one of (or none of) a set
+// - a `Regex` matches anywhere in the name unless anchored with `^` / `$`,
+//   and supports `.`, `[...]` / `[^...]`, `\d \w \s` (and `\D \W \S`),
+//   `* + ? {n} {n,} {n,m}`, `|` and `( )` groups; other backslashed
+//   punctuation is literal (`\.`). Backreferences, lookaround and lazy
+//   quantifiers aren't supported.
+//
+// Like every other name comparison (see names.rs), matching ignores case:
+// the name and the pattern's letters are case-folded first.
+//
+// Key concept: a backtracking matcher tries one way through the pattern at
+// a time, and a pattern such as `(a*)*b` has exponentially many ways
+// through a name of a's. Patterns here arrive from `daemon` and `ipc`
+// clients too, so they are compiled into a Thompson NFA instead: matching
+// follows every way through at once, one character at a time, which takes
+// at most (name length x pattern size) steps. A pattern whose compiled
+// form would pass `MAX_PROGRAM` instructions (`a{1000}{1000}`) is refused.
+// ============================================================================
+
+use crate::names::fold_case;
+use std::fmt;
+use std::str::FromStr;
+
+/// A set of characters that one position can match.
+#[derive(Debug, Clone, PartialEq)]
+enum CharSet {
+    /// `.` or `?`
+    Any,
+    /// A range, e.g. `a-z`; a single character is a range of one.
+    Range(char, char),
+    /// `\d`
+    Digit,
+    /// `\w`: letters, digits and `_`.
+    Word,
+    /// `\s`
+    Space,
+    Not(Box<CharSet>),
+    Union(Vec<CharSet>),
+}
+
+impl CharSet {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharSet::Any => true,
+            CharSet::Range(low, high) => (*low..=*high).contains(&c),
+            CharSet::Digit => c.is_ascii_digit(),
+            CharSet::Word => c.is_alphanumeric() || c == '_',
+            CharSet::Space => c.is_whitespace(),
+            CharSet::Not(set) => !set.matches(c),
+            CharSet::Union(sets) => sets.iter().any(|set| set.matches(c)),
+        }
+    }
+}
+
+/// One step of a compiled pattern.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    /// One character from the set.
+    Char(CharSet),
+    /// `^`
+    Start,
+    /// `$`
+    End,
+    /// `(a|b)`: one of the alternatives.
+    Group(Vec<Vec<Node>>),
+    /// `x*`, `x{2,3}`, ...: greedy repetition.
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// The most instructions a compiled pattern may have.
+const MAX_PROGRAM: usize = 10_000;
+
+/// How deeply groups and quantifiers (`((a)*)+`) may nest; the parser and
+/// the compiler recurse once per level.
+const MAX_DEPTH: usize = 128;
+
+/// One instruction of a compiled pattern.
+#[derive(Debug, Clone, PartialEq)]
+enum Inst {
+    /// Consume one character from the set.
+    Char(CharSet),
+    /// Carry on only at the start of the name.
+    Start,
+    /// Carry on only at the end of the name.
+    End,
+    /// Carry on at both instructions.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+/// A compiled pattern, matching anywhere in the (folded) name.
+#[derive(Debug, Clone, PartialEq)]
+struct Matcher {
+    program: Vec<Inst>,
+}
+
+/// The compiled form of a pattern would be over `MAX_PROGRAM`.
+struct TooLarge;
+
+impl Matcher {
+    fn new(alternatives: Vec<Vec<Node>>) -> Result<Matcher, TooLarge> {
+        let mut program = Vec::new();
+        compile(&Node::Group(alternatives), &mut program)?;
+        program.push(Inst::Match);
+        Ok(Matcher { program })
+    }
+
+    fn is_match(&self, name: &str) -> bool {
+        let text: Vec<char> = fold_case(name).chars().collect();
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        for at in 0..=text.len() {
+            // A match may start anywhere
+            if self.add(&mut current, 0, at, text.len()) {
+                return true;
+            }
+            let Some(c) = text.get(at) else { break };
+            next.clear();
+            for &pc in &current.list {
+                if let Inst::Char(set) = &self.program[pc] {
+                    if set.matches(*c) && self.add(&mut next, pc + 1, at + 1, text.len()) {
+                        return true;
+                    }
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+        }
+        false
+    }
+
+    /// Add the thread at `pc`, and every one it leads to without consuming
+    /// a character, to `threads`; `true` if one of them reaches `Match`.
+    fn add(&self, threads: &mut Threads, pc: usize, at: usize, len: usize) -> bool {
+        let mut pending = vec![pc];
+        while let Some(pc) = pending.pop() {
+            if threads.seen[pc] {
+                continue;
+            }
+            threads.seen[pc] = true;
+            threads.list.push(pc);
+            match &self.program[pc] {
+                Inst::Match => return true,
+                Inst::Jump(to) => pending.push(*to),
+                Inst::Split(first, second) => pending.extend([*second, *first]),
+                Inst::Start if at == 0 => pending.push(pc + 1),
+                Inst::End if at == len => pending.push(pc + 1),
+                Inst::Start | Inst::End | Inst::Char(_) => {}
+            }
+        }
+        false
+    }
+}
+
+/// The instructions reached at one position of the name.
+struct Threads {
+    list: Vec<usize>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(size: usize) -> Self {
+        Threads {
+            list: Vec::new(),
+            seen: vec![false; size],
+        }
+    }
+
+    fn clear(&mut self) {
+        for pc in self.list.drain(..) {
+            self.seen[pc] = false;
+        }
+    }
+}
+
+/// Append the instructions for `node` to `program`.
+fn compile(node: &Node, program: &mut Vec<Inst>) -> Result<(), TooLarge> {
+    let emit = |program: &mut Vec<Inst>, inst: Inst| {
+        if program.len() >= MAX_PROGRAM {
+            return Err(TooLarge);
+        }
+        program.push(inst);
+        Ok(program.len() - 1)
+    };
+    match node {
+        Node::Char(set) => {
+            emit(program, Inst::Char(set.clone()))?;
+        }
+        Node::Start => {
+            emit(program, Inst::Start)?;
+        }
+        Node::End => {
+            emit(program, Inst::End)?;
+        }
+        Node::Group(alternatives) => {
+            let mut jumps = Vec::new();
+            for (index, sequence) in alternatives.iter().enumerate() {
+                let last = index + 1 == alternatives.len();
+                let split = if last {
+                    None
+                } else {
+                    Some(emit(program, Inst::Split(0, 0))?)
+                };
+                for node in sequence {
+                    compile(node, program)?;
+                }
+                if let Some(split) = split {
+                    jumps.push(emit(program, Inst::Jump(0))?);
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat { node, min, max } => {
+            // A node that compiles to nothing (`(){1000000}`) is the same
+            // however often it is repeated
+            for _ in 0..*min {
+                let before = program.len();
+                compile(node, program)?;
+                if program.len() == before {
+                    break;
+                }
+            }
+            match max {
+                None => {
+                    let split = emit(program, Inst::Split(0, 0))?;
+                    compile(node, program)?;
+                    emit(program, Inst::Jump(split))?;
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(emit(program, Inst::Split(0, 0))?);
+                        let before = program.len();
+                        compile(node, program)?;
+                        if program.len() == before {
+                            break;
+                        }
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The nodes matching the literal character `c`, case-folded (which may
+/// make it several characters: "ß" folds to "ss").
+fn literal(c: char) -> Node {
+    let mut folded: Vec<Node> = fold_case(&c.to_string())
+        .chars()
+        .map(|c| Node::Char(CharSet::Range(c, c)))
+        .collect();
+    match folded.len() {
+        1 => folded.remove(0),
+        _ => Node::Group(vec![folded]),
+    }
+}
+
+/// `c`, case-folded, if it is still one character.
+fn fold_char(c: char) -> char {
+    let folded = fold_case(&c.to_string());
+    let mut chars = folded.chars();
+    match (chars.next(), chars.next()) {
+        (Some(folded), None) => folded,
+        _ => c,
+    }
+}
+
+// ============================================================================
+// PARSING
+// ============================================================================
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+    kind: &'static str,
+    /// How many groups and quantifiers deep the parser is.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn new<'a>(source: &'a str, kind: &'static str) -> Parser<'a> {
+        Parser {
+            source,
+            chars: source.chars().collect(),
+            pos: 0,
+            kind,
+            depth: 0,
+        }
+    }
+
+    /// Go one level deeper (see `MAX_DEPTH`).
+    fn deeper(&mut self) -> Result<(), String> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(format!("nested more than {} levels deep", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += usize::from(c.is_some());
+        c
+    }
+
+    fn error(&self, problem: impl fmt::Display) -> String {
+        format!("invalid {} '{}': {}", self.kind, self.source, problem)
+    }
+
+    fn too_large(&self) -> String {
+        self.error(format!(
+            "it compiles to more than {} instructions",
+            MAX_PROGRAM
+        ))
+    }
+
+    /// A set in brackets, after the `[`; `negations` are the characters
+    /// that may start a negated one.
+    fn class(&mut self, negations: &[char]) -> Result<CharSet, String> {
+        let negated = self.peek().is_some_and(|c| negations.contains(&c));
+        if negated {
+            self.pos += 1;
+        }
+        let mut sets = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                None => return Err(self.error("unclosed '['")),
+                Some(']') if !first => break,
+                Some(c) => c,
+            };
+            first = false;
+            let low = match c {
+                '\\' if self.kind == "regex" => match self.escape()? {
+                    CharSet::Range(low, _) => low,
+                    set => {
+                        sets.push(set);
+                        continue;
+                    }
+                },
+                c => fold_char(c),
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let high = match self.next() {
+                    Some('\\') if self.kind == "regex" => match self.escape()? {
+                        CharSet::Range(high, _) => high,
+                        _ => return Err(self.error("a range can't end in a class")),
+                    },
+                    Some(c) => fold_char(c),
+                    None => return Err(self.error("unclosed '['")),
+                };
+                if high < low {
+                    return Err(self.error(format!("range '{}-{}' is backwards", low, high)));
+                }
+                sets.push(CharSet::Range(low, high));
+            } else {
+                sets.push(CharSet::Range(low, low));
+            }
+        }
+        let set = CharSet::Union(sets);
+        Ok(if negated {
+            CharSet::Not(Box::new(set))
+        } else {
+            set
+        })
+    }
+
+    /// A regex escape, after the `\`.
+    fn escape(&mut self) -> Result<CharSet, String> {
+        let c = self.next().ok_or_else(|| self.error("trailing '\\'"))?;
+        Ok(match c {
+            'd' => CharSet::Digit,
+            'w' => CharSet::Word,
+            's' => CharSet::Space,
+            'D' => CharSet::Not(Box::new(CharSet::Digit)),
+            'W' => CharSet::Not(Box::new(CharSet::Word)),
+            'S' => CharSet::Not(Box::new(CharSet::Space)),
+            't' => CharSet::Range('\t', '\t'),
+            'n' => CharSet::Range('\n', '\n'),
+            c if c.is_alphanumeric() => {
+                return Err(self.error(format!("unsupported escape '\\{}'", c)))
+            }
+            c => {
+                let c = fold_char(c);
+                CharSet::Range(c, c)
+            }
+        })
+    }
+
+    /// A glob: the whole pattern, which must match the whole name.
+    fn glob(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = vec![Node::Start];
+        while let Some(c) = self.next() {
+            nodes.push(match c {
+                '*' => Node::Repeat {
+                    node: Box::new(Node::Char(CharSet::Any)),
+                    min: 0,
+                    max: None,
+                },
+                '?' => Node::Char(CharSet::Any),
+                '[' => Node::Char(self.class(&['!', '^'])?),
+                c => literal(c),
+            });
+        }
+        nodes.push(Node::End);
+        Ok(nodes)
+    }
+
+    /// `a|b|...`, up to a `)` or the end.
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    /// Atoms and their quantifiers, up to a `|`, a `)` or the end.
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            self.pos += 1;
+            let node = match c {
+                '.' => Node::Char(CharSet::Any),
+                '^' => Node::Start,
+                '$' => Node::End,
+                '[' => Node::Char(self.class(&['^'])?),
+                '\\' => Node::Char(self.escape()?),
+                '(' => {
+                    // `(?:...)` is the same group, as nothing is captured
+                    if self.chars[self.pos..].starts_with(&['?', ':']) {
+                        self.pos += 2;
+                    }
+                    self.deeper()?;
+                    let alternatives = self.alternatives()?;
+                    if self.next() != Some(')') {
+                        return Err(self.error("unclosed '('"));
+                    }
+                    self.depth -= 1;
+                    Node::Group(alternatives)
+                }
+                '*' | '+' | '?' | '{' => {
+                    return Err(self.error(format!("nothing to repeat before '{}'", c)))
+                }
+                c => literal(c),
+            };
+            nodes.push(self.quantified(node)?);
+        }
+        Ok(nodes)
+    }
+
+    /// `node`, with the quantifiers that follow it.
+    fn quantified(&mut self, mut node: Node) -> Result<Node, String> {
+        let depth = self.depth;
+        while let Some(c) = self.peek() {
+            if !matches!(c, '*' | '+' | '?' | '{') {
+                break;
+            }
+            self.deeper()?;
+            self.pos += 1;
+            let (min, max) = match c {
+                '*' => (0, None),
+                '+' => (1, None),
+                '?' => (0, Some(1)),
+                _ => self.bounds()?,
+            };
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+            };
+        }
+        self.depth = depth;
+        Ok(node)
+    }
+
+    /// `n}`, `n,}` or `n,m}`, after the `{`; leaves the position after the
+    /// `}`.
+    fn bounds(&mut self) -> Result<(usize, Option<usize>), String> {
+        let end = self.chars[self.pos..]
+            .iter()
+            .position(|c| *c == '}')
+            .ok_or_else(|| self.error("unclosed '{'"))?;
+        let inside: String = self.chars[self.pos..self.pos + end].iter().collect();
+        self.pos += end + 1;
+        let number = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| self.error(format!("invalid repetition '{{{}}}'", inside)))
+        };
+        let (min, max) = match inside.split_once(',') {
+            None => (number(&inside)?, Some(number(&inside)?)),
+            Some((min, "")) => (number(min)?, None),
+            Some((min, max)) => (number(min)?, Some(number(max)?)),
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error(format!("repetition '{{{}}}' is backwards", inside)));
+        }
+        Ok((min, max))
+    }
+}
+
+// ============================================================================
+// PUBLIC TYPES
+// ============================================================================
+
+/// A shell-style wildcard pattern that matches whole fruit names,
+/// ignoring case.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::pattern::Glob;
+///
+/// let glob: Glob = "p*".parse().unwrap();
+/// assert!(glob.is_match("Pear"));
+/// assert!(!glob.is_match("Apple"));
+///
+/// let glob: Glob = "[a-c]?????".parse().unwrap();
+/// assert!(glob.is_match("Banana"));
+/// assert!(!glob.is_match("Orange"));
+///
+/// // Many stars take no longer than one
+/// let glob: Glob = "*a*a*a*a*a*a*a*a*a*b".parse().unwrap();
+/// assert!(!glob.is_match(&"a".repeat(200)));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glob {
+    source: String,
+    matcher: Matcher,
+}
+
+impl Glob {
+    /// Whether the whole of `name` matches.
+    pub fn is_match(&self, name: &str) -> bool {
+        self.matcher.is_match(name)
+    }
+}
+
+impl FromStr for Glob {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s, "glob");
+        let nodes = parser.glob()?;
+        let matcher = Matcher::new(vec![nodes]).map_err(|TooLarge| parser.too_large())?;
+        Ok(Glob {
+            source: s.to_string(),
+            matcher,
+        })
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// A regular expression that matches anywhere in a fruit name (unless
+/// anchored), ignoring case.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::pattern::Regex;
+///
+/// let regex: Regex = "^.{3,4}$".parse().unwrap();
+/// assert!(regex.is_match("Pear"));
+/// assert!(!regex.is_match("Banana"));
+///
+/// let regex: Regex = "an(an)+|^ki".parse().unwrap();
+/// assert!(regex.is_match("BANANA"));
+/// assert!(regex.is_match("Kiwi"));
+/// assert!(!regex.is_match("Mango"));
+///
+/// assert!("ab(c".parse::<Regex>().is_err());
+///
+/// // Nested and overlapping repeats take linear time
+/// let name = "a".repeat(200);
+/// assert!(!"(a*)*b".parse::<Regex>().unwrap().is_match(&name));
+/// assert!(!"(a|a)*b".parse::<Regex>().unwrap().is_match(&name));
+/// assert!("(a|a)*$".parse::<Regex>().unwrap().is_match(&name));
+///
+/// let deep = format!("{}a{}", "(".repeat(5000), ")".repeat(5000));
+/// let error = deep.parse::<Regex>().unwrap_err();
+/// assert!(error.ends_with("nested more than 128 levels deep"));
+///
+/// let error = "a{1000}{1000}".parse::<Regex>().unwrap_err();
+/// assert_eq!(error, "invalid regex 'a{1000}{1000}': it compiles to more than 10000 instructions");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regex {
+    source: String,
+    matcher: Matcher,
+}
+
+impl Regex {
+    /// Whether the pattern matches somewhere in `name`.
+    pub fn is_match(&self, name: &str) -> bool {
+        self.matcher.is_match(name)
+    }
+}
+
+impl FromStr for Regex {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s, "regex");
+        let alternatives = parser.alternatives()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("unmatched ')'"));
+        }
+        let matcher = Matcher::new(alternatives).map_err(|TooLarge| parser.too_large())?;
+        Ok(Regex {
+            source: s.to_string(),
+            matcher,
+        })
+    }
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
@@ -0,0 +1,227 @@
+// ============================================================================
+// pivot.rs - Cross Tabulation of a Catalogue
+// ============================================================================
+// `stats --group-by` compares the groups of one key. A pivot table crosses
+// two: one row per origin, say, one column per tag, and in each cell a
+// value computed over the fruits with that origin and tag:
+//
+//     fruitdata summary --rows origin --cols tag --value 'mean(volume)'
+//
+//     origin  citrus  stone
+//     Mexico      64      -
+//     Spain      175     56
+//
+// The cells are `stats::group_by` aggregates, keyed by (row, column), so a
+// pivot agrees with `stats --group-by` about every group.
+// ============================================================================
+
+use crate::models::FruitDimensions;
+use crate::stats::{group_by, Aggregate, GroupBy};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// How a pivot cell combines the fruits in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Count,
+    Sum,
+    Mean,
+}
+
+/// The measurement of a fruit that a pivot cell sums or averages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    Volume,
+    /// Estimated, see `stats::estimated_mass`.
+    Mass,
+    Length,
+    Width,
+    Height,
+}
+
+/// What each pivot cell shows: `count`, or `sum(...)` or `mean(...)` of a
+/// measurement, e.g. `mean(volume)`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::pivot::CellValue;
+///
+/// let value: CellValue = "mean(volume)".parse().unwrap();
+/// assert_eq!(value.to_string(), "mean(volume)");
+/// assert!("count".parse::<CellValue>().is_ok());
+/// assert!("median(volume)".parse::<CellValue>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellValue {
+    pub function: Function,
+    /// Ignored by `Function::Count`.
+    pub measure: Measure,
+}
+
+impl CellValue {
+    /// The value of a cell whose fruits aggregate to `aggregate`.
+    pub fn of(&self, aggregate: &Aggregate) -> f32 {
+        let count = aggregate.count as f32;
+        let mean = match self.measure {
+            Measure::Volume => aggregate.mean_volume,
+            Measure::Mass if aggregate.count == 0 => 0.0,
+            Measure::Mass => aggregate.total_mass / count,
+            Measure::Length => aggregate.mean_length,
+            Measure::Width => aggregate.mean_width,
+            Measure::Height => aggregate.mean_height,
+        };
+        match (self.function, self.measure) {
+            (Function::Count, _) => count,
+            (Function::Sum, Measure::Volume) => aggregate.total_volume,
+            (Function::Sum, Measure::Mass) => aggregate.total_mass,
+            (Function::Sum, _) => mean * count,
+            (Function::Mean, _) => mean,
+        }
+    }
+}
+
+impl FromStr for CellValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim().to_ascii_lowercase();
+        if text == "count" || text == "count()" {
+            return Ok(CellValue {
+                function: Function::Count,
+                measure: Measure::Volume,
+            });
+        }
+        let invalid = || {
+            format!(
+                "unknown cell value '{}' (expected count, or sum(...) or mean(...) of volume, mass, length, width or height)",
+                s
+            )
+        };
+        let (function, rest) = text.split_once('(').ok_or_else(invalid)?;
+        let measure = rest.strip_suffix(')').ok_or_else(invalid)?;
+        let function = match function.trim() {
+            "sum" => Function::Sum,
+            "mean" | "avg" => Function::Mean,
+            _ => return Err(invalid()),
+        };
+        let measure = match measure.trim() {
+            "volume" => Measure::Volume,
+            "mass" => Measure::Mass,
+            "length" => Measure::Length,
+            "width" => Measure::Width,
+            "height" => Measure::Height,
+            _ => return Err(invalid()),
+        };
+        Ok(CellValue { function, measure })
+    }
+}
+
+impl fmt::Display for CellValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let function = match self.function {
+            Function::Count => return write!(f, "count"),
+            Function::Sum => "sum",
+            Function::Mean => "mean",
+        };
+        let measure = match self.measure {
+            Measure::Volume => "volume",
+            Measure::Mass => "mass",
+            Measure::Length => "length",
+            Measure::Width => "width",
+            Measure::Height => "height",
+        };
+        write!(f, "{}({})", function, measure)
+    }
+}
+
+/// A table with one row per `rows` key and one column per `cols` key.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::pivot::Pivot;
+/// use fruitdata::stats::GroupBy;
+///
+/// let mut fruits = Vec::new();
+/// for (name, origin, tag, length) in [
+///     ("Lemon", "Spain", "citrus", 6.0),
+///     ("Plum", "Spain", "stone", 4.0),
+///     ("Lime", "Mexico", "citrus", 4.0),
+/// ] {
+///     let mut fruit = FruitDimensions::new(name, length, 4.0, 4.0);
+///     fruit.extra.insert("origin".into(), origin.into());
+///     fruit.extra.insert("tags".into(), serde_json::json!([tag]));
+///     fruits.push(fruit);
+/// }
+///
+/// let pivot = Pivot::new(&fruits, GroupBy::Origin, Some(GroupBy::Tag), "mean(length)".parse().unwrap());
+/// assert_eq!(pivot.row_keys, ["Mexico", "Spain"]);
+/// assert_eq!(pivot.col_keys, ["citrus", "stone"]);
+/// assert_eq!(pivot.cells, [[Some(4.0), None], [Some(6.0), Some(4.0)]]);
+/// ```
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Pivot {
+    pub rows: GroupBy,
+    /// `None` for a single column over every fruit in the row.
+    pub cols: Option<GroupBy>,
+    #[serde(serialize_with = "as_text")]
+    pub value: CellValue,
+    pub row_keys: Vec<String>,
+    pub col_keys: Vec<String>,
+    /// `cells[row][col]`; `None` where no fruit has both keys.
+    pub cells: Vec<Vec<Option<f32>>>,
+}
+
+impl Pivot {
+    /// Cross-tabulate `fruits`, in key order both ways.
+    pub fn new(
+        fruits: &[FruitDimensions],
+        rows: GroupBy,
+        cols: Option<GroupBy>,
+        value: CellValue,
+    ) -> Self {
+        let groups = group_by(fruits, |fruit| {
+            let col_keys = match cols {
+                Some(cols) => cols.keys(fruit),
+                None => vec![value.to_string()],
+            };
+            rows.keys(fruit)
+                .into_iter()
+                .flat_map(|row| col_keys.iter().map(move |col| (row.clone(), col.clone())))
+                .collect::<Vec<_>>()
+        });
+
+        let mut row_keys: Vec<String> = groups.keys().map(|(row, _)| row.clone()).collect();
+        row_keys.dedup();
+        let mut col_keys: Vec<String> = groups.keys().map(|(_, col)| col.clone()).collect();
+        col_keys.sort();
+        col_keys.dedup();
+
+        let cells = row_keys
+            .iter()
+            .map(|row| {
+                col_keys
+                    .iter()
+                    .map(|col| {
+                        groups
+                            .get(&(row.clone(), col.clone()))
+                            .map(|aggregate| value.of(aggregate))
+                    })
+                    .collect()
+            })
+            .collect();
+        Pivot {
+            rows,
+            cols,
+            value,
+            row_keys,
+            col_keys,
+            cells,
+        }
+    }
+}
+
+fn as_text<S: serde::Serializer>(value: &CellValue, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
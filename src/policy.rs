@@ -0,0 +1,317 @@
+// ============================================================================
+// policy.rs - Rules That Catalogue Data Must Follow
+// ============================================================================
+// The catalogue always rejects empty names and duplicates. Organisations often
+// want stricter rules on top of that ("no names longer than 40 characters",
+// "no emoji", "nobody may call a fruit 'Unknown'"). Those rules are described
+// by the policy types in this module, read from the config file, and enforced
+// by `Catalogue` whenever a fruit is added or renamed.
+//
+// `Limits` caps how big a catalogue may grow, so that a runaway script can't
+// fill a shared disk with a multi-gigabyte JSON file, and `Uniqueness` picks
+// whether entries are told apart by name (the default) or by ID.
+//
+// `PlausibleRange` catches unit mix-ups: a 45 cm apple was almost certainly
+// measured in millimetres.
+// ============================================================================
+
+use crate::error::CatalogError;
+use crate::models::FruitDimensions;
+use crate::names::names_equal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Naming conventions enforced by `Catalogue::add` and `Catalogue::rename`.
+///
+/// Every rule is optional; the default policy accepts any non-empty name.
+///
+/// # Config file example
+/// ```json
+/// {
+///   "name_policy": {
+///     "max_length": 40,
+///     "allowed_characters": " -'",
+///     "reserved_names": ["Unknown", "Default"]
+///   }
+/// }
+/// ```
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::policy::NamePolicy;
+///
+/// let policy = NamePolicy {
+///     max_length: Some(10),
+///     allowed_characters: Some(" -".into()),
+///     reserved_names: vec!["Unknown".into()],
+/// };
+///
+/// assert!(policy.check("Blood Orange").is_err()); // 12 characters
+/// assert!(policy.check("Kiwi-Gold").is_ok());
+/// assert!(policy.check("Kiwi_Gold").is_err()); // '_' isn't allowed
+/// assert!(policy.check("unknown").is_err()); // reserved, any case
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct NamePolicy {
+    /// Maximum number of characters (not bytes) in a name.
+    pub max_length: Option<usize>,
+
+    /// Characters allowed in addition to letters and digits.
+    /// `None` allows every character; `Some("")` allows only letters and digits.
+    pub allowed_characters: Option<String>,
+
+    /// Names that may never be used, compared case-insensitively.
+    pub reserved_names: Vec<String>,
+}
+
+impl NamePolicy {
+    /// Check a (trimmed) name against every rule in the policy.
+    ///
+    /// # Returns
+    /// - `Ok(())` - The name satisfies the policy
+    /// - `Err(String)` - A short, human-readable reason for the rejection
+    pub fn check(&self, name: &str) -> Result<(), String> {
+        // Rule 1: Length, counted in characters so "Açaí" is 4, not 6 bytes
+        if let Some(max) = self.max_length {
+            let length = name.chars().count();
+            if length > max {
+                return Err(format!(
+                    "it is {} characters long (maximum is {})",
+                    length, max
+                ));
+            }
+        }
+
+        // Rule 2: Character set
+        if let Some(allowed) = &self.allowed_characters {
+            if let Some(bad) = name
+                .chars()
+                .find(|c| !c.is_alphanumeric() && !allowed.contains(*c))
+            {
+                return Err(format!("it contains the character '{}'", bad));
+            }
+        }
+
+        // Rule 3: Reserved names
+        if self
+            .reserved_names
+            .iter()
+            .any(|reserved| names_equal(reserved, name))
+        {
+            return Err("it is a reserved name".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Which of the `Limits` was hit, carried by `CatalogError::QuotaExceeded`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quota {
+    /// The number of fruits in the catalogue.
+    Entries,
+    /// The size in bytes of the saved catalogue file.
+    FileSize,
+}
+
+impl fmt::Display for Quota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Quota::Entries => write!(f, "number of fruits"),
+            Quota::FileSize => write!(f, "file size in bytes"),
+        }
+    }
+}
+
+/// Optional size limits for a catalogue.
+///
+/// `max_entries` is checked by `Catalogue::add`; `max_file_size` is checked
+/// by `Catalogue::save` before anything is written, so an over-sized
+/// catalogue never reaches the disk.
+///
+/// # Config file example
+/// ```json
+/// {
+///   "limits": { "max_entries": 10000, "max_file_size": 5000000 }
+/// }
+/// ```
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::error::CatalogError;
+/// use fruitdata::policy::{Limits, Quota};
+///
+/// let limits = Limits { max_entries: Some(2), max_file_size: None };
+/// assert!(limits.check_entries(2).is_ok());
+/// assert_eq!(
+///     limits.check_entries(3),
+///     Err(CatalogError::QuotaExceeded { quota: Quota::Entries, limit: 2, actual: 3 })
+/// );
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Limits {
+    /// Maximum number of fruits the catalogue may hold.
+    pub max_entries: Option<usize>,
+
+    /// Maximum size of the saved JSON file, in bytes.
+    pub max_file_size: Option<u64>,
+}
+
+impl Limits {
+    /// Check that a catalogue with `count` fruits is within `max_entries`.
+    pub fn check_entries(&self, count: usize) -> Result<(), CatalogError> {
+        match self.max_entries {
+            Some(max) if count > max => Err(CatalogError::QuotaExceeded {
+                quota: Quota::Entries,
+                limit: max as u64,
+                actual: count as u64,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that a file of `bytes` bytes is within `max_file_size`.
+    pub fn check_file_size(&self, bytes: u64) -> Result<(), CatalogError> {
+        match self.max_file_size {
+            Some(max) if bytes > max => Err(CatalogError::QuotaExceeded {
+                quota: Quota::FileSize,
+                limit: max,
+                actual: bytes,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What happens to a fruit whose dimensions are outside its
+/// `PlausibleRange`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutOfRange {
+    /// Keep the fruit, but say that it looks wrong. This is the default.
+    #[default]
+    Warn,
+    /// Reject the change with `CatalogError::ImplausibleDimensions`.
+    Fail,
+}
+
+/// The dimensions, in cm, that a real fruit can plausibly have.
+///
+/// Checked by `Catalogue::add` and `Catalogue::apply_patch` (and so by
+/// `add`, `update` and `import`). The range comes from the config file, or
+/// from a catalogue's own `"plausible_range"` header, which wins.
+///
+/// # Config file example
+/// ```json
+/// {
+///   "plausible_range": { "min": 0.1, "max": 100, "on_violation": "fail" }
+/// }
+/// ```
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::policy::{OutOfRange, PlausibleRange};
+///
+/// let range = PlausibleRange { min: Some(0.1), max: Some(100.0), on_violation: OutOfRange::Warn };
+/// assert!(range.check(&FruitDimensions::new("Apple", 8.0, 7.5, 7.0)).is_ok());
+/// assert_eq!(
+///     range.check(&FruitDimensions::new("Apple", 80.0, 750.0, 70.0)),
+///     Err("its width (750 cm) is above the plausible maximum of 100 cm".to_string())
+/// );
+///
+/// // No bounds, no check
+/// assert!(PlausibleRange::default().check(&FruitDimensions::new("Apple", 1e6, 1.0, 1.0)).is_ok());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default)]
+pub struct PlausibleRange {
+    /// The smallest plausible dimension, in cm.
+    pub min: Option<f32>,
+
+    /// The largest plausible dimension, in cm.
+    pub max: Option<f32>,
+
+    /// Whether a fruit outside the range is kept with a warning or rejected.
+    pub on_violation: OutOfRange,
+}
+
+impl PlausibleRange {
+    /// Check every dimension of `fruit` against the range.
+    ///
+    /// # Returns
+    /// - `Ok(())` - All dimensions are plausible
+    /// - `Err(String)` - What is wrong with the first one that isn't
+    pub fn check(&self, fruit: &FruitDimensions) -> Result<(), String> {
+        let dimensions = [
+            ("length", fruit.length),
+            ("width", fruit.width),
+            ("height", fruit.height),
+        ];
+        for (field, value) in dimensions {
+            if let Some(min) = self.min.filter(|min| value < *min) {
+                return Err(format!(
+                    "its {} ({} cm) is below the plausible minimum of {} cm",
+                    field, value, min
+                ));
+            }
+            if let Some(max) = self.max.filter(|max| value > *max) {
+                return Err(format!(
+                    "its {} ({} cm) is above the plausible maximum of {} cm",
+                    field, value, max
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which property of a fruit must be unique within the catalogue.
+///
+/// # Config file example
+/// ```json
+/// { "uniqueness": "id" }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Uniqueness {
+    /// Names are unique (case-insensitively). This is the default.
+    #[default]
+    Name,
+    /// IDs are unique and names may repeat, e.g. for several batches of
+    /// "Apple". Every fruit is given an ID automatically.
+    Id,
+}
+
+/// What `Catalogue::save` does when the file on disk was changed by someone
+/// else after the catalogue was loaded.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Refuse to save, leaving the other process's changes in place.
+    #[default]
+    Abort,
+    /// Save anyway, overwriting the other process's changes.
+    Force,
+    /// Combine both sets of changes; fail only if they touch the same fruit.
+    Merge,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "abort" => Ok(ConflictPolicy::Abort),
+            "force" => Ok(ConflictPolicy::Force),
+            "merge" => Ok(ConflictPolicy::Merge),
+            other => Err(format!(
+                "unknown conflict policy '{}' (expected abort, force or merge)",
+                other
+            )),
+        }
+    }
+}
@@ -0,0 +1,25 @@
+// ============================================================================
+// prelude.rs - The Types Most Programs Need, in One Import
+// ============================================================================
+// Programs that use fruitdata as a library mostly need the same handful of
+// types: the catalogue, a store to keep it in, the fruit itself (and its
+// `Centimeters`), the errors operations fail with, and the ways to filter
+// and sort fruits. Rather than a line per module, they can write
+//
+//     use fruitdata::prelude::*;
+//
+// Only types are re-exported (no free functions), and only ones whose names
+// are unlikely to clash with a program's own; everything else stays at its
+// full path, e.g. `fruitdata::policy::NamePolicy` or `fruitdata::pattern::Regex`
+// (which would shadow the regex crate's).
+// ============================================================================
+
+pub use crate::catalog::{Catalogue, FruitKey, ReadOnlyCatalogue, SaveOptions};
+pub use crate::config::Config;
+pub use crate::error::{CatalogError, RecordError};
+pub use crate::length::Centimeters;
+pub use crate::models::{FruitDimensions, FruitPatch};
+pub use crate::pattern::Glob;
+pub use crate::query::Query;
+pub use crate::sort::{SortDirection, SortField, SortSpec};
+pub use crate::store::{CatalogStore, FileStore, MemoryStore};
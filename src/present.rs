@@ -0,0 +1,1216 @@
+// ============================================================================
+// present.rs - Turning Command Results into Text or JSON
+// ============================================================================
+// `cli::run_command` returns what a command did as a `Report` (the fruits
+// found, the change made, the validation result, ...) and never prints.
+// This module is the other half: it renders a report the way the
+// `fruitdata` binary shows it, as human-readable lines or as JSON.
+//
+// Keeping the two apart means the wording of a message can change here
+// without touching the command logic, and programs embedding the commands
+// can skip this module altogether and use the report's fields.
+//
+// `RenderOptions` say how text output shows numbers, e.g. in inches with
+// `--units imperial`. Every report is rendered through them, so each
+// command shows the same units without doing anything itself.
+// ============================================================================
+
+use crate::cli::Report;
+use crate::duplicates::DuplicateReason;
+use crate::events::CatalogEvent;
+use crate::expr::ComputedField;
+use crate::history::Revision;
+use crate::import::JsonPath;
+use crate::ingest::Ingested;
+use crate::measurements::{self, Measurement};
+use crate::models::{FieldChange, FruitDimensions};
+use crate::pivot::{CellValue, Function, Measure};
+use crate::sanitize::NameChange;
+use crate::stats::Aggregate;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// How commands that produce reports (such as `audit`) print them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One human-readable line per item.
+    Text,
+    /// A pretty-printed JSON array, for other tools to consume.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(format!(
+                "unknown format '{}' (expected text or json)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Text => write!(f, "text"),
+            OutputFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// The units text output shows lengths and volumes in. The catalogue is
+/// always stored in centimetres; this only changes what is printed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// Centimetres and cubic centimetres.
+    #[default]
+    Metric,
+    /// Inches and cubic inches.
+    Imperial,
+}
+
+impl Units {
+    /// How many of this system's length units one centimetre is.
+    fn per_cm(self) -> f64 {
+        match self {
+            Units::Metric => 1.0,
+            Units::Imperial => 1.0 / 2.54,
+        }
+    }
+}
+
+impl FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "metric" => Ok(Units::Metric),
+            "imperial" => Ok(Units::Imperial),
+            other => Err(format!(
+                "unknown units '{}' (expected metric or imperial)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Units::Metric => write!(f, "metric"),
+            Units::Imperial => write!(f, "imperial"),
+        }
+    }
+}
+
+/// How `render_with` shows the numbers in text (and CSV) output. The
+/// config's `"output"` section sets the defaults:
+///
+/// ```json
+/// "output": { "units": "imperial", "precision": 2 }
+/// ```
+///
+/// JSON output is meant for other tools, so it always has the catalogue's
+/// own units and every digit.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct RenderOptions {
+    pub units: Units,
+    /// Decimal places for every number; `None` shows each one with as few
+    /// digits as identify it (`4`, `2.5`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precision: Option<usize>,
+}
+
+impl RenderOptions {
+    /// A number as text, to `precision` decimal places (CSV files written
+    /// by `export` use it too).
+    pub(crate) fn number(&self, n: f32) -> String {
+        match self.precision {
+            Some(precision) => format!("{:.*}", precision, n),
+            None => n.to_string(),
+        }
+    }
+
+    /// A number as text with its sign, e.g. a trend of `+0.1`.
+    fn signed(&self, n: f32) -> String {
+        match self.precision {
+            Some(precision) => format!("{:+.*}", precision, n),
+            None => format!("{:+}", n),
+        }
+    }
+
+    /// A fraction as a signed percentage, to one decimal place unless
+    /// `precision` says otherwise: `+26.7%`.
+    fn percent(&self, fraction: f32) -> String {
+        format!("{:+.*}%", self.precision.unwrap_or(1), fraction * 100.0)
+    }
+
+    /// A fruit's dimensions, `4 x 2.5 x 1.5`.
+    fn dimensions(
+        &self,
+        length: impl Into<f32>,
+        width: impl Into<f32>,
+        height: impl Into<f32>,
+    ) -> String {
+        format!(
+            "{} x {} x {}",
+            self.number(length.into()),
+            self.number(width.into()),
+            self.number(height.into())
+        )
+    }
+
+    /// A `FieldChange` as text, with a dimension in the display units and
+    /// to `precision` decimal places.
+    fn change(&self, change: &FieldChange) -> String {
+        if !matches!(change.field.as_str(), "length" | "width" | "height") {
+            return change.to_string();
+        }
+        let show = |value: &Value| match value.as_f64() {
+            Some(cm) => self.number(self.length(cm as f32)),
+            None => value.to_string(),
+        };
+        format!(
+            "{} {} -> {}",
+            change.field,
+            show(&change.before),
+            show(&change.after)
+        )
+    }
+
+    /// A length in centimetres, in the display units.
+    fn length(&self, cm: f32) -> f32 {
+        (f64::from(cm) * self.units.per_cm()) as f32
+    }
+
+    /// A volume in cubic centimetres, in the display units.
+    fn volume(&self, cm3: f32) -> f32 {
+        (f64::from(cm3) * self.units.per_cm().powi(3)) as f32
+    }
+
+    /// `fruit` measured in the display units, so that everything computed
+    /// from it (its volume, formulas, measurement summaries) is too.
+    fn fruit<'a>(&self, fruit: &'a FruitDimensions) -> Cow<'a, FruitDimensions> {
+        if self.units == Units::Metric {
+            return Cow::Borrowed(fruit);
+        }
+        let mut converted = fruit.clone();
+        converted.length = self.length(fruit.length.value()).into();
+        converted.width = self.length(fruit.width.value()).into();
+        converted.height = self.length(fruit.height.value()).into();
+        for measurement in &mut converted.measurements {
+            *measurement = self.measurement(measurement);
+        }
+        Cow::Owned(converted)
+    }
+
+    /// A measurement in the display units.
+    fn measurement(&self, measurement: &Measurement) -> Measurement {
+        Measurement {
+            length: self.length(measurement.length.value()).into(),
+            width: self.length(measurement.width.value()).into(),
+            height: self.length(measurement.height.value()).into(),
+            ..*measurement
+        }
+    }
+
+    /// An aggregate's lengths and volumes in the display units (the mass
+    /// stays in grams).
+    fn aggregate(&self, aggregate: &Aggregate) -> Aggregate {
+        Aggregate {
+            total_volume: self.volume(aggregate.total_volume),
+            mean_length: self.length(aggregate.mean_length),
+            mean_width: self.length(aggregate.mean_width),
+            mean_height: self.length(aggregate.mean_height),
+            mean_volume: self.volume(aggregate.mean_volume),
+            ..*aggregate
+        }
+    }
+
+    /// A pivot cell showing `value`, in the display units.
+    fn cell(&self, value: CellValue, cell: f32) -> f32 {
+        match (value.function, value.measure) {
+            (Function::Count, _) | (_, Measure::Mass) => cell,
+            (_, Measure::Volume) => self.volume(cell),
+            (_, Measure::Length | Measure::Width | Measure::Height) => self.length(cell),
+        }
+    }
+}
+
+/// The fields of each fruit to print, as dotted paths: `name,volume`.
+///
+/// The paths are looked up in the fruit as saved, plus its `volume` and
+/// the catalogue's computed fields. A path that leads nowhere gives `null`,
+/// so every fruit has the same fields.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::present::FieldSelection;
+/// use serde_json::json;
+///
+/// let fields: FieldSelection = "name,volume,tags.0".parse().unwrap();
+/// let apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+/// assert_eq!(
+///     fields.project(&apple, &[]),
+///     json!({"name": "Apple", "volume": 15.0, "tags": {"0": null}})
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSelection(Vec<JsonPath>);
+
+impl FieldSelection {
+    /// The selected fields of `fruit`, nested the way their paths are.
+    pub fn project(&self, fruit: &FruitDimensions, computed: &[ComputedField]) -> Value {
+        let source = fruit_value(fruit, computed);
+        let mut projected = Map::new();
+        for path in &self.0 {
+            let value = path.get(&source).cloned().unwrap_or(Value::Null);
+            insert(&mut projected, path.segments(), value);
+        }
+        Value::Object(projected)
+    }
+
+    /// The selected fields of `fruit`, in order and without nesting.
+    fn values(&self, fruit: &FruitDimensions, computed: &[ComputedField]) -> Vec<Value> {
+        let source = fruit_value(fruit, computed);
+        self.0
+            .iter()
+            .map(|path| path.get(&source).cloned().unwrap_or(Value::Null))
+            .collect()
+    }
+}
+
+impl FromStr for FieldSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let paths = s
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<JsonPath>, _>>()?;
+        Ok(FieldSelection(paths))
+    }
+}
+
+impl fmt::Display for FieldSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let paths: Vec<String> = self.0.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}", paths.join(","))
+    }
+}
+
+/// A fruit as JSON, with its volume and computed fields (`null` where one
+/// can't be computed).
+fn fruit_value(fruit: &FruitDimensions, computed: &[ComputedField]) -> Value {
+    let mut value = serde_json::to_string(fruit)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or(Value::Null);
+    if let Value::Object(fields) = &mut value {
+        fields.insert("volume".to_string(), number(fruit.volume()));
+        for field in computed {
+            let value = field.evaluate(fruit).map_or(Value::Null, number);
+            fields.insert(field.name.clone(), value);
+        }
+    }
+    value
+}
+
+/// An `f32` as a JSON number. Going through its text keeps values like 4.2
+/// from turning into 4.199999809265137.
+fn number(n: f32) -> Value {
+    serde_json::from_str(&format!("{:?}", n)).unwrap_or(Value::Null)
+}
+
+/// Put `value` at `segments` in `object`, creating objects on the way.
+fn insert(object: &mut Map<String, Value>, segments: &[String], value: Value) {
+    match segments {
+        [] => {}
+        [last] => {
+            object.insert(last.clone(), value);
+        }
+        [first, rest @ ..] => {
+            let child = object
+                .entry(first.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(child) = child {
+                insert(child, rest, value);
+            }
+        }
+    }
+}
+
+/// Render a report for the terminal.
+///
+/// The JSON of reports that list things (`list`, `audit`, `history`,
+/// `lint`) is the list itself; other reports serialize as an object whose `"outcome"` says
+/// what happened.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::cli::Report;
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::present::{render, OutputFormat};
+///
+/// let fruit = FruitDimensions::new("Kiwi", 3.0, 2.0, 2.0);
+/// let report = Report::Added { fruit, cleaned: None, warnings: Vec::new() };
+/// assert_eq!(render(&report, OutputFormat::Text).unwrap(), "Added 'Kiwi'.");
+///
+/// let json = render(&report, OutputFormat::Json).unwrap();
+/// assert!(json.contains(r#""outcome": "added""#));
+/// ```
+pub fn render(report: &Report, format: OutputFormat) -> Result<String, serde_json::Error> {
+    render_with(report, format, &RenderOptions::default())
+}
+
+/// Render a report like `render`, showing numbers as `options` say.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::cli::Report;
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::present::{render_with, OutputFormat, RenderOptions, Units};
+///
+/// let report = Report::Found {
+///     name: "Apple".into(),
+///     fruits: vec![FruitDimensions::new("Apple", 5.08, 2.54, 1.27)],
+///     computed: Vec::new(),
+///     measurements: false,
+/// };
+/// let options = RenderOptions { units: Units::Imperial, ..RenderOptions::default() };
+/// let text = render_with(&report, OutputFormat::Text, &options).unwrap();
+/// assert!(text.contains("Dimensions: 2 x 1 x 0.5"));
+/// assert!(text.contains("Volume: 1"));
+///
+/// let options = RenderOptions { precision: Some(2), ..RenderOptions::default() };
+/// let text = render_with(&report, OutputFormat::Text, &options).unwrap();
+/// assert!(text.contains("Dimensions: 5.08 x 2.54 x 1.27"));
+/// assert!(text.contains("Volume: 16.39"));
+/// ```
+pub fn render_with(
+    report: &Report,
+    format: OutputFormat,
+    options: &RenderOptions,
+) -> Result<String, serde_json::Error> {
+    match format {
+        OutputFormat::Text => Ok(text(report, options)),
+        OutputFormat::Json => json(report),
+    }
+}
+
+fn json(report: &Report) -> Result<String, serde_json::Error> {
+    match report {
+        Report::Listed {
+            fruits,
+            fields: Some(fields),
+            computed,
+            ..
+        } => serde_json::to_string_pretty(
+            &fruits
+                .iter()
+                .map(|f| fields.project(f, computed))
+                .collect::<Vec<_>>(),
+        ),
+        Report::Listed { fruits, .. } => serde_json::to_string_pretty(fruits),
+        Report::Audited { records } => serde_json::to_string_pretty(records),
+        Report::History { revisions, .. } => serde_json::to_string_pretty(revisions),
+        Report::Linted { warnings } => serde_json::to_string_pretty(warnings),
+        Report::Duplicates { clusters, .. } => serde_json::to_string_pretty(clusters),
+        Report::Graded { summary } => serde_json::to_string_pretty(summary),
+        Report::Stats { aggregate } => serde_json::to_string_pretty(aggregate),
+        Report::GroupedStats { groups, .. } => serde_json::to_string_pretty(groups),
+        Report::Growth { steps, .. } => serde_json::to_string_pretty(steps),
+        Report::Validated { report, .. } => serde_json::to_string_pretty(report),
+        Report::Diffed { diff, .. } => serde_json::to_string_pretty(diff),
+        Report::Repaired {
+            recovery, output, ..
+        } => serde_json::to_string_pretty(&serde_json::json!({
+            "recovered": recovery.fruits.len(),
+            "output": output,
+            "lost": recovery.lost,
+        })),
+        other => serde_json::to_string_pretty(other),
+    }
+}
+
+fn text(report: &Report, options: &RenderOptions) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    match report {
+        Report::Rejected { error } => lines.push(error.to_string()),
+
+        Report::Listed {
+            fruits,
+            template: Some(template),
+            ..
+        } => lines.extend(
+            fruits
+                .iter()
+                .map(|f| template.render_with(&options.fruit(f), |n| options.number(n))),
+        ),
+        // One tab-separated line per fruit, for `cut` and friends
+        Report::Listed {
+            fruits,
+            fields: Some(fields),
+            computed,
+            ..
+        } => {
+            for fruit in fruits {
+                let values: Vec<String> = fields
+                    .values(&options.fruit(fruit), computed)
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::Null => String::new(),
+                        Value::String(text) => text,
+                        // IDs and other whole numbers have no decimals to round
+                        Value::Number(n) if n.is_f64() => {
+                            options.number(n.as_f64().unwrap_or(0.0) as f32)
+                        }
+                        other => other.to_string(),
+                    })
+                    .collect();
+                lines.push(values.join("\t"));
+            }
+        }
+        Report::Listed { fruits, .. } => {
+            lines.push("--- Available Fruits ---".to_string());
+            // Fruits with an ID show it, since names may repeat in ID mode
+            for f in fruits {
+                match f.id {
+                    Some(id) => lines.push(format!("{} (#{})", f.name, id)),
+                    None => lines.push(f.name.clone()),
+                }
+            }
+        }
+
+        Report::Found {
+            name,
+            fruits,
+            computed,
+            measurements,
+        } => {
+            if fruits.is_empty() {
+                lines.push(format!("Fruit '{}' not found.", name));
+            }
+            for (i, fruit) in fruits.iter().enumerate() {
+                // Separate multiple matches with a blank line
+                if i > 0 {
+                    lines.push(String::new());
+                }
+                let fruit = options.fruit(fruit);
+                describe_fruit(&fruit, computed, options, &mut lines);
+                if *measurements {
+                    describe_measurements(&fruit, options, &mut lines);
+                }
+            }
+        }
+
+        // The answer is the exit status alone
+        Report::Exists { .. } => {}
+
+        Report::Added {
+            fruit,
+            cleaned,
+            warnings,
+        } => {
+            if let Some(change) = cleaned {
+                lines.push(describe_name_change(change));
+            }
+            lines.push(format!("Added '{}'.", fruit.name));
+            lines.extend(warnings.iter().cloned());
+        }
+        Report::Removed { fruit } => lines.push(format!("Removed '{}'.", fruit.name)),
+        Report::Renamed { before, after } => {
+            lines.push(format!("Renamed '{}' to '{}'.", before.name, after.name))
+        }
+        Report::Updated { before, .. } => lines.push(format!("Updated '{}'.", before.name)),
+        Report::NothingToUpdate => lines.push("Nothing to update.".to_string()),
+
+        Report::Audited { records } if records.is_empty() => {
+            lines.push("No matching changes.".to_string())
+        }
+        Report::Audited { records } => {
+            for record in records {
+                lines.push(format!(
+                    "{}  {}  {}",
+                    record.timestamp,
+                    record.actor,
+                    describe_change(&record.event, options)
+                ));
+            }
+        }
+
+        Report::History { name, revisions } if revisions.is_empty() => {
+            lines.push(format!("No history recorded for '{}'.", name))
+        }
+        Report::History { revisions, .. } => describe_revisions(revisions, options, &mut lines),
+
+        Report::NoRetentionPeriod => lines.push(
+            "No retention period: use --keep (e.g. --keep 90d) or set journal.keep in the config."
+                .to_string(),
+        ),
+        Report::Pruned { cutoff, report } if report.entries_removed == 0 => {
+            lines.push(format!("Nothing older than {} to prune.", cutoff))
+        }
+        Report::Pruned { cutoff, report } => lines.push(format!(
+            "Pruned {} journal entries older than {}; reclaimed {} bytes ({} -> {}).",
+            report.entries_removed,
+            cutoff,
+            report.reclaimed(),
+            report.bytes_before,
+            report.bytes_after
+        )),
+
+        Report::NotAnArray { input } => lines.push(format!(
+            "Expected an array of records in '{}'.",
+            input.display()
+        )),
+        Report::NoRecords => lines.push("No records to import.".to_string()),
+        Report::ImportRejected { mapping, problems } => {
+            lines.push(format!("Mapping: {}", mapping.join(", ")));
+            match problems.as_slice() {
+                [problem] => lines.push(format!("Nothing imported: {}", problem)),
+                _ => {
+                    lines.push("Nothing imported:".to_string());
+                    for problem in problems {
+                        lines.push(format!("  {}", problem));
+                    }
+                }
+            }
+        }
+        Report::Imported {
+            mapping,
+            input,
+            mode,
+            summary,
+            cleaned,
+            warnings,
+            dry_run,
+        } => {
+            lines.push(format!("Mapping: {}", mapping.join(", ")));
+            lines.extend(cleaned.iter().map(describe_name_change));
+            if *dry_run {
+                if summary.removed > 0 {
+                    lines.push(format!("Would remove all {} fruits.", summary.removed));
+                }
+                for fruit in &summary.inserted {
+                    lines.push(format!("Would add '{}'.", fruit.name));
+                }
+                for fruit in &summary.updated {
+                    lines.push(format!("Would overwrite '{}'.", fruit.name));
+                }
+                for name in &summary.skipped {
+                    lines.push(format!("Would skip '{}'.", name));
+                }
+            } else {
+                lines.push(format!(
+                    "Imported from '{}' ({}): {} inserted, {} updated, {} skipped{}.",
+                    input.display(),
+                    mode,
+                    summary.inserted.len(),
+                    summary.updated.len(),
+                    summary.skipped.len(),
+                    match summary.removed {
+                        0 => String::new(),
+                        removed => format!(", {} removed", removed),
+                    }
+                ));
+            }
+            lines.extend(warnings.iter().cloned());
+        }
+
+        Report::Compacted {
+            store,
+            bytes_before,
+            bytes_after,
+        } => lines.push(format!(
+            "Compacted '{}': {} -> {} bytes.",
+            store, bytes_before, bytes_after
+        )),
+
+        Report::Initialised {
+            store,
+            template,
+            count,
+        } => lines.push(format!(
+            "Created '{}' from the {} template ({} fruits).",
+            store, template, count
+        )),
+        Report::AlreadyInitialised { store } => lines.push(format!(
+            "Catalogue '{}' already exists; nothing was changed.",
+            store
+        )),
+
+        Report::OutputExists { path } => lines.push(format!(
+            "File '{}' already exists; choose another output.",
+            path.display()
+        )),
+        Report::Converted {
+            count,
+            input,
+            from,
+            output,
+            to,
+            calibration,
+        } => {
+            lines.push(format!(
+                "Converted {} fruits from '{}' ({}) to '{}' ({}).",
+                count,
+                input.display(),
+                from,
+                output.display(),
+                to
+            ));
+            if let Some(calibration) = calibration.as_ref().filter(|c| c.is_identity()) {
+                lines.push(format!(
+                    "The calibration of {} was already applied; the dimensions are unchanged.",
+                    calibration.device.as_deref().unwrap_or("an unnamed device")
+                ));
+            } else if let Some(calibration) = calibration {
+                lines.push(format!(
+                    "Applied the calibration of {}: offset {} {}, now in cm.",
+                    calibration.device.as_deref().unwrap_or("an unnamed device"),
+                    options.number(calibration.offset),
+                    calibration.unit
+                ));
+            }
+        }
+        Report::Exported { count, output, to } => lines.push(format!(
+            "Exported {} fruits to '{}' ({}).",
+            count,
+            output.display(),
+            to
+        )),
+
+        // The validation report is meant for tools, so it is JSON either way
+        Report::Validated { report, .. } => lines
+            .push(serde_json::to_string_pretty(report).unwrap_or_else(|e| format!("error: {}", e))),
+
+        Report::Diffed {
+            before,
+            after,
+            diff,
+        } if diff.is_empty() => lines.push(format!(
+            "No differences between '{}' and '{}'.",
+            before.display(),
+            after.display()
+        )),
+        Report::Diffed {
+            before,
+            after,
+            diff,
+        } => {
+            lines.push(format!("--- {}", before.display()));
+            lines.push(format!("+++ {}", after.display()));
+            for fruit in &diff.removed {
+                lines.push(format!("- {}", fruit.name));
+            }
+            for fruit in &diff.added {
+                let fruit = options.fruit(fruit);
+                lines.push(format!(
+                    "+ {} ({})",
+                    fruit.name,
+                    options.dimensions(fruit.length, fruit.width, fruit.height)
+                ));
+            }
+            for changed in &diff.changed {
+                let changes: Vec<String> = changed
+                    .changes
+                    .iter()
+                    .map(|change| options.change(change))
+                    .collect();
+                let described = if changes.is_empty() {
+                    "other fields changed".to_string()
+                } else {
+                    changes.join(", ")
+                };
+                lines.push(format!("~ {}: {}", changed.name, described));
+            }
+            lines.push(format!(
+                "{} added, {} removed, {} changed.",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.changed.len()
+            ));
+        }
+
+        Report::Linted { warnings } if warnings.is_empty() => {
+            lines.push("No warnings.".to_string())
+        }
+        Report::Linted { warnings } => {
+            lines.extend(warnings.iter().map(|warning| warning.to_string()))
+        }
+
+        Report::Labelled {
+            label,
+            output: None,
+            ..
+        } => lines.push(label.trim_end_matches('\n').to_string()),
+        Report::Labelled {
+            name,
+            format,
+            output: Some(output),
+            ..
+        } => lines.push(format!(
+            "Wrote the {} label of '{}' to '{}'.",
+            format,
+            name,
+            output.display()
+        )),
+
+        Report::Barcoded {
+            name,
+            symbology,
+            text,
+            output,
+        } => lines.push(format!(
+            "Wrote the {} barcode of '{}' ({}) to '{}'.",
+            symbology,
+            name,
+            text,
+            output.display()
+        )),
+
+        Report::DaemonStopped {
+            socket,
+            commands,
+            saves,
+        } => lines.push(format!(
+            "Stopped listening on '{}' after {} commands ({} saves).",
+            socket.display(),
+            commands,
+            saves
+        )),
+
+        Report::IpcStopped {
+            socket,
+            clients,
+            requests,
+        } => lines.push(format!(
+            "Stopped listening on '{}' after {} requests from {} clients.",
+            socket.display(),
+            requests,
+            clients
+        )),
+
+        Report::Graded { summary } => {
+            lines.push(format!(
+                "Graded by {} into '{}' ({} changed):",
+                summary.by, summary.field, summary.changed
+            ));
+            for grade in &summary.grades {
+                lines.push(format!("  {}: {}", grade.grade, grade.count));
+            }
+            lines.push(format!("  (ungraded): {}", summary.ungraded));
+        }
+
+        Report::Duplicates { clusters, .. } if clusters.is_empty() => {
+            lines.push("No possible duplicates.".to_string())
+        }
+        Report::Duplicates {
+            tolerance,
+            clusters,
+        } => {
+            for cluster in clusters {
+                lines.push(match cluster.reason {
+                    DuplicateReason::SameName => {
+                        "Same name (ignoring case and spaces):".to_string()
+                    }
+                    DuplicateReason::SimilarDimensions => format!(
+                        "Similar dimensions (within {}%):",
+                        options.number(tolerance * 100.0)
+                    ),
+                });
+                for fruit in &cluster.fruits {
+                    let fruit = options.fruit(fruit);
+                    let id = fruit.id.map(|id| format!(" (#{})", id)).unwrap_or_default();
+                    lines.push(format!(
+                        "  {}{}: {}",
+                        fruit.name,
+                        id,
+                        options.dimensions(fruit.length, fruit.width, fruit.height)
+                    ));
+                }
+            }
+            lines.push(format!(
+                "{} {} of possible duplicates.",
+                clusters.len(),
+                if clusters.len() == 1 {
+                    "cluster"
+                } else {
+                    "clusters"
+                }
+            ));
+        }
+
+        Report::Queried { results, raw } => {
+            for result in results {
+                match result {
+                    Value::String(text) if *raw => lines.push(text.clone()),
+                    other => lines.push(
+                        serde_json::to_string_pretty(other)
+                            .unwrap_or_else(|e| format!("error: {}", e)),
+                    ),
+                }
+            }
+        }
+
+        Report::Counted { count } => lines.push(count.to_string()),
+
+        Report::Stats { aggregate } => {
+            let aggregate = options.aggregate(aggregate);
+            lines.push(format!("Fruits: {}", aggregate.count));
+            lines.push(format!(
+                "Total volume: {}",
+                options.number(aggregate.total_volume)
+            ));
+            lines.push(format!(
+                "Estimated mass: {} g",
+                options.number(aggregate.total_mass)
+            ));
+            lines.push(format!(
+                "Mean dimensions: {}",
+                options.dimensions(
+                    aggregate.mean_length,
+                    aggregate.mean_width,
+                    aggregate.mean_height
+                )
+            ));
+            lines.push(format!(
+                "Mean volume: {}",
+                options.number(aggregate.mean_volume)
+            ));
+        }
+        Report::GroupedStats { group_by, groups } => {
+            let header = vec![
+                group_by.to_string(),
+                "fruits".to_string(),
+                "total volume".to_string(),
+                "est. mass (g)".to_string(),
+                "mean volume".to_string(),
+            ];
+            let rows = groups.iter().map(|group| {
+                let aggregate = options.aggregate(&group.aggregate);
+                vec![
+                    group.key.clone(),
+                    aggregate.count.to_string(),
+                    options.number(aggregate.total_volume),
+                    options.number(aggregate.total_mass),
+                    options.number(aggregate.mean_volume),
+                ]
+            });
+            lines.extend(table(std::iter::once(header).chain(rows).collect()));
+        }
+        Report::Summarized { pivot, csv } => {
+            let mut header = vec![pivot.rows.to_string()];
+            header.extend(pivot.col_keys.iter().cloned());
+            let empty = if *csv { "" } else { "-" };
+            let rows = pivot.row_keys.iter().zip(&pivot.cells).map(|(key, cells)| {
+                let mut row = vec![key.clone()];
+                row.extend(cells.iter().map(|cell| match cell {
+                    // A count has no decimals to round
+                    Some(cell) if pivot.value.function == Function::Count => cell.to_string(),
+                    Some(cell) => options.number(options.cell(pivot.value, *cell)),
+                    None => empty.to_string(),
+                }));
+                row
+            });
+            let rows: Vec<Vec<String>> = std::iter::once(header).chain(rows).collect();
+            if *csv {
+                lines.extend(rows.iter().map(|row| {
+                    let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+                    fields.join(",")
+                }));
+            } else {
+                lines.extend(table(rows));
+            }
+        }
+
+        Report::Ingested {
+            topic,
+            outcomes,
+            rejected,
+        } => {
+            let added = outcomes
+                .iter()
+                .filter(|o| matches!(o, Ingested::Added { .. }))
+                .count();
+            lines.push(format!(
+                "Received {} readings on '{}': {} measured, {} added, {} rejected.",
+                outcomes.len() + rejected.len(),
+                topic,
+                outcomes.len() - added,
+                added,
+                rejected.len()
+            ));
+            for reason in rejected {
+                lines.push(format!("Rejected a reading on {}", reason));
+            }
+        }
+        Report::Measured { fruit, measurement } => {
+            let measurement = options.measurement(measurement);
+            lines.push(format!(
+                "Recorded a measurement of {} taken {}: {}.",
+                fruit.name,
+                measurement.taken,
+                options.dimensions(measurement.length, measurement.width, measurement.height)
+            ))
+        }
+        Report::Growth { name, steps } if steps.is_empty() => {
+            lines.push(format!("No measurements of {}.", name))
+        }
+        Report::Growth { steps, .. } => {
+            let header = ["taken", "length", "width", "height", "volume", "change"];
+            let rows = steps.iter().map(|step| {
+                let measurement = options.measurement(&step.measurement);
+                vec![
+                    measurement.taken.to_string(),
+                    options.number(measurement.length.value()),
+                    options.number(measurement.width.value()),
+                    options.number(measurement.height.value()),
+                    options.number(measurement.volume()),
+                    match step.volume_change {
+                        Some(change) => options.percent(change),
+                        None => "-".to_string(),
+                    },
+                ]
+            });
+            let header = header.iter().map(|column| column.to_string()).collect();
+            lines.extend(table(std::iter::once(header).chain(rows).collect()));
+        }
+
+        Report::Repaired {
+            input,
+            output,
+            recovery,
+        } => {
+            lines.push(format!(
+                "Recovered {} fruits from '{}' into '{}'.",
+                recovery.fruits.len(),
+                input.display(),
+                output.display()
+            ));
+            if !recovery.lost.is_empty() {
+                lines.push("Lost:".to_string());
+                for lost in &recovery.lost {
+                    lines.push(format!(
+                        "  line {}: {} ({})",
+                        lost.line, lost.text, lost.reason
+                    ));
+                }
+            }
+        }
+
+        Report::NoRecordAt { at } => lines.push(format!(
+            "The journal has no record of the catalogue at {}.",
+            at
+        )),
+        Report::RestoredToFile { at, output, fruits } => lines.push(format!(
+            "Wrote {} fruits as of {} to '{}'.",
+            fruits,
+            at,
+            output.display()
+        )),
+        Report::Restored { store, at } => lines.push(format!("Restored '{}' to {}.", store, at)),
+        Report::RestoreCancelled => lines.push("Restore cancelled.".to_string()),
+    }
+    lines.join("\n")
+}
+
+/// Describe a fruit for `get`, one detail per line, with the computed
+/// fields last:
+///
+/// ```text
+/// Name: Apple
+/// Dimensions: 4 x 2.5 x 1.5
+/// Volume: 15
+/// aspect_ratio: 1.6
+/// ```
+fn describe_fruit(
+    fruit: &FruitDimensions,
+    computed: &[ComputedField],
+    options: &RenderOptions,
+    lines: &mut Vec<String>,
+) {
+    lines.push(format!("Name: {}", fruit.name));
+    if let Some(id) = fruit.id {
+        lines.push(format!("ID: {}", id));
+    }
+    lines.push(format!(
+        "Dimensions: {}",
+        options.dimensions(fruit.length, fruit.width, fruit.height)
+    ));
+    lines.push(format!("Volume: {}", options.number(fruit.volume())));
+    if let Some(formula) = &fruit.volume_formula {
+        lines.push(format!("Volume formula: {}", formula));
+    }
+    for field in computed {
+        match field.evaluate(fruit) {
+            Ok(value) => lines.push(format!("{}: {}", field.name, options.number(value))),
+            Err(e) => lines.push(format!("{}: n/a ({})", field.name, e)),
+        }
+    }
+}
+
+/// The `summary` of a fruit's measurements: when they span, then a line
+/// per dimension.
+fn describe_measurements(
+    fruit: &FruitDimensions,
+    options: &RenderOptions,
+    lines: &mut Vec<String>,
+) {
+    let Some(summary) = measurements::summary(&fruit.measurements) else {
+        lines.push("Measurements: none".to_string());
+        return;
+    };
+    lines.push(format!(
+        "Measurements: {}, {} to {}",
+        summary.count, summary.first, summary.last
+    ));
+    for (name, dimension) in [
+        ("length", summary.length),
+        ("width", summary.width),
+        ("height", summary.height),
+    ] {
+        let mut parts = vec![format!("mean {}", options.number(dimension.mean))];
+        if let Some(variance) = dimension.variance {
+            parts.push(format!("variance {}", options.number(variance)));
+        }
+        if let Some(trend) = dimension.trend {
+            parts.push(format!("trend {} per day", options.signed(trend)));
+        }
+        lines.push(format!("  {}: {}", name, parts.join(", ")));
+    }
+}
+
+/// One line per revision, saying what changed since the one before.
+fn describe_revisions(
+    revisions: &[Revision<FruitDimensions>],
+    options: &RenderOptions,
+    lines: &mut Vec<String>,
+) {
+    let mut previous: Option<Cow<FruitDimensions>> = None;
+    for revision in revisions {
+        let value = revision.value.as_ref().map(|f| options.fruit(f));
+        let actor = revision.actor.as_deref().unwrap_or("-");
+        let description = match (&previous, &value) {
+            (_, None) => "removed".to_string(),
+            (None, Some(f)) => {
+                // A revision without an actor comes from a snapshot
+                let how = if revision.actor.is_some() {
+                    "added"
+                } else {
+                    "recorded"
+                };
+                format!(
+                    "{} {} ({})",
+                    how,
+                    f.name,
+                    options.dimensions(f.length, f.width, f.height)
+                )
+            }
+            (Some(before), Some(after)) => describe_differences(before, after, options),
+        };
+        lines.push(format!(
+            "{}  {}  {}",
+            revision.timestamp, actor, description
+        ));
+        previous = value;
+    }
+}
+
+/// Say how a name was cleaned up, e.g. "Cleaned name ' Kiwi ' to 'Kiwi'.".
+/// Control characters are shown escaped, so the line stays readable.
+fn describe_name_change(change: &NameChange) -> String {
+    format!(
+        "Cleaned name '{}' to '{}'.",
+        change.original.escape_debug(),
+        change.cleaned
+    )
+}
+
+/// Summarise a journal event in one line, e.g. "remove Durian" or
+/// "update Apple: width 2.5 -> 2.8".
+fn describe_change(event: &CatalogEvent, options: &RenderOptions) -> String {
+    match event {
+        CatalogEvent::Added { fruit } => {
+            let fruit = options.fruit(fruit);
+            format!(
+                "add {} ({})",
+                fruit.name,
+                options.dimensions(fruit.length, fruit.width, fruit.height)
+            )
+        }
+        CatalogEvent::Removed { fruit } => format!("remove {}", fruit.name),
+        CatalogEvent::Updated { before, after } => format!(
+            "{} {}: {}",
+            event.action(),
+            before.name,
+            describe_differences(&options.fruit(before), &options.fruit(after), options)
+        ),
+    }
+}
+
+/// List what differs between two versions of a fruit, e.g.
+/// "name Pear -> Nashi, width 3.5 -> 3.6".
+fn describe_differences(
+    before: &FruitDimensions,
+    after: &FruitDimensions,
+    options: &RenderOptions,
+) -> String {
+    let changes: Vec<String> = before
+        .changes_to(after)
+        .iter()
+        .map(|change| options.change(change))
+        .collect();
+    if changes.is_empty() {
+        "no changes".to_string()
+    } else {
+        changes.join(", ")
+    }
+}
+
+/// Lay out `rows` (the first being the header) in columns: the first, a
+/// name, aligned left, and the rest, numbers, aligned right.
+fn table(rows: Vec<Vec<String>>) -> Vec<String> {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in &rows {
+        widths.resize(widths.len().max(row.len()), 0);
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(column, (cell, &width))| match column {
+                    0 => format!("{:<width$}", cell),
+                    _ => format!("{:>width$}", cell),
+                })
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        })
+        .collect()
+}
+
+/// A CSV field, quoted if it has to be.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,173 @@
+// ============================================================================
+// progress.rs - Progress Bars for Long-Running Commands
+// ============================================================================
+// Converting or importing a file of a few hundred megabytes takes long
+// enough that a silent terminal looks like a hang. Commands that work
+// through large files report how far they have got to a `Progress`; the
+// binary passes a `ProgressBar`, which draws on stderr:
+//
+//     Converting [##############                ]  45%
+//
+// The bar only appears once an operation has run for half a second, so
+// small files (the usual case) print nothing extra. It is never drawn when
+// stderr isn't a terminal, so logs and pipes stay clean, and `--no-progress`
+// turns it off altogether. Library users get `NoProgress` unless they ask.
+// ============================================================================
+
+use std::io::{self, IsTerminal, Read, Write};
+use std::time::{Duration, Instant};
+
+/// Don't draw anything for operations quicker than this.
+const DELAY: Duration = Duration::from_millis(500);
+
+/// Don't redraw more often than this.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The width of the bar itself, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Something that is told how far an operation has got.
+///
+/// An operation calls `start`, then `advance` as it goes, then `finish`.
+pub trait Progress {
+    /// A new operation, e.g. "Converting", of `total` units (bytes, records)
+    /// if that is known in advance.
+    fn start(&mut self, label: &str, total: Option<u64>);
+
+    /// `done` more units are finished.
+    fn advance(&mut self, done: u64);
+
+    /// The operation is over (whether or not it succeeded).
+    fn finish(&mut self);
+}
+
+/// Progress that goes nowhere.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn start(&mut self, _label: &str, _total: Option<u64>) {}
+    fn advance(&mut self, _done: u64) {}
+    fn finish(&mut self) {}
+}
+
+/// A progress bar drawn on stderr.
+#[derive(Debug)]
+pub struct ProgressBar {
+    label: String,
+    total: Option<u64>,
+    done: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+    /// The length of the last line drawn, to blank it out afterwards.
+    width: usize,
+}
+
+impl ProgressBar {
+    /// A progress bar on stderr, or `None` if stderr isn't a terminal.
+    pub fn stderr() -> Option<Self> {
+        io::stderr().is_terminal().then(|| ProgressBar {
+            label: String::new(),
+            total: None,
+            done: 0,
+            started: Instant::now(),
+            drawn: None,
+            width: 0,
+        })
+    }
+
+    fn draw(&mut self) {
+        let line = match self.total {
+            Some(total) if total > 0 => {
+                let fraction = (self.done.min(total) as f64) / (total as f64);
+                let filled = (fraction * BAR_WIDTH as f64) as usize;
+                format!(
+                    "{} [{}{}] {:>3}%",
+                    self.label,
+                    "#".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled),
+                    (fraction * 100.0) as u32
+                )
+            }
+            // Without a total, count instead
+            _ => format!("{} {}", self.label, self.done),
+        };
+        let padding = " ".repeat(self.width.saturating_sub(line.len()));
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r{}{}", line, padding);
+        let _ = stderr.flush();
+        self.width = line.len();
+        self.drawn = Some(Instant::now());
+    }
+}
+
+impl Progress for ProgressBar {
+    fn start(&mut self, label: &str, total: Option<u64>) {
+        self.label = label.to_string();
+        self.total = total;
+        self.done = 0;
+        self.started = Instant::now();
+        self.drawn = None;
+    }
+
+    fn advance(&mut self, done: u64) {
+        self.done += done;
+        let due = match self.drawn {
+            None => self.started.elapsed() >= DELAY,
+            Some(drawn) => drawn.elapsed() >= REDRAW_INTERVAL,
+        };
+        if due {
+            self.draw();
+        }
+    }
+
+    fn finish(&mut self) {
+        // Leave the terminal as it was, rather than with a stale bar
+        if self.drawn.take().is_some() {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r{}\r", " ".repeat(self.width));
+            let _ = stderr.flush();
+        }
+        self.width = 0;
+    }
+}
+
+/// A reader that reports each byte read to a `Progress`, so that any
+/// operation reading a file can show how much of it is done.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::progress::{Progress, Tracked};
+/// use std::io::Read;
+///
+/// #[derive(Default)]
+/// struct Count(u64);
+/// impl Progress for Count {
+///     fn start(&mut self, _label: &str, _total: Option<u64>) {}
+///     fn advance(&mut self, done: u64) { self.0 += done; }
+///     fn finish(&mut self) {}
+/// }
+///
+/// let mut count = Count::default();
+/// let mut text = String::new();
+/// Tracked::new(&b"[1, 2, 3]"[..], &mut count).read_to_string(&mut text).unwrap();
+/// assert_eq!(count.0, 9);
+/// ```
+pub struct Tracked<'p, R> {
+    inner: R,
+    progress: &'p mut dyn Progress,
+}
+
+impl<'p, R: Read> Tracked<'p, R> {
+    pub fn new(inner: R, progress: &'p mut dyn Progress) -> Self {
+        Tracked { inner, progress }
+    }
+}
+
+impl<R: Read> Read for Tracked<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress.advance(n as u64);
+        Ok(n)
+    }
+}
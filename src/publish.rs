@@ -0,0 +1,243 @@
+// ============================================================================
+// publish.rs - Sending Catalogue Changes to Other Systems
+// ============================================================================
+// The catalogue is the master data for fruit; data pipelines downstream
+// want to hear about every change to it as it happens rather than polling
+// files. When the config file names a Kafka topic,
+//
+//     { "publish": { "kafka": { "brokers": ["kafka-1:9092"],
+//                               "topic": "fruit-changes",
+//                               "encoding": "avro", "schema_id": 17 } } }
+//
+// the CLI publishes each change a command makes (the same `ChangeRecord`s
+// the journal records) once the catalogue is saved, one message per change,
+// keyed by the fruit's name so that the changes to one fruit stay in order.
+// Sending needs the `kafka` feature (see kafka.rs); encoding doesn't.
+//
+// Messages are encoded in one of two ways:
+//
+// - json (the default): the change record exactly as in the journal, e.g.
+//   `{"timestamp": "...", "actor": "alice", "event": {"type": "added", ...}}`
+// - avro: Avro binary data in the schema `CHANGE_SCHEMA`, which carries the
+//   fields most pipelines need (not measurements or unknown fields). With a
+//   `schema_id` (the schema's ID in a Confluent-compatible schema registry)
+//   each message starts with the registry's 5-byte header, so standard
+//   deserializers can read it.
+//
+// The journal stays the record of what happened: a change that can't be
+// published is reported as a warning, not undone.
+// ============================================================================
+
+use crate::events::Action;
+use crate::journal::ChangeRecord;
+use crate::models::FruitDimensions;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// Where changes are published, from the config file's `publish` section.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct PublishSettings {
+    /// Publish to a Kafka topic (not by default).
+    pub kafka: Option<KafkaSettings>,
+}
+
+/// The Kafka topic changes are published to, and how.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct KafkaSettings {
+    /// Brokers to ask for the topic's partitions, as `host:port`; any one
+    /// that answers is enough (default: `localhost:9092`).
+    pub brokers: Vec<String>,
+    /// The topic (default: `fruit-changes`).
+    pub topic: String,
+    /// How changes are encoded (default: JSON).
+    pub encoding: EventEncoding,
+    /// The ID of `CHANGE_SCHEMA` in a schema registry, to prefix Avro
+    /// messages with (see the module comment).
+    pub schema_id: Option<u32>,
+    /// The client ID brokers log requests under (default: `fruitdata`).
+    pub client_id: String,
+}
+
+impl Default for KafkaSettings {
+    fn default() -> Self {
+        KafkaSettings {
+            brokers: vec!["localhost:9092".to_string()],
+            topic: "fruit-changes".to_string(),
+            encoding: EventEncoding::default(),
+            schema_id: None,
+            client_id: "fruitdata".to_string(),
+        }
+    }
+}
+
+impl KafkaSettings {
+    /// Publish `records`, one message each, to the topic (a no-op for no
+    /// records).
+    ///
+    /// # Error Cases
+    /// - fruitdata was built without the `kafka` feature
+    /// - The brokers can't be reached, or rejected the messages
+    pub fn publish(&self, records: &[ChangeRecord]) -> Result<(), Box<dyn Error>> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        #[cfg(feature = "kafka")]
+        {
+            let messages: Vec<_> = records.iter().map(|r| self.message(r)).collect();
+            crate::kafka::publish(&self.brokers, &self.client_id, &self.topic, &messages)?;
+            Ok(())
+        }
+        #[cfg(not(feature = "kafka"))]
+        Err("this build of fruitdata can't publish to Kafka (it needs the `kafka` feature)".into())
+    }
+
+    /// The key and value of the message announcing `record`.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::events::CatalogEvent;
+    /// use fruitdata::journal::ChangeRecord;
+    /// use fruitdata::models::FruitDimensions;
+    /// use fruitdata::publish::{EventEncoding, KafkaSettings};
+    /// use fruitdata::timestamp::Timestamp;
+    ///
+    /// let record = ChangeRecord {
+    ///     timestamp: Timestamp(1_718_000_000),
+    ///     actor: "alice".into(),
+    ///     event: CatalogEvent::Added { fruit: FruitDimensions::new("Fig", 5.0, 5.0, 2.0) },
+    /// };
+    ///
+    /// let settings = KafkaSettings::default();
+    /// let (key, value) = settings.message(&record);
+    /// assert_eq!(key, b"Fig");
+    /// let json: serde_json::Value = serde_json::from_slice(&value).unwrap();
+    /// assert_eq!(json["event"]["type"], "added");
+    ///
+    /// let settings = KafkaSettings { encoding: EventEncoding::Avro, schema_id: Some(17), ..settings };
+    /// let (_, value) = settings.message(&record);
+    /// assert_eq!(value[..5], [0, 0, 0, 0, 17]); // registry header
+    /// assert_eq!(value[5..11], [0x80, 0xF0, 0xBC, 0x8C, 0x80, 0x64]); // timestamp, in ms
+    /// ```
+    pub fn message(&self, record: &ChangeRecord) -> (Vec<u8>, Vec<u8>) {
+        let key = record.event.name().as_bytes().to_vec();
+        let value = match self.encoding {
+            EventEncoding::Json => {
+                serde_json::to_vec(record).expect("change records serialize as JSON")
+            }
+            EventEncoding::Avro => {
+                let mut value = Vec::new();
+                if let Some(id) = self.schema_id {
+                    value.push(0);
+                    value.extend_from_slice(&id.to_be_bytes());
+                }
+                avro_change(&mut value, record);
+                value
+            }
+        };
+        (key, value)
+    }
+}
+
+/// How a published change is encoded.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventEncoding {
+    /// The change record as JSON, as in the journal.
+    #[default]
+    Json,
+    /// Avro binary data in `CHANGE_SCHEMA`.
+    Avro,
+}
+
+/// The Avro schema of published changes: when the change was saved (in
+/// milliseconds), who made it, what kind of change it was, the fruit's
+/// name, and the fruit before and after the change.
+pub const CHANGE_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "FruitChange",
+  "namespace": "fruitdata",
+  "fields": [
+    {"name": "timestamp", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+    {"name": "actor", "type": "string"},
+    {"name": "action", "type": {"type": "enum", "name": "Action", "symbols": ["add", "remove", "update", "rename"]}},
+    {"name": "name", "type": "string"},
+    {"name": "before", "type": ["null", {
+      "type": "record",
+      "name": "Fruit",
+      "fields": [
+        {"name": "name", "type": "string"},
+        {"name": "length", "type": "float"},
+        {"name": "width", "type": "float"},
+        {"name": "height", "type": "float"},
+        {"name": "id", "type": ["null", "long"]},
+        {"name": "volume_formula", "type": ["null", "string"]}
+      ]
+    }]},
+    {"name": "after", "type": ["null", "Fruit"]}
+  ]
+}"#;
+
+/// Append `record` to `out` as Avro binary data in `CHANGE_SCHEMA`.
+fn avro_change(out: &mut Vec<u8>, record: &ChangeRecord) {
+    let event = &record.event;
+    avro_long(out, record.timestamp.0.saturating_mul(1000) as i64);
+    avro_string(out, &record.actor);
+    avro_long(
+        out,
+        match event.action() {
+            Action::Add => 0,
+            Action::Remove => 1,
+            Action::Update => 2,
+            Action::Rename => 3,
+        },
+    );
+    avro_string(out, event.name());
+    for fruit in [event.before(), event.after()] {
+        match fruit {
+            None => avro_long(out, 0),
+            Some(fruit) => {
+                avro_long(out, 1);
+                avro_fruit(out, fruit);
+            }
+        }
+    }
+}
+
+fn avro_fruit(out: &mut Vec<u8>, fruit: &FruitDimensions) {
+    avro_string(out, &fruit.name);
+    for dimension in [fruit.length, fruit.width, fruit.height] {
+        out.extend_from_slice(&dimension.value().to_le_bytes());
+    }
+    match fruit.id {
+        None => avro_long(out, 0),
+        Some(id) => {
+            avro_long(out, 1);
+            avro_long(out, id as i64);
+        }
+    }
+    match &fruit.volume_formula {
+        None => avro_long(out, 0),
+        Some(formula) => {
+            avro_long(out, 1);
+            avro_string(out, &formula.to_string());
+        }
+    }
+}
+
+/// An Avro `int` or `long`: zig-zag encoded, then 7 bits per byte, low
+/// bits first.
+fn avro_long(out: &mut Vec<u8>, n: i64) {
+    let mut n = ((n << 1) ^ (n >> 63)) as u64;
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn avro_string(out: &mut Vec<u8>, text: &str) {
+    avro_long(out, text.len() as i64);
+    out.extend_from_slice(text.as_bytes());
+}
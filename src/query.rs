@@ -0,0 +1,705 @@
+// ============================================================================
+// query.rs - jq-Style Queries over the Catalogue
+// ============================================================================
+// For one-off questions ("which fruits are longer than 5?", "how many are
+// there?") a flag for every kind of filter would be clumsy. `fruitdata
+// query` takes a small jq-like expression instead and runs it over the
+// catalogue as a JSON array of fruits:
+//
+//     fruitdata query '.[] | select(.length > 5) | .name'
+//     fruitdata query '[.[] | .length * .width * .height] | length'
+//
+// The supported subset of jq:
+//
+// - `.` (the input), `.field`, `.[n]`, `.[]` (every element), and chains
+//   of those such as `.dims.length` or `.[].name`
+// - literals: numbers, "strings", true, false, null
+// - `a | b` (feed every result of a into b), `[ ... ]` (collect results)
+// - arithmetic `+ - * /`, comparisons `== != < <= > >=`, `and`, `or`
+// - `select(cond)`, `not`, `length`, `keys`, and parentheses
+//
+// Like jq, an expression produces a stream of values: `.[]` produces one
+// per fruit, and everything after it runs once for each.
+// ============================================================================
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A binary operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+/// A parsed expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    /// `.`
+    Identity,
+    /// `e.field`
+    Field(Box<Expr>, String),
+    /// `e[n]`
+    Index(Box<Expr>, i64),
+    /// `e[]`
+    Each(Box<Expr>),
+    Literal(Value),
+    /// `[e]`
+    Collect(Box<Expr>),
+    /// `a | b`
+    Pipe(Box<Expr>, Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Select(Box<Expr>),
+    Not,
+    Length,
+    Keys,
+}
+
+/// A jq-style query, checked when it is parsed.
+///
+/// Serialized as its text.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::query::Query;
+/// use serde_json::json;
+///
+/// let fruits = json!([
+///     {"name": "Apple", "length": 4.0},
+///     {"name": "Banana", "length": 7.0},
+/// ]);
+/// let query: Query = ".[] | select(.length > 5) | .name".parse().unwrap();
+/// assert_eq!(query.run(&fruits).unwrap(), vec![json!("Banana")]);
+///
+/// let count: Query = "[.[] | select(.name != \"Apple\")] | length".parse().unwrap();
+/// assert_eq!(count.run(&fruits).unwrap(), vec![json!(1)]);
+///
+/// let deep = format!("{}.{}", "(".repeat(1000), ")".repeat(1000));
+/// assert_eq!(deep.parse::<Query>().unwrap_err(), "nested more than 128 levels deep");
+/// assert!(vec!["1"; 1000].join(" + ").parse::<Query>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Query {
+    source: String,
+    expr: Expr,
+}
+
+impl Query {
+    /// Run the query over `input`, returning every value it produces.
+    ///
+    /// # Error Cases
+    /// A step that doesn't fit its input, e.g. `.name` on a number or
+    /// `.[]` on a string.
+    pub fn run(&self, input: &Value) -> Result<Vec<Value>, String> {
+        eval(&self.expr, input)
+    }
+
+    /// Whether `input` passes the query used as a condition, as in
+    /// `select(...)`: some value it produces is neither `false` nor `null`.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::query::Query;
+    /// use serde_json::json;
+    ///
+    /// let long: Query = ".length > 5".parse().unwrap();
+    /// assert_eq!(long.matches(&json!({"name": "Banana", "length": 7.0})), Ok(true));
+    /// assert_eq!(long.matches(&json!({"name": "Apple", "length": 4.0})), Ok(false));
+    /// ```
+    ///
+    /// # Error Cases
+    /// As for `run`.
+    pub fn matches(&self, input: &Value) -> Result<bool, String> {
+        Ok(self.run(input)?.iter().any(truthy))
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let expr = parser.pipe()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected '{}' in query '{}'", token, s));
+        }
+        Ok(Query {
+            source: s.to_string(),
+            expr,
+        })
+    }
+}
+
+impl TryFrom<String> for Query {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Query> for String {
+    fn from(query: Query) -> Self {
+        query.source
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+// ============================================================================
+// PARSING
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Dot,
+    /// A name after a dot, or a bare word such as `select`.
+    Word(String),
+    Number(f64),
+    Str(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Dot => write!(f, "."),
+            Token::Word(word) => write!(f, "{}", word),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Str(s) => write!(f, "{:?}", s),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+/// Symbols, longest first so that `<=` isn't read as `<` then `=`.
+const SYMBOLS: [&str; 15] = [
+    "==", "!=", "<=", ">=", "<", ">", "|", "[", "]", "(", ")", "+", "-", "*", "/",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c == '.' {
+            tokens.push(Token::Dot);
+            rest = &rest[1..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let number = rest[..end]
+                .parse()
+                .map_err(|_| format!("invalid number '{}'", &rest[..end]))?;
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c == '"' {
+            // Let serde_json handle the escapes
+            let end = string_end(rest).ok_or_else(|| format!("unclosed string in '{}'", s))?;
+            let text = serde_json::from_str(&rest[..end])
+                .map_err(|e| format!("invalid string {}: {}", &rest[..end], e))?;
+            tokens.push(Token::Str(text));
+            rest = &rest[end..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            return Err(format!("unexpected '{}' in query '{}'", c, s));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// The length of the string literal at the start of `s`, quotes included.
+fn string_end(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// How deeply a query may nest. Parentheses, brackets, `select(...)` and
+/// unary minus each add a level, and so does each operator in a chain such
+/// as `1 + 2 + 3` (the expression tree is one deeper for each). Parsing and
+/// evaluation recurse once per level, so without a limit a long enough
+/// query would overflow the stack; 128 is serde_json's limit too.
+const MAX_DEPTH: usize = 128;
+
+/// A recursive-descent parser, one method per precedence level (lowest
+/// first): `|`, `or`, `and`, comparisons, `+ -`, `* /`, then postfix steps.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// How many levels deep the parser is (see `MAX_DEPTH`).
+    depth: usize,
+}
+
+impl Parser {
+    /// Go one level deeper (see `MAX_DEPTH`).
+    fn deeper(&mut self) -> Result<(), String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("nested more than {} levels deep", MAX_DEPTH));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Run `parse` one level deeper.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        self.deeper()?;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consume `token` if it comes next.
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            Some(other) => Err(format!("expected '{}', found '{}'", symbol, other)),
+            None => Err(format!("expected '{}' at the end of the query", symbol)),
+        }
+    }
+
+    fn pipe(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.or()?;
+        while self.eat(&Token::Symbol("|")) {
+            self.deeper()?;
+            expr = Expr::Pipe(Box::new(expr), Box::new(self.or()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.and()?;
+        while self.eat(&Token::Word("or".into())) {
+            self.deeper()?;
+            expr = Expr::Binary(Op::Or, Box::new(expr), Box::new(self.and()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.comparison()?;
+        while self.eat(&Token::Word("and".into())) {
+            self.deeper()?;
+            expr = Expr::Binary(Op::And, Box::new(expr), Box::new(self.comparison()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let expr = self.additive()?;
+        let op = match self.peek() {
+            Some(Token::Symbol("==")) => Op::Eq,
+            Some(Token::Symbol("!=")) => Op::Ne,
+            Some(Token::Symbol("<")) => Op::Lt,
+            Some(Token::Symbol("<=")) => Op::Le,
+            Some(Token::Symbol(">")) => Op::Gt,
+            Some(Token::Symbol(">=")) => Op::Ge,
+            _ => return Ok(expr),
+        };
+        self.pos += 1;
+        Ok(Expr::Binary(op, Box::new(expr), Box::new(self.additive()?)))
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("+")) => Op::Add,
+                Some(Token::Symbol("-")) => Op::Sub,
+                _ => {
+                    self.depth = depth;
+                    return Ok(expr);
+                }
+            };
+            self.pos += 1;
+            self.deeper()?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.postfix()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("*")) => Op::Mul,
+                Some(Token::Symbol("/")) => Op::Div,
+                _ => {
+                    self.depth = depth;
+                    return Ok(expr);
+                }
+            };
+            self.pos += 1;
+            self.deeper()?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.postfix()?));
+        }
+    }
+
+    /// A primary expression followed by any number of `.field`, `[n]` and
+    /// `[]` steps.
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(&Token::Dot) {
+                expr = self.field_or_brackets(expr)?;
+            } else if self.peek() == Some(&Token::Symbol("[")) {
+                expr = self.brackets(expr)?;
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    /// After a dot: a field name, brackets, or (for a lone `.`) nothing.
+    fn field_or_brackets(&mut self, expr: Expr) -> Result<Expr, String> {
+        match self.peek() {
+            Some(Token::Word(_)) | Some(Token::Str(_)) => match self.next() {
+                Some(Token::Word(name)) | Some(Token::Str(name)) => {
+                    Ok(Expr::Field(Box::new(expr), name))
+                }
+                _ => unreachable!(),
+            },
+            Some(Token::Symbol("[")) => self.brackets(expr),
+            _ => Ok(expr),
+        }
+    }
+
+    /// `[]` or `[n]`.
+    fn brackets(&mut self, expr: Expr) -> Result<Expr, String> {
+        self.expect("[")?;
+        if self.eat(&Token::Symbol("]")) {
+            return Ok(Expr::Each(Box::new(expr)));
+        }
+        let negative = self.eat(&Token::Symbol("-"));
+        let index = match self.next() {
+            Some(Token::Number(n)) if n.fract() == 0.0 => n as i64,
+            Some(other) => return Err(format!("expected an index, found '{}'", other)),
+            None => return Err("expected an index at the end of the query".to_string()),
+        };
+        self.expect("]")?;
+        Ok(Expr::Index(
+            Box::new(expr),
+            if negative { -index } else { index },
+        ))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Dot) => self.field_or_brackets(Expr::Identity),
+            Some(Token::Number(n)) => Ok(Expr::Literal(number(n))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::Symbol("(")) => {
+                let expr = self.nested(Self::pipe)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Symbol("[")) => {
+                if self.eat(&Token::Symbol("]")) {
+                    return Ok(Expr::Literal(Value::Array(Vec::new())));
+                }
+                let expr = self.nested(Self::pipe)?;
+                self.expect("]")?;
+                Ok(Expr::Collect(Box::new(expr)))
+            }
+            Some(Token::Symbol("-")) => {
+                let expr = self.nested(Self::postfix)?;
+                Ok(Expr::Binary(
+                    Op::Sub,
+                    Box::new(Expr::Literal(number(0.0))),
+                    Box::new(expr),
+                ))
+            }
+            Some(Token::Word(word)) => match word.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                "not" => Ok(Expr::Not),
+                "length" => Ok(Expr::Length),
+                "keys" => Ok(Expr::Keys),
+                "select" => {
+                    self.expect("(")?;
+                    let condition = self.nested(Self::pipe)?;
+                    self.expect(")")?;
+                    Ok(Expr::Select(Box::new(condition)))
+                }
+                other => Err(format!("unknown function '{}'", other)),
+            },
+            Some(other) => Err(format!("unexpected '{}'", other)),
+            None => Err("unexpected end of query".to_string()),
+        }
+    }
+}
+
+// ============================================================================
+// EVALUATION
+// ============================================================================
+
+fn eval(expr: &Expr, input: &Value) -> Result<Vec<Value>, String> {
+    match expr {
+        Expr::Identity => Ok(vec![input.clone()]),
+        Expr::Literal(value) => Ok(vec![value.clone()]),
+        Expr::Field(inner, name) => each_of(inner, input, |value| match value {
+            Value::Object(fields) => Ok(vec![fields.get(name).cloned().unwrap_or(Value::Null)]),
+            Value::Null => Ok(vec![Value::Null]),
+            other => Err(format!(
+                "cannot get '{}' of {} {}",
+                name,
+                type_name(&other),
+                other
+            )),
+        }),
+        Expr::Index(inner, index) => each_of(inner, input, |value| match value {
+            Value::Array(items) => {
+                let at = if *index < 0 {
+                    items.len() as i64 + index
+                } else {
+                    *index
+                };
+                let item = usize::try_from(at).ok().and_then(|at| items.get(at));
+                Ok(vec![item.cloned().unwrap_or(Value::Null)])
+            }
+            Value::Null => Ok(vec![Value::Null]),
+            other => Err(format!("cannot index {} {}", type_name(&other), other)),
+        }),
+        Expr::Each(inner) => each_of(inner, input, |value| match value {
+            Value::Array(items) => Ok(items),
+            Value::Object(fields) => Ok(fields.into_iter().map(|(_, v)| v).collect()),
+            other => Err(format!(
+                "cannot iterate over {} {}",
+                type_name(&other),
+                other
+            )),
+        }),
+        Expr::Collect(inner) => Ok(vec![Value::Array(eval(inner, input)?)]),
+        Expr::Pipe(first, second) => {
+            let mut results = Vec::new();
+            for value in eval(first, input)? {
+                results.extend(eval(second, &value)?);
+            }
+            Ok(results)
+        }
+        Expr::Binary(op, left, right) => {
+            // Like jq, every combination of the two sides' results
+            let mut results = Vec::new();
+            for r in eval(right, input)? {
+                for l in eval(left, input)? {
+                    results.push(binary(*op, &l, &r)?);
+                }
+            }
+            Ok(results)
+        }
+        Expr::Select(condition) => {
+            let mut results = Vec::new();
+            for value in eval(condition, input)? {
+                if truthy(&value) {
+                    results.push(input.clone());
+                }
+            }
+            Ok(results)
+        }
+        Expr::Not => Ok(vec![Value::Bool(!truthy(input))]),
+        Expr::Length => Ok(vec![match input {
+            Value::Array(items) => number(items.len() as f64),
+            Value::Object(fields) => number(fields.len() as f64),
+            Value::String(s) => number(s.chars().count() as f64),
+            Value::Number(n) => number(n.as_f64().unwrap_or(0.0).abs()),
+            Value::Null => number(0.0),
+            Value::Bool(_) => return Err("a boolean has no length".to_string()),
+        }]),
+        Expr::Keys => match input {
+            Value::Object(fields) => {
+                let mut keys: Vec<&String> = fields.keys().collect();
+                keys.sort();
+                Ok(vec![Value::Array(
+                    keys.into_iter().map(|k| Value::String(k.clone())).collect(),
+                )])
+            }
+            Value::Array(items) => Ok(vec![Value::Array(
+                (0..items.len()).map(|i| number(i as f64)).collect(),
+            )]),
+            other => Err(format!("{} {} has no keys", type_name(other), other)),
+        },
+    }
+}
+
+/// Evaluate `inner`, then apply `step` to each of its results.
+fn each_of(
+    inner: &Expr,
+    input: &Value,
+    mut step: impl FnMut(Value) -> Result<Vec<Value>, String>,
+) -> Result<Vec<Value>, String> {
+    let mut results = Vec::new();
+    for value in eval(inner, input)? {
+        results.extend(step(value)?);
+    }
+    Ok(results)
+}
+
+fn binary(op: Op, left: &Value, right: &Value) -> Result<Value, String> {
+    let ordering = || compare(left, right);
+    Ok(match op {
+        Op::And => Value::Bool(truthy(left) && truthy(right)),
+        Op::Or => Value::Bool(truthy(left) || truthy(right)),
+        Op::Eq => Value::Bool(ordering() == Ordering::Equal),
+        Op::Ne => Value::Bool(ordering() != Ordering::Equal),
+        Op::Lt => Value::Bool(ordering() == Ordering::Less),
+        Op::Le => Value::Bool(ordering() != Ordering::Greater),
+        Op::Gt => Value::Bool(ordering() == Ordering::Greater),
+        Op::Ge => Value::Bool(ordering() != Ordering::Less),
+        Op::Add => match (left, right) {
+            (Value::Null, other) | (other, Value::Null) => other.clone(),
+            (Value::String(a), Value::String(b)) => Value::String(format!("{}{}", a, b)),
+            (Value::Array(a), Value::Array(b)) => {
+                Value::Array(a.iter().chain(b).cloned().collect())
+            }
+            _ => arithmetic(op, left, right)?,
+        },
+        Op::Sub | Op::Mul | Op::Div => arithmetic(op, left, right)?,
+    })
+}
+
+fn arithmetic(op: Op, left: &Value, right: &Value) -> Result<Value, String> {
+    let (Some(a), Some(b)) = (left.as_f64(), right.as_f64()) else {
+        return Err(format!(
+            "cannot combine {} {} and {} {}",
+            type_name(left),
+            left,
+            type_name(right),
+            right
+        ));
+    };
+    let result = match op {
+        Op::Add => a + b,
+        Op::Sub => a - b,
+        Op::Mul => a * b,
+        Op::Div if b == 0.0 => return Err(format!("cannot divide {} by zero", a)),
+        Op::Div => a / b,
+        _ => unreachable!("not an arithmetic operator"),
+    };
+    Ok(number(result))
+}
+
+/// jq's order of values: null < false < true < numbers < strings < arrays
+/// < objects, then by value within a type.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => {
+            let (x, y) = (x.as_f64().unwrap_or(0.0), y.as_f64().unwrap_or(0.0));
+            x.total_cmp(&y)
+        }
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Array(x), Value::Array(y)) => x
+            .iter()
+            .zip(y)
+            .map(|(x, y)| compare(x, y))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| x.len().cmp(&y.len())),
+        (Value::Object(_), Value::Object(_)) if a == b => Ordering::Equal,
+        (Value::Object(_), Value::Object(_)) => a.to_string().cmp(&b.to_string()),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Everything but `false` and `null` counts as true.
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+/// A JSON number, written without a fraction when it is whole (so counts
+/// print as `3`, not `3.0`).
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        Value::Number(Number::from(n as i64))
+    } else {
+        Number::from_f64(n).map_or(Value::Null, Value::Number)
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
@@ -0,0 +1,215 @@
+// ============================================================================
+// raw.rs - Fruit Records Exactly as They Are on Disk
+// ============================================================================
+// `FruitDimensions` is the domain type: a fruit whose name is a string and
+// whose dimensions are finite numbers. Deserializing one from JSON stops at
+// the first wrong value, and says so in serde's words ("invalid type:
+// string "6", expected f32"), which is all a strict load needs.
+//
+// `RawFruitRecord` is the step before. Every field holds whatever JSON
+// value the file has there (or nothing), so any object loads as one, and
+// converting it with `FruitDimensions::try_from` checks every field and
+// reports each bad one as a `FieldError`. Partial loading uses this to
+// describe a skipped fruit completely, and tools that need to look at a
+// broken file (an editor showing every problem, a migration script) can
+// read its records without writing their own JSON handling.
+// ============================================================================
+
+use crate::error::FieldError;
+use crate::expr::Expression;
+use crate::measurements::Measurement;
+use crate::models::FruitDimensions;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// One fruit record as read from a file, before any validation.
+///
+/// The dimension fields accept the same aliases as `FruitDimensions` (see
+/// compat.rs), and unknown keys are kept in `extra`. A field that is `null`
+/// in the file reads as missing.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::raw::RawFruitRecord;
+///
+/// let json = r#"{"name": "Pear", "len": "6", "width": 3.5, "origin": "Spain"}"#;
+/// let raw: RawFruitRecord = serde_json::from_str(json).unwrap();
+/// assert_eq!(raw.length, Some("6".into()));
+/// assert_eq!(raw.extra["origin"], "Spain");
+///
+/// let problems = FruitDimensions::try_from(raw).unwrap_err();
+/// let problems: Vec<String> = problems.iter().map(ToString::to_string).collect();
+/// assert_eq!(
+///     problems,
+///     ["length: expected a number, found a string", "height: missing"]
+/// );
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RawFruitRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<Value>,
+    #[serde(
+        default,
+        alias = "len",
+        alias = "lengthCm",
+        alias = "length_cm",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub length: Option<Value>,
+    #[serde(
+        default,
+        alias = "w",
+        alias = "widthCm",
+        alias = "width_cm",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub width: Option<Value>,
+    #[serde(
+        default,
+        alias = "h",
+        alias = "heightCm",
+        alias = "height_cm",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub height: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_formula: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measurements: Option<Value>,
+    /// Keys that aren't fields of `FruitDimensions`, as they were read.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl RawFruitRecord {
+    /// The record's name, if it has one that is a string.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().and_then(Value::as_str)
+    }
+}
+
+/// Validate every field of the record, returning all the problems found
+/// (in field order) rather than just the first.
+impl TryFrom<RawFruitRecord> for FruitDimensions {
+    type Error = Vec<FieldError>;
+
+    fn try_from(raw: RawFruitRecord) -> Result<Self, Self::Error> {
+        let mut problems = Vec::new();
+        let p = &mut problems;
+        let name = check(p, "name", required(raw.name).and_then(string));
+        let length = check(
+            p,
+            "length",
+            required(raw.length).and_then(|v| dimension(&v)),
+        );
+        let width = check(p, "width", required(raw.width).and_then(|v| dimension(&v)));
+        let height = check(
+            p,
+            "height",
+            required(raw.height).and_then(|v| dimension(&v)),
+        );
+        let id = check(p, "id", raw.id.map(identifier).transpose());
+        let volume_formula = check(
+            p,
+            "volume_formula",
+            raw.volume_formula.map(formula).transpose(),
+        );
+        let measurements = check(
+            p,
+            "measurements",
+            raw.measurements
+                .map(|v| Vec::<Measurement>::deserialize(v).map_err(|e| e.to_string()))
+                .transpose(),
+        );
+
+        match (
+            name,
+            length,
+            width,
+            height,
+            id,
+            volume_formula,
+            measurements,
+        ) {
+            (
+                Some(name),
+                Some(length),
+                Some(width),
+                Some(height),
+                Some(id),
+                Some(volume_formula),
+                Some(measurements),
+            ) => Ok(FruitDimensions {
+                name,
+                length: length.into(),
+                width: width.into(),
+                height: height.into(),
+                id,
+                volume_formula: volume_formula.map(Box::new),
+                measurements: measurements.unwrap_or_default(),
+                extra: raw.extra,
+            }),
+            _ => Err(problems),
+        }
+    }
+}
+
+/// The value of a field, or `None` after adding its problem to `problems`.
+fn check<T>(
+    problems: &mut Vec<FieldError>,
+    field: &'static str,
+    result: Result<T, String>,
+) -> Option<T> {
+    result
+        .map_err(|message| problems.push(FieldError { field, message }))
+        .ok()
+}
+
+/// A dimension's value as an `f32`, if it is a number that fits in one.
+/// Strict loading shares these messages.
+pub(crate) fn dimension(value: &Value) -> Result<f32, String> {
+    match value.as_f64() {
+        None => Err(format!("expected a number, found {}", describe(value))),
+        Some(n) if !(n as f32).is_finite() => Err(format!("{} doesn't fit in a 32-bit float", n)),
+        Some(n) => Ok(n as f32),
+    }
+}
+
+/// A short description of a JSON value's type, for error messages.
+pub(crate) fn describe(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn required(value: Option<Value>) -> Result<Value, String> {
+    value.ok_or_else(|| "missing".to_string())
+}
+
+fn string(value: Value) -> Result<String, String> {
+    match value {
+        Value::String(text) => Ok(text),
+        other => Err(format!("expected a string, found {}", describe(&other))),
+    }
+}
+
+fn identifier(value: Value) -> Result<u64, String> {
+    value.as_u64().ok_or_else(|| {
+        format!(
+            "expected a non-negative integer, found {}",
+            describe(&value)
+        )
+    })
+}
+
+fn formula(value: Value) -> Result<Expression, String> {
+    string(value)?.parse()
+}
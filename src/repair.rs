@@ -0,0 +1,188 @@
+// ============================================================================
+// repair.rs - Recovering Fruits from a Damaged Catalogue File
+// ============================================================================
+// A catalogue file cut short by a full disk, or mangled by a bad merge, is
+// no longer valid JSON, and serde refuses the whole file over one broken
+// spot. Usually most of the fruits are still there, intact.
+//
+// `recover` finds them by scanning for JSON objects: from every `{` it looks
+// for the matching `}` (skipping braces inside strings) and tries to read
+// the text in between as a fruit. Whatever doesn't read as a fruit is
+// reported as lost, so the user knows exactly what to re-enter by hand.
+//
+// Objects nest, so an object that isn't a fruit is scanned inside as well:
+// that is how the fruits inside a `{"fruits": [...]}` envelope are found,
+// even when the envelope itself was truncated and never closed.
+// ============================================================================
+
+use crate::models::FruitDimensions;
+use serde::Serialize;
+
+/// Part of a damaged file that couldn't be recovered.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Lost {
+    /// The line (from 1) where the lost text starts.
+    pub line: usize,
+    /// The start of the lost text, shortened to one line.
+    pub text: String,
+    /// Why it couldn't be read, e.g. "truncated" or a parse error.
+    pub reason: String,
+}
+
+/// The result of `recover`.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Recovery {
+    /// The fruits that could be read, in file order.
+    pub fruits: Vec<FruitDimensions>,
+    /// The records (and other top-level data) that couldn't.
+    pub lost: Vec<Lost>,
+}
+
+/// How much of a lost record's text is quoted in the report.
+const SNIPPET_LENGTH: usize = 60;
+
+/// Recover every well-formed fruit from a possibly broken catalogue file.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::repair::recover;
+///
+/// // Truncated in the middle of the third fruit, with a typo in the second
+/// let damaged = br#"[
+///   {"name": "Apple", "length": 4.0, "width": 2.5, "height": 1.5},
+///   {"name": "Pear", "length": 6.0, "width": 3.5 "height": 2.5},
+///   {"name": "Kiwi", "length": 3.0, "wid"#;
+/// let recovery = recover(damaged);
+///
+/// assert_eq!(recovery.fruits.len(), 1);
+/// assert_eq!(recovery.lost.len(), 2);
+/// assert_eq!(recovery.lost[0].line, 3);
+/// assert_eq!(recovery.lost[1].reason, "truncated");
+/// ```
+pub fn recover(bytes: &[u8]) -> Recovery {
+    let mut fruits = Vec::new();
+    // (start, end, reason) of every object that wasn't a fruit
+    let mut failed: Vec<(usize, usize, String)> = Vec::new();
+    let mut recovered: Vec<(usize, usize)> = Vec::new();
+
+    let mut pos = 0;
+    let mut in_string = false;
+    while pos < bytes.len() {
+        let byte = bytes[pos];
+        if in_string {
+            match byte {
+                b'\\' => pos += 1,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            pos += 1;
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'{' => match matching_brace(bytes, pos) {
+                Some(end) => match serde_json::from_slice::<FruitDimensions>(&bytes[pos..=end]) {
+                    Ok(fruit) => {
+                        fruits.push(fruit);
+                        recovered.push((pos, end));
+                        pos = end + 1;
+                        continue;
+                    }
+                    // Not a fruit; its contents are scanned next. The
+                    // error's position is within the object, so drop it
+                    Err(e) => {
+                        let message = e.to_string();
+                        let reason = match message.rsplit_once(" at line ") {
+                            Some((reason, _)) => reason.to_string(),
+                            None => message,
+                        };
+                        failed.push((pos, end, reason));
+                    }
+                },
+                None => failed.push((pos, bytes.len(), "truncated".to_string())),
+            },
+            _ => {}
+        }
+        pos += 1;
+    }
+
+    // An object inside a lost one is part of the same loss. An object
+    // holding recovered fruits is the envelope, or a fruit whose closing
+    // brace is missing (so the scan overran into the fruits after it)
+    let contains = |outer: (usize, usize), inner: (usize, usize)| {
+        outer != inner && outer.0 <= inner.0 && inner.1 <= outer.1
+    };
+    let is_container = |span: (usize, usize)| recovered.iter().any(|r| contains(span, *r));
+    let first_byte = bytes.iter().position(|b| !b.is_ascii_whitespace());
+    let mut lost = Vec::new();
+    for (start, end, reason) in &failed {
+        let span = (*start, *end);
+        if failed
+            .iter()
+            .any(|(s, e, _)| contains((*s, *e), span) && !is_container((*s, *e)))
+        {
+            continue;
+        }
+        if is_container(span) {
+            let reason = if Some(*start) == first_byte {
+                "the file's other top-level keys, if any"
+            } else {
+                "incomplete record"
+            };
+            lost.push(lost_at(bytes, *start, reason));
+            continue;
+        }
+        lost.push(lost_at(bytes, *start, reason));
+    }
+
+    Recovery { fruits, lost }
+}
+
+/// The position of the `}` closing the object that starts at `start`, or
+/// `None` if the file ends first.
+fn matching_brace(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (offset, &byte) in bytes[start..].iter().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + offset);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn lost_at(bytes: &[u8], start: usize, reason: &str) -> Lost {
+    let line = bytes[..start].iter().filter(|&&b| b == b'\n').count() + 1;
+    // Enough bytes for the snippet even after collapsing whitespace
+    let end = bytes.len().min(start + SNIPPET_LENGTH * 8);
+    let text = String::from_utf8_lossy(&bytes[start..end]);
+    let text: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut snippet: String = text.chars().take(SNIPPET_LENGTH).collect();
+    if text.chars().count() > SNIPPET_LENGTH {
+        snippet.push_str("...");
+    }
+    Lost {
+        line,
+        text: snippet,
+        reason: reason.to_string(),
+    }
+}
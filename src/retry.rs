@@ -0,0 +1,211 @@
+// ============================================================================
+// retry.rs - Retrying Transient Store Failures
+// ============================================================================
+// Some failures go away if you simply try again: a read interrupted by a
+// signal, a network share that timed out, a connection reset by a server
+// being restarted. Aborting a long batch job over one of those is
+// frustrating, so a store can be wrapped in `Retrying`, which repeats a
+// failed load or save according to a `RetryPolicy`: up to `max_attempts`
+// tries, waiting longer after each failure (exponential backoff), with some
+// randomness (jitter) so that many clients don't all retry at once.
+//
+// Only errors that look transient are retried (see `is_transient`). A
+// broken file or a rejected change fails the same way every time, so those
+// are returned at once. The policy comes from the config file:
+//
+//     { "retry": { "max_attempts": 5, "initial_delay_ms": 200 } }
+// ============================================================================
+
+use crate::catalog::Catalogue;
+use crate::journal::Journal;
+use crate::store::CatalogStore;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often, and how patiently, to retry a failed operation.
+///
+/// # Config file example
+/// ```json
+/// { "retry": { "max_attempts": 5, "initial_delay_ms": 100, "max_delay_ms": 2000 } }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// How many times to try in all (1 means "don't retry").
+    pub max_attempts: u32,
+    /// How long to wait before the first retry, in milliseconds.
+    pub initial_delay_ms: u64,
+    /// The longest wait between two tries, in milliseconds.
+    pub max_delay_ms: u64,
+    /// How much longer each wait is than the one before.
+    pub multiplier: f64,
+    /// Wait a random time between half and all of the delay, so that
+    /// clients that failed together don't retry together.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay_ms: 100,
+            max_delay_ms: 5000,
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that tries once and never retries.
+    pub fn never() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// How long to wait after failed try number `attempt` (from 1), before
+    /// jitter.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::retry::RetryPolicy;
+    /// use std::time::Duration;
+    ///
+    /// let policy = RetryPolicy { max_delay_ms: 300, ..RetryPolicy::default() };
+    /// assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    /// assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    /// assert_eq!(policy.backoff(3), Duration::from_millis(300)); // capped
+    /// ```
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .max(1.0)
+            .powi(attempt.saturating_sub(1) as i32);
+        let millis = (self.initial_delay_ms as f64 * factor).min(self.max_delay_ms as f64);
+        Duration::from_millis(millis as u64)
+    }
+
+    /// Run `operation`, retrying it while it fails with a transient error
+    /// and tries are left. Returns the first success, or the last error.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::retry::RetryPolicy;
+    /// use std::io::{Error, ErrorKind};
+    ///
+    /// let policy = RetryPolicy { initial_delay_ms: 1, ..RetryPolicy::default() };
+    /// let mut tries = 0;
+    /// let result = policy.run(|| {
+    ///     tries += 1;
+    ///     if tries < 3 {
+    ///         Err(Error::from(ErrorKind::TimedOut).into())
+    ///     } else {
+    ///         Ok(tries)
+    ///     }
+    /// });
+    /// assert_eq!(result.unwrap(), 3);
+    /// ```
+    pub fn run<T>(
+        &self,
+        mut operation: impl FnMut() -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut attempt = 1;
+        loop {
+            match operation() {
+                Err(e) if attempt < self.max_attempts && is_transient(e.as_ref()) => {
+                    thread::sleep(self.delay(attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The backoff after try `attempt`, with jitter if enabled.
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.backoff(attempt);
+        if !self.jitter {
+            return delay;
+        }
+        // Any randomness will do here, so the clock's nanoseconds suffice
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        let random = f64::from(nanos % 1000) / 1000.0;
+        delay.mul_f64(0.5 + random / 2.0)
+    }
+}
+
+/// Whether an error is worth retrying: an I/O error of a kind that tends
+/// to go away by itself (timeouts, interruptions, dropped connections).
+pub fn is_transient(error: &(dyn Error + 'static)) -> bool {
+    let Some(error) = error.downcast_ref::<std::io::Error>() else {
+        return false;
+    };
+    matches!(
+        error.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::TimedOut
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof
+    )
+}
+
+/// A store whose loads and saves are retried according to a policy.
+#[derive(Debug, Clone)]
+pub struct Retrying<S> {
+    inner: S,
+    policy: RetryPolicy,
+}
+
+impl<S: CatalogStore> Retrying<S> {
+    pub fn new(inner: S, policy: RetryPolicy) -> Self {
+        Retrying { inner, policy }
+    }
+
+    /// The wrapped store.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: CatalogStore> CatalogStore for Retrying<S> {
+    fn describe(&self) -> String {
+        self.inner.describe()
+    }
+
+    fn exists(&self) -> bool {
+        self.inner.exists()
+    }
+
+    fn load(&self) -> Result<Catalogue, Box<dyn Error>> {
+        self.policy.run(|| self.inner.load())
+    }
+
+    fn save(&self, catalogue: &mut Catalogue) -> Result<(), Box<dyn Error>> {
+        self.policy.run(|| self.inner.save(catalogue))
+    }
+
+    fn explain(&self, error: &(dyn Error + 'static)) -> Option<String> {
+        self.inner.explain(error)
+    }
+
+    fn journal(&self) -> Option<Journal> {
+        self.inner.journal()
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.inner.path()
+    }
+}
@@ -0,0 +1,947 @@
+// ============================================================================
+// rpc.rs - The JSON-RPC Protocol
+// ============================================================================
+// `fruitdata ipc` and `fruitdata daemon` answer JSON-RPC 2.0 requests, one
+// per line. This module is the protocol they share, and that editors and
+// other frontends can build on instead of scraping the command line's text:
+//
+// - `Request` and `Response`, the JSON-RPC envelope
+// - `Call`, one variant per operation, with its params by name:
+//
+//     {"jsonrpc": "2.0", "id": 1, "method": "update",
+//      "params": {"name": "Apple", "width": 2.8}}
+//
+// - `CommandResult`, what a call returns: the exit status the command
+//   would have had, its report as `--format json` prints it (an object
+//   whose `"outcome"` says what happened), and its notes and hints
+// - `RpcError`, with the standard JSON-RPC `codes` and our own; a command
+//   that ran but failed (say, a file that didn't validate) carries its
+//   `CommandResult` as the error's `data`
+//
+// A call is turned into the words of a command line and parsed like one,
+// so it takes exactly what the command line does, and its params are named
+// after the command's arguments (`old_name`, `dry_run`, ...). Output
+// formats don't apply, as the report is always JSON. For anything `Call`
+// doesn't cover, the params can instead be an array of the command's
+// arguments as typed after its name (`"params": ["Apple", "--width",
+// "2.8"]`). The servers' own long-running commands (daemon, ipc,
+// ingest-mqtt) can't be called.
+//
+// A request without an `id` is a notification: it is run, but not
+// answered. A batch (an array of requests) is answered with an array.
+// ============================================================================
+
+use crate::cli::{parse_words, run_command, CommandOutcome, Commands, Context};
+use crate::present::{render_with, OutputFormat, RenderOptions};
+use crate::store::CatalogStore;
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::PathBuf;
+
+/// The JSON-RPC version spoken.
+pub const VERSION: &str = "2.0";
+
+/// The error codes of `RpcError`: JSON-RPC's own, then fruitdata's.
+pub mod codes {
+    /// The message isn't valid JSON.
+    pub const PARSE_ERROR: i64 = -32700;
+    /// The message isn't a JSON-RPC 2.0 request.
+    pub const INVALID_REQUEST: i64 = -32600;
+    /// There is no such method.
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    /// The params aren't valid for the method.
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// The command ran and failed; `data` holds its result if it has one.
+    pub const COMMAND_FAILED: i64 = -32000;
+    /// The command can't be run through the protocol (`daemon`, `ipc`,
+    /// `ingest-mqtt`).
+    pub const NOT_ALLOWED: i64 = -32001;
+}
+
+/// The methods of `Call`.
+pub const METHODS: &[&str] = &[
+    "init",
+    "list",
+    "get",
+    "exists",
+    "add",
+    "remove",
+    "rename",
+    "update",
+    "audit",
+    "history",
+    "history-gc",
+    "import",
+    "compact",
+    "convert",
+    "export",
+    "diff",
+    "validate",
+    "lint",
+    "dupes",
+    "grade",
+    "query",
+    "count",
+    "stats",
+    "summary",
+    "measure-add",
+    "growth",
+    "label",
+    #[cfg(feature = "barcode")]
+    "barcode",
+    "repair",
+    "restore",
+];
+
+/// A request's ID, echoed in its response.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum Id {
+    Number(i64),
+    String(String),
+}
+
+impl From<i64> for Id {
+    fn from(id: i64) -> Self {
+        Id::Number(id)
+    }
+}
+
+impl From<&str> for Id {
+    fn from(id: &str) -> Self {
+        Id::String(id.to_string())
+    }
+}
+
+/// One operation and its params, by name (see the module comment). Each
+/// field is the command-line argument of the same name; `None` and `false`
+/// leave it out.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::rpc::Call;
+/// use serde_json::json;
+///
+/// let call: Call = serde_json::from_value(json!({
+///     "method": "rename",
+///     "params": {"old_name": "Kiwi", "new_name": "Golden Kiwi"}
+/// }))
+/// .unwrap();
+/// assert_eq!(call.to_args(), ["rename", "--", "Kiwi", "Golden Kiwi"]);
+///
+/// // Misspelt params are refused rather than ignored
+/// let typo = json!({"method": "add", "params": {"name": "Kiwi", "lenght": 3}});
+/// assert!(serde_json::from_value::<Call>(typo).is_err());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(
+    tag = "method",
+    content = "params",
+    rename_all = "kebab-case",
+    deny_unknown_fields
+)]
+pub enum Call {
+    Init {
+        path: Option<PathBuf>,
+        template: Option<String>,
+    },
+    List {
+        sort_by: Option<String>,
+        template: Option<String>,
+        fields: Option<String>,
+        glob: Option<String>,
+        regex: Option<String>,
+        size_class: Option<String>,
+        head: Option<usize>,
+        tail: Option<usize>,
+        #[serde(default)]
+        count: bool,
+    },
+    Get {
+        name: String,
+        id: Option<u64>,
+        #[serde(default)]
+        measurements: bool,
+    },
+    Exists {
+        name: String,
+    },
+    Add {
+        name: String,
+        length: f32,
+        width: f32,
+        height: f32,
+    },
+    Remove {
+        name: String,
+        id: Option<u64>,
+    },
+    Rename {
+        old_name: String,
+        new_name: String,
+        id: Option<u64>,
+    },
+    Update {
+        name: String,
+        id: Option<u64>,
+        new_name: Option<String>,
+        length: Option<f32>,
+        width: Option<f32>,
+        height: Option<f32>,
+        /// The fields to change, as a JSON object.
+        patch: Option<Value>,
+    },
+    Audit {
+        since: Option<String>,
+        until: Option<String>,
+        actor: Option<String>,
+        action: Option<String>,
+        fruit: Option<String>,
+    },
+    History {
+        name: String,
+        id: Option<u64>,
+    },
+    /// `history gc`.
+    HistoryGc {
+        keep: Option<String>,
+    },
+    Import {
+        input: PathBuf,
+        /// `--map`, once for each mapping.
+        #[serde(default)]
+        map: Vec<String>,
+        columns: Option<String>,
+        columns_file: Option<PathBuf>,
+        records: Option<String>,
+        format: Option<String>,
+        delimiter: Option<char>,
+        #[serde(default)]
+        no_header: bool,
+        mode: Option<String>,
+        on_duplicate: Option<String>,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    Compact {
+        #[serde(default)]
+        sort_keys: bool,
+    },
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        from: Option<String>,
+        to: Option<String>,
+        #[serde(default)]
+        calibrate: bool,
+    },
+    Export {
+        #[serde(rename = "where")]
+        condition: Option<String>,
+        output: PathBuf,
+        to: Option<String>,
+        delimiter: Option<char>,
+        #[serde(default)]
+        no_header: bool,
+    },
+    Diff {
+        before: PathBuf,
+        after: PathBuf,
+    },
+    Validate {
+        file: Option<PathBuf>,
+        checksum: Option<String>,
+    },
+    Lint {
+        #[serde(default)]
+        deny: Vec<String>,
+    },
+    Dupes {
+        tolerance: Option<f32>,
+    },
+    Grade {},
+    Query {
+        filter: String,
+        #[serde(default)]
+        raw_output: bool,
+    },
+    Count {
+        #[serde(rename = "where")]
+        condition: Option<String>,
+    },
+    Stats {
+        group_by: Option<String>,
+    },
+    Summary {
+        rows: String,
+        cols: Option<String>,
+        value: Option<String>,
+        #[serde(default)]
+        csv: bool,
+    },
+    /// `measure add`.
+    MeasureAdd {
+        name: String,
+        length: f32,
+        width: f32,
+        height: f32,
+        at: Option<String>,
+        id: Option<u64>,
+    },
+    Growth {
+        name: String,
+        id: Option<u64>,
+    },
+    Label {
+        name: String,
+        id: Option<u64>,
+        format: Option<String>,
+        output: Option<PathBuf>,
+    },
+    #[cfg(feature = "barcode")]
+    Barcode {
+        name: String,
+        id: Option<u64>,
+        output: PathBuf,
+        symbology: Option<String>,
+        scale: Option<u32>,
+        height: Option<u32>,
+    },
+    Repair {
+        input: Option<PathBuf>,
+        output: Option<PathBuf>,
+    },
+    Restore {
+        at: String,
+        output: Option<PathBuf>,
+        #[serde(default)]
+        yes: bool,
+    },
+}
+
+/// The words of a command line, built up option by option. Options are
+/// written `--flag=value` and the positional arguments come after `--`,
+/// so that values starting with `-` aren't taken for flags.
+struct Words {
+    words: Vec<String>,
+    positionals: Vec<String>,
+}
+
+impl Words {
+    fn new(command: &[&str]) -> Words {
+        Words {
+            words: command.iter().map(|word| word.to_string()).collect(),
+            positionals: Vec::new(),
+        }
+    }
+
+    fn arg(mut self, value: impl ToString) -> Words {
+        self.positionals.push(value.to_string());
+        self
+    }
+
+    /// A positional argument that may be left out.
+    fn args(self, value: Option<impl ToString>) -> Words {
+        match value {
+            Some(value) => self.arg(value),
+            None => self,
+        }
+    }
+
+    fn opt(mut self, flag: &str, value: Option<impl ToString>) -> Words {
+        if let Some(value) = value {
+            self.words.push(format!("--{}={}", flag, value.to_string()));
+        }
+        self
+    }
+
+    fn flag(mut self, flag: &str, on: bool) -> Words {
+        if on {
+            self.words.push(format!("--{}", flag));
+        }
+        self
+    }
+
+    fn done(mut self) -> Vec<String> {
+        if !self.positionals.is_empty() {
+            self.words.push("--".to_string());
+            self.words.append(&mut self.positionals);
+        }
+        self.words
+    }
+}
+
+impl Call {
+    /// The call as the words of a command line, without `fruitdata`.
+    pub fn to_args(&self) -> Vec<String> {
+        let path = |path: &Option<PathBuf>| path.as_ref().map(|p| p.display().to_string());
+        match self {
+            Call::Init {
+                path: file,
+                template,
+            } => Words::new(&["init"])
+                .opt("template", template.as_ref())
+                .args(file.as_ref().map(|p| p.display()))
+                .done(),
+            Call::List {
+                sort_by,
+                template,
+                fields,
+                glob,
+                regex,
+                size_class,
+                head,
+                tail,
+                count,
+            } => Words::new(&["list"])
+                .opt("sort-by", sort_by.as_ref())
+                .opt("template", template.as_ref())
+                .opt("fields", fields.as_ref())
+                .opt("glob", glob.as_ref())
+                .opt("regex", regex.as_ref())
+                .opt("size-class", size_class.as_ref())
+                .opt("head", *head)
+                .opt("tail", *tail)
+                .flag("count", *count)
+                .done(),
+            Call::Get {
+                name,
+                id,
+                measurements,
+            } => Words::new(&["get"])
+                .opt("id", *id)
+                .flag("measurements", *measurements)
+                .arg(name)
+                .done(),
+            Call::Exists { name } => Words::new(&["exists"]).arg(name).done(),
+            Call::Add {
+                name,
+                length,
+                width,
+                height,
+            } => Words::new(&["add"])
+                .arg(name)
+                .arg(length)
+                .arg(width)
+                .arg(height)
+                .done(),
+            Call::Remove { name, id } => Words::new(&["remove"]).opt("id", *id).arg(name).done(),
+            Call::Rename {
+                old_name,
+                new_name,
+                id,
+            } => Words::new(&["rename"])
+                .opt("id", *id)
+                .arg(old_name)
+                .arg(new_name)
+                .done(),
+            Call::Update {
+                name,
+                id,
+                new_name,
+                length,
+                width,
+                height,
+                patch,
+            } => Words::new(&["update"])
+                .opt("id", *id)
+                .opt("new-name", new_name.as_ref())
+                .opt("length", *length)
+                .opt("width", *width)
+                .opt("height", *height)
+                .opt("patch", patch.as_ref())
+                .arg(name)
+                .done(),
+            Call::Audit {
+                since,
+                until,
+                actor,
+                action,
+                fruit,
+            } => Words::new(&["audit"])
+                .opt("since", since.as_ref())
+                .opt("until", until.as_ref())
+                .opt("actor", actor.as_ref())
+                .opt("action", action.as_ref())
+                .opt("fruit", fruit.as_ref())
+                .done(),
+            Call::History { name, id } => Words::new(&["history"]).opt("id", *id).arg(name).done(),
+            Call::HistoryGc { keep } => Words::new(&["history", "gc"])
+                .opt("keep", keep.as_ref())
+                .done(),
+            Call::Import {
+                input,
+                map,
+                columns,
+                columns_file,
+                records,
+                format,
+                delimiter,
+                no_header,
+                mode,
+                on_duplicate,
+                dry_run,
+            } => map
+                .iter()
+                .fold(Words::new(&["import"]), |words, map| {
+                    words.opt("map", Some(map))
+                })
+                .opt("columns", columns.as_ref())
+                .opt("columns-file", path(columns_file))
+                .opt("records", records.as_ref())
+                .opt("format", format.as_ref())
+                .opt("delimiter", *delimiter)
+                .flag("no-header", *no_header)
+                .opt("mode", mode.as_ref())
+                .opt("on-duplicate", on_duplicate.as_ref())
+                .flag("dry-run", *dry_run)
+                .arg(input.display())
+                .done(),
+            Call::Compact { sort_keys } => Words::new(&["compact"])
+                .flag("sort-keys", *sort_keys)
+                .done(),
+            Call::Convert {
+                input,
+                output,
+                from,
+                to,
+                calibrate,
+            } => Words::new(&["convert"])
+                .opt("from", from.as_ref())
+                .opt("to", to.as_ref())
+                .flag("calibrate", *calibrate)
+                .arg(input.display())
+                .arg(output.display())
+                .done(),
+            Call::Export {
+                condition,
+                output,
+                to,
+                delimiter,
+                no_header,
+            } => Words::new(&["export"])
+                .opt("where", condition.as_ref())
+                .opt("output", Some(output.display()))
+                .opt("to", to.as_ref())
+                .opt("delimiter", *delimiter)
+                .flag("no-header", *no_header)
+                .done(),
+            Call::Diff { before, after } => Words::new(&["diff"])
+                .arg(before.display())
+                .arg(after.display())
+                .done(),
+            Call::Validate { file, checksum } => Words::new(&["validate"])
+                .opt("checksum", checksum.as_ref())
+                .args(file.as_ref().map(|p| p.display()))
+                .done(),
+            Call::Lint { deny } => deny
+                .iter()
+                .fold(Words::new(&["lint"]), |words, deny| {
+                    words.opt("deny", Some(deny))
+                })
+                .done(),
+            Call::Dupes { tolerance } => Words::new(&["dupes"]).opt("tolerance", *tolerance).done(),
+            Call::Grade {} => Words::new(&["grade"]).done(),
+            Call::Query { filter, raw_output } => Words::new(&["query"])
+                .flag("raw-output", *raw_output)
+                .arg(filter)
+                .done(),
+            Call::Count { condition } => Words::new(&["count"])
+                .opt("where", condition.as_ref())
+                .done(),
+            Call::Stats { group_by } => Words::new(&["stats"])
+                .opt("group-by", group_by.as_ref())
+                .done(),
+            Call::Summary {
+                rows,
+                cols,
+                value,
+                csv,
+            } => Words::new(&["summary"])
+                .opt("rows", Some(rows))
+                .opt("cols", cols.as_ref())
+                .opt("value", value.as_ref())
+                .flag("csv", *csv)
+                .done(),
+            Call::MeasureAdd {
+                name,
+                length,
+                width,
+                height,
+                at,
+                id,
+            } => Words::new(&["measure", "add"])
+                .opt("at", at.as_ref())
+                .opt("id", *id)
+                .arg(name)
+                .arg(length)
+                .arg(width)
+                .arg(height)
+                .done(),
+            Call::Growth { name, id } => Words::new(&["growth"]).opt("id", *id).arg(name).done(),
+            Call::Label {
+                name,
+                id,
+                format,
+                output,
+            } => Words::new(&["label"])
+                .opt("id", *id)
+                .opt("format", format.as_ref())
+                .opt("output", path(output))
+                .arg(name)
+                .done(),
+            #[cfg(feature = "barcode")]
+            Call::Barcode {
+                name,
+                id,
+                output,
+                symbology,
+                scale,
+                height,
+            } => Words::new(&["barcode"])
+                .opt("id", *id)
+                .opt("output", Some(output.display()))
+                .opt("symbology", symbology.as_ref())
+                .opt("scale", *scale)
+                .opt("height", *height)
+                .arg(name)
+                .done(),
+            Call::Repair { input, output } => Words::new(&["repair"])
+                .opt("output", path(output))
+                .args(input.as_ref().map(|p| p.display()))
+                .done(),
+            Call::Restore { at, output, yes } => Words::new(&["restore"])
+                .opt("at", Some(at))
+                .opt("output", path(output))
+                .flag("yes", *yes)
+                .done(),
+        }
+    }
+
+    /// The command the call runs.
+    ///
+    /// # Error Cases
+    /// The params aren't valid arguments for the command (`INVALID_PARAMS`,
+    /// with clap's message).
+    pub fn command(&self) -> Result<Commands, RpcError> {
+        parse_words(self.to_args())
+            .map_err(|e| RpcError::new(codes::INVALID_PARAMS, e.to_string().trim_end().to_string()))
+    }
+}
+
+/// A JSON-RPC request.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::cli::Commands;
+/// use fruitdata::rpc::{Call, Request};
+///
+/// let call = Call::Remove { name: "-Kiwi-".into(), id: None };
+/// let request = Request::new(7, &call);
+/// assert_eq!(
+///     serde_json::to_string(&request).unwrap(),
+///     r#"{"jsonrpc":"2.0","id":7,"method":"remove","params":{"id":null,"name":"-Kiwi-"}}"#
+/// );
+/// assert!(matches!(request.command().unwrap(), Commands::Remove { name, .. } if name == "-Kiwi-"));
+///
+/// // Arguments as typed on the command line work too
+/// let line: Request = serde_json::from_str(
+///     r#"{"jsonrpc": "2.0", "id": 8, "method": "get", "params": ["Apple", "--measurements"]}"#,
+/// )
+/// .unwrap();
+/// assert!(matches!(line.command().unwrap(), Commands::Get { measurements: true, .. }));
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// `None` for a notification, which isn't answered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+    pub method: String,
+    /// An object of named params (see `Call`) or an array of command-line
+    /// arguments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+impl Request {
+    /// A request to make `call`, answered with `id`.
+    pub fn new(id: impl Into<Id>, call: &Call) -> Request {
+        Request {
+            id: Some(id.into()),
+            ..Request::notification(call)
+        }
+    }
+
+    /// A request to make `call` without being answered.
+    pub fn notification(call: &Call) -> Request {
+        let mut call = serde_json::to_value(call).expect("calls serialize to JSON");
+        Request {
+            jsonrpc: VERSION.to_string(),
+            id: None,
+            method: call["method"].as_str().unwrap_or_default().to_string(),
+            params: call.get_mut("params").map(Value::take),
+        }
+    }
+
+    /// The call, if the params are named.
+    ///
+    /// # Error Cases
+    /// - The method isn't one of `METHODS` (`METHOD_NOT_FOUND`)
+    /// - The params are missing one, have an unknown one, or have one of the
+    ///   wrong type (`INVALID_PARAMS`)
+    pub fn call(&self) -> Result<Call, RpcError> {
+        if !METHODS.contains(&self.method.as_str()) {
+            return Err(RpcError::new(
+                codes::METHOD_NOT_FOUND,
+                format!("there is no '{}' method", self.method),
+            ));
+        }
+        let params = match &self.params {
+            None | Some(Value::Null) => json!({}),
+            Some(params) => params.clone(),
+        };
+        serde_json::from_value(json!({ "method": self.method, "params": params }))
+            .map_err(|e| RpcError::new(codes::INVALID_PARAMS, e.to_string()))
+    }
+
+    /// The command the request runs, from its named params or its
+    /// command-line arguments.
+    ///
+    /// # Error Cases
+    /// Those of `call` and `Call::command`, and: arguments given for a
+    /// method that isn't a command, arguments that aren't strings, numbers
+    /// or booleans, or a command that can't be run this way
+    /// (`NOT_ALLOWED`).
+    pub fn command(&self) -> Result<Commands, RpcError> {
+        let command = match &self.params {
+            Some(Value::Array(params)) => {
+                if !Commands::has_subcommand(&self.method) {
+                    return Err(RpcError::new(
+                        codes::METHOD_NOT_FOUND,
+                        format!("there is no '{}' command", self.method),
+                    ));
+                }
+                let mut words = vec![self.method.clone()];
+                for param in params {
+                    words.push(match param {
+                        Value::String(text) => text.clone(),
+                        Value::Number(number) => number.to_string(),
+                        Value::Bool(flag) => flag.to_string(),
+                        other => {
+                            return Err(RpcError::new(
+                                codes::INVALID_PARAMS,
+                                format!("{} can't be an argument", other),
+                            ))
+                        }
+                    });
+                }
+                parse_words(words).map_err(|e| {
+                    RpcError::new(codes::INVALID_PARAMS, e.to_string().trim_end().to_string())
+                })?
+            }
+            _ => self.call()?.command()?,
+        };
+        if command.is_server() {
+            return Err(RpcError::new(
+                codes::NOT_ALLOWED,
+                format!("'{}' can't be run over JSON-RPC", self.method),
+            ));
+        }
+        Ok(command)
+    }
+}
+
+/// What a command returned.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CommandResult {
+    /// The exit status the command would have had (see
+    /// `CommandOutcome::status`; 1 for a failure).
+    pub status: i32,
+    /// The report, as `--format json` prints it.
+    pub report: Value,
+    /// What the command would have printed on stderr before the report.
+    #[serde(default)]
+    pub notes: Vec<String>,
+    /// What it would have printed on stderr after the report.
+    #[serde(default)]
+    pub hints: Vec<String>,
+}
+
+impl CommandResult {
+    /// The result of a command that ran, or an error carrying it if its
+    /// verdict was "no" (see `CommandOutcome::failure`).
+    pub fn from_outcome(
+        outcome: CommandOutcome,
+        output: &RenderOptions,
+    ) -> Result<CommandResult, RpcError> {
+        let json = render_with(&outcome.report, OutputFormat::Json, output)
+            .map_err(|e| RpcError::new(codes::COMMAND_FAILED, e.to_string()))?;
+        let result = CommandResult {
+            status: if outcome.failure.is_some() {
+                1
+            } else {
+                outcome.status
+            },
+            report: serde_json::from_str(&json).unwrap_or(Value::String(json)),
+            notes: outcome.notes,
+            hints: outcome.hints,
+        };
+        match outcome.failure {
+            Some(message) => Err(RpcError {
+                data: Some(result),
+                ..RpcError::new(codes::COMMAND_FAILED, message)
+            }),
+            None => Ok(result),
+        }
+    }
+}
+
+/// Why a request wasn't carried out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcError {
+    /// One of `codes`.
+    pub code: i64,
+    pub message: String,
+    /// The result of a command that ran and failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<CommandResult>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (JSON-RPC error {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// The answer to a `Request`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Response {
+    pub jsonrpc: String,
+    /// The request's ID (`None` if it couldn't be read).
+    pub id: Option<Id>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<CommandResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Response {
+    /// The response to request `id`.
+    pub fn new(id: Option<Id>, answer: Result<CommandResult, RpcError>) -> Response {
+        let (result, error) = match answer {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Response {
+            jsonrpc: VERSION.to_string(),
+            id,
+            result,
+            error,
+        }
+    }
+
+    /// The result, or the error.
+    pub fn into_result(self) -> Result<CommandResult, RpcError> {
+        match (self.result, self.error) {
+            (_, Some(error)) => Err(error),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(RpcError::new(
+                codes::INVALID_REQUEST,
+                "the response has neither a result nor an error",
+            )),
+        }
+    }
+}
+
+/// Run a command against `store`, as a server does for a request.
+///
+/// # Error Cases
+/// The command went wrong (see `run_command`), or failed (see
+/// `CommandResult::from_outcome`).
+pub fn run(
+    command: &Commands,
+    store: &dyn CatalogStore,
+    context: &mut Context,
+    output: &RenderOptions,
+) -> Result<CommandResult, RpcError> {
+    let outcome = run_command(command, store, context)
+        .map_err(|e| RpcError::new(codes::COMMAND_FAILED, e.to_string()))?;
+    CommandResult::from_outcome(outcome, output)
+}
+
+/// Answer one line of JSON: a request, or a batch of them, each answered by
+/// `answer`. Returns the line to send back, if any (notifications aren't
+/// answered, so neither is a batch of nothing else).
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::rpc::{handle, CommandResult, Response};
+/// use serde_json::json;
+///
+/// let echo = |request: &fruitdata::rpc::Request| {
+///     Ok(CommandResult { report: json!({"method": request.method}), ..CommandResult::default() })
+/// };
+///
+/// let reply = handle(r#"{"jsonrpc": "2.0", "id": "a", "method": "count"}"#, echo).unwrap();
+/// let response: Response = serde_json::from_str(&reply).unwrap();
+/// assert_eq!(response.into_result().unwrap().report["method"], "count");
+///
+/// assert_eq!(handle(r#"{"jsonrpc": "2.0", "method": "count"}"#, echo), None);
+/// assert!(handle("{oops", echo).unwrap().contains("-32700"));
+/// ```
+pub fn handle(
+    line: &str,
+    mut answer: impl FnMut(&Request) -> Result<CommandResult, RpcError>,
+) -> Option<String> {
+    let mut respond = |message: Value| -> Option<Response> {
+        let id = message.get("id").and_then(|id| Id::deserialize(id).ok());
+        let request = match Request::deserialize(message) {
+            Ok(request) if request.jsonrpc == VERSION => request,
+            // An invalid request is answered even without an ID
+            _ => {
+                let error = RpcError::new(
+                    codes::INVALID_REQUEST,
+                    "expected a JSON-RPC 2.0 request with a method",
+                );
+                return Some(Response::new(id, Err(error)));
+            }
+        };
+        let result = answer(&request);
+        request.id.map(|id| Response::new(Some(id), result))
+    };
+
+    let reply = match serde_json::from_str(line) {
+        Err(e) => serde_json::to_value(Response::new(
+            None,
+            Err(RpcError::new(codes::PARSE_ERROR, e.to_string())),
+        )),
+        Ok(Value::Array(batch)) if batch.is_empty() => serde_json::to_value(Response::new(
+            None,
+            Err(RpcError::new(codes::INVALID_REQUEST, "empty batch")),
+        )),
+        Ok(Value::Array(batch)) => {
+            let responses: Vec<Response> = batch.into_iter().filter_map(&mut respond).collect();
+            if responses.is_empty() {
+                return None;
+            }
+            serde_json::to_value(responses)
+        }
+        Ok(message) => serde_json::to_value(respond(message)?),
+    };
+    Some(reply.expect("responses serialize to JSON").to_string())
+}
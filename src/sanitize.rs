@@ -0,0 +1,180 @@
+// ============================================================================
+// sanitize.rs - Cleaning Up Names as They Come In
+// ============================================================================
+// Names typed by hand or exported from spreadsheets are often messy: stray
+// spaces, tabs and line breaks inside them, invisible control characters,
+// or accents that another system can't store. Before `add` and `import`
+// hand a fruit to the catalogue, its name goes through a small pipeline:
+//
+// 1. trim: drop whitespace at both ends
+// 2. strip_control: drop control characters (tabs and line breaks become
+//    spaces, so "Dragon\tfruit" doesn't turn into "Dragonfruit")
+// 3. collapse_whitespace: turn every run of whitespace into one space
+// 4. transliterate: spell accented Latin letters without their accents
+//    ("Açaí" -> "Acai"); off unless enabled
+//
+// Each step can be switched off in the config file:
+//
+//     { "sanitize": { "collapse_whitespace": false, "transliterate": true } }
+//
+// Commands report every name the pipeline changed, so nothing is renamed
+// behind the user's back.
+// ============================================================================
+
+use serde::{Deserialize, Serialize};
+
+/// Which clean-up steps are applied to the names of added and imported
+/// fruits (see the module comment). All but `transliterate` are on by
+/// default.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::sanitize::SanitizeSettings;
+///
+/// let settings = SanitizeSettings::default();
+/// assert_eq!(settings.clean("  Dragon\t\tfruit\u{7}\n"), "Dragon fruit");
+/// assert_eq!(settings.clean("Açaí"), "Açaí");
+///
+/// let settings = SanitizeSettings { transliterate: true, ..SanitizeSettings::default() };
+/// assert_eq!(settings.clean("Açaí"), "Acai");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct SanitizeSettings {
+    /// Drop whitespace at both ends.
+    pub trim: bool,
+    /// Drop control characters; tabs and line breaks become spaces.
+    pub strip_control: bool,
+    /// Turn every run of whitespace into a single space.
+    pub collapse_whitespace: bool,
+    /// Replace accented Latin letters (and ligatures such as "æ") with
+    /// plain ASCII ones.
+    pub transliterate: bool,
+}
+
+impl Default for SanitizeSettings {
+    fn default() -> Self {
+        SanitizeSettings {
+            trim: true,
+            strip_control: true,
+            collapse_whitespace: true,
+            transliterate: false,
+        }
+    }
+}
+
+impl SanitizeSettings {
+    /// `name` with every enabled step applied.
+    pub fn clean(&self, name: &str) -> String {
+        let mut cleaned: String = if self.strip_control {
+            name.chars()
+                .filter_map(|c| match c {
+                    '\t' | '\n' | '\r' => Some(' '),
+                    c if c.is_control() => None,
+                    c => Some(c),
+                })
+                .collect()
+        } else {
+            name.to_string()
+        };
+
+        if self.collapse_whitespace {
+            let mut collapsed = String::with_capacity(cleaned.len());
+            let mut in_space = false;
+            for c in cleaned.chars() {
+                if c.is_whitespace() {
+                    if !in_space {
+                        collapsed.push(' ');
+                    }
+                    in_space = true;
+                } else {
+                    collapsed.push(c);
+                    in_space = false;
+                }
+            }
+            cleaned = collapsed;
+        }
+
+        if self.transliterate {
+            let mut plain = String::with_capacity(cleaned.len());
+            for c in cleaned.chars() {
+                match transliteration(c) {
+                    Some(ascii) => plain.push_str(ascii),
+                    None => plain.push(c),
+                }
+            }
+            cleaned = plain;
+        }
+
+        if self.trim {
+            cleaned = cleaned.trim().to_string();
+        }
+        cleaned
+    }
+
+    /// Clean `name`, returning the change if there was one.
+    pub fn rename(&self, name: &str) -> Option<NameChange> {
+        let cleaned = self.clean(name);
+        (cleaned != name).then(|| NameChange {
+            original: name.to_string(),
+            cleaned,
+        })
+    }
+}
+
+/// A name the pipeline changed, as reported by `add` and `import`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct NameChange {
+    /// The name as given.
+    pub original: String,
+    /// The name as stored.
+    pub cleaned: String,
+}
+
+/// The ASCII spelling of an accented Latin letter or ligature, if it has
+/// one. Covers Latin-1 and the letters of Latin Extended-A that European
+/// fruit names use.
+fn transliteration(c: char) -> Option<&'static str> {
+    Some(match c {
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Ç' | 'Ć' | 'Č' => "C",
+        'ç' | 'ć' | 'č' => "c",
+        'Ď' | 'Đ' | 'Ð' => "D",
+        'ď' | 'đ' | 'ð' => "d",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'Ğ' | 'Ģ' => "G",
+        'ğ' | 'ģ' => "g",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' | 'Į' | 'İ' => "I",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => "i",
+        'Ķ' => "K",
+        'ķ' => "k",
+        'Ĺ' | 'Ļ' | 'Ľ' | 'Ł' => "L",
+        'ĺ' | 'ļ' | 'ľ' | 'ł' => "l",
+        'Ñ' | 'Ń' | 'Ņ' | 'Ň' => "N",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ő' => "O",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'Ŕ' | 'Ř' => "R",
+        'ŕ' | 'ř' => "r",
+        'Ś' | 'Ş' | 'Š' => "S",
+        'ś' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'Ţ' | 'Ť' => "T",
+        'ţ' | 'ť' => "t",
+        'Þ' => "TH",
+        'þ' => "th",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' | 'Ů' | 'Ű' | 'Ų' => "U",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' | 'ų' => "u",
+        'Ý' | 'Ÿ' => "Y",
+        'ý' | 'ÿ' => "y",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
@@ -0,0 +1,169 @@
+// ============================================================================
+// service.rs - The Catalogue as an Actor on Its Own Thread
+// ============================================================================
+// `CatalogHandle` (handle.rs) lets every thread lock the catalogue itself.
+// `CatalogService` takes the other approach: one background thread owns the
+// catalogue and its store, and everyone else sends it requests over a
+// channel. Requests are handled one at a time, in the order they arrive, so
+// there is a single owner and no lock to hold for too long:
+//
+//     let service = CatalogService::spawn(FileStore::new("fruits.json"))?;
+//     let changes = service.subscribe()?;
+//     service.write(|c| c.remove("Durian"))?;
+//     // changes.recv() == Ok(CatalogEvent::Removed { .. })
+//
+// Every successful write is saved to the store before it is answered, and
+// only then are its events sent to the subscribers (see
+// `Catalogue::subscribe`), which suits a server pushing updates to clients
+// or a TUI redrawing on change.
+//
+// fruitdata doesn't depend on an async runtime, so the service runs on a
+// plain thread and its methods block until the answer arrives; an async
+// frontend can call them from its blocking thread pool.
+// ============================================================================
+
+use crate::catalog::Catalogue;
+use crate::error::CatalogError;
+use crate::events::CatalogEvent;
+use crate::store::CatalogStore;
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// An error sent back from the service thread.
+type ThreadError = Box<dyn Error + Send + Sync>;
+
+/// A request, run on the service thread. It sends its own answer back.
+type Job = Box<dyn FnOnce(&mut Catalogue, &dyn CatalogStore) + Send>;
+
+/// A handle to a catalogue owned by a background thread.
+///
+/// Cloning the handle is cheap; the thread stops once every clone has been
+/// dropped.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::initialise_fruit_catalogue;
+/// use fruitdata::events::CatalogEvent;
+/// use fruitdata::service::CatalogService;
+/// use fruitdata::store::MemoryStore;
+///
+/// let store = MemoryStore::new(initialise_fruit_catalogue());
+/// let service = CatalogService::spawn(store).unwrap();
+/// let changes = service.subscribe().unwrap();
+///
+/// let removed = service.write(|c| c.remove("Pear")).unwrap();
+/// assert_eq!(removed.name, "Pear");
+/// assert_eq!(changes.recv().unwrap(), CatalogEvent::Removed { fruit: removed });
+///
+/// assert!(service.read(|c| c.get("Pear").is_none()).unwrap());
+/// assert!(service.write(|c| c.remove("Pear")).is_err()); // nothing sent
+/// ```
+#[derive(Debug, Clone)]
+pub struct CatalogService {
+    requests: Sender<Job>,
+}
+
+impl CatalogService {
+    /// Load the catalogue from `store` and start a thread to own both.
+    ///
+    /// # Error Cases
+    /// Whatever `store.load()` fails with.
+    pub fn spawn<S>(store: S) -> Result<Self, Box<dyn Error>>
+    where
+        S: CatalogStore + Send + 'static,
+    {
+        let catalogue = store.load()?;
+        Ok(CatalogService::spawn_with(catalogue, store))
+    }
+
+    /// Start a thread owning `catalogue`, saving it to `store`. The
+    /// catalogue keeps its rules, so configure it (`with_name_policy`, ...)
+    /// first.
+    pub fn spawn_with<S>(catalogue: Catalogue, store: S) -> Self
+    where
+        S: CatalogStore + Send + 'static,
+    {
+        let (requests, inbox) = mpsc::channel();
+        thread::spawn(move || serve(catalogue, store, inbox));
+        CatalogService { requests }
+    }
+
+    /// Run `f` with the catalogue on the service thread, and return its
+    /// result.
+    pub fn read<T, F>(&self, f: F) -> Result<T, Box<dyn Error>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Catalogue) -> T + Send + 'static,
+    {
+        let (reply, answer) = mpsc::channel();
+        self.send(Box::new(move |catalogue, _| {
+            let _ = reply.send(f(catalogue));
+        }))?;
+        answer.recv().map_err(|_| stopped())
+    }
+
+    /// Change the catalogue with `f` on the service thread, save it, and
+    /// tell the subscribers what changed.
+    ///
+    /// Like `CatalogHandle::write`, `f` works on a copy that is dropped if
+    /// `f` fails, so either the whole change happens or none of it does.
+    /// The same goes for a failed save: the catalogue stays as it was.
+    ///
+    /// # Error Cases
+    /// - The `CatalogError` returned by `f`
+    /// - The store couldn't be saved
+    /// - The service thread has stopped (e.g. a closure panicked)
+    pub fn write<T, F>(&self, f: F) -> Result<T, Box<dyn Error>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Catalogue) -> Result<T, CatalogError> + Send + 'static,
+    {
+        let (reply, answer) = mpsc::channel();
+        self.send(Box::new(move |catalogue, store| {
+            let mut draft = catalogue.clone();
+            let result = f(&mut draft).map_err(ThreadError::from).and_then(|value| {
+                // The store's errors needn't be `Send`, so only their
+                // message crosses back
+                store.save(&mut draft).map_err(|e| e.to_string())?;
+                Ok(value)
+            });
+            if result.is_ok() {
+                catalogue.commit(draft);
+                // There is no journal to record the events in
+                catalogue.take_changes();
+            }
+            let _ = reply.send(result);
+        }))?;
+        answer
+            .recv()
+            .map_err(|_| stopped())?
+            .map_err(|e| -> Box<dyn Error> { e })
+    }
+
+    /// Receive the events of every change saved from now on, in order.
+    ///
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> Result<Receiver<CatalogEvent>, Box<dyn Error>> {
+        let (reply, answer) = mpsc::channel();
+        self.send(Box::new(move |catalogue, _| {
+            let _ = reply.send(catalogue.subscribe());
+        }))?;
+        answer.recv().map_err(|_| stopped())
+    }
+
+    fn send(&self, job: Job) -> Result<(), Box<dyn Error>> {
+        self.requests.send(job).map_err(|_| stopped())
+    }
+}
+
+/// The service thread: run requests until every handle is gone.
+fn serve<S: CatalogStore>(mut catalogue: Catalogue, store: S, inbox: Receiver<Job>) {
+    for job in inbox {
+        job(&mut catalogue, &store);
+    }
+}
+
+fn stopped() -> Box<dyn Error> {
+    "the catalogue service has stopped".into()
+}
@@ -0,0 +1,111 @@
+// ============================================================================
+// shared.rs - Cheaply Shared Catalogue Snapshots
+// ============================================================================
+// A server answering many requests at once wants every request to see one
+// consistent catalogue, without copying the fruits for each of them.
+// `SharedCatalog` is an immutable snapshot of the fruits in an
+// `Arc<[FruitDimensions]>`: cloning it only bumps a reference count, and
+// the snapshot can be handed to as many threads as needed.
+//
+// Changing a snapshot is copy-on-write. `edit` copies the fruits into a
+// `Catalogue` (so the usual rules apply), runs the change, and returns a
+// new snapshot. Readers holding the old one are unaffected and keep it for
+// as long as they need; the caller decides when to publish the new one.
+// ============================================================================
+
+use crate::catalog::Catalogue;
+use crate::models::FruitDimensions;
+use crate::names::names_equal;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// An immutable, cheaply cloned snapshot of a catalogue's fruits.
+///
+/// The fruits are available as a slice through `Deref`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::initialise_fruit_catalogue;
+/// use fruitdata::shared::SharedCatalog;
+///
+/// let snapshot = SharedCatalog::new(initialise_fruit_catalogue());
+/// let reader = snapshot.clone(); // no fruits copied
+///
+/// let (next, removed) = snapshot.edit(|c| c.remove("Pear")).unwrap();
+/// assert_eq!(removed.name, "Pear");
+/// assert!(next.get("Pear").is_none());
+/// assert!(reader.get("Pear").is_some()); // the old snapshot is unchanged
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedCatalog {
+    fruits: Arc<[FruitDimensions]>,
+}
+
+impl SharedCatalog {
+    /// A snapshot of `fruits`, taken as-is like `Catalogue::new`.
+    pub fn new(fruits: Vec<FruitDimensions>) -> Self {
+        SharedCatalog {
+            fruits: fruits.into(),
+        }
+    }
+
+    /// Find a fruit by name (case-insensitive), like `Catalogue::get`.
+    pub fn get(&self, name: &str) -> Option<&FruitDimensions> {
+        self.fruits.iter().find(|f| names_equal(&f.name, name))
+    }
+
+    /// A `Catalogue` holding a copy of the fruits, with the default rules.
+    ///
+    /// For changes that need other rules (a name policy, limits, ...),
+    /// configure the catalogue, change it, and turn it back into a snapshot
+    /// with `SharedCatalog::from`.
+    pub fn to_catalogue(&self) -> Catalogue {
+        Catalogue::new(self.fruits.to_vec())
+    }
+
+    /// Build the next snapshot by applying `change` to a copy of this one.
+    ///
+    /// Returns the new snapshot together with what `change` returned. If
+    /// `change` fails, no snapshot is made and its error is returned.
+    pub fn edit<T, E>(
+        &self,
+        change: impl FnOnce(&mut Catalogue) -> Result<T, E>,
+    ) -> Result<(SharedCatalog, T), E> {
+        let mut catalogue = self.to_catalogue();
+        let value = change(&mut catalogue)?;
+        Ok((SharedCatalog::from(catalogue), value))
+    }
+
+    /// Whether two snapshots are the very same one (not just equal).
+    pub fn ptr_eq(&self, other: &SharedCatalog) -> bool {
+        Arc::ptr_eq(&self.fruits, &other.fruits)
+    }
+}
+
+impl Default for SharedCatalog {
+    fn default() -> Self {
+        SharedCatalog::new(Vec::new())
+    }
+}
+
+impl From<Catalogue> for SharedCatalog {
+    fn from(catalogue: Catalogue) -> Self {
+        SharedCatalog::new(catalogue.into_fruits())
+    }
+}
+
+impl From<&Catalogue> for SharedCatalog {
+    fn from(catalogue: &Catalogue) -> Self {
+        SharedCatalog {
+            fruits: catalogue.fruits().into(),
+        }
+    }
+}
+
+impl Deref for SharedCatalog {
+    type Target = [FruitDimensions];
+
+    fn deref(&self) -> &[FruitDimensions] {
+        &self.fruits
+    }
+}
@@ -0,0 +1,237 @@
+// ============================================================================
+// sort.rs - Multi-Key Sorting of Fruits
+// ============================================================================
+// This module lets callers describe *how* a list of fruits should be ordered
+// using a small, composable specification:
+//
+//     volume:desc,name:asc
+//
+// Each comma-separated part is a key (a field plus an optional direction).
+// Fruits are compared by the first key; only when two fruits are equal on
+// that key is the next key consulted, and so on. This is the same idea as
+// `ORDER BY volume DESC, name ASC` in SQL.
+//
+// Key concept: `std::cmp::Ordering` values can be chained with `.then_with()`,
+// which is exactly the "fall back to the next key on a tie" behaviour we need.
+// ============================================================================
+
+use crate::models::FruitDimensions;
+use crate::names;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A field of `FruitDimensions` that fruits can be sorted by.
+///
+/// `Volume` isn't stored in the struct; it is computed with
+/// `FruitDimensions::volume()` when comparing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Name,
+    Length,
+    Width,
+    Height,
+    Volume,
+}
+
+impl SortField {
+    /// Compare two fruits on this field alone (always ascending).
+    ///
+    /// Names are compared with `names::collate` so that "apple" and "Banana"
+    /// sort the way a human expects. Numbers use `f32::total_cmp`, which
+    /// gives a total order even for unusual values like NaN.
+    pub fn compare(self, a: &FruitDimensions, b: &FruitDimensions) -> Ordering {
+        match self {
+            SortField::Name => names::collate(&a.name, &b.name),
+            SortField::Length => a.length.total_cmp(&b.length),
+            SortField::Width => a.width.total_cmp(&b.width),
+            SortField::Height => a.height.total_cmp(&b.height),
+            SortField::Volume => a.volume().total_cmp(&b.volume()),
+        }
+    }
+}
+
+impl FromStr for SortField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "name" => Ok(SortField::Name),
+            "length" => Ok(SortField::Length),
+            "width" => Ok(SortField::Width),
+            "height" => Ok(SortField::Height),
+            "volume" => Ok(SortField::Volume),
+            other => Err(format!(
+                "unknown sort field '{}' (expected name, length, width, height or volume)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SortField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SortField::Name => "name",
+            SortField::Length => "length",
+            SortField::Width => "width",
+            SortField::Height => "height",
+            SortField::Volume => "volume",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The direction of a single sort key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortDirection {
+    /// Smallest first (the default when no direction is given).
+    #[default]
+    Asc,
+    /// Largest first.
+    Desc,
+}
+
+impl FromStr for SortDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "asc" => Ok(SortDirection::Asc),
+            "desc" => Ok(SortDirection::Desc),
+            other => Err(format!(
+                "unknown sort direction '{}' (expected asc or desc)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SortDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortDirection::Asc => write!(f, "asc"),
+            SortDirection::Desc => write!(f, "desc"),
+        }
+    }
+}
+
+/// One part of a sort specification: a field and the direction to sort it in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub direction: SortDirection,
+}
+
+impl SortKey {
+    /// Compare two fruits on this key, honouring its direction.
+    pub fn compare(&self, a: &FruitDimensions, b: &FruitDimensions) -> Ordering {
+        let ordering = self.field.compare(a, b);
+        match self.direction {
+            SortDirection::Asc => ordering,
+            SortDirection::Desc => ordering.reverse(),
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    /// Parse `field` or `field:direction` (e.g. `"volume:desc"`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, direction) = match s.split_once(':') {
+            Some((field, direction)) => (field.parse()?, direction.parse()?),
+            None => (s.parse()?, SortDirection::default()),
+        };
+        Ok(SortKey { field, direction })
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.field, self.direction)
+    }
+}
+
+/// An ordered list of sort keys, applied left to right.
+///
+/// A `SortSpec` can be parsed from a string (`"volume:desc,name"`) or built
+/// in code with `by()` and `then()`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::initialise_fruit_catalogue;
+/// use fruitdata::sort::{SortDirection, SortField, SortSpec};
+///
+/// let mut fruits = initialise_fruit_catalogue();
+///
+/// // Parsed from a string, as the CLI does for `--sort-by`
+/// let spec: SortSpec = "volume:desc,name:asc".parse().unwrap();
+/// spec.sort(&mut fruits);
+/// assert_eq!(fruits[0].name, "Banana"); // ties with Pear on volume, wins on name
+/// assert_eq!(fruits[1].name, "Pear");
+///
+/// // Built in code
+/// let spec = SortSpec::by(SortField::Name, SortDirection::Desc);
+/// spec.sort(&mut fruits);
+/// assert_eq!(fruits[0].name, "Pear");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SortSpec {
+    keys: Vec<SortKey>,
+}
+
+impl SortSpec {
+    /// Start a specification with a single key.
+    pub fn by(field: SortField, direction: SortDirection) -> Self {
+        SortSpec {
+            keys: vec![SortKey { field, direction }],
+        }
+    }
+
+    /// Add a tie-breaking key after the existing ones.
+    pub fn then(mut self, field: SortField, direction: SortDirection) -> Self {
+        self.keys.push(SortKey { field, direction });
+        self
+    }
+
+    /// The keys in the order they are applied.
+    pub fn keys(&self) -> &[SortKey] {
+        &self.keys
+    }
+
+    /// Compare two fruits using every key in turn until one of them differs.
+    pub fn compare(&self, a: &FruitDimensions, b: &FruitDimensions) -> Ordering {
+        self.keys.iter().fold(Ordering::Equal, |ordering, key| {
+            ordering.then_with(|| key.compare(a, b))
+        })
+    }
+
+    /// Sort a slice of fruits in place according to this specification.
+    ///
+    /// The sort is stable: fruits that compare equal on every key keep
+    /// their original relative order.
+    pub fn sort(&self, fruits: &mut [FruitDimensions]) {
+        fruits.sort_by(|a, b| self.compare(a, b));
+    }
+}
+
+impl FromStr for SortSpec {
+    type Err = String;
+
+    /// Parse a comma-separated list of keys, e.g. `"volume:desc,name:asc"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<SortKey>, _>>()?;
+        Ok(SortSpec { keys })
+    }
+}
+
+impl fmt::Display for SortSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self.keys.iter().map(SortKey::to_string).collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
@@ -0,0 +1,371 @@
+// ============================================================================
+// stats.rs - Totals and Averages Over a Catalogue
+// ============================================================================
+// `fruitdata stats` and dashboards built on the library want the same few
+// numbers about a set of fruits: how many there are, how much room and
+// weight they add up to, and how big one is on average. An `Aggregate`
+// holds them, computed in one pass:
+//
+//     let stats = catalogue.aggregate();
+//     println!("{} fruits, {} g", stats.count, stats.total_mass);
+//
+// Sums are accumulated in f64, so a catalogue of millions of fruits doesn't
+// lose the small ones to rounding, and stored as f32 like the dimensions.
+//
+// `group_by` computes one aggregate per group instead, to compare citrus
+// with stone fruit, say. `fruitdata stats --group-by` groups by the keys in
+// `GroupBy`: a fruit's `"tags"`, its `"origin"`, or its `Shape`.
+//
+// Grading sorts fruits by size as well as shape: a `SizeClass` (small,
+// medium or large) is decided by volume, with the cut-offs from the config
+// file's `size_classes` section:
+//
+//     { "size_classes": { "medium": 20.0, "large": 100.0 } }
+// ============================================================================
+
+use crate::models::FruitDimensions;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// The density used to estimate a fruit's mass, in g/cm³, unless the fruit
+/// has its own `"density"`. Most fresh fruit is a little lighter than water.
+pub const DEFAULT_DENSITY: f64 = 0.9;
+
+/// A fruit's estimated mass in grams: its volume (in cm³) times its
+/// `"density"` field if it has a positive one, or `DEFAULT_DENSITY`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::stats::estimated_mass;
+///
+/// let mut apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+/// assert_eq!(estimated_mass(&apple), 13.5);
+/// apple.extra.insert("density".into(), 0.8.into());
+/// assert_eq!(estimated_mass(&apple), 12.0);
+/// ```
+pub fn estimated_mass(fruit: &FruitDimensions) -> f32 {
+    mass(fruit) as f32
+}
+
+/// The estimated mass in f64: an f32 density like 0.9 isn't exact, and
+/// would show up as a mass of 170.09999 rather than 170.1.
+fn mass(fruit: &FruitDimensions) -> f64 {
+    let density = fruit
+        .extra
+        .get("density")
+        .and_then(|density| density.as_f64())
+        .filter(|density| *density > 0.0)
+        .unwrap_or(DEFAULT_DENSITY);
+    f64::from(fruit.volume()) * density
+}
+
+/// Totals and averages over a set of fruits.
+///
+/// The means are 0 when there are no fruits.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+///
+/// let stats = Catalogue::new(initialise_fruit_catalogue()).aggregate();
+/// assert_eq!(stats.count, 4);
+/// assert_eq!(stats.total_volume, 150.0); // 30 + 15 + 52.5 + 52.5
+/// assert_eq!(stats.total_mass, 135.0);
+/// assert_eq!(stats.mean_length, 5.25);
+/// assert_eq!(stats.mean_volume, 37.5);
+/// ```
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Aggregate {
+    pub count: usize,
+    pub total_volume: f32,
+    /// In grams; see `estimated_mass`.
+    pub total_mass: f32,
+    pub mean_length: f32,
+    pub mean_width: f32,
+    pub mean_height: f32,
+    pub mean_volume: f32,
+}
+
+impl Aggregate {
+    /// Compute the aggregate of `fruits`.
+    pub fn of<'a>(fruits: impl IntoIterator<Item = &'a FruitDimensions>) -> Self {
+        let mut count = 0;
+        let [mut volume, mut mass, mut length, mut width, mut height] = [0.0f64; 5];
+        for fruit in fruits {
+            count += 1;
+            volume += f64::from(fruit.volume());
+            mass += self::mass(fruit);
+            length += f64::from(fruit.length.value());
+            width += f64::from(fruit.width.value());
+            height += f64::from(fruit.height.value());
+        }
+
+        let mean = |total: f64| {
+            if count == 0 {
+                0.0
+            } else {
+                (total / count as f64) as f32
+            }
+        };
+        Aggregate {
+            count,
+            total_volume: volume as f32,
+            total_mass: mass as f32,
+            mean_length: mean(length),
+            mean_width: mean(width),
+            mean_height: mean(height),
+            mean_volume: mean(volume),
+        }
+    }
+}
+
+/// Compute an aggregate for each group of `fruits`.
+///
+/// `keys` gives the groups a fruit belongs to: usually one, but a fruit
+/// with several tags can be in several groups, and one without any is left
+/// out. Groups are ordered by key.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::initialise_fruit_catalogue;
+/// use fruitdata::stats::group_by;
+///
+/// let fruits = initialise_fruit_catalogue();
+/// let groups = group_by(&fruits, |fruit| Some(fruit.length > 5.0));
+/// assert_eq!(groups[&false].count, 2); // Orange, Apple
+/// assert_eq!(groups[&true].mean_volume, 52.5); // Banana, Pear
+/// ```
+pub fn group_by<'a, K, I>(
+    fruits: impl IntoIterator<Item = &'a FruitDimensions>,
+    keys: impl Fn(&FruitDimensions) -> I,
+) -> BTreeMap<K, Aggregate>
+where
+    K: Ord,
+    I: IntoIterator<Item = K>,
+{
+    let mut groups: BTreeMap<K, Vec<&FruitDimensions>> = BTreeMap::new();
+    for fruit in fruits {
+        for key in keys(fruit) {
+            groups.entry(key).or_default().push(fruit);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(key, fruits)| (key, Aggregate::of(fruits)))
+        .collect()
+}
+
+/// The rough shape of a fruit, from the proportions of its dimensions.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    /// No dimension much bigger than another, like an orange.
+    Round,
+    /// One dimension at least 1.5 times the others, like a banana.
+    Elongated,
+    /// One dimension at most 2/3 of the others, like a fig.
+    Flat,
+}
+
+impl Shape {
+    /// How many times bigger one dimension must be than the next to count.
+    const RATIO: f32 = 1.5;
+
+    /// Classify `fruit`.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::models::FruitDimensions;
+    /// use fruitdata::stats::Shape;
+    ///
+    /// assert_eq!(Shape::of(&FruitDimensions::new("Orange", 5.0, 4.5, 4.5)), Shape::Round);
+    /// assert_eq!(Shape::of(&FruitDimensions::new("Banana", 18.0, 3.5, 3.0)), Shape::Elongated);
+    /// assert_eq!(Shape::of(&FruitDimensions::new("Fig", 5.0, 5.0, 2.0)), Shape::Flat);
+    /// ```
+    pub fn of(fruit: &FruitDimensions) -> Shape {
+        if fruit.is_elongated(Shape::RATIO) {
+            Shape::Elongated
+        } else if fruit.aspect_ratios().flatness >= Shape::RATIO {
+            Shape::Flat
+        } else {
+            Shape::Round
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shape::Round => write!(f, "round"),
+            Shape::Elongated => write!(f, "elongated"),
+            Shape::Flat => write!(f, "flat"),
+        }
+    }
+}
+
+/// A fruit's size, by volume (see `FruitDimensions::size_class`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeClass {
+    Small,
+    Medium,
+    Large,
+}
+
+impl FromStr for SizeClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "small" => Ok(SizeClass::Small),
+            "medium" => Ok(SizeClass::Medium),
+            "large" => Ok(SizeClass::Large),
+            other => Err(format!(
+                "unknown size class '{}' (expected small, medium or large)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SizeClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SizeClass::Small => write!(f, "small"),
+            SizeClass::Medium => write!(f, "medium"),
+            SizeClass::Large => write!(f, "large"),
+        }
+    }
+}
+
+/// The volumes (in cm³) at which fruits stop being small and become medium,
+/// and stop being medium and become large, from the config file's
+/// `size_classes` section.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SizeClasses {
+    /// The smallest volume of a medium fruit (default: 20 cm³).
+    pub medium: f32,
+    /// The smallest volume of a large fruit (default: 100 cm³).
+    pub large: f32,
+}
+
+impl Default for SizeClasses {
+    fn default() -> Self {
+        SizeClasses {
+            medium: 20.0,
+            large: 100.0,
+        }
+    }
+}
+
+impl SizeClasses {
+    /// The size class of a fruit with this `volume`.
+    pub fn classify(&self, volume: f32) -> SizeClass {
+        if volume >= self.large {
+            SizeClass::Large
+        } else if volume >= self.medium {
+            SizeClass::Medium
+        } else {
+            SizeClass::Small
+        }
+    }
+}
+
+/// What `fruitdata stats --group-by` groups fruits by.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// Each of the fruit's `"tags"` (an array, or a comma-separated string).
+    Tag,
+    /// The fruit's `"origin"`.
+    Origin,
+    /// The fruit's `Shape`.
+    Shape,
+}
+
+impl GroupBy {
+    /// The group a fruit is in when it has no tags or origin.
+    pub const NONE: &'static str = "(none)";
+
+    /// The groups `fruit` belongs to.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::models::FruitDimensions;
+    /// use fruitdata::stats::GroupBy;
+    ///
+    /// let mut lime = FruitDimensions::new("Lime", 4.0, 4.0, 4.0);
+    /// lime.extra.insert("tags".into(), serde_json::json!(["citrus", "sour"]));
+    /// assert_eq!(GroupBy::Tag.keys(&lime), vec!["citrus", "sour"]);
+    /// assert_eq!(GroupBy::Origin.keys(&lime), vec!["(none)"]);
+    /// assert_eq!(GroupBy::Shape.keys(&lime), vec!["round"]);
+    /// ```
+    pub fn keys(self, fruit: &FruitDimensions) -> Vec<String> {
+        let keys: Vec<String> = match self {
+            GroupBy::Tag => match fruit.extra.get("tags") {
+                Some(Value::Array(tags)) => tags.iter().map(text).collect(),
+                Some(Value::String(tags)) => tags.split(',').map(str::to_string).collect(),
+                Some(other) => vec![text(other)],
+                None => Vec::new(),
+            },
+            GroupBy::Origin => fruit.extra.get("origin").map(text).into_iter().collect(),
+            GroupBy::Shape => vec![Shape::of(fruit).to_string()],
+        };
+        let mut keys: Vec<String> = keys
+            .into_iter()
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+            .collect();
+        if keys.is_empty() {
+            keys.push(GroupBy::NONE.to_string());
+        }
+        keys
+    }
+}
+
+/// A JSON value as a group key: strings without their quotes.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "tag" | "tags" => Ok(GroupBy::Tag),
+            "origin" => Ok(GroupBy::Origin),
+            "shape" => Ok(GroupBy::Shape),
+            other => Err(format!(
+                "unknown grouping '{}' (expected tag, origin or shape)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for GroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupBy::Tag => write!(f, "tag"),
+            GroupBy::Origin => write!(f, "origin"),
+            GroupBy::Shape => write!(f, "shape"),
+        }
+    }
+}
+
+/// One group's key and aggregate, as `fruitdata stats --group-by` reports it.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Group {
+    pub key: String,
+    #[serde(flatten)]
+    pub aggregate: Aggregate,
+}
@@ -0,0 +1,268 @@
+// ============================================================================
+// store.rs - Where a Catalogue Is Kept
+// ============================================================================
+// The command handlers in cli.rs don't care whether the catalogue lives in a
+// JSON file or somewhere else; they load it from a `CatalogStore`, change
+// it, and hand it back to be saved. `FileStore` is the store the `fruitdata`
+// binary uses: the `--file` JSON file plus the change journal next to it.
+// `MemoryStore` keeps the catalogue in memory only, for services and tests
+// that want fruitdata's rules without any files, and `BorrowedStore` lends
+// commands a catalogue the caller already has.
+//
+// A store only moves whole catalogues. The rules for changing one (names,
+// limits, conflict detection) stay in `Catalogue`, so every store enforces
+// them the same way.
+// ============================================================================
+
+use crate::catalog::Catalogue;
+use crate::diagnostic::explain;
+use crate::format::Format;
+use crate::journal::Journal;
+use crate::models::FruitDimensions;
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// Somewhere a catalogue can be loaded from and saved to.
+pub trait CatalogStore {
+    /// How the store is named in messages, e.g. the file name.
+    fn describe(&self) -> String;
+
+    /// Whether anything has been stored yet. A store that exists but can't
+    /// be loaded is broken, rather than new.
+    fn exists(&self) -> bool;
+
+    /// Load the stored catalogue.
+    fn load(&self) -> Result<Catalogue, Box<dyn Error>>;
+
+    /// Store `catalogue`, replacing what was there.
+    fn save(&self, catalogue: &mut Catalogue) -> Result<(), Box<dyn Error>>;
+
+    /// Explain a failure of `load` in more detail (for instance with the
+    /// offending line of a file), if the store can.
+    fn explain(&self, _error: &(dyn Error + 'static)) -> Option<String> {
+        None
+    }
+
+    /// The change journal kept with the catalogue, if there is one.
+    fn journal(&self) -> Option<Journal> {
+        None
+    }
+
+    /// The file holding the catalogue, for commands that work on the file
+    /// itself (such as `validate` and `repair`).
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// A catalogue in a JSON file (or TOML or YAML, see
+/// `Format::for_catalogue`), with its journal alongside.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::Catalogue;
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::store::{CatalogStore, FileStore};
+///
+/// let path = std::env::temp_dir().join("fruitdata-store-example.json");
+/// let path = path.to_str().unwrap();
+/// let store = FileStore::new(path);
+///
+/// let mut catalogue = Catalogue::new(vec![FruitDimensions::new("Apple", 4.0, 2.5, 1.5)]);
+/// store.save(&mut catalogue).unwrap();
+/// assert!(store.exists());
+/// assert_eq!(store.load().unwrap().fruits().len(), 1);
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStore {
+    path: String,
+    strict: bool,
+}
+
+impl FileStore {
+    /// Use the catalogue file at `path` (which need not exist yet).
+    pub fn new(path: &str) -> Self {
+        FileStore {
+            path: path.to_string(),
+            strict: false,
+        }
+    }
+
+    /// Load with `Catalogue::load_strict`, rejecting unknown keys and
+    /// wrongly typed values.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
+
+impl CatalogStore for FileStore {
+    fn describe(&self) -> String {
+        self.path.clone()
+    }
+
+    fn exists(&self) -> bool {
+        Path::new(&self.path).exists()
+    }
+
+    fn load(&self) -> Result<Catalogue, Box<dyn Error>> {
+        if self.strict {
+            Catalogue::load_strict(&self.path)
+        } else {
+            Catalogue::load(&self.path)
+        }
+    }
+
+    fn save(&self, catalogue: &mut Catalogue) -> Result<(), Box<dyn Error>> {
+        catalogue.save(&self.path)
+    }
+
+    fn explain(&self, error: &(dyn Error + 'static)) -> Option<String> {
+        // A TOML or YAML file's syntax errors name their line already, and
+        // positions in the JSON it was translated to would mislead
+        if Format::for_catalogue(Path::new(&self.path)) != Format::Json {
+            return Some(match error.downcast_ref::<serde_json::Error>() {
+                Some(e) if e.line() > 0 => {
+                    let text = e.to_string();
+                    let message = text.rsplit_once(" at line ").map_or(&*text, |(m, _)| m);
+                    format!("{}: {}", self.path, message)
+                }
+                _ => error.to_string(),
+            });
+        }
+        let bytes = fs::read(&self.path).ok()?;
+        explain(&self.path, &bytes, error)
+    }
+
+    fn journal(&self) -> Option<Journal> {
+        Some(Journal::for_catalogue(&self.path))
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(Path::new(&self.path))
+    }
+}
+
+/// A catalogue kept in memory, gone when the store is dropped.
+///
+/// Saving replaces the stored fruits, so the store can be shared (for
+/// instance in an `Arc`) and used from several threads through `&self`.
+/// There is no journal, and no conflict detection: the last save wins.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::cli::{run_command, Commands, Context};
+/// use fruitdata::config::Config;
+/// use fruitdata::store::MemoryStore;
+///
+/// let store = MemoryStore::default();
+/// let mut context = Context::new(Config::default());
+/// let add = Commands::Add { name: "Kiwi".into(), length: 3.0, width: 2.0, height: 2.0 };
+/// run_command(&add, &store, &mut context).unwrap();
+///
+/// assert_eq!(store.fruits()[0].name, "Kiwi");
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    fruits: Mutex<Vec<FruitDimensions>>,
+}
+
+impl MemoryStore {
+    /// A store holding `fruits`.
+    pub fn new(fruits: Vec<FruitDimensions>) -> Self {
+        MemoryStore {
+            fruits: Mutex::new(fruits),
+        }
+    }
+
+    /// A copy of the stored fruits.
+    pub fn fruits(&self) -> Vec<FruitDimensions> {
+        self.lock().clone()
+    }
+
+    /// The stored fruits. A thread that panicked while saving can't have
+    /// left them half-written (saving is one assignment), so a poisoned
+    /// lock is still safe to use.
+    fn lock(&self) -> MutexGuard<'_, Vec<FruitDimensions>> {
+        self.fruits.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl CatalogStore for MemoryStore {
+    fn describe(&self) -> String {
+        "memory".to_string()
+    }
+
+    fn exists(&self) -> bool {
+        true
+    }
+
+    fn load(&self) -> Result<Catalogue, Box<dyn Error>> {
+        Ok(Catalogue::new(self.fruits()))
+    }
+
+    fn save(&self, catalogue: &mut Catalogue) -> Result<(), Box<dyn Error>> {
+        *self.lock() = catalogue.fruits().to_vec();
+        Ok(())
+    }
+}
+
+/// A catalogue the caller already holds, lent to `run_command` (see
+/// `cli::parse_and_run`).
+///
+/// Loading hands out a copy. Saving puts the copy back in its place and
+/// tells the catalogue's subscribers about the changes, so a command that
+/// fails halfway leaves the catalogue as it was. There is no journal.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::{initialise_fruit_catalogue, Catalogue};
+/// use fruitdata::cli::{run_command, Commands, Context};
+/// use fruitdata::config::Config;
+/// use fruitdata::store::BorrowedStore;
+///
+/// let mut catalogue = Catalogue::new(initialise_fruit_catalogue());
+/// let changes = catalogue.subscribe();
+///
+/// let remove = Commands::Remove { name: "Pear".into(), id: None };
+/// run_command(&remove, &BorrowedStore::new(&mut catalogue), &mut Context::new(Config::default())).unwrap();
+///
+/// assert!(catalogue.find("Pear").is_err());
+/// assert!(changes.try_recv().is_ok());
+/// ```
+#[derive(Debug)]
+pub struct BorrowedStore<'a> {
+    catalogue: RefCell<&'a mut Catalogue>,
+}
+
+impl<'a> BorrowedStore<'a> {
+    /// Lend `catalogue` to the commands run with this store.
+    pub fn new(catalogue: &'a mut Catalogue) -> Self {
+        BorrowedStore {
+            catalogue: RefCell::new(catalogue),
+        }
+    }
+}
+
+impl CatalogStore for BorrowedStore<'_> {
+    fn describe(&self) -> String {
+        "catalogue".to_string()
+    }
+
+    fn exists(&self) -> bool {
+        true
+    }
+
+    fn load(&self) -> Result<Catalogue, Box<dyn Error>> {
+        Ok(self.catalogue.borrow().clone())
+    }
+
+    fn save(&self, catalogue: &mut Catalogue) -> Result<(), Box<dyn Error>> {
+        self.catalogue.borrow_mut().commit(catalogue.clone());
+        Ok(())
+    }
+}
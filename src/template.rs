@@ -0,0 +1,230 @@
+// ============================================================================
+// template.rs - User-Defined Output Lines
+// ============================================================================
+// Labels, signage and other tools often want fruits printed in their own
+// shape rather than fruitdata's. A `Template` is a line of text with
+// `{{field}}` placeholders, filled in once per fruit:
+//
+//     fruitdata list --template '{{name}}: {{volume}} cm³'
+//
+// prints "Apple: 15 cm³", "Banana: ...", and so on. The fields are the
+// fruit's `name`, `id`, `length`, `width` and `height`, plus the computed
+// `volume`, and for labels (see label.rs) its `plu` and `barcode`.
+// Templates are checked when they are parsed, so a misspelt field is
+// reported before anything is printed.
+// ============================================================================
+
+use crate::label::{barcode_data, plu};
+use crate::models::FruitDimensions;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A value of a fruit that a template can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateField {
+    Name,
+    /// Empty for fruits without an ID.
+    Id,
+    Length,
+    Width,
+    Height,
+    Volume,
+    /// The fruit's `"plu"` field; empty if it has none.
+    Plu,
+    /// What its barcode holds: the PLU, or else the ID.
+    Barcode,
+}
+
+impl TemplateField {
+    fn value(self, fruit: &FruitDimensions, number: &impl Fn(f32) -> String) -> String {
+        match self {
+            TemplateField::Name => fruit.name.clone(),
+            TemplateField::Id => fruit.id.map(|id| id.to_string()).unwrap_or_default(),
+            TemplateField::Length => number(fruit.length.value()),
+            TemplateField::Width => number(fruit.width.value()),
+            TemplateField::Height => number(fruit.height.value()),
+            TemplateField::Volume => number(fruit.volume()),
+            TemplateField::Plu => plu(fruit).unwrap_or_default(),
+            TemplateField::Barcode => barcode_data(fruit).unwrap_or_default(),
+        }
+    }
+}
+
+impl FromStr for TemplateField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "name" => Ok(TemplateField::Name),
+            "id" => Ok(TemplateField::Id),
+            "length" => Ok(TemplateField::Length),
+            "width" => Ok(TemplateField::Width),
+            "height" => Ok(TemplateField::Height),
+            "volume" => Ok(TemplateField::Volume),
+            "plu" => Ok(TemplateField::Plu),
+            "barcode" => Ok(TemplateField::Barcode),
+            other => Err(format!(
+                "unknown template field '{}' (expected name, id, length, width, height, volume, plu or barcode)",
+                other
+            )),
+        }
+    }
+}
+
+/// One piece of a template: literal text or a placeholder.
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(TemplateField),
+}
+
+/// A line of text with `{{field}}` placeholders.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::template::Template;
+///
+/// let template: Template = "{{name}}: {{ volume }} cm³".parse().unwrap();
+/// let apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+/// assert_eq!(template.render(&apple), "Apple: 15 cm³");
+///
+/// assert!("{{colour}}".parse::<Template>().is_err());
+/// ```
+///
+/// Serialized as its text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Fill in the placeholders with the values of `fruit`.
+    pub fn render(&self, fruit: &FruitDimensions) -> String {
+        self.render_with(fruit, |n| n.to_string())
+    }
+
+    /// Fill in the placeholders like `render`, writing numbers with
+    /// `number` (e.g. to round them).
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::models::FruitDimensions;
+    /// use fruitdata::template::Template;
+    ///
+    /// let template: Template = "{{name}}: {{length}}".parse().unwrap();
+    /// let apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+    /// assert_eq!(template.render_with(&apple, |n| format!("{:.2}", n)), "Apple: 4.00");
+    /// ```
+    pub fn render_with(&self, fruit: &FruitDimensions, number: impl Fn(f32) -> String) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(field) => field.value(fruit, &number),
+            })
+            .collect()
+    }
+
+    /// Fill in the placeholders like `render_with`, passing each value
+    /// through `escape`, and leave out every line with a placeholder that
+    /// has no value (say, the `{{plu}}` of a fruit without one).
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::models::FruitDimensions;
+    /// use fruitdata::template::Template;
+    ///
+    /// let template: Template = "<{{name}}>\nPLU {{plu}}\n".parse().unwrap();
+    /// let apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+    /// let escape = |value: &str| value.replace('<', "&lt;");
+    /// assert_eq!(template.render_lines(&apple, |n| n.to_string(), escape), "<Apple>\n");
+    /// ```
+    pub fn render_lines(
+        &self,
+        fruit: &FruitDimensions,
+        number: impl Fn(f32) -> String,
+        escape: impl Fn(&str) -> String,
+    ) -> String {
+        let mut rendered = String::new();
+        let mut line = String::new();
+        let mut missing = false;
+        for part in &self.parts {
+            match part {
+                Part::Field(field) => {
+                    let value = field.value(fruit, &number);
+                    missing |= value.is_empty();
+                    line.push_str(&escape(&value));
+                }
+                Part::Text(text) => {
+                    for piece in text.split_inclusive('\n') {
+                        line.push_str(piece);
+                        if piece.ends_with('\n') {
+                            if !missing {
+                                rendered.push_str(&line);
+                            }
+                            line.clear();
+                            missing = false;
+                        }
+                    }
+                }
+            }
+        }
+        if !missing {
+            rendered.push_str(&line);
+        }
+        rendered
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    /// Parse a template. Text outside `{{ }}` is copied as-is; a `{{` must
+    /// be closed and name a known field.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(open) = rest.find("{{") {
+            if open > 0 {
+                parts.push(Part::Text(rest[..open].to_string()));
+            }
+            let after = &rest[open + 2..];
+            let close = after
+                .find("}}")
+                .ok_or_else(|| format!("unclosed '{{{{' in template '{}'", s))?;
+            parts.push(Part::Field(after[..close].parse()?));
+            rest = &after[close + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Template {
+            source: s.to_string(),
+            parts,
+        })
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Template> for String {
+    fn from(template: Template) -> Self {
+        template.source
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
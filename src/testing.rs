@@ -0,0 +1,157 @@
+// ============================================================================
+// testing.rs - Generated Fruit Data for Tests (the `testing` feature)
+// ============================================================================
+// Code that consumes fruit data is best tested against many fruits, not the
+// four in `initialise_fruit_catalogue`. `FruitGenerator` makes as many as a
+// test wants, all of them valid: a non-empty name made of letters, and
+// positive dimensions, so `Catalogue::add` accepts every one.
+//
+// The generator is seeded, so the same seed always gives the same fruits
+// and a failing case can be replayed:
+//
+//     let mut fruits = FruitGenerator::new(42);
+//     for fruit in fruits.by_ref().take(1000) { check(&fruit); }
+//
+// fruitdata has no dependencies beyond serde and clap, so this is a small
+// generator of its own rather than `arbitrary` or `proptest` impls; a
+// proptest strategy can be built on it with `any::<u64>().prop_map(...)`.
+//
+// Enable it with: `cargo build --features testing`
+// ============================================================================
+
+use crate::models::FruitDimensions;
+
+/// Syllables that names are built from. Each has two letters, so names made
+/// from different sequences of them are always different.
+const SYLLABLES: [&str; 20] = [
+    "ba", "ko", "mi", "ra", "lu", "pe", "no", "si", "ta", "ve", "di", "go", "ma", "ru", "le", "pi",
+    "na", "so", "ti", "za",
+];
+
+/// The smallest and largest dimension generated, in centimetres.
+const MIN_DIMENSION: f32 = 0.5;
+const MAX_DIMENSION: f32 = 30.0;
+
+/// A seeded source of valid fruits.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::catalog::Catalogue;
+/// use fruitdata::testing::FruitGenerator;
+///
+/// let fruits = FruitGenerator::new(7).fruits(100);
+/// assert_eq!(fruits, FruitGenerator::new(7).fruits(100)); // same seed, same fruits
+///
+/// let mut catalogue = Catalogue::default();
+/// for fruit in fruits {
+///     catalogue.add(fruit).unwrap(); // valid, with distinct names
+/// }
+/// assert_eq!(catalogue.fruits().len(), 100);
+/// ```
+///
+/// A property test: a fruit that loads has finite dimensions, so its
+/// volume is never NaN (and, for real fruit sizes, finite), whatever number
+/// the file holds.
+/// ```
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::testing::FruitGenerator;
+///
+/// let mut generator = FruitGenerator::new(2024);
+/// for (i, fruit) in generator.fruits(800).into_iter().enumerate() {
+///     let json = serde_json::to_string(&fruit).unwrap();
+///     let loaded: FruitDimensions = serde_json::from_str(&json).unwrap();
+///     assert!(loaded.volume().is_finite());
+///
+///     // The same fruit with a width from 1e-400 to 1e399, either sign
+///     let width = format!("{}{}.5e{}", if i % 2 == 0 { "" } else { "-" }, i % 10, i as i32 - 400);
+///     let json = json.replacen(&format!("\"width\":{:?}", fruit.width), &format!("\"width\":{}", width), 1);
+///     match serde_json::from_str::<FruitDimensions>(&json) {
+///         Ok(loaded) => assert!(loaded.width.is_finite() && !loaded.volume().is_nan()),
+///         // Too large for an f32, or (past 1.8e308) for serde_json's f64
+///         Err(e) => assert!(
+///             e.to_string().contains("finite number") || e.to_string().contains("out of range"),
+///             "{}: {}",
+///             width,
+///             e
+///         ),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FruitGenerator {
+    state: u64,
+}
+
+impl FruitGenerator {
+    /// A generator whose fruits are determined by `seed`.
+    pub fn new(seed: u64) -> Self {
+        FruitGenerator { state: seed }
+    }
+
+    /// A valid fruit. Its name may repeat one generated earlier; use
+    /// `fruits` for a set of fruits that fit in one catalogue.
+    pub fn fruit(&mut self) -> FruitDimensions {
+        let syllables = 2 + self.below(3);
+        let mut name = String::new();
+        for _ in 0..syllables {
+            name.push_str(SYLLABLES[self.below(SYLLABLES.len() as u64) as usize]);
+        }
+        self.with_name(name)
+    }
+
+    /// `count` valid fruits whose names all differ, in any case.
+    pub fn fruits(&mut self, count: usize) -> Vec<FruitDimensions> {
+        (0..count)
+            .map(|index| {
+                // Writing the index in base 20 gives each fruit its own name;
+                // the leading syllable keeps short names from looking alike
+                let mut name = String::from(SYLLABLES[self.below(20) as usize]);
+                let mut rest = index;
+                loop {
+                    name.push_str(SYLLABLES[rest % SYLLABLES.len()]);
+                    rest /= SYLLABLES.len();
+                    if rest == 0 {
+                        break;
+                    }
+                }
+                self.with_name(name)
+            })
+            .collect()
+    }
+
+    fn with_name(&mut self, mut name: String) -> FruitDimensions {
+        name[..1].make_ascii_uppercase();
+        let (length, width, height) = (self.dimension(), self.dimension(), self.dimension());
+        FruitDimensions::new(&name, length, width, height)
+    }
+
+    /// A dimension between `MIN_DIMENSION` and `MAX_DIMENSION`, to one
+    /// decimal place, as a person measuring would write it.
+    fn dimension(&mut self) -> f32 {
+        let steps = ((MAX_DIMENSION - MIN_DIMENSION) * 10.0) as u64;
+        (MIN_DIMENSION * 10.0 + self.below(steps + 1) as f32) / 10.0
+    }
+
+    /// A number in `0..bound`.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// SplitMix64: fast, and good enough for test data.
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Iterator for FruitGenerator {
+    type Item = FruitDimensions;
+
+    /// Never ends; take as many fruits as needed.
+    fn next(&mut self) -> Option<FruitDimensions> {
+        Some(self.fruit())
+    }
+}
@@ -0,0 +1,267 @@
+// ============================================================================
+// timestamp.rs - Points in Time for the Change Journal
+// ============================================================================
+// The journal needs to record *when* each change happened and to answer
+// questions such as "what changed since 2024-01-01?". The standard library
+// can tell us the current time (`SystemTime`), but it can't parse or print
+// calendar dates, so this module converts between seconds since the Unix
+// epoch and ISO 8601 strings like `2024-06-01T12:00:00Z`. `Period` does the
+// same for lengths of time such as `90d`, used by retention settings.
+//
+// All times are UTC. The calendar conversion uses the well-known
+// days-from-civil algorithm (Howard Hinnant), which is exact for every date
+// in the proleptic Gregorian calendar.
+// ============================================================================
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// A point in time, stored as whole seconds since 1970-01-01T00:00:00Z.
+///
+/// Serialized as an ISO 8601 string so journal files stay readable.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::timestamp::Timestamp;
+///
+/// let t: Timestamp = "2024-06-01T12:00".parse().unwrap();
+/// assert_eq!(t.to_string(), "2024-06-01T12:00:00Z");
+///
+/// let day: Timestamp = "2024-06-01".parse().unwrap(); // midnight
+/// assert!(day < t);
+///
+/// // Years run from 1970 to 9999
+/// assert!("9999-12-31T23:59:59".parse::<Timestamp>().is_ok());
+/// assert!("999999999999-01-01".parse::<Timestamp>().is_err());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(into = "String", try_from = "String")]
+pub struct Timestamp(pub u64);
+
+impl Timestamp {
+    /// The current time according to the system clock.
+    pub fn now() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Timestamp(seconds)
+    }
+
+    /// The time `period` before this one (clamped at the Unix epoch).
+    pub fn minus(self, period: Period) -> Timestamp {
+        Timestamp(self.0.saturating_sub(period.0))
+    }
+}
+
+/// Number of days from 1970-01-01 to the given civil date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The civil date (year, month, day) `days` days after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = String;
+
+    /// Parse `YYYY-MM-DD`, `YYYY-MM-DDTHH:MM` or `YYYY-MM-DDTHH:MM:SS`,
+    /// optionally followed by `Z`. A space may be used instead of `T`.
+    /// The year must be between 1970 and 9999.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid timestamp '{}' (expected YYYY-MM-DD or YYYY-MM-DDTHH:MM[:SS])",
+                s
+            )
+        };
+
+        let text = s.trim().trim_end_matches('Z');
+        let (date, time) = match text.split_once(['T', ' ']) {
+            Some((date, time)) => (date, Some(time)),
+            None => (text, None),
+        };
+
+        // Date part: year-month-day
+        let mut parts = date.split('-');
+        let (year, month, day) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(y), Some(m), Some(d), None) => (
+                y.parse::<i64>().map_err(|_| invalid())?,
+                m.parse::<u32>().map_err(|_| invalid())?,
+                d.parse::<u32>().map_err(|_| invalid())?,
+            ),
+            _ => return Err(invalid()),
+        };
+        if !(1970..=9999).contains(&year)
+            || !(1..=12).contains(&month)
+            || day == 0
+            || day > days_in_month(year, month)
+        {
+            return Err(invalid());
+        }
+
+        // Time part: hours:minutes[:seconds]
+        let (hours, minutes, seconds) = match time {
+            None => (0, 0, 0),
+            Some(time) => {
+                let fields: Vec<&str> = time.split(':').collect();
+                let field = |i: usize| -> Result<u64, String> {
+                    fields
+                        .get(i)
+                        .map_or(Ok(0), |f| f.parse::<u64>().map_err(|_| invalid()))
+                };
+                if !(2..=3).contains(&fields.len()) {
+                    return Err(invalid());
+                }
+                (field(0)?, field(1)?, field(2)?)
+            }
+        };
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return Err(invalid());
+        }
+
+        let days = days_from_civil(year, month, day) as u64;
+        Ok(Timestamp(
+            days * SECONDS_PER_DAY + hours * 3600 + minutes * 60 + seconds,
+        ))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days = (self.0 / SECONDS_PER_DAY) as i64;
+        let rest = self.0 % SECONDS_PER_DAY;
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            rest / 3600,
+            rest % 3600 / 60,
+            rest % 60
+        )
+    }
+}
+
+impl From<Timestamp> for String {
+    fn from(timestamp: Timestamp) -> String {
+        timestamp.to_string()
+    }
+}
+
+impl TryFrom<String> for Timestamp {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// A length of time, stored as whole seconds.
+///
+/// Written as a number followed by a unit: `s` (seconds), `m` (minutes),
+/// `h` (hours), `d` (days) or `w` (weeks), e.g. `90d`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::timestamp::{Period, Timestamp};
+///
+/// let keep: Period = "2d".parse().unwrap();
+/// assert_eq!(keep, Period(2 * 86_400));
+/// assert_eq!(Timestamp(3 * 86_400).minus(keep), Timestamp(86_400));
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(into = "String", try_from = "String")]
+pub struct Period(pub u64);
+
+const PERIOD_UNITS: [(char, u64); 5] = [
+    ('w', 7 * SECONDS_PER_DAY),
+    ('d', SECONDS_PER_DAY),
+    ('h', 3600),
+    ('m', 60),
+    ('s', 1),
+];
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid period '{}' (expected e.g. 90d, 12h, 2w)", s);
+
+        let text = s.trim();
+        let unit = text.chars().last().ok_or_else(invalid)?;
+        let seconds_per_unit = PERIOD_UNITS
+            .iter()
+            .find(|(u, _)| *u == unit.to_ascii_lowercase())
+            .map(|(_, seconds)| *seconds)
+            .ok_or_else(invalid)?;
+        let count: u64 = text[..text.len() - unit.len_utf8()]
+            .trim()
+            .parse()
+            .map_err(|_| invalid())?;
+
+        count
+            .checked_mul(seconds_per_unit)
+            .map(Period)
+            .ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for Period {
+    /// Uses the largest unit that divides the period exactly.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, seconds) = PERIOD_UNITS
+            .iter()
+            .find(|(_, seconds)| self.0.is_multiple_of(*seconds))
+            .unwrap_or(&('s', 1));
+        write!(f, "{}{}", self.0 / seconds, unit)
+    }
+}
+
+impl From<Period> for String {
+    fn from(period: Period) -> String {
+        period.to_string()
+    }
+}
+
+impl TryFrom<String> for Period {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
@@ -0,0 +1,671 @@
+// ============================================================================
+// toml.rs - Catalogues in TOML
+// ============================================================================
+// JSON is hard to edit by hand: a missing comma or an extra one breaks the
+// whole file, and there is nowhere to put a comment. With the `toml`
+// feature a catalogue can be kept in TOML instead, one `[[fruits]]` table
+// per fruit:
+//
+//     # Measured in the Leeds warehouse
+//     [[fruits]]
+//     name = "Apple"
+//     length = 4.0
+//     width = 2.5
+//     height = 1.5
+//
+// The file's other keys (`[calibration]`, `schema_version = 2`, ...) sit
+// above the fruits, as in the object layout of a JSON file (see
+// document.rs). `parse` reads TOML into the same JSON value a JSON file
+// would give, so loading a TOML catalogue validates it exactly like a JSON
+// one; `write` turns a JSON value back into TOML.
+//
+// All of TOML 1.0 is read except that dates and times are kept as strings
+// (JSON has no date type). TOML has no null, so null fields are left out
+// when writing, which reads back the same as far as fruitdata is
+// concerned.
+// ============================================================================
+
+use crate::tree::Tree;
+use serde_json::{Map, Number, Value};
+use std::collections::HashSet;
+
+/// Parse TOML text into a JSON value (always an object).
+///
+/// # Error Cases
+/// The text isn't valid TOML, or holds a number JSON can't (`inf`, `nan`).
+/// The error names the line.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::toml;
+/// use serde_json::json;
+///
+/// let text = r#"
+/// ## Fruits from the spring delivery
+/// [[fruits]]
+/// name = "Apple"
+/// length = 4.0
+/// width = 2.5
+/// height = 1.5
+/// tags = ["red", "crisp"]
+/// "#;
+/// assert_eq!(
+///     toml::parse(text).unwrap(),
+///     json!({"fruits": [{"name": "Apple", "length": 4.0, "width": 2.5, "height": 1.5,
+///                        "tags": ["red", "crisp"]}]})
+/// );
+///
+/// let error = toml::parse("name = \"Apple\"\nlength = ").unwrap_err();
+/// assert_eq!(error, "line 2: expected a value");
+/// ```
+pub fn parse(text: &str) -> Result<Value, String> {
+    Parser::new(text).document()
+}
+
+/// Write a JSON value as TOML.
+///
+/// Tables and lists of tables at any depth become `[table]` and
+/// `[[table]]` sections; tables inside other lists are written inline.
+/// Keys keep the order of `tree` (see tree.rs).
+///
+/// # Error Cases
+/// `tree` isn't a table, or a list holds a null (TOML has no null).
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::toml;
+/// use fruitdata::tree::Tree;
+/// use std::collections::BTreeMap;
+///
+/// let document = BTreeMap::from([("fruits", [FruitDimensions::new("Apple", 4.0, 2.5, 1.5)])]);
+/// let text = toml::write(&Tree::of(&document).unwrap()).unwrap();
+/// assert_eq!(text, "[[fruits]]\nname = \"Apple\"\nlength = 4.0\nwidth = 2.5\nheight = 1.5\n");
+/// assert_eq!(toml::parse(&text).unwrap(), serde_json::to_value(&document).unwrap());
+/// ```
+pub fn write(tree: &Tree) -> Result<String, String> {
+    let Tree::Table(entries) = tree else {
+        return Err("a TOML file holds a table, not a single value or a list".into());
+    };
+    let mut out = String::new();
+    write_table(&mut out, &mut Vec::new(), entries)?;
+    Ok(out)
+}
+
+// ============================================================================
+// WRITING
+// ============================================================================
+
+/// Whether `value` is written as a section of its own rather than as
+/// `key = value`.
+fn is_section(value: &Tree) -> bool {
+    match value {
+        Tree::Table(_) => true,
+        Tree::Array(items) => {
+            !items.is_empty() && items.iter().all(|item| matches!(item, Tree::Table(_)))
+        }
+        _ => false,
+    }
+}
+
+/// Write the body of the table at `path`: its plain keys, then its
+/// sections.
+fn write_table(
+    out: &mut String,
+    path: &mut Vec<String>,
+    entries: &[(String, Tree)],
+) -> Result<(), String> {
+    for (key, value) in entries {
+        if is_section(value) || *value == Tree::Null {
+            continue;
+        }
+        out.push_str(&format!("{} = {}\n", key_text(key), inline(value)?));
+    }
+
+    for (key, value) in entries.iter().filter(|(_, value)| is_section(value)) {
+        path.push(key_text(key));
+        match value {
+            Tree::Table(inner) => {
+                section(out, &format!("[{}]", path.join(".")));
+                write_table(out, path, inner)?;
+            }
+            Tree::Array(items) => {
+                for item in items {
+                    if let Tree::Table(inner) = item {
+                        section(out, &format!("[[{}]]", path.join(".")));
+                        write_table(out, path, inner)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+/// Start a section, with a blank line before it unless it is the first.
+fn section(out: &mut String, header: &str) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(header);
+    out.push('\n');
+}
+
+/// A value as written after `key = ` (or inside a list).
+fn inline(value: &Tree) -> Result<String, String> {
+    Ok(match value {
+        Tree::Null => return Err("TOML has no null, so a list can't hold one".into()),
+        Tree::Bool(value) => value.to_string(),
+        Tree::Number(number) => number.to_string(),
+        Tree::String(text) => quote(text),
+        Tree::Array(items) => {
+            let items = items.iter().map(inline).collect::<Result<Vec<_>, _>>()?;
+            format!("[{}]", items.join(", "))
+        }
+        Tree::Table(entries) => {
+            let mut fields = Vec::new();
+            for (key, value) in entries {
+                if *value != Tree::Null {
+                    fields.push(format!("{} = {}", key_text(key), inline(value)?));
+                }
+            }
+            if fields.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", fields.join(", "))
+            }
+        }
+    })
+}
+
+/// A key, bare if TOML allows it and quoted otherwise.
+fn key_text(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+/// A basic (double-quoted) string.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// ============================================================================
+// READING
+// ============================================================================
+
+/// Reads a TOML document one character at a time.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Self {
+        Parser {
+            chars: text.trim_start_matches('\u{feff}').chars().collect(),
+            pos: 0,
+            line: 1,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> String {
+        format!("line {}: {}", self.line, message.into())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    /// Whether the next characters are `text`, consuming them if so.
+    fn eat(&mut self, text: &str) -> bool {
+        let matches = text
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| self.peek_at(offset) == Some(c));
+        if matches {
+            for _ in text.chars() {
+                self.bump();
+            }
+        }
+        matches
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        if self.eat(text) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", text)))
+        }
+    }
+
+    /// Skip spaces and tabs.
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    /// Skip spaces, line breaks and comments.
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => {
+                    self.bump();
+                }
+                Some('#') => self.skip_comment(),
+                _ => break,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Expect the end of a line, after an optional comment.
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        self.skip_comment();
+        self.eat("\r");
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("unexpected '{}' (expected a new line)", c))),
+        }
+    }
+
+    fn document(mut self) -> Result<Value, String> {
+        let mut root = Map::new();
+        let mut current: Vec<String> = Vec::new();
+        // Tables given a `[header]`, which may only be given one once
+        let mut defined: HashSet<Vec<String>> = HashSet::new();
+
+        loop {
+            self.skip_blank();
+            let Some(c) = self.peek() else { break };
+            if c == '[' {
+                let list = self.eat("[[");
+                if !list {
+                    self.bump();
+                }
+                self.skip_spaces();
+                let path = self.key()?;
+                self.skip_spaces();
+                self.expect(if list { "]]" } else { "]" })?;
+                self.end_of_line()?;
+
+                let (last, parents) = path.split_last().expect("a key has a part");
+                let parent = table_at(&mut root, parents).map_err(|e| self.error(e))?;
+                if list {
+                    let entry = parent
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    let Value::Array(items) = entry else {
+                        return Err(self.error(format!("'{}' isn't a list of tables", last)));
+                    };
+                    items.push(Value::Object(Map::new()));
+                    // The new table's own sections haven't been given yet
+                    defined.retain(|table| !table.starts_with(&path));
+                } else {
+                    match parent
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Object(Map::new()))
+                    {
+                        Value::Object(_) => {}
+                        _ => return Err(self.error(format!("'{}' isn't a table", last))),
+                    }
+                    if !defined.insert(path.clone()) {
+                        return Err(
+                            self.error(format!("table [{}] is defined twice", path.join(".")))
+                        );
+                    }
+                }
+                current = path;
+            } else {
+                let key = self.key()?;
+                self.skip_spaces();
+                self.expect("=")?;
+                self.skip_spaces();
+                let value = self.value()?;
+                self.end_of_line()?;
+                let table = table_at(&mut root, &current).map_err(|e| self.error(e))?;
+                insert(table, &key, value).map_err(|e| self.error(e))?;
+            }
+        }
+        Ok(Value::Object(root))
+    }
+
+    /// A key, which may be dotted (`calibration.unit`).
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut parts = vec![self.simple_key()?];
+        loop {
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                break;
+            }
+            self.bump();
+            self.skip_spaces();
+            parts.push(self.simple_key()?);
+        }
+        Ok(parts)
+    }
+
+    fn simple_key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let mut key = String::new();
+                while let Some(c) = self.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                        break;
+                    }
+                    key.push(c);
+                    self.bump();
+                }
+                if key.is_empty() {
+                    return Err(self.error("expected a key"));
+                }
+                Ok(key)
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') if self.eat("\"\"\"") => self.multiline_string('"').map(Value::String),
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') if self.eat("'''") => self.multiline_string('\'').map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some(_) if self.eat("true") => Ok(Value::Bool(true)),
+            Some(_) if self.eat("false") => Ok(Value::Bool(false)),
+            Some(c) if c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.') => self.scalar(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    /// A number, or a date or time (kept as a string).
+    fn scalar(&mut self) -> Result<Value, String> {
+        let mut token = self.token();
+        // A date and a time may be separated by a space
+        let is_date = token.len() == 10 && token.as_bytes()[4] == b'-';
+        if is_date
+            && self.peek() == Some(' ')
+            && self.peek_at(1).is_some_and(|c| c.is_ascii_digit())
+        {
+            self.bump();
+            token.push(' ');
+            token.push_str(&self.token());
+        }
+        number(&token)
+            .or_else(|| is_date_or_time(&token).then(|| Value::String(token.clone())))
+            .ok_or_else(|| match token.trim_start_matches(['+', '-']) {
+                "inf" | "nan" => self.error(format!("'{}' can't be stored in a catalogue", token)),
+                _ => self.error(format!("'{}' isn't a valid value", token)),
+            })
+    }
+
+    fn token(&mut self) -> String {
+        let mut token = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_' | ':')) {
+                break;
+            }
+            token.push(c);
+            self.bump();
+        }
+        token
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect("[")?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.eat("]") {
+                break;
+            }
+            items.push(self.value()?);
+            self.skip_blank();
+            if self.eat("]") {
+                break;
+            }
+            self.expect(",")?;
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn inline_table(&mut self) -> Result<Value, String> {
+        self.expect("{")?;
+        let mut table = Map::new();
+        self.skip_spaces();
+        if self.eat("}") {
+            return Ok(Value::Object(table));
+        }
+        loop {
+            self.skip_spaces();
+            let key = self.key()?;
+            self.skip_spaces();
+            self.expect("=")?;
+            self.skip_spaces();
+            let value = self.value()?;
+            insert(&mut table, &key, value).map_err(|e| self.error(e))?;
+            self.skip_spaces();
+            if self.eat("}") {
+                break;
+            }
+            self.expect(",")?;
+        }
+        Ok(Value::Object(table))
+    }
+
+    /// A `"basic string"`, with escapes.
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(text),
+                Some('\\') => text.push(self.escape()?),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    /// A `'literal string'`, taken as it is.
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.expect("'")?;
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('\'') => return Ok(text),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    /// The rest of a `"""` or `'''` string, whose opening quotes have been
+    /// read.
+    fn multiline_string(&mut self, quote: char) -> Result<String, String> {
+        let delimiter = quote.to_string().repeat(3);
+        // A line break right after the opening quotes isn't part of the text
+        if !self.eat("\r\n") {
+            self.eat("\n");
+        }
+        let mut text = String::new();
+        loop {
+            if self.eat(&delimiter) {
+                // Up to two quotes may come right before the closing ones
+                for _ in 0..2 {
+                    if self.peek() == Some(quote) {
+                        self.bump();
+                        text.push(quote);
+                    }
+                }
+                return Ok(text);
+            }
+            match self.bump() {
+                None => return Err(self.error("unterminated string")),
+                Some('\\') if quote == '"' => {
+                    if matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                        // A backslash at the end of a line joins it to the next
+                        while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                            self.bump();
+                        }
+                    } else {
+                        text.push(self.escape()?);
+                    }
+                }
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    /// The character of an escape, whose backslash has been read.
+    fn escape(&mut self) -> Result<char, String> {
+        let c = match self.bump() {
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('e') => '\u{1b}',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some(kind @ ('u' | 'U')) => {
+                let digits: String = (0..if kind == 'u' { 4 } else { 8 })
+                    .filter_map(|_| self.bump())
+                    .collect();
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        self.error(format!("'\\{}{}' isn't a character", kind, digits))
+                    })?
+            }
+            Some(c) => return Err(self.error(format!("unknown escape '\\{}'", c))),
+            None => return Err(self.error("unterminated string")),
+        };
+        Ok(c)
+    }
+}
+
+/// The table at `path` below `root`, creating missing tables. A list of
+/// tables on the way stands for its last table.
+fn table_at<'a>(
+    mut table: &'a mut Map<String, Value>,
+    path: &[String],
+) -> Result<&'a mut Map<String, Value>, String> {
+    for key in path {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        table = match entry {
+            Value::Object(inner) => inner,
+            Value::Array(items) => match items.last_mut() {
+                Some(Value::Object(inner)) => inner,
+                _ => return Err(format!("'{}' isn't a list of tables", key)),
+            },
+            _ => return Err(format!("'{}' isn't a table", key)),
+        };
+    }
+    Ok(table)
+}
+
+/// Set the (possibly dotted) `key` of `table` to `value`.
+fn insert(table: &mut Map<String, Value>, key: &[String], value: Value) -> Result<(), String> {
+    let (last, parents) = key.split_last().expect("a key has a part");
+    let table = table_at(table, parents)?;
+    if table.contains_key(last) {
+        return Err(format!("'{}' is given twice", key.join(".")));
+    }
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+/// A TOML integer or float as a JSON number, if `token` is one.
+fn number(token: &str) -> Option<Value> {
+    if token.starts_with('_') || token.ends_with('_') || token.contains("__") {
+        return None;
+    }
+    let digits = token.replace('_', "");
+    let (sign, unsigned) = match digits.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, digits.strip_prefix('+').unwrap_or(&digits)),
+    };
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(rest) = unsigned.strip_prefix(prefix) {
+            if sign < 0 || digits.starts_with('+') {
+                return None;
+            }
+            return i64::from_str_radix(rest, radix).ok().map(Value::from);
+        }
+    }
+    if !unsigned.chars().next()?.is_ascii_digit() {
+        return None;
+    }
+    if unsigned.contains(['.', 'e', 'E']) {
+        let value: f64 = digits.parse().ok()?;
+        Number::from_f64(value).map(Value::Number)
+    } else {
+        digits.parse::<i64>().ok().map(Value::from)
+    }
+}
+
+/// Whether `token` looks like a TOML date, time or date-time.
+fn is_date_or_time(token: &str) -> bool {
+    let bytes = token.as_bytes();
+    let date = bytes.len() >= 10
+        && bytes[..4].iter().all(u8::is_ascii_digit)
+        && bytes[4] == b'-'
+        && bytes[7] == b'-';
+    let time = bytes.len() >= 8 && bytes[..2].iter().all(u8::is_ascii_digit) && bytes[2] == b':';
+    date || time
+}
@@ -0,0 +1,123 @@
+// ============================================================================
+// tree.rs - Documents in Their Written Order
+// ============================================================================
+// serde_json's `Value` keeps the keys of an object sorted, which is fine
+// for reading a file but not for writing one a person will edit: a fruit
+// would come out as height, length, name, width. A `Tree` is the same
+// kind of value with each table's keys in the order they were serialized,
+// so the TOML and YAML writers (toml.rs, yaml.rs) can lay a fruit out as
+// `FruitDimensions` declares it, name first.
+// ============================================================================
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Serialize;
+use serde_json::Number;
+use std::fmt;
+
+/// A JSON value whose tables keep their keys in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tree {
+    /// JSON's `null`.
+    Null,
+    /// `true` or `false`.
+    Bool(bool),
+    /// Any number, kept as serde_json does.
+    Number(Number),
+    /// A string.
+    String(String),
+    /// A list of values.
+    Array(Vec<Tree>),
+    /// An object, as its keys and values in order.
+    Table(Vec<(String, Tree)>),
+}
+
+impl Tree {
+    /// The tree of anything serializable, with struct fields in declaration
+    /// order.
+    ///
+    /// # Example Usage
+    /// ```
+    /// use fruitdata::models::FruitDimensions;
+    /// use fruitdata::tree::Tree;
+    ///
+    /// let Tree::Table(fields) = Tree::of(&FruitDimensions::new("Apple", 4.0, 2.5, 1.5)).unwrap() else {
+    ///     panic!("a fruit is a table");
+    /// };
+    /// let keys: Vec<&str> = fields.iter().map(|(key, _)| key.as_str()).collect();
+    /// assert_eq!(keys, ["name", "length", "width", "height"]);
+    /// ```
+    pub fn of<T: Serialize + ?Sized>(value: &T) -> Result<Tree, serde_json::Error> {
+        serde_json::from_str(&serde_json::to_string(value)?)
+    }
+
+    /// Parse JSON text, keeping its keys in order.
+    pub fn from_json(json: &str) -> Result<Tree, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+impl<'de> Deserialize<'de> for Tree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TreeVisitor)
+    }
+}
+
+struct TreeVisitor;
+
+impl<'de> Visitor<'de> for TreeVisitor {
+    type Value = Tree;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Tree, E> {
+        Ok(Tree::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Tree, E> {
+        Ok(Tree::Null)
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Tree, E> {
+        Ok(Tree::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Tree, E> {
+        Ok(Tree::Number(value.into()))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Tree, E> {
+        Ok(Tree::Number(value.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Tree, E> {
+        Number::from_f64(value)
+            .map(Tree::Number)
+            .ok_or_else(|| E::custom(format!("{} isn't a finite number", value)))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Tree, E> {
+        Ok(Tree::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Tree, E> {
+        Ok(Tree::String(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Tree, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Tree::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Tree, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Tree::Table(entries))
+    }
+}
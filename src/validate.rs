@@ -0,0 +1,291 @@
+// ============================================================================
+// validate.rs - Checking a Catalogue File Before It Is Used
+// ============================================================================
+// `Catalogue` checks every change as it is made, but a file can also be
+// edited by hand, generated by a script or merged by git, and then nothing
+// has checked it. `Validator` looks at a whole file at once and reports
+// every problem it finds, so that CI can reject a bad catalogue change
+// before it is published. It checks:
+//
+// - structure: the file is a catalogue (and, when strict, has no unknown
+//   keys or wrongly typed values; see `CatalogueDocument::from_slice_strict`)
+// - invariants: the rules `Catalogue::add` enforces, such as a non-empty
+//   name allowed by the `NamePolicy`, positive dimensions, and the `Limits`
+// - duplicates: repeated names (in name mode, ignoring case unless the file
+//   is case-sensitive) and repeated IDs
+// - checksum: optionally, that the file is exactly the one expected
+//
+// Each problem is located with the same JSONPath-style paths strict loading
+// uses (`$[2]`, `$.fruits[0]`), and the report serializes to JSON for tools.
+// ============================================================================
+
+use crate::catalog::{content_hash, validate_dimensions};
+use crate::document::CatalogueDocument;
+use crate::error::CatalogError;
+use crate::models::FruitDimensions;
+use crate::names::CaseSensitivity;
+use crate::policy::{Limits, NamePolicy, Uniqueness};
+use serde::Serialize;
+
+/// The kind of check a `Finding` failed.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Check {
+    Structure,
+    Invariant,
+    Duplicate,
+    Checksum,
+}
+
+/// One problem found in a file.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub check: Check,
+    /// Where the problem is, e.g. `$[2]` for the third fruit.
+    pub path: String,
+    pub message: String,
+}
+
+/// The result of validating one file.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    /// `true` if no errors were found.
+    pub valid: bool,
+    /// How many fruits the file holds (0 if it couldn't be read).
+    pub fruits: usize,
+    /// The file's `content_hash`, as 16 hex digits.
+    pub checksum: String,
+    pub errors: Vec<Finding>,
+}
+
+/// Checks catalogue files against a set of rules.
+///
+/// The rules start out as the defaults; set them from the config with the
+/// `with_*` methods, as for `Catalogue`.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::validate::{Check, Validator};
+///
+/// let json = br#"[
+///     {"name": "Apple", "length": 4.0, "width": 2.5, "height": 1.5},
+///     {"name": "apple", "length": 4.1, "width": 2.5, "height": 0.0}
+/// ]"#;
+/// let report = Validator::default().validate(json);
+///
+/// assert!(!report.valid);
+/// assert_eq!(report.fruits, 2);
+/// let checks: Vec<Check> = report.errors.iter().map(|e| e.check).collect();
+/// assert_eq!(checks, vec![Check::Invariant, Check::Duplicate]);
+/// assert_eq!(report.errors[1].path, "$[1]");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Validator {
+    name_policy: NamePolicy,
+    limits: Limits,
+    uniqueness: Uniqueness,
+    case_sensitive: bool,
+    strict: bool,
+    expected_checksum: Option<String>,
+}
+
+impl Validator {
+    /// Check names against this policy.
+    pub fn with_name_policy(mut self, policy: NamePolicy) -> Self {
+        self.name_policy = policy;
+        self
+    }
+
+    /// Check the number of fruits and the file size against these limits.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Look for duplicate names only in name mode (IDs are always checked).
+    pub fn with_uniqueness(mut self, uniqueness: Uniqueness) -> Self {
+        self.uniqueness = uniqueness;
+        self
+    }
+
+    /// Only count names that match exactly as duplicates, even in a file
+    /// without the `"case_sensitive": true` header.
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Treat unknown keys and wrongly typed values as structure errors.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Require the file's checksum to be this one (16 hex digits, as in
+    /// `ValidationReport::checksum`; case doesn't matter).
+    pub fn with_expected_checksum(mut self, checksum: Option<String>) -> Self {
+        self.expected_checksum = checksum;
+        self
+    }
+
+    /// Check the contents of a catalogue file, reporting every problem.
+    ///
+    /// If the file can't be read as a catalogue at all, that is the only
+    /// structure error and the other checks (which need the fruits) are
+    /// skipped; the checksum is always checked.
+    pub fn validate(&self, bytes: &[u8]) -> ValidationReport {
+        let mut errors = Vec::new();
+        let checksum = format!("{:016x}", content_hash(bytes));
+
+        // A strict load that fails for another reason isn't even JSON, and
+        // `from_slice` below reports that
+        if self.strict {
+            let error = CatalogueDocument::from_slice_strict(bytes).err();
+            if let Some(CatalogError::SchemaViolations(problems)) = error
+                .and_then(|e| e.downcast::<CatalogError>().ok())
+                .map(|e| *e)
+            {
+                // Split "$[0].lenght: unknown field" into path and message
+                for problem in problems {
+                    let (path, message) = problem.split_once(": ").unwrap_or(("$", &problem));
+                    errors.push(finding(Check::Structure, path, message));
+                }
+            }
+        }
+
+        let fruits = match CatalogueDocument::from_slice(bytes) {
+            // Only possible in strict mode when the schema was violated,
+            // which has been reported already
+            Err(_) if !errors.is_empty() => 0,
+            Err(e) => {
+                errors.push(finding(Check::Structure, "$", e));
+                0
+            }
+            Ok(document) => {
+                let root = if document.extra.is_some() {
+                    "$.fruits"
+                } else {
+                    "$"
+                };
+                // A broken header is a structure error in strict mode
+                let case = if self.case_sensitive {
+                    CaseSensitivity::Sensitive
+                } else {
+                    document.case_sensitivity().unwrap_or_default()
+                };
+                self.check_fruits(&document.fruits, root, case, &mut errors);
+                self.check_limits(document.fruits.len(), bytes.len(), &mut errors);
+                document.fruits.len()
+            }
+        };
+
+        if let Some(expected) = &self.expected_checksum {
+            if !expected.trim().eq_ignore_ascii_case(&checksum) {
+                errors.push(finding(
+                    Check::Checksum,
+                    "$",
+                    format!("checksum is {}, expected {}", checksum, expected.trim()),
+                ));
+            }
+        }
+
+        ValidationReport {
+            valid: errors.is_empty(),
+            fruits,
+            checksum,
+            errors,
+        }
+    }
+
+    /// The per-fruit invariants, then duplicates.
+    fn check_fruits(
+        &self,
+        fruits: &[FruitDimensions],
+        root: &str,
+        case: CaseSensitivity,
+        errors: &mut Vec<Finding>,
+    ) {
+        let path = |index: usize| format!("{}[{}]", root, index);
+
+        for (index, fruit) in fruits.iter().enumerate() {
+            let name = fruit.name.trim();
+            if name.is_empty() {
+                errors.push(finding(
+                    Check::Invariant,
+                    &path(index),
+                    CatalogError::EmptyName,
+                ));
+            } else if name != fruit.name {
+                errors.push(finding(
+                    Check::Invariant,
+                    &path(index),
+                    format!("Name '{}' has leading or trailing spaces.", fruit.name),
+                ));
+            } else if let Err(reason) = self.name_policy.check(name) {
+                let error = CatalogError::InvalidName {
+                    name: name.to_string(),
+                    reason,
+                };
+                errors.push(finding(Check::Invariant, &path(index), error));
+            }
+
+            if let Err(error) = validate_dimensions(fruit) {
+                errors.push(finding(Check::Invariant, &path(index), error));
+            }
+
+            if self.uniqueness == Uniqueness::Id && fruit.id.is_none() {
+                errors.push(finding(
+                    Check::Invariant,
+                    &path(index),
+                    format!("Fruit '{}' has no id (required in ID mode).", name),
+                ));
+            }
+        }
+
+        // Each duplicate is reported once, pointing back at the first copy
+        for (index, fruit) in fruits.iter().enumerate() {
+            let earlier = &fruits[..index];
+            if self.uniqueness == Uniqueness::Name && !fruit.name.trim().is_empty() {
+                let name = fruit.name.trim();
+                if let Some(first) = earlier
+                    .iter()
+                    .position(|f| case.names_equal(f.name.trim(), name))
+                {
+                    errors.push(finding(
+                        Check::Duplicate,
+                        &path(index),
+                        format!("Fruit '{}' is already at {}.", name, path(first)),
+                    ));
+                }
+            }
+            if let Some(id) = fruit.id {
+                if let Some(first) = earlier.iter().position(|f| f.id == Some(id)) {
+                    errors.push(finding(
+                        Check::Duplicate,
+                        &path(index),
+                        format!("Id {} is already used at {}.", id, path(first)),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// The catalogue-wide `Limits`.
+    fn check_limits(&self, count: usize, bytes: usize, errors: &mut Vec<Finding>) {
+        let checks = [
+            self.limits.check_entries(count),
+            self.limits.check_file_size(bytes as u64),
+        ];
+        for error in checks.into_iter().filter_map(Result::err) {
+            errors.push(finding(Check::Invariant, "$", error));
+        }
+    }
+}
+
+fn finding(check: Check, path: &str, message: impl ToString) -> Finding {
+    Finding {
+        check,
+        path: path.to_string(),
+        message: message.to_string(),
+    }
+}
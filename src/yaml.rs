@@ -0,0 +1,682 @@
+// ============================================================================
+// yaml.rs - Catalogues in YAML
+// ============================================================================
+// With the `yaml` feature a catalogue can be kept in YAML, which many
+// people find the easiest format to edit by hand:
+//
+//     # Measured in the Leeds warehouse
+//     - name: Apple
+//       length: 4.0
+//       width: 2.5
+//       height: 1.5
+//
+// or, with the file-level keys of the object layout (see document.rs):
+//
+//     calibration:
+//       unit: mm
+//     fruits:
+//       - name: Apple
+//         ...
+//
+// `parse` reads YAML into the same JSON value a JSON file would give, so a
+// YAML catalogue is validated exactly like a JSON one; `write` turns a JSON
+// value back into YAML.
+//
+// The part of YAML that configuration files use is read: block mappings
+// and lists, flow collections on one line (`[red, crisp]`,
+// `{unit: mm}`), plain, single- and double-quoted scalars, `|` and `>`
+// block scalars, and comments. Plain scalars follow the YAML 1.2 core
+// schema (`null`, `~`, `true`, `false`, numbers). Anchors, aliases, tags
+// and multiple documents in one file are not supported.
+// ============================================================================
+
+use crate::tree::Tree;
+use serde_json::{Map, Number, Value};
+
+/// Parse YAML text into a JSON value.
+///
+/// # Error Cases
+/// The text isn't valid YAML, uses something this reader doesn't support
+/// (see the module comment), or holds a number JSON can't (`.inf`,
+/// `.nan`). The error names the line.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::yaml;
+/// use serde_json::json;
+///
+/// let text = "
+/// ## Fruits from the spring delivery
+/// - name: Apple
+///   length: 4.0
+///   width: 2.5
+///   height: 1.5
+///   tags: [red, crisp]
+///   notes: |
+///     Keep cool.
+/// ";
+/// assert_eq!(
+///     yaml::parse(text).unwrap(),
+///     json!([{"name": "Apple", "length": 4.0, "width": 2.5, "height": 1.5,
+///             "tags": ["red", "crisp"], "notes": "Keep cool.\n"}])
+/// );
+///
+/// let error = yaml::parse("- name: Apple\n   length: 4.0").unwrap_err();
+/// assert_eq!(error, "line 2: unexpected indentation");
+/// ```
+pub fn parse(text: &str) -> Result<Value, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let mut parser = Parser {
+        lines: text
+            .lines()
+            .enumerate()
+            .map(|(index, raw)| Line::new(index + 1, raw))
+            .collect(),
+        pos: 0,
+    };
+    // A `---` may open the document
+    if let Some(line) = parser.peek() {
+        if line.indent == 0 && (line.text == "---" || line.text.starts_with("--- ")) {
+            let rest = line.text[3..].trim_start().to_string();
+            if rest.is_empty() || rest.starts_with('#') {
+                parser.pos += 1;
+            } else {
+                parser.lines[parser.pos].text = rest;
+            }
+        }
+    }
+    let value = parser.block(0)?;
+    if let Some(line) = parser.peek() {
+        if line.indent == 0 && line.text.starts_with("---") {
+            return Err(line.error("only one document per file is supported"));
+        }
+        if line.indent == 0 && line.text.starts_with("...") {
+            return Ok(value);
+        }
+        return Err(line.error(if line.indent > 0 {
+            "unexpected indentation"
+        } else {
+            "unexpected text after the document"
+        }));
+    }
+    Ok(value)
+}
+
+/// Write a JSON value as YAML, in block style.
+///
+/// Keys keep the order of `tree` (see tree.rs). Strings that would read
+/// back as something else (`"null"`, `"4.0"`, `"yes"`) or that need
+/// escapes are double-quoted.
+///
+/// # Example Usage
+/// ```
+/// use fruitdata::models::FruitDimensions;
+/// use fruitdata::tree::Tree;
+/// use fruitdata::yaml;
+///
+/// let mut apple = FruitDimensions::new("Apple", 4.0, 2.5, 1.5);
+/// apple.extra.insert("plu".into(), "4131".into());
+/// let text = yaml::write(&Tree::of(&[&apple]).unwrap());
+/// assert_eq!(text, "- name: Apple\n  length: 4.0\n  width: 2.5\n  height: 1.5\n  plu: \"4131\"\n");
+/// assert_eq!(yaml::parse(&text).unwrap(), serde_json::to_value([&apple]).unwrap());
+/// ```
+pub fn write(tree: &Tree) -> String {
+    let mut lines = Vec::new();
+    write_block(&mut lines, tree, 0);
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+// ============================================================================
+// WRITING
+// ============================================================================
+
+/// Write `tree` as a block whose lines start `indent` spaces in.
+fn write_block(lines: &mut Vec<String>, tree: &Tree, indent: usize) {
+    let pad = " ".repeat(indent);
+    match tree {
+        Tree::Table(entries) if !entries.is_empty() => {
+            for (key, value) in entries {
+                let key = format!("{}{}:", pad, scalar_text(key));
+                if is_block(value) {
+                    lines.push(key);
+                    write_block(lines, value, indent + 2);
+                } else {
+                    lines.push(format!("{} {}", key, inline(value)));
+                }
+            }
+        }
+        Tree::Array(items) if !items.is_empty() => {
+            for item in items {
+                if is_block(item) {
+                    // The item's first line, moved up after the dash
+                    let first = lines.len();
+                    write_block(lines, item, indent + 2);
+                    lines[first].replace_range(indent..indent + 2, "- ");
+                } else {
+                    lines.push(format!("{}- {}", pad, inline(item)));
+                }
+            }
+        }
+        _ => lines.push(format!("{}{}", pad, inline(tree))),
+    }
+}
+
+/// Whether `tree` is written as a block of lines of its own.
+fn is_block(tree: &Tree) -> bool {
+    match tree {
+        Tree::Table(entries) => !entries.is_empty(),
+        Tree::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+/// A scalar or an empty collection, on one line.
+fn inline(tree: &Tree) -> String {
+    match tree {
+        Tree::Null => "null".to_string(),
+        Tree::Bool(value) => value.to_string(),
+        Tree::Number(number) => number.to_string(),
+        Tree::String(text) => scalar_text(text),
+        Tree::Array(_) => "[]".to_string(),
+        Tree::Table(_) => "{}".to_string(),
+    }
+}
+
+/// A string, plain when it reads back as the same string and quoted
+/// otherwise.
+fn scalar_text(text: &str) -> String {
+    let plain = !text.is_empty()
+        && text.trim() == text
+        && !text.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`',
+        ])
+        && !text.ends_with(':')
+        && !text.contains(": ")
+        && !text.contains(" #")
+        && !text.contains([',', '[', ']', '{', '}'])
+        && !text.chars().any(char::is_control)
+        && resolve(text) == Value::String(text.to_string())
+        // YAML 1.1 readers take these for booleans
+        && !matches!(
+            text.to_ascii_lowercase().as_str(),
+            "yes" | "no" | "on" | "off" | "y" | "n"
+        );
+    if plain {
+        text.to_string()
+    } else {
+        // JSON's escapes are all valid in a double-quoted YAML scalar
+        serde_json::to_string(text).expect("a string serializes")
+    }
+}
+
+// ============================================================================
+// READING
+// ============================================================================
+
+/// One line of the file, split into its indentation and the rest.
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+    raw: String,
+}
+
+impl Line {
+    fn new(number: usize, raw: &str) -> Self {
+        let text = raw.trim_start_matches(' ');
+        Line {
+            number,
+            indent: raw.len() - text.len(),
+            text: text.trim_end().to_string(),
+            raw: raw.to_string(),
+        }
+    }
+
+    /// Whether the line has anything but spaces and a comment.
+    fn is_content(&self) -> bool {
+        !self.text.is_empty() && !self.text.starts_with('#')
+    }
+
+    /// Whether the line starts a list item.
+    fn is_item(&self) -> bool {
+        self.text == "-" || self.text.starts_with("- ")
+    }
+
+    fn error(&self, message: impl Into<String>) -> String {
+        format!("line {}: {}", self.number, message.into())
+    }
+}
+
+/// Reads a YAML document line by line.
+struct Parser {
+    lines: Vec<Line>,
+    pos: usize,
+}
+
+impl Parser {
+    /// The next line with content, skipping blank lines and comments.
+    fn peek(&mut self) -> Option<&Line> {
+        while self.pos < self.lines.len() && !self.lines[self.pos].is_content() {
+            self.pos += 1;
+        }
+        self.lines.get(self.pos)
+    }
+
+    /// The node starting at the next line, if it is indented at least
+    /// `min_indent` spaces (null otherwise).
+    fn block(&mut self, min_indent: usize) -> Result<Value, String> {
+        let Some(line) = self.peek() else {
+            return Ok(Value::Null);
+        };
+        if line.indent < min_indent {
+            return Ok(Value::Null);
+        }
+        if line.text.starts_with('\t') {
+            return Err(line.error("tabs can't be used for indentation"));
+        }
+        let indent = line.indent;
+        if line.is_item() {
+            self.sequence(indent)
+        } else if split_key(&line.text).map_err(|e| line.error(e))?.is_some() {
+            self.mapping(indent)
+        } else {
+            let line = &self.lines[self.pos];
+            let value = flow_value(&line.text).map_err(|e| line.error(e))?;
+            self.pos += 1;
+            Ok(value)
+        }
+    }
+
+    /// The list whose items start `indent` spaces in.
+    fn sequence(&mut self, indent: usize) -> Result<Value, String> {
+        let mut items = Vec::new();
+        while let Some(line) = self.peek() {
+            if line.indent < indent {
+                break;
+            }
+            if line.indent > indent {
+                return Err(line.error("unexpected indentation"));
+            }
+            if !line.is_item() {
+                break;
+            }
+            let rest = line.text[1..].trim_start();
+            if rest.is_empty() || rest.starts_with('#') {
+                self.pos += 1;
+                items.push(self.block(indent + 1)?);
+            } else {
+                // Whatever follows the dash is read as if it started a line
+                // of its own, indented to where it is
+                let column = indent + line.text.len() - rest.len();
+                let rest = rest.to_string();
+                self.lines[self.pos].indent = column;
+                self.lines[self.pos].text = rest;
+                items.push(self.block(column)?);
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    /// The mapping whose keys start `indent` spaces in.
+    fn mapping(&mut self, indent: usize) -> Result<Value, String> {
+        let mut map = Map::new();
+        while let Some(line) = self.peek() {
+            if line.indent < indent {
+                break;
+            }
+            if line.indent > indent {
+                return Err(line.error("unexpected indentation"));
+            }
+            if line.is_item() {
+                return Err(line.error("expected a key, not a list item"));
+            }
+            let Some((key, rest)) = split_key(&line.text).map_err(|e| line.error(e))? else {
+                return Err(line.error("expected 'key: value'"));
+            };
+            if map.contains_key(&key) {
+                return Err(line.error(format!("'{}' is given twice", key)));
+            }
+            let header = line.number;
+            self.pos += 1;
+
+            let value = if rest.is_empty() || rest.starts_with('#') {
+                match self.peek().map(|next| (next.indent, next.is_item())) {
+                    Some((next, _)) if next > indent => self.block(next)?,
+                    // A list may sit at the same indentation as its key
+                    Some((next, true)) if next == indent => self.sequence(indent)?,
+                    _ => Value::Null,
+                }
+            } else if rest.starts_with(['|', '>']) {
+                self.block_scalar(indent, &rest, header)?
+            } else {
+                flow_value(&rest).map_err(|e| format!("line {}: {}", header, e))?
+            };
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// The text of a `|` (literal) or `>` (folded) block scalar, whose
+    /// header is `header`, belonging to a key `indent` spaces in.
+    fn block_scalar(&mut self, indent: usize, header: &str, line: usize) -> Result<Value, String> {
+        let folded = header.starts_with('>');
+        let chomping = header[1..].split('#').next().unwrap_or_default().trim();
+        if !matches!(chomping, "" | "-" | "+") {
+            return Err(format!(
+                "line {}: unsupported block scalar header '{}'",
+                line, header
+            ));
+        }
+
+        // The block is every line indented further than the key, and the
+        // blank lines between them
+        let mut body: Vec<&Line> = Vec::new();
+        let mut end = self.pos;
+        while let Some(line) = self.lines.get(end) {
+            if !line.text.is_empty() && line.indent <= indent {
+                break;
+            }
+            body.push(line);
+            end += 1;
+        }
+        self.pos = end;
+        let content_indent = body
+            .iter()
+            .filter(|line| !line.text.is_empty())
+            .map(|line| line.indent)
+            .min()
+            .unwrap_or(indent + 1);
+        let texts: Vec<&str> = body
+            .iter()
+            .map(|line| line.raw.get(content_indent..).unwrap_or(""))
+            .collect();
+
+        let mut text = String::new();
+        for (index, line) in texts.iter().enumerate() {
+            if index > 0 {
+                let joins = folded
+                    && !line.is_empty()
+                    && !texts[index - 1].is_empty()
+                    && !line.starts_with(' ');
+                text.push(if joins { ' ' } else { '\n' });
+            }
+            text.push_str(line);
+        }
+        let content = text.trim_end_matches('\n').len();
+        match chomping {
+            "-" => text.truncate(content),
+            "+" => text.push('\n'),
+            _ => {
+                text.truncate(content);
+                if content > 0 {
+                    text.push('\n');
+                }
+            }
+        }
+        Ok(Value::String(text))
+    }
+}
+
+/// Split `text` into a mapping key and what follows its colon, or `None`
+/// if it isn't a `key: value` line.
+fn split_key(text: &str) -> Result<Option<(String, String)>, String> {
+    if text.starts_with(['"', '\'']) {
+        let mut cursor = Cursor::new(text);
+        let key = cursor.quoted()?;
+        cursor.skip_spaces();
+        if !cursor.eat(':') || !matches!(cursor.peek(), None | Some(' ')) {
+            return Ok(None);
+        }
+        return Ok(Some((key, cursor.rest().trim_start().to_string())));
+    }
+    if text.starts_with(['[', '{']) {
+        return Ok(None);
+    }
+    let end = text.find(" #").unwrap_or(text.len());
+    let colon = text[..end]
+        .match_indices(':')
+        .map(|(index, _)| index)
+        .find(|index| matches!(text[index + 1..].chars().next(), None | Some(' ')));
+    Ok(colon.map(|index| {
+        (
+            text[..index].trim_end().to_string(),
+            text[index + 1..].trim_start().to_string(),
+        )
+    }))
+}
+
+/// A whole value on one line: a scalar or a flow collection, then perhaps
+/// a comment.
+fn flow_value(text: &str) -> Result<Value, String> {
+    let mut cursor = Cursor::new(text);
+    let value = cursor.value(false)?;
+    cursor.skip_spaces();
+    match cursor.peek() {
+        None | Some('#') => Ok(value),
+        Some(c) => Err(format!("unexpected '{}' after a value", c)),
+    }
+}
+
+/// What a plain scalar stands for, following the YAML 1.2 core schema.
+fn resolve(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => return Value::Null,
+        "true" | "True" | "TRUE" => return Value::Bool(true),
+        "false" | "False" | "FALSE" => return Value::Bool(false),
+        _ => {}
+    }
+    let unsigned = text.strip_prefix(['-', '+']).unwrap_or(text);
+    if let Some(hex) = text.strip_prefix("0x") {
+        if let Ok(value) = i64::from_str_radix(hex, 16) {
+            return value.into();
+        }
+    }
+    if let Some(octal) = text.strip_prefix("0o") {
+        if let Ok(value) = i64::from_str_radix(octal, 8) {
+            return value.into();
+        }
+    }
+    let is_number = unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && unsigned.chars().any(|c| c.is_ascii_digit())
+        && unsigned
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '-' | '+'));
+    if is_number {
+        if let Ok(value) = text.parse::<i64>() {
+            return value.into();
+        }
+        if let Some(number) = text.parse::<f64>().ok().and_then(Number::from_f64) {
+            return Value::Number(number);
+        }
+    }
+    Value::String(text.to_string())
+}
+
+/// Reads the scalars and flow collections on one line.
+struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Cursor {
+    fn new(text: &str) -> Self {
+        Cursor {
+            chars: text.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let matches = self.peek() == Some(c);
+        if matches {
+            self.pos += 1;
+        }
+        matches
+    }
+
+    fn rest(&self) -> String {
+        self.chars[self.pos..].iter().collect()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(' ') {
+            self.pos += 1;
+        }
+    }
+
+    /// A value; `in_flow` inside `[...]` and `{...}`, where `,` and
+    /// brackets end a plain scalar.
+    fn value(&mut self, in_flow: bool) -> Result<Value, String> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('"' | '\'') => self.quoted().map(Value::String),
+            Some('[') => self.flow_sequence(),
+            Some('{') => self.flow_mapping(),
+            Some('&' | '*' | '!') => Err("anchors, aliases and tags aren't supported".into()),
+            Some('|' | '>') => {
+                Err("block scalars ('|', '>') can only be the values of keys".into())
+            }
+            _ => Ok(resolve(&self.plain(in_flow))),
+        }
+    }
+
+    /// A plain (unquoted) scalar, up to a comment or the end of the line.
+    fn plain(&mut self, in_flow: bool) -> String {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            let next = self.chars.get(self.pos + 1).copied();
+            let ends = (c == '#' && text.ends_with(' '))
+                || (in_flow && matches!(c, ',' | '[' | ']' | '{' | '}'))
+                || (in_flow && c == ':' && matches!(next, None | Some(' ' | ',' | ']' | '}')));
+            if ends {
+                break;
+            }
+            text.push(c);
+            self.pos += 1;
+        }
+        text.trim_end().to_string()
+    }
+
+    /// A `"double-quoted"` or `'single-quoted'` scalar.
+    fn quoted(&mut self) -> Result<String, String> {
+        let quote = self.peek().expect("a quote is next");
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err("unterminated string (quoted strings must end on their line)".into());
+            };
+            self.pos += 1;
+            match c {
+                '\'' if quote == '\'' => {
+                    // '' stands for one quote
+                    if !self.eat('\'') {
+                        return Ok(text);
+                    }
+                    text.push('\'');
+                }
+                '"' if quote == '"' => return Ok(text),
+                '\\' if quote == '"' => text.push(self.escape()?),
+                c => text.push(c),
+            }
+        }
+    }
+
+    /// The character of an escape, whose backslash has been read.
+    fn escape(&mut self) -> Result<char, String> {
+        let Some(c) = self.peek() else {
+            return Err("unterminated string".into());
+        };
+        self.pos += 1;
+        let digits = match c {
+            '0' => return Ok('\0'),
+            'a' => return Ok('\u{7}'),
+            'b' => return Ok('\u{8}'),
+            't' | '\t' => return Ok('\t'),
+            'n' => return Ok('\n'),
+            'v' => return Ok('\u{b}'),
+            'f' => return Ok('\u{c}'),
+            'r' => return Ok('\r'),
+            'e' => return Ok('\u{1b}'),
+            ' ' | '"' | '/' | '\\' => return Ok(c),
+            'N' => return Ok('\u{85}'),
+            '_' => return Ok('\u{a0}'),
+            'L' => return Ok('\u{2028}'),
+            'P' => return Ok('\u{2029}'),
+            'x' => 2,
+            'u' => 4,
+            'U' => 8,
+            c => return Err(format!("unknown escape '\\{}'", c)),
+        };
+        let hex: String = self.chars.iter().skip(self.pos).take(digits).collect();
+        self.pos += hex.chars().count();
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .filter(|_| hex.len() == digits)
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("'\\{}{}' isn't a character", c, hex))
+    }
+
+    fn flow_sequence(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_spaces();
+            if self.eat(']') {
+                break;
+            }
+            items.push(self.value(true)?);
+            self.skip_spaces();
+            if self.eat(']') {
+                break;
+            }
+            if !self.eat(',') {
+                return Err(
+                    "expected ',' or ']' (lists in brackets must end on their line)".into(),
+                );
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn flow_mapping(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut map = Map::new();
+        loop {
+            self.skip_spaces();
+            if self.eat('}') {
+                break;
+            }
+            let key = match self.peek() {
+                Some('"' | '\'') => self.quoted()?,
+                _ => self.plain(true),
+            };
+            self.skip_spaces();
+            let value = if self.eat(':') {
+                self.value(true)?
+            } else {
+                Value::Null
+            };
+            if map.insert(key.clone(), value).is_some() {
+                return Err(format!("'{}' is given twice", key));
+            }
+            self.skip_spaces();
+            if self.eat('}') {
+                break;
+            }
+            if !self.eat(',') {
+                return Err(
+                    "expected ',' or '}' (mappings in braces must end on their line)".into(),
+                );
+            }
+        }
+        Ok(Value::Object(map))
+    }
+}